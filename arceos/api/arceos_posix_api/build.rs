@@ -57,6 +57,8 @@ typedef struct {{
         let allow_vars = [
             "CLOCK_.*",
             "O_.*",
+            "AT_.*",
+            "[RWX]_OK",
             "AF_.*",
             "SOCK_.*",
             "IPPROTO_.*",
@@ -15,39 +15,174 @@ pub const AX_FILE_LIMIT: usize = 1024;
 pub trait FileLike: Send + Sync {
     fn read(&self, buf: &mut [u8]) -> LinuxResult<usize>;
     fn write(&self, buf: &[u8]) -> LinuxResult<usize>;
+    /// Reads at the given offset without moving the file offset, for `pread`
+    /// and `mmap`. Returns `ESPIPE` if the file is not seekable.
+    fn read_at(&self, _offset: u64, _buf: &mut [u8]) -> LinuxResult<usize> {
+        Err(LinuxError::ESPIPE)
+    }
     fn stat(&self) -> LinuxResult<ctypes::stat>;
     fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync>;
     fn poll(&self) -> LinuxResult<PollState>;
     fn set_nonblocking(&self, nonblocking: bool) -> LinuxResult;
 }
 
+/// The flag of [`sys_fcntl`] with `F_GETFD` or `F_SETFD` for the close-on-exec
+/// flag.
+const FD_CLOEXEC: usize = 1;
+
+#[derive(Clone)]
+struct FdEntry {
+    file: Arc<dyn FileLike>,
+    cloexec: bool,
+}
+
+/// A file descriptor table.
+///
+/// Each entry has a close-on-exec flag, which is used by the monolithic
+/// kernel to close the file on `execve`.
+pub struct FdTable {
+    files: RwLock<FlattenObjects<FdEntry, AX_FILE_LIMIT>>,
+}
+
+impl FdTable {
+    /// Creates an empty table.
+    pub fn new() -> Self {
+        Self {
+            files: RwLock::new(FlattenObjects::new()),
+        }
+    }
+
+    /// Creates a table with stdin, stdout and stderr opened as file
+    /// descriptors 0, 1 and 2.
+    pub fn with_stdio() -> Self {
+        let table = Self::new();
+        table.add_at(0, Arc::new(stdin()), false).unwrap(); // stdin
+        table.add_at(1, Arc::new(stdout()), false).unwrap(); // stdout
+        table.add_at(2, Arc::new(stdout()), false).unwrap(); // stderr
+        table
+    }
+
+    /// Creates a copy of the table for a forked process, which shares the
+    /// open files with this one.
+    pub fn fork(&self) -> Self {
+        let files = self.files.read();
+        let mut new_files = FlattenObjects::new();
+        for fd in (0..AX_FILE_LIMIT).filter(|&fd| files.is_assigned(fd)) {
+            let entry = files.get(fd).unwrap().clone();
+            new_files.add_at(fd, entry);
+        }
+        Self {
+            files: RwLock::new(new_files),
+        }
+    }
+
+    /// Returns the file of `fd`.
+    pub fn get(&self, fd: c_int) -> LinuxResult<Arc<dyn FileLike>> {
+        let files = self.files.read();
+        let entry = files.get(fd as usize).ok_or(LinuxError::EBADF)?;
+        Ok(entry.file.clone())
+    }
+
+    /// Adds a file with the lowest available file descriptor, and returns
+    /// the file descriptor.
+    pub fn add(&self, file: Arc<dyn FileLike>, cloexec: bool) -> LinuxResult<c_int> {
+        let fd = self
+            .files
+            .write()
+            .add(FdEntry { file, cloexec })
+            .ok_or(LinuxError::EMFILE)?;
+        Ok(fd as c_int)
+    }
+
+    /// Adds a file with the file descriptor `fd`. The file previously opened
+    /// as `fd` is closed.
+    pub fn add_at(&self, fd: c_int, file: Arc<dyn FileLike>, cloexec: bool) -> LinuxResult<c_int> {
+        if fd < 0 || fd as usize >= AX_FILE_LIMIT {
+            return Err(LinuxError::EBADF);
+        }
+        let mut files = self.files.write();
+        let old = files.remove(fd as usize);
+        files.add_at(fd as usize, FdEntry { file, cloexec });
+        drop(files);
+        drop(old);
+        Ok(fd)
+    }
+
+    /// Removes the file descriptor `fd`, and returns the file.
+    pub fn remove(&self, fd: c_int) -> LinuxResult<Arc<dyn FileLike>> {
+        let entry = self
+            .files
+            .write()
+            .remove(fd as usize)
+            .ok_or(LinuxError::EBADF)?;
+        Ok(entry.file)
+    }
+
+    /// Returns the close-on-exec flag of `fd`.
+    pub fn cloexec(&self, fd: c_int) -> LinuxResult<bool> {
+        let files = self.files.read();
+        let entry = files.get(fd as usize).ok_or(LinuxError::EBADF)?;
+        Ok(entry.cloexec)
+    }
+
+    /// Sets the close-on-exec flag of `fd`.
+    pub fn set_cloexec(&self, fd: c_int, cloexec: bool) -> LinuxResult {
+        let mut files = self.files.write();
+        let entry = files.get_mut(fd as usize).ok_or(LinuxError::EBADF)?;
+        entry.cloexec = cloexec;
+        Ok(())
+    }
+
+    /// Closes the file descriptors with the close-on-exec flag set.
+    pub fn close_on_exec(&self) {
+        let mut files = self.files.write();
+        let mut closed = alloc::vec::Vec::new();
+        for fd in 0..AX_FILE_LIMIT {
+            if files.get(fd).is_some_and(|entry| entry.cloexec) {
+                closed.extend(files.remove(fd));
+            }
+        }
+        drop(files);
+        drop(closed);
+    }
+}
+
+impl Default for FdTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A slice of functions that return the file descriptor table of the current
+/// task, e.g., from the task extended data defined by `def_task_ext!`.
+///
+/// If there is none, or it returns `None`, the default table shared by all
+/// tasks is used, like in the unikernel.
+#[linkme::distributed_slice]
+pub static CURRENT_FD_TABLE: [fn() -> Option<Arc<FdTable>>];
+
 lazy_static::lazy_static! {
-    static ref FD_TABLE: RwLock<FlattenObjects<Arc<dyn FileLike>, AX_FILE_LIMIT>> = {
-        let mut fd_table = FlattenObjects::new();
-        fd_table.add_at(0, Arc::new(stdin()) as _).unwrap(); // stdin
-        fd_table.add_at(1, Arc::new(stdout()) as _).unwrap(); // stdout
-        fd_table.add_at(2, Arc::new(stdout()) as _).unwrap(); // stderr
-        RwLock::new(fd_table)
-    };
+    static ref DEFAULT_FD_TABLE: Arc<FdTable> = Arc::new(FdTable::with_stdio());
+}
+
+/// Returns the file descriptor table of the current task.
+pub fn current_fd_table() -> Arc<FdTable> {
+    CURRENT_FD_TABLE
+        .first()
+        .and_then(|f| f())
+        .unwrap_or_else(|| DEFAULT_FD_TABLE.clone())
 }
 
 pub fn get_file_like(fd: c_int) -> LinuxResult<Arc<dyn FileLike>> {
-    FD_TABLE
-        .read()
-        .get(fd as usize)
-        .cloned()
-        .ok_or(LinuxError::EBADF)
+    current_fd_table().get(fd)
 }
 
 pub fn add_file_like(f: Arc<dyn FileLike>) -> LinuxResult<c_int> {
-    Ok(FD_TABLE.write().add(f).ok_or(LinuxError::EMFILE)? as c_int)
+    current_fd_table().add(f, false)
 }
 
 pub fn close_file_like(fd: c_int) -> LinuxResult {
-    let f = FD_TABLE
-        .write()
-        .remove(fd as usize)
-        .ok_or(LinuxError::EBADF)?;
+    let f = current_fd_table().remove(fd)?;
     drop(f);
     Ok(())
 }
@@ -61,21 +196,22 @@ pub fn sys_close(fd: c_int) -> c_int {
     syscall_body!(sys_close, close_file_like(fd).map(|_| 0))
 }
 
-fn dup_fd(old_fd: c_int) -> LinuxResult<c_int> {
-    let f = get_file_like(old_fd)?;
-    let new_fd = add_file_like(f)?;
+fn dup_fd(old_fd: c_int, cloexec: bool) -> LinuxResult<c_int> {
+    let table = current_fd_table();
+    let f = table.get(old_fd)?;
+    let new_fd = table.add(f, cloexec)?;
     Ok(new_fd)
 }
 
 /// Duplicate a file descriptor.
 pub fn sys_dup(old_fd: c_int) -> c_int {
     debug!("sys_dup <= {}", old_fd);
-    syscall_body!(sys_dup, dup_fd(old_fd))
+    syscall_body!(sys_dup, dup_fd(old_fd, false))
 }
 
 /// Duplicate a file descriptor, but it uses the file descriptor number specified in `new_fd`.
 ///
-/// TODO: `dup2` should forcibly close new_fd if it is already opened.
+/// The file previously opened as `new_fd` is closed.
 pub fn sys_dup2(old_fd: c_int, new_fd: c_int) -> c_int {
     debug!("sys_dup2 <= old_fd: {}, new_fd: {}", old_fd, new_fd);
     syscall_body!(sys_dup2, {
@@ -91,27 +227,28 @@ pub fn sys_dup2(old_fd: c_int, new_fd: c_int) -> c_int {
             return Err(LinuxError::EBADF);
         }
 
-        let f = get_file_like(old_fd)?;
-        FD_TABLE
-            .write()
-            .add_at(new_fd as usize, f)
-            .ok_or(LinuxError::EMFILE)?;
-
-        Ok(new_fd)
+        let table = current_fd_table();
+        let f = table.get(old_fd)?;
+        table.add_at(new_fd, f, false)
     })
 }
 
 /// Manipulate file descriptor.
 ///
-/// TODO: `SET/GET` command is ignored, hard-code stdin/stdout
+/// TODO: `F_SETFL` is ignored for stdin/stdout
 pub fn sys_fcntl(fd: c_int, cmd: c_int, arg: usize) -> c_int {
     debug!("sys_fcntl <= fd: {} cmd: {} arg: {}", fd, cmd, arg);
     syscall_body!(sys_fcntl, {
         match cmd as u32 {
-            ctypes::F_DUPFD => dup_fd(fd),
-            ctypes::F_DUPFD_CLOEXEC => {
-                // TODO: Change fd flags
-                dup_fd(fd)
+            ctypes::F_DUPFD => dup_fd(fd, false),
+            ctypes::F_DUPFD_CLOEXEC => dup_fd(fd, true),
+            ctypes::F_GETFD => {
+                let cloexec = current_fd_table().cloexec(fd)?;
+                Ok(if cloexec { FD_CLOEXEC as c_int } else { 0 })
+            }
+            ctypes::F_SETFD => {
+                current_fd_table().set_cloexec(fd, arg & FD_CLOEXEC != 0)?;
+                Ok(0)
             }
             ctypes::F_SETFL => {
                 if fd == 0 || fd == 1 || fd == 2 {
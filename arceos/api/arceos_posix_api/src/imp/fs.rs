@@ -1,18 +1,26 @@
-use alloc::sync::Arc;
-use core::ffi::{c_char, c_int};
+use alloc::{format, string::String, sync::Arc};
+use core::ffi::{c_char, c_int, c_void};
 
 use axerrno::{LinuxError, LinuxResult};
-use axfs::fops::OpenOptions;
+use axfs::fops::{DirEntry, FileAttr, OpenOptions};
 use axio::{PollState, SeekFrom};
 use axsync::Mutex;
 
 use super::fd_ops::{get_file_like, FileLike};
 use crate::{ctypes, utils::char_ptr_to_str};
 
+/// `renameat2` flag: do not overwrite the target.
+const RENAME_NOREPLACE: c_int = 1;
+
 pub struct File {
     inner: Mutex<axfs::fops::File>,
 }
 
+pub struct Directory {
+    inner: Mutex<axfs::fops::Directory>,
+    path: String,
+}
+
 impl File {
     fn new(inner: axfs::fops::File) -> Self {
         Self {
@@ -20,8 +28,8 @@ impl File {
         }
     }
 
-    fn add_to_fd_table(self) -> LinuxResult<c_int> {
-        super::fd_ops::add_file_like(Arc::new(self))
+    fn add_to_fd_table(self, cloexec: bool) -> LinuxResult<c_int> {
+        super::fd_ops::current_fd_table().add(Arc::new(self), cloexec)
     }
 
     fn from_fd(fd: c_int) -> LinuxResult<Arc<Self>> {
@@ -41,22 +49,12 @@ impl FileLike for File {
         Ok(self.inner.lock().write(buf)?)
     }
 
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> LinuxResult<usize> {
+        Ok(self.inner.lock().read_at(offset, buf)?)
+    }
+
     fn stat(&self) -> LinuxResult<ctypes::stat> {
-        let metadata = self.inner.lock().get_attr()?;
-        let ty = metadata.file_type() as u8;
-        let perm = metadata.perm().bits() as u32;
-        let st_mode = ((ty as u32) << 12) | perm;
-        Ok(ctypes::stat {
-            st_ino: 1,
-            st_nlink: 1,
-            st_mode,
-            st_uid: 1000,
-            st_gid: 1000,
-            st_size: metadata.size() as _,
-            st_blocks: metadata.blocks() as _,
-            st_blksize: 512,
-            ..Default::default()
-        })
+        Ok(attr_to_stat(self.inner.lock().get_attr()?))
     }
 
     fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
@@ -75,6 +73,152 @@ impl FileLike for File {
     }
 }
 
+impl Directory {
+    fn new(inner: axfs::fops::Directory, path: String) -> Self {
+        Self {
+            inner: Mutex::new(inner),
+            path,
+        }
+    }
+
+    fn add_to_fd_table(self, cloexec: bool) -> LinuxResult<c_int> {
+        super::fd_ops::current_fd_table().add(Arc::new(self), cloexec)
+    }
+
+    fn from_fd(fd: c_int) -> LinuxResult<Arc<Self>> {
+        let f = super::fd_ops::get_file_like(fd)?;
+        f.into_any()
+            .downcast::<Self>()
+            .map_err(|_| LinuxError::ENOTDIR)
+    }
+}
+
+impl FileLike for Directory {
+    fn read(&self, _buf: &mut [u8]) -> LinuxResult<usize> {
+        Err(LinuxError::EISDIR)
+    }
+
+    fn write(&self, _buf: &[u8]) -> LinuxResult<usize> {
+        Err(LinuxError::EISDIR)
+    }
+
+    fn stat(&self) -> LinuxResult<ctypes::stat> {
+        Ok(attr_to_stat(self.inner.lock().get_attr()?))
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
+        self
+    }
+
+    fn poll(&self) -> LinuxResult<PollState> {
+        Ok(PollState {
+            readable: true,
+            writable: false,
+        })
+    }
+
+    fn set_nonblocking(&self, _nonblocking: bool) -> LinuxResult {
+        Ok(())
+    }
+}
+
+fn attr_to_stat(attr: FileAttr) -> ctypes::stat {
+    let ty = attr.file_type() as u8;
+    let perm = attr.perm().bits() as u32;
+    let st_mode = ((ty as u32) << 12) | perm;
+    ctypes::stat {
+        st_ino: 1,
+        st_nlink: 1,
+        st_mode,
+        st_uid: 1000,
+        st_gid: 1000,
+        st_size: attr.size() as _,
+        st_blocks: attr.blocks() as _,
+        st_blksize: 512,
+        ..Default::default()
+    }
+}
+
+/// Resolves `path` relative to the directory referred to by `dirfd` into an
+/// absolute path.
+///
+/// If `path` is absolute, `dirfd` is ignored. If `dirfd` is `AT_FDCWD`, the
+/// path is relative to the current directory.
+fn resolve_at(dirfd: c_int, path: &str) -> LinuxResult<String> {
+    if path.is_empty() {
+        return Err(LinuxError::ENOENT);
+    }
+    if path.starts_with('/') {
+        return Ok(axfs::api::canonicalize(path)?);
+    }
+    let dir = if dirfd == ctypes::AT_FDCWD {
+        current_dir()?
+    } else {
+        Directory::from_fd(dirfd)?.path.clone()
+    };
+    Ok(axfs::api::canonicalize(&format!("{}/{}", dir, path))?)
+}
+
+/// A slice of functions that return the current directory of the current
+/// task, e.g., from the process that the task belongs to. It holds an
+/// absolute path.
+///
+/// If there is none, or it returns `None`, the current directory of `axfs`
+/// shared by all tasks is used, like in the unikernel.
+#[linkme::distributed_slice]
+pub static CURRENT_DIR: [fn() -> Option<Arc<Mutex<String>>>];
+
+fn task_current_dir() -> Option<Arc<Mutex<String>>> {
+    CURRENT_DIR.first().and_then(|f| f())
+}
+
+/// Returns the absolute path of the current directory.
+fn current_dir() -> LinuxResult<String> {
+    match task_current_dir() {
+        Some(cwd) => Ok(cwd.lock().clone()),
+        None => Ok(axfs::api::current_dir()?),
+    }
+}
+
+/// Changes the current directory to the absolute `path`.
+fn set_current_dir(path: &str) -> LinuxResult {
+    let Some(cwd) = task_current_dir() else {
+        return Ok(axfs::api::set_current_dir(path)?);
+    };
+    let metadata = axfs::api::metadata(path)?;
+    if !metadata.is_dir() {
+        return Err(LinuxError::ENOTDIR);
+    }
+    if !metadata.permissions().owner_executable() {
+        return Err(LinuxError::EACCES);
+    }
+    *cwd.lock() = path.into();
+    Ok(())
+}
+
+/// Gets the metadata of the file or directory at the absolute `path`.
+fn stat_path(path: &str) -> LinuxResult<ctypes::stat> {
+    let mut options = OpenOptions::new();
+    options.read(true);
+    let file = axfs::fops::File::open(path, &options)?;
+    Ok(attr_to_stat(file.get_attr()?))
+}
+
+/// Gets the metadata of the file at `path` relative to the directory `dirfd`,
+/// or of `dirfd` itself if `path` is empty and `AT_EMPTY_PATH` is set in
+/// `flags`.
+fn stat_at(dirfd: c_int, path: &str, flags: u32) -> LinuxResult<ctypes::stat> {
+    if path.is_empty() && flags & ctypes::AT_EMPTY_PATH != 0 {
+        if dirfd == ctypes::AT_FDCWD {
+            stat_path(&current_dir()?)
+        } else {
+            get_file_like(dirfd)?.stat()
+        }
+    } else {
+        stat_path(&resolve_at(dirfd, path)?)
+    }
+}
+
 /// Convert open flags to [`OpenOptions`].
 fn flags_to_options(flags: c_int, _mode: ctypes::mode_t) -> OpenOptions {
     let flags = flags as u32;
@@ -107,12 +251,44 @@ fn flags_to_options(flags: c_int, _mode: ctypes::mode_t) -> OpenOptions {
 /// Return its index in the file table (`fd`). Return `EMFILE` if it already
 /// has the maximum number of files open.
 pub fn sys_open(filename: *const c_char, flags: c_int, mode: ctypes::mode_t) -> c_int {
+    sys_openat(ctypes::AT_FDCWD, filename, flags, mode)
+}
+
+/// Open a file or directory by `filename` relative to the directory `dirfd`,
+/// and insert it into the file descriptor table.
+///
+/// Directories can only be opened read-only. Return its index in the file
+/// table (`fd`).
+pub fn sys_openat(
+    dirfd: c_int,
+    filename: *const c_char,
+    flags: c_int,
+    mode: ctypes::mode_t,
+) -> c_int {
     let filename = char_ptr_to_str(filename);
-    debug!("sys_open <= {:?} {:#o} {:#o}", filename, flags, mode);
-    syscall_body!(sys_open, {
+    debug!(
+        "sys_openat <= {} {:?} {:#o} {:#o}",
+        dirfd, filename, flags, mode
+    );
+    syscall_body!(sys_openat, {
+        let path = resolve_at(dirfd, filename?)?;
+        let uflags = flags as u32;
+        let cloexec = uflags & ctypes::O_CLOEXEC != 0;
+        let read_only = uflags & 0b11 == ctypes::O_RDONLY && uflags & ctypes::O_CREAT == 0;
+        if read_only {
+            let mut options = OpenOptions::new();
+            options.read(true);
+            match axfs::fops::Directory::open_dir(&path, &options) {
+                Ok(dir) => return Directory::new(dir, path).add_to_fd_table(cloexec),
+                Err(axerrno::AxError::NotADirectory) if uflags & ctypes::O_DIRECTORY == 0 => {}
+                Err(e) => return Err(e.into()),
+            }
+        } else if uflags & ctypes::O_DIRECTORY != 0 {
+            return Err(LinuxError::EINVAL);
+        }
         let options = flags_to_options(flags, mode);
-        let file = axfs::fops::File::open(filename?, &options)?;
-        File::new(file).add_to_fd_table()
+        let file = axfs::fops::File::open(&path, &options)?;
+        File::new(file).add_to_fd_table(cloexec)
     })
 }
 
@@ -128,7 +304,17 @@ pub fn sys_lseek(fd: c_int, offset: ctypes::off_t, whence: c_int) -> ctypes::off
             2 => SeekFrom::End(offset as _),
             _ => return Err(LinuxError::EINVAL),
         };
-        let off = File::from_fd(fd)?.inner.lock().seek(pos)?;
+        let f = get_file_like(fd)?.into_any();
+        if let Ok(dir) = f.clone().downcast::<Directory>() {
+            // The position of a directory is the index of the next entry.
+            let SeekFrom::Start(idx) = pos else {
+                return Err(LinuxError::EINVAL);
+            };
+            dir.inner.lock().set_entry_idx(idx as _);
+            return Ok(idx);
+        }
+        let file = f.downcast::<File>().map_err(|_| LinuxError::EINVAL)?;
+        let off = file.inner.lock().seek(pos)?;
         Ok(off)
     })
 }
@@ -143,10 +329,7 @@ pub unsafe fn sys_stat(path: *const c_char, buf: *mut ctypes::stat) -> c_int {
         if buf.is_null() {
             return Err(LinuxError::EFAULT);
         }
-        let mut options = OpenOptions::new();
-        options.read(true);
-        let file = axfs::fops::File::open(path?, &options)?;
-        let st = File::new(file).stat()?;
+        let st = stat_path(&resolve_at(ctypes::AT_FDCWD, path?)?)?;
         unsafe { *buf = st };
         Ok(0)
     })
@@ -190,7 +373,7 @@ pub fn sys_getcwd(buf: *mut c_char, size: usize) -> *mut c_char {
             return Ok(core::ptr::null::<c_char>() as _);
         }
         let dst = unsafe { core::slice::from_raw_parts_mut(buf as *mut u8, size as _) };
-        let cwd = axfs::api::current_dir()?;
+        let cwd = current_dir()?;
         let cwd = cwd.as_bytes();
         if cwd.len() < size {
             dst[..cwd.len()].copy_from_slice(cwd);
@@ -211,7 +394,265 @@ pub fn sys_rename(old: *const c_char, new: *const c_char) -> c_int {
         let old_path = char_ptr_to_str(old)?;
         let new_path = char_ptr_to_str(new)?;
         debug!("sys_rename <= old: {:?}, new: {:?}", old_path, new_path);
-        axfs::api::rename(old_path, new_path)?;
+        axfs::api::rename(
+            &resolve_at(ctypes::AT_FDCWD, old_path)?,
+            &resolve_at(ctypes::AT_FDCWD, new_path)?,
+        )?;
+        Ok(0)
+    })
+}
+
+/// Get the metadata of the file at `path` relative to the directory `dirfd`
+/// and write into `buf`.
+///
+/// If `path` is empty and `AT_EMPTY_PATH` is set in `flags`, get the metadata
+/// of `dirfd` itself. Return 0 if success.
+pub unsafe fn sys_fstatat(
+    dirfd: c_int,
+    path: *const c_char,
+    buf: *mut ctypes::stat,
+    flags: c_int,
+) -> c_int {
+    let path = char_ptr_to_str(path);
+    debug!(
+        "sys_fstatat <= {} {:?} {:#x} {:#x}",
+        dirfd, path, buf as usize, flags
+    );
+    syscall_body!(sys_fstatat, {
+        if buf.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        unsafe { *buf = stat_at(dirfd, path?, flags as u32)? };
+        Ok(0)
+    })
+}
+
+/// Read directory entries of the directory `fd` into `dirp` as a sequence of
+/// `struct linux_dirent64`.
+///
+/// Return the number of bytes read, 0 at the end of the directory, or
+/// `EINVAL` if `count` is too small to hold the next entry.
+pub unsafe fn sys_getdents64(fd: c_int, dirp: *mut c_void, count: usize) -> ctypes::ssize_t {
+    debug!("sys_getdents64 <= {} {:#x} {}", fd, dirp as usize, count);
+    syscall_body!(sys_getdents64, {
+        if dirp.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        // d_ino (u64), d_off (i64), d_reclen (u16), d_type (u8), d_name
+        const NAME_OFFSET: usize = 19;
+
+        let buf = unsafe { core::slice::from_raw_parts_mut(dirp as *mut u8, count) };
+        let dir = Directory::from_fd(fd)?;
+        let mut dir = dir.inner.lock();
+        let mut len = 0;
+        loop {
+            let idx = dir.entry_idx();
+            let mut entry = [DirEntry::default()];
+            if dir.read_dir(&mut entry)? == 0 {
+                break;
+            }
+            let name = entry[0].name_as_bytes();
+            let reclen = (NAME_OFFSET + name.len() + 1).next_multiple_of(8);
+            if len + reclen > buf.len() {
+                // Leave the entry for the next call.
+                dir.set_entry_idx(idx);
+                if len == 0 {
+                    return Err(LinuxError::EINVAL);
+                }
+                break;
+            }
+            let rec = &mut buf[len..len + reclen];
+            rec[0..8].copy_from_slice(&(idx as u64 + 1).to_ne_bytes());
+            rec[8..16].copy_from_slice(&(idx as i64 + 1).to_ne_bytes());
+            rec[16..18].copy_from_slice(&(reclen as u16).to_ne_bytes());
+            rec[18] = entry[0].entry_type() as u8;
+            rec[NAME_OFFSET..NAME_OFFSET + name.len()].copy_from_slice(name);
+            rec[NAME_OFFSET + name.len()..].fill(0);
+            len += reclen;
+        }
+        Ok(len as ctypes::ssize_t)
+    })
+}
+
+/// Create a directory at `path` relative to the directory `dirfd`.
+///
+/// Return 0 if success.
+pub fn sys_mkdirat(dirfd: c_int, path: *const c_char, mode: ctypes::mode_t) -> c_int {
+    let path = char_ptr_to_str(path);
+    debug!("sys_mkdirat <= {} {:?} {:#o}", dirfd, path, mode);
+    syscall_body!(sys_mkdirat, {
+        axfs::api::create_dir(&resolve_at(dirfd, path?)?)?;
+        Ok(0)
+    })
+}
+
+/// Remove the file at `path` relative to the directory `dirfd`, or the
+/// directory if `AT_REMOVEDIR` is set in `flags`.
+///
+/// Return 0 if success.
+pub fn sys_unlinkat(dirfd: c_int, path: *const c_char, flags: c_int) -> c_int {
+    let path = char_ptr_to_str(path);
+    debug!("sys_unlinkat <= {} {:?} {:#x}", dirfd, path, flags);
+    syscall_body!(sys_unlinkat, {
+        let flags = flags as u32;
+        if flags & !ctypes::AT_REMOVEDIR != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let path = resolve_at(dirfd, path?)?;
+        if flags & ctypes::AT_REMOVEDIR != 0 {
+            axfs::api::remove_dir(&path)?;
+        } else {
+            axfs::api::remove_file(&path)?;
+        }
+        Ok(0)
+    })
+}
+
+/// Rename `old` relative to the directory `olddirfd` to `new` relative to
+/// the directory `newdirfd`.
+///
+/// If `RENAME_NOREPLACE` is set in `flags`, fail with `EEXIST` if `new`
+/// exists, otherwise it is first removed. Return 0 if success.
+pub fn sys_renameat2(
+    olddirfd: c_int,
+    old: *const c_char,
+    newdirfd: c_int,
+    new: *const c_char,
+    flags: c_int,
+) -> c_int {
+    syscall_body!(sys_renameat2, {
+        let old_path = char_ptr_to_str(old)?;
+        let new_path = char_ptr_to_str(new)?;
+        debug!(
+            "sys_renameat2 <= {} {:?} {} {:?} {:#x}",
+            olddirfd, old_path, newdirfd, new_path, flags
+        );
+        if flags & !RENAME_NOREPLACE != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let old_path = resolve_at(olddirfd, old_path)?;
+        let new_path = resolve_at(newdirfd, new_path)?;
+        if flags & RENAME_NOREPLACE != 0 && axfs::api::metadata(&new_path).is_ok() {
+            return Err(LinuxError::EEXIST);
+        }
+        axfs::api::rename(&old_path, &new_path)?;
+        Ok(0)
+    })
+}
+
+/// Truncate or extend the file `fd` to `length` bytes.
+///
+/// Return 0 if success.
+pub fn sys_ftruncate(fd: c_int, length: ctypes::off_t) -> c_int {
+    debug!("sys_ftruncate <= {} {}", fd, length);
+    syscall_body!(sys_ftruncate, {
+        if length < 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        File::from_fd(fd)?.inner.lock().truncate(length as _)?;
+        Ok(0)
+    })
+}
+
+/// Write the buffered data of the file `fd` to the underlying device.
+///
+/// Return 0 if success.
+pub fn sys_fsync(fd: c_int) -> c_int {
+    debug!("sys_fsync <= {}", fd);
+    syscall_body!(sys_fsync, {
+        let Ok(file) = get_file_like(fd)?.into_any().downcast::<File>() else {
+            // Nothing to flush for directories, pipes, sockets, etc.
+            return Ok(0);
+        };
+        match file.inner.lock().flush() {
+            // A file opened read-only has nothing to flush either.
+            Ok(()) | Err(axerrno::AxError::PermissionDenied) => Ok(0),
+            Err(e) => Err(e.into()),
+        }
+    })
+}
+
+/// Change the current directory to `path`.
+///
+/// Return 0 if success.
+pub fn sys_chdir(path: *const c_char) -> c_int {
+    let path = char_ptr_to_str(path);
+    debug!("sys_chdir <= {:?}", path);
+    syscall_body!(sys_chdir, {
+        set_current_dir(&resolve_at(ctypes::AT_FDCWD, path?)?)?;
+        Ok(0)
+    })
+}
+
+/// Change the current directory to the directory `fd`.
+///
+/// Return 0 if success.
+pub fn sys_fchdir(fd: c_int) -> c_int {
+    debug!("sys_fchdir <= {}", fd);
+    syscall_body!(sys_fchdir, {
+        set_current_dir(&Directory::from_fd(fd)?.path)?;
+        Ok(0)
+    })
+}
+
+/// Check whether the file at `path` relative to the directory `dirfd` exists
+/// and can be accessed with `mode` (`F_OK` or a mask of `R_OK`, `W_OK` and
+/// `X_OK`).
+///
+/// If `path` is empty and `AT_EMPTY_PATH` is set in `flags`, check `dirfd`
+/// itself. There is a single user and no symbolic links, so `AT_EACCESS` and
+/// `AT_SYMLINK_NOFOLLOW` make no difference.
+///
+/// Return 0 if success, or `EACCES` if any of the permissions is missing.
+pub fn sys_faccessat(dirfd: c_int, path: *const c_char, mode: c_int, flags: c_int) -> c_int {
+    let path = char_ptr_to_str(path);
+    debug!(
+        "sys_faccessat <= {} {:?} {:#o} {:#x}",
+        dirfd, path, mode, flags
+    );
+    syscall_body!(sys_faccessat, {
+        let mode = mode as u32;
+        if mode & !(ctypes::R_OK | ctypes::W_OK | ctypes::X_OK) != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let flags = flags as u32;
+        if flags & !(ctypes::AT_EACCESS | ctypes::AT_SYMLINK_NOFOLLOW | ctypes::AT_EMPTY_PATH) != 0
+        {
+            return Err(LinuxError::EINVAL);
+        }
+        // The owner permission bits, i.e., `S_IRUSR`, `S_IWUSR` and `S_IXUSR`.
+        let perm = stat_at(dirfd, path?, flags)?.st_mode >> 6;
+        if (mode & ctypes::R_OK != 0 && perm & 0o4 == 0)
+            || (mode & ctypes::W_OK != 0 && perm & 0o2 == 0)
+            || (mode & ctypes::X_OK != 0 && perm & 0o1 == 0)
+        {
+            return Err(LinuxError::EACCES);
+        }
+        Ok(0)
+    })
+}
+
+/// Change the access and modification times of the file at `path` relative
+/// to the directory `dirfd`, or of `dirfd` itself if `path` is null.
+///
+/// The file systems do not record timestamps, so this only checks that the
+/// file exists. Return 0 if success.
+pub fn sys_utimensat(
+    dirfd: c_int,
+    path: *const c_char,
+    times: *const ctypes::timespec,
+    flags: c_int,
+) -> c_int {
+    debug!(
+        "sys_utimensat <= {} {:#x} {:#x} {:#x}",
+        dirfd, path as usize, times as usize, flags
+    );
+    syscall_body!(sys_utimensat, {
+        if path.is_null() {
+            get_file_like(dirfd)?;
+        } else {
+            stat_path(&resolve_at(dirfd, char_ptr_to_str(path)?)?)?;
+        }
         Ok(0)
     })
 }
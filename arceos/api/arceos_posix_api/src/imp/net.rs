@@ -18,8 +18,8 @@ pub enum Socket {
 }
 
 impl Socket {
-    fn add_to_fd_table(self) -> LinuxResult<c_int> {
-        super::fd_ops::add_file_like(Arc::new(self))
+    fn add_to_fd_table(self, cloexec: bool) -> LinuxResult<c_int> {
+        super::fd_ops::current_fd_table().add(Arc::new(self), cloexec)
     }
 
     fn from_fd(fd: c_int) -> LinuxResult<Arc<Self>> {
@@ -233,17 +233,22 @@ pub fn sys_socket(domain: c_int, socktype: c_int, protocol: c_int) -> c_int {
     debug!("sys_socket <= {} {} {}", domain, socktype, protocol);
     let (domain, socktype, protocol) = (domain as u32, socktype as u32, protocol as u32);
     syscall_body!(sys_socket, {
-        match (domain, socktype, protocol) {
+        let cloexec = socktype & ctypes::SOCK_CLOEXEC != 0;
+        let nonblocking = socktype & ctypes::SOCK_NONBLOCK != 0;
+        let socktype = socktype & !(ctypes::SOCK_CLOEXEC | ctypes::SOCK_NONBLOCK);
+        let socket = match (domain, socktype, protocol) {
             (ctypes::AF_INET, ctypes::SOCK_STREAM, ctypes::IPPROTO_TCP)
             | (ctypes::AF_INET, ctypes::SOCK_STREAM, 0) => {
-                Socket::Tcp(Mutex::new(TcpSocket::new())).add_to_fd_table()
+                Socket::Tcp(Mutex::new(TcpSocket::new()))
             }
             (ctypes::AF_INET, ctypes::SOCK_DGRAM, ctypes::IPPROTO_UDP)
-            | (ctypes::AF_INET, ctypes::SOCK_DGRAM, 0) => {
-                Socket::Udp(Mutex::new(UdpSocket::new())).add_to_fd_table()
-            }
-            _ => Err(LinuxError::EINVAL),
+            | (ctypes::AF_INET, ctypes::SOCK_DGRAM, 0) => Socket::Udp(Mutex::new(UdpSocket::new())),
+            _ => return Err(LinuxError::EINVAL),
+        };
+        if nonblocking {
+            socket.set_nonblocking(true)?;
         }
+        socket.add_to_fd_table(cloexec)
     })
 }
 
@@ -419,7 +424,7 @@ pub unsafe fn sys_accept(
         let socket = Socket::from_fd(socket_fd)?;
         let new_socket = socket.accept()?;
         let addr = new_socket.peer_addr()?;
-        let new_fd = Socket::add_to_fd_table(Socket::Tcp(Mutex::new(new_socket)))?;
+        let new_fd = Socket::Tcp(Mutex::new(new_socket)).add_to_fd_table(false)?;
         unsafe {
             (*socket_addr, *socket_len) = into_sockaddr(addr);
         }
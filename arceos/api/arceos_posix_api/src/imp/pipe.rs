@@ -5,7 +5,7 @@ use axerrno::{LinuxError, LinuxResult};
 use axio::PollState;
 use axsync::Mutex;
 
-use super::fd_ops::{add_file_like, close_file_like, FileLike};
+use super::fd_ops::{close_file_like, current_fd_table, FileLike};
 use crate::ctypes;
 
 #[derive(Copy, Clone, PartialEq)]
@@ -195,20 +195,35 @@ impl FileLike for Pipe {
 /// Return 0 if succeed
 pub fn sys_pipe(fds: &mut [c_int]) -> c_int {
     debug!("sys_pipe <= {:#x}", fds.as_ptr() as usize);
-    syscall_body!(sys_pipe, {
-        if fds.len() != 2 {
-            return Err(LinuxError::EFAULT);
-        }
+    syscall_body!(sys_pipe, create_pipe(fds, 0))
+}
+
+/// Create a pipe with flags, which can be `O_CLOEXEC` and `O_NONBLOCK`.
+///
+/// Return 0 if succeed
+pub fn sys_pipe2(fds: &mut [c_int], flags: c_int) -> c_int {
+    debug!("sys_pipe2 <= {:#x} {:#x}", fds.as_ptr() as usize, flags);
+    syscall_body!(sys_pipe2, create_pipe(fds, flags as u32))
+}
+
+fn create_pipe(fds: &mut [c_int], flags: u32) -> LinuxResult<c_int> {
+    if fds.len() != 2 {
+        return Err(LinuxError::EFAULT);
+    }
+    if flags & !(ctypes::O_CLOEXEC | ctypes::O_NONBLOCK) != 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    let cloexec = flags & ctypes::O_CLOEXEC != 0;
 
-        let (read_end, write_end) = Pipe::new();
-        let read_fd = add_file_like(Arc::new(read_end))?;
-        let write_fd = add_file_like(Arc::new(write_end)).inspect_err(|_| {
-            close_file_like(read_fd).ok();
-        })?;
+    let table = current_fd_table();
+    let (read_end, write_end) = Pipe::new();
+    let read_fd = table.add(Arc::new(read_end), cloexec)?;
+    let write_fd = table.add(Arc::new(write_end), cloexec).inspect_err(|_| {
+        close_file_like(read_fd).ok();
+    })?;
 
-        fds[0] = read_fd as c_int;
-        fds[1] = write_fd as c_int;
+    fds[0] = read_fd as c_int;
+    fds[1] = write_fd as c_int;
 
-        Ok(0)
-    })
+    Ok(0)
 }
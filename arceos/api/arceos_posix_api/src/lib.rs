@@ -37,9 +37,16 @@ pub use imp::task::{sys_exit, sys_getpid, sys_sched_yield};
 pub use imp::time::{sys_clock_gettime, sys_nanosleep};
 
 #[cfg(feature = "fd")]
-pub use imp::fd_ops::{sys_close, sys_dup, sys_dup2, sys_fcntl, get_file_like};
+pub use imp::fd_ops::{
+    add_file_like, current_fd_table, get_file_like, sys_close, sys_dup, sys_dup2, sys_fcntl,
+    FdTable, FileLike, CURRENT_FD_TABLE,
+};
 #[cfg(feature = "fs")]
-pub use imp::fs::{sys_fstat, sys_getcwd, sys_lseek, sys_lstat, sys_open, sys_rename, sys_stat};
+pub use imp::fs::{
+    sys_chdir, sys_faccessat, sys_fchdir, sys_fstat, sys_fstatat, sys_fsync, sys_ftruncate,
+    sys_getcwd, sys_getdents64, sys_lseek, sys_lstat, sys_mkdirat, sys_open, sys_openat,
+    sys_rename, sys_renameat2, sys_stat, sys_unlinkat, sys_utimensat, CURRENT_DIR,
+};
 #[cfg(feature = "select")]
 pub use imp::io_mpx::sys_select;
 #[cfg(feature = "epoll")]
@@ -51,7 +58,7 @@ pub use imp::net::{
     sys_socket,
 };
 #[cfg(feature = "pipe")]
-pub use imp::pipe::sys_pipe;
+pub use imp::pipe::{sys_pipe, sys_pipe2};
 #[cfg(feature = "multitask")]
 pub use imp::pthread::mutex::{
     sys_pthread_mutex_init, sys_pthread_mutex_lock, sys_pthread_mutex_unlock,
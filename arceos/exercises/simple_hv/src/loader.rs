@@ -1,37 +1,32 @@
-use std::io::{self, Read};
-use std::fs::File;
+use alloc::vec::Vec;
 use axhal::paging::MappingFlags;
-use axhal::mem::{PAGE_SIZE_4K, phys_to_virt};
 use axmm::AddrSpace;
-use crate::VM_ENTRY;
+use axvmconfig::VmConfig;
+use std::io;
 
-pub fn load_vm_image(fname: &str, uspace: &mut AddrSpace) -> io::Result<()> {
-    let mut buf = [0u8; 64];
-    load_file(fname, &mut buf)?;
-
-    uspace.map_alloc(VM_ENTRY.into(), PAGE_SIZE_4K, MappingFlags::READ|MappingFlags::WRITE|MappingFlags::EXECUTE|MappingFlags::USER, true).unwrap();
-
-    let (paddr, _, _) = uspace
-        .page_table()
-        .query(VM_ENTRY.into())
-        .unwrap_or_else(|_| panic!("Mapping failed for segment: {:#x}", VM_ENTRY));
+pub fn load_vm_config(fname: &str) -> io::Result<VmConfig> {
+    ax_println!("config: {}", fname);
+    let text = std::fs::read_to_string(fname)?;
+    VmConfig::from_toml(&text)
+}
 
-    ax_println!("paddr: {:#x}", paddr);
+pub fn load_vm_image(config: &VmConfig, uspace: &mut AddrSpace) -> io::Result<()> {
+    let flags =
+        MappingFlags::READ | MappingFlags::WRITE | MappingFlags::EXECUTE | MappingFlags::USER;
+    for region in config.memory.iter() {
+        uspace.map_alloc(region.gpa.into(), region.size, flags, true)?;
+    }
 
-    unsafe {
-        core::ptr::copy_nonoverlapping(
-            buf.as_ptr(),
-            phys_to_virt(paddr).as_mut_ptr(),
-            PAGE_SIZE_4K,
-        );
+    for image in config.images.iter() {
+        let buf = load_file(&image.path)?;
+        ax_println!("load_addr: {:#x}", image.load_addr);
+        uspace.write(image.load_addr.into(), &buf)?;
     }
 
     Ok(())
 }
 
-fn load_file(fname: &str, buf: &mut [u8]) -> io::Result<usize> {
+fn load_file(fname: &str) -> io::Result<Vec<u8>> {
     ax_println!("app: {}", fname);
-    let mut file = File::open(fname)?;
-    let n = file.read(buf)?;
-    Ok(n)
+    std::fs::read(fname)
 }
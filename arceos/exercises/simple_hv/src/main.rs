@@ -22,12 +22,13 @@ use csrs::defs::hstatus;
 use tock_registers::LocalRegisterCopy;
 use csrs::{RiscvCsrTrait, CSR};
 use vcpu::_run_guest;
-use sbi::SbiMessage;
-use loader::load_vm_image;
+use sbi::{SbiMessage, SBI_ERR_NOT_SUPPORTED};
+use loader::{load_vm_config, load_vm_image};
 use axhal::mem::PhysAddr;
 use crate::regs::GprIndex::{A0, A1};
 
-const VM_ENTRY: usize = 0x8020_0000;
+/// The layout of the vm, i.e., its memory, images and entry.
+const VM_CONFIG: &str = "/vm/simple_hv.toml";
 
 #[cfg_attr(feature = "axstd", no_mangle)]
 fn main() {
@@ -36,14 +37,20 @@ fn main() {
     // A new address space for vm.
     let mut uspace = axmm::new_user_aspace().unwrap();
 
+    // Read the layout of the vm.
+    let config = match load_vm_config(VM_CONFIG) {
+        Ok(config) => config,
+        Err(e) => panic!("Cannot load config! {:?}", e),
+    };
+
     // Load vm binary file into address space.
-    if let Err(e) = load_vm_image("/sbin/skernel2", &mut uspace) {
+    if let Err(e) = load_vm_image(&config, &mut uspace) {
         panic!("Cannot load app! {:?}", e);
     }
 
     // Setup context to prepare to enter guest mode.
     let mut ctx = VmCpuRegisters::default();
-    prepare_guest_context(&mut ctx);
+    prepare_guest_context(&mut ctx, config.entry);
 
     // Setup pagetable for 2nd address mapping.
     let ept_root = uspace.page_table_root();
@@ -95,10 +102,10 @@ fn vmexit_handler(ctx: &mut VmCpuRegisters) -> bool {
                         ax_println!("Shutdown vm normally!");
                         return true;
                     },
-                    _ => todo!(),
+                    _ => not_supported(ctx),
                 }
             } else {
-                panic!("bad sbi message! ");
+                not_supported(ctx);
             }
         },
         Trap::Exception(Exception::IllegalInstruction) => {
@@ -125,7 +132,15 @@ fn vmexit_handler(ctx: &mut VmCpuRegisters) -> bool {
     false
 }
 
-fn prepare_guest_context(ctx: &mut VmCpuRegisters) {
+/// Fails the SBI call of the guest with `SBI_ERR_NOT_SUPPORTED`, and resumes
+/// it after the `ecall`.
+fn not_supported(ctx: &mut VmCpuRegisters) {
+    ctx.guest_regs.gprs.set_reg(A0, SBI_ERR_NOT_SUPPORTED as usize);
+    ctx.guest_regs.gprs.set_reg(A1, 0);
+    ctx.guest_regs.sepc += 4;
+}
+
+fn prepare_guest_context(ctx: &mut VmCpuRegisters, entry: usize) {
     // Set hstatus
     let mut hstatus = LocalRegisterCopy::<usize, hstatus::Register>::new(
         riscv::register::hstatus::read().bits(),
@@ -142,5 +157,5 @@ fn prepare_guest_context(ctx: &mut VmCpuRegisters) {
     sstatus.set_spp(sstatus::SPP::Supervisor);
     ctx.guest_regs.sstatus = sstatus.bits();
     // Return to entry to start vm.
-    ctx.guest_regs.sepc = VM_ENTRY;
+    ctx.guest_regs.sepc = entry;
 }
@@ -0,0 +1,251 @@
+//! File system syscalls.
+//!
+//! Paths and buffers are copied between user space and the kernel, and the
+//! work is done by the POSIX API, which resolves the paths relative to the
+//! `dirfd` arguments.
+
+use core::ffi::{c_char, c_int, c_void};
+
+use alloc::vec;
+use alloc::vec::Vec;
+use arceos_posix_api::{self as api, ctypes};
+use axerrno::{LinuxError, LinuxResult};
+use axhal::mem::VirtAddr;
+use axhal::paging::MappingFlags;
+use axmm::{copy_to_user, strncpy_from_user, UserPtr};
+use axtask::{current, TaskExtRef};
+
+use axsyscall::syscall_body;
+
+/// Maximum length of a path, including the terminating NUL.
+const PATH_MAX: usize = 4096;
+
+/// Maximum size of the buffer of `getdents64`. Larger buffers are only
+/// partially filled, which is fine as the caller loops until the end.
+const DIRENTS_BUF_MAX: usize = 4096;
+
+/// `struct stat` of the asm-generic syscall ABI, used by riscv64 and aarch64.
+#[repr(C)]
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Kstat {
+    st_dev: u64,
+    st_ino: u64,
+    st_mode: u32,
+    st_nlink: u32,
+    st_uid: u32,
+    st_gid: u32,
+    st_rdev: u64,
+    __pad1: u64,
+    st_size: i64,
+    st_blksize: i32,
+    __pad2: i32,
+    st_blocks: i64,
+    st_atime_sec: i64,
+    st_atime_nsec: i64,
+    st_mtime_sec: i64,
+    st_mtime_nsec: i64,
+    st_ctime_sec: i64,
+    st_ctime_nsec: i64,
+    __unused: [u32; 2],
+}
+
+impl From<ctypes::stat> for Kstat {
+    fn from(st: ctypes::stat) -> Self {
+        Self {
+            st_dev: st.st_dev as _,
+            st_ino: st.st_ino as _,
+            st_mode: st.st_mode as _,
+            st_nlink: st.st_nlink as _,
+            st_uid: st.st_uid as _,
+            st_gid: st.st_gid as _,
+            st_rdev: st.st_rdev as _,
+            st_size: st.st_size as _,
+            st_blksize: st.st_blksize as _,
+            st_blocks: st.st_blocks as _,
+            st_atime_sec: st.st_atime.tv_sec as _,
+            st_atime_nsec: st.st_atime.tv_nsec as _,
+            st_mtime_sec: st.st_mtime.tv_sec as _,
+            st_mtime_nsec: st.st_mtime.tv_nsec as _,
+            st_ctime_sec: st.st_ctime.tv_sec as _,
+            st_ctime_nsec: st.st_ctime.tv_nsec as _,
+            ..Default::default()
+        }
+    }
+}
+
+/// Copies the NUL-terminated path at `path` from user space. The returned
+/// buffer includes the NUL.
+fn read_path(path: *const c_char) -> LinuxResult<Vec<u8>> {
+    let mut buf = vec![0u8; PATH_MAX];
+    let len = strncpy_from_user(
+        &mut current().task_ext().aspace.lock(),
+        &mut buf,
+        VirtAddr::from(path as usize),
+    )?;
+    if len == PATH_MAX {
+        return Err(LinuxError::ENAMETOOLONG);
+    }
+    buf.truncate(len + 1);
+    Ok(buf)
+}
+
+pub fn sys_openat(dirfd: c_int, path: *const c_char, flags: c_int, mode: ctypes::mode_t) -> isize {
+    syscall_body!(sys_openat, {
+        let path = read_path(path)?;
+        Ok(api::sys_openat(dirfd, path.as_ptr() as _, flags, mode))
+    })
+}
+
+pub fn sys_getdents64(fd: c_int, dirp: *mut c_void, count: usize) -> isize {
+    syscall_body!(sys_getdents64, {
+        let curr = current();
+        let ubuf = VirtAddr::from(dirp as usize);
+        let count = count.min(DIRENTS_BUF_MAX);
+        curr.task_ext()
+            .aspace
+            .lock()
+            .check_user_access(ubuf, count, MappingFlags::WRITE)?;
+        let mut kbuf = vec![0u8; count];
+        let n = unsafe { api::sys_getdents64(fd, kbuf.as_mut_ptr() as _, count) };
+        if n > 0 {
+            copy_to_user(
+                &mut curr.task_ext().aspace.lock(),
+                ubuf,
+                &kbuf[..n as usize],
+            )?;
+        }
+        Ok(n)
+    })
+}
+
+pub fn sys_newfstatat(
+    dirfd: c_int,
+    path: *const c_char,
+    statbuf: *mut Kstat,
+    flags: c_int,
+) -> isize {
+    syscall_body!(sys_newfstatat, {
+        let path = read_path(path)?;
+        let mut st = ctypes::stat::default();
+        let ret = unsafe { api::sys_fstatat(dirfd, path.as_ptr() as _, &mut st, flags) };
+        if ret == 0 {
+            UserPtr::from(statbuf).write(&mut current().task_ext().aspace.lock(), st.into())?;
+        }
+        Ok(ret)
+    })
+}
+
+pub fn sys_fstat(fd: c_int, statbuf: *mut Kstat) -> isize {
+    syscall_body!(sys_fstat, {
+        let mut st = ctypes::stat::default();
+        let ret = unsafe { api::sys_fstat(fd, &mut st) };
+        if ret == 0 {
+            UserPtr::from(statbuf).write(&mut current().task_ext().aspace.lock(), st.into())?;
+        }
+        Ok(ret)
+    })
+}
+
+pub fn sys_mkdirat(dirfd: c_int, path: *const c_char, mode: ctypes::mode_t) -> isize {
+    syscall_body!(sys_mkdirat, {
+        let path = read_path(path)?;
+        Ok(api::sys_mkdirat(dirfd, path.as_ptr() as _, mode))
+    })
+}
+
+pub fn sys_unlinkat(dirfd: c_int, path: *const c_char, flags: c_int) -> isize {
+    syscall_body!(sys_unlinkat, {
+        let path = read_path(path)?;
+        Ok(api::sys_unlinkat(dirfd, path.as_ptr() as _, flags))
+    })
+}
+
+pub fn sys_renameat2(
+    olddirfd: c_int,
+    oldpath: *const c_char,
+    newdirfd: c_int,
+    newpath: *const c_char,
+    flags: c_int,
+) -> isize {
+    syscall_body!(sys_renameat2, {
+        let oldpath = read_path(oldpath)?;
+        let newpath = read_path(newpath)?;
+        Ok(api::sys_renameat2(
+            olddirfd,
+            oldpath.as_ptr() as _,
+            newdirfd,
+            newpath.as_ptr() as _,
+            flags,
+        ))
+    })
+}
+
+pub fn sys_ftruncate(fd: c_int, length: ctypes::off_t) -> isize {
+    api::sys_ftruncate(fd, length) as _
+}
+
+pub fn sys_fsync(fd: c_int) -> isize {
+    api::sys_fsync(fd) as _
+}
+
+pub fn sys_chdir(path: *const c_char) -> isize {
+    syscall_body!(sys_chdir, {
+        let path = read_path(path)?;
+        Ok(api::sys_chdir(path.as_ptr() as _))
+    })
+}
+
+pub fn sys_fchdir(fd: c_int) -> isize {
+    api::sys_fchdir(fd) as _
+}
+
+pub fn sys_getcwd(buf: *mut c_char, size: usize) -> isize {
+    syscall_body!(sys_getcwd, {
+        let curr = current();
+        let mut cwd = curr.task_ext().process.cwd().lock().clone().into_bytes();
+        cwd.push(0);
+        if cwd.len() > size {
+            return Err(LinuxError::ERANGE);
+        }
+        copy_to_user(
+            &mut curr.task_ext().aspace.lock(),
+            VirtAddr::from(buf as usize),
+            &cwd,
+        )?;
+        Ok(cwd.len() as isize)
+    })
+}
+
+pub fn sys_faccessat(dirfd: c_int, path: *const c_char, mode: c_int) -> isize {
+    sys_faccessat2(dirfd, path, mode, 0)
+}
+
+pub fn sys_faccessat2(dirfd: c_int, path: *const c_char, mode: c_int, flags: c_int) -> isize {
+    syscall_body!(sys_faccessat2, {
+        let path = read_path(path)?;
+        Ok(api::sys_faccessat(dirfd, path.as_ptr() as _, mode, flags))
+    })
+}
+
+pub fn sys_utimensat(
+    dirfd: c_int,
+    path: *const c_char,
+    times: *const [ctypes::timespec; 2],
+    flags: c_int,
+) -> isize {
+    syscall_body!(sys_utimensat, {
+        let curr = current();
+        let times = if times.is_null() {
+            None
+        } else {
+            Some(UserPtr::from(times).read(&mut curr.task_ext().aspace.lock())?)
+        };
+        let times = times.as_ref().map_or(core::ptr::null(), |t| t.as_ptr());
+        if path.is_null() {
+            return Ok(api::sys_utimensat(dirfd, core::ptr::null(), times, flags));
+        }
+        let path = read_path(path)?;
+        Ok(api::sys_utimensat(dirfd, path.as_ptr() as _, times, flags))
+    })
+}
@@ -10,51 +10,73 @@ extern crate axlog;
 
 mod task;
 mod syscall;
-mod loader;
+mod fs;
+mod shm;
+mod process;
+mod mm;
+mod signal;
 
 use axstd::io;
 use axhal::paging::MappingFlags;
 use axhal::arch::UspaceContext;
 use axhal::mem::VirtAddr;
-use axsync::Mutex;
-use alloc::sync::Arc;
+use axhal::trap::{register_trap_handler, UNHANDLED_PAGE_FAULT};
+use axtask::TaskExtRef;
 use alloc::string::String;
 use alloc::collections::BTreeMap;
 use axmm::AddrSpace;
-use loader::load_user_app;
 
 const USER_STACK_SIZE: usize = 0x10000;
 const KERNEL_STACK_SIZE: usize = 0x40000; // 256 KiB
 
+/// The first user app, which can be chosen with `SYS_MAP_APP` at build time.
+const APP_PATH: &str = match option_env!("SYS_MAP_APP") {
+    Some(path) => path,
+    None => "/sbin/mapfile",
+};
+
 #[cfg_attr(feature = "axstd", no_mangle)]
 fn main() {
+    // Pages of any process can be swapped out when another one needs memory.
+    axmm::set_reclaim_handler(task::reclaim_user_pages);
+
     // A new address space for user app.
     let mut uspace = axmm::new_user_aspace().unwrap();
 
     // Load user app binary file into address space.
-    let entry = match load_user_app("/sbin/mapfile", &mut uspace) {
-        Ok(e) => e,
+    let app_path = APP_PATH;
+    let app = match axloader::load_user_app(app_path, &mut uspace) {
+        Ok(app) => app,
         Err(err) => panic!("Cannot load app! {:?}", err),
     };
-    ax_println!("entry: {:#x}", entry);
+    ax_println!("entry: {:#x}", app.entry);
 
     // Init user stack.
-    let ustack_top = init_user_stack(&mut uspace, true).unwrap();
+    let ustack_top =
+        init_user_stack(&mut uspace, &[String::from(app_path)], &[], &app.auxv, true).unwrap();
     ax_println!("New user address space: {:#x?}", uspace);
 
     // Let's kick off the user process.
-    let user_task = task::spawn_user_task(
-        Arc::new(Mutex::new(uspace)),
-        UspaceContext::new(entry, ustack_top),
+    let user_task = task::spawn_init_task(
+        task::share_aspace(uspace),
+        UspaceContext::new(app.entry.as_usize(), ustack_top),
+        app.brk_start,
     );
 
     // Wait for user process to exit ...
     let exit_code = user_task.join();
+    axsyscall::print_summary();
     ax_println!("monolithic kernel exit [{:?}] normally!", exit_code);
 }
 
-fn init_user_stack(uspace: &mut AddrSpace, populating: bool) -> io::Result<VirtAddr> {
-    let ustack_top = uspace.end();
+fn init_user_stack(
+    uspace: &mut AddrSpace,
+    args: &[String],
+    envs: &[String],
+    auxv: &BTreeMap<u8, usize>,
+    populating: bool,
+) -> io::Result<VirtAddr> {
+    let ustack_top = uspace.layout().stack_top;
     let ustack_vaddr = ustack_top - crate::USER_STACK_SIZE;
     ax_println!(
         "Mapping user stack: {:#x?} -> {:#x?}",
@@ -65,18 +87,29 @@ fn init_user_stack(uspace: &mut AddrSpace, populating: bool) -> io::Result<VirtA
         crate::USER_STACK_SIZE,
         MappingFlags::READ | MappingFlags::WRITE | MappingFlags::USER,
         populating,
-    ).unwrap();
-
-    let app_name = "hello";
-    let av = BTreeMap::new();
-    let (stack_data, ustack_pointer) = kernel_elf_parser::get_app_stack_region(
-        &[String::from(app_name)],
-        &[],
-        &av,
-        ustack_vaddr,
-        crate::USER_STACK_SIZE,
-    );
-    uspace.write(VirtAddr::from_usize(ustack_pointer), stack_data.as_slice())?;
+    )?;
 
-    Ok(ustack_pointer.into())
+    let (stack_data, ustack_pointer) = axloader::build_user_stack(args, envs, auxv, ustack_top);
+    uspace.write(ustack_pointer, stack_data.as_slice())?;
+
+    Ok(ustack_pointer)
+}
+
+#[register_trap_handler(UNHANDLED_PAGE_FAULT)]
+fn report_page_fault(vaddr: VirtAddr, access_flags: MappingFlags, is_user: bool) {
+    let curr = axtask::current();
+    if unsafe { curr.task_ext_ptr() }.is_null() {
+        return; // not a user task
+    }
+    ax_println!(
+        "{}: unhandled {} page fault @ {:#x} ({:?})",
+        curr.id_name(),
+        if is_user { "user" } else { "kernel" },
+        vaddr,
+        access_flags
+    );
+    match curr.task_ext().aspace.try_lock() {
+        Some(aspace) => ax_println!("{}", aspace.dump()),
+        None => ax_println!("(address space is locked, cannot dump)"),
+    }
 }
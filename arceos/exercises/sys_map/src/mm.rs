@@ -0,0 +1,434 @@
+//! Memory mapping syscalls: `mmap`, `munmap`, `mprotect`, `madvise` and `brk`.
+//!
+//! Anonymous private mappings are allocated on demand, and shared ones are
+//! backed by [`SharedPages`], so that they stay shared after `fork`. Private
+//! file mappings get a copy of the file contents, read in chunks before the
+//! address space is touched. Shared mappings of a memfd map its pages directly.
+//! Other files cannot be mapped shared and writable, as the changes could not
+//! be written back.
+
+use core::ffi::c_int;
+
+use alloc::sync::Arc;
+use alloc::vec;
+use arceos_posix_api::{self as api, FileLike};
+use axerrno::{LinuxError, LinuxResult};
+use axhal::mem::{MemoryAddr, VirtAddr, PAGE_SIZE_4K};
+use axhal::paging::{MappingFlags, PageSize};
+use axmm::{AddrSpace, SharedPages};
+use axtask::{current, TaskExtRef};
+use memory_addr::VirtAddrRange;
+
+use crate::shm::MemFd;
+use axsyscall::syscall_body;
+
+/// The bits of the mmap flags for the mapping type.
+const MAP_TYPE: i32 = 0x0f;
+/// Like `MAP_SHARED`, but fails on unknown flags.
+const MAP_SHARED_VALIDATE: i32 = 0x03;
+
+/// No special treatment.
+const MADV_NORMAL: i32 = 0;
+/// Expect random page references.
+const MADV_RANDOM: i32 = 1;
+/// Expect sequential page references.
+const MADV_SEQUENTIAL: i32 = 2;
+/// Will need these pages.
+const MADV_WILLNEED: i32 = 3;
+/// Don't need these pages.
+const MADV_DONTNEED: i32 = 4;
+/// Free the pages only if memory pressure.
+const MADV_FREE: i32 = 8;
+
+/// Size of the kernel buffer to copy the file contents into a mapping.
+const FILE_CHUNK_SIZE: usize = 16 * PAGE_SIZE_4K;
+
+bitflags::bitflags! {
+    #[derive(Debug, Clone, Copy)]
+    /// permissions for sys_mmap
+    ///
+    /// See <https://github.com/bminor/glibc/blob/master/bits/mman.h>
+    struct MmapProt: i32 {
+        /// Page can be read.
+        const PROT_READ = 1 << 0;
+        /// Page can be written.
+        const PROT_WRITE = 1 << 1;
+        /// Page can be executed.
+        const PROT_EXEC = 1 << 2;
+    }
+}
+
+impl From<MmapProt> for MappingFlags {
+    fn from(value: MmapProt) -> Self {
+        let mut flags = MappingFlags::USER;
+        if value.contains(MmapProt::PROT_READ) {
+            flags |= MappingFlags::READ;
+        }
+        if value.contains(MmapProt::PROT_WRITE) {
+            flags |= MappingFlags::WRITE;
+        }
+        if value.contains(MmapProt::PROT_EXEC) {
+            flags |= MappingFlags::EXECUTE;
+        }
+        flags
+    }
+}
+
+bitflags::bitflags! {
+    #[derive(Debug, Clone, Copy)]
+    /// flags for sys_mmap
+    ///
+    /// See <https://github.com/bminor/glibc/blob/master/bits/mman.h>
+    struct MmapFlags: i32 {
+        /// Share changes
+        const MAP_SHARED = 1 << 0;
+        /// Changes private; copy pages on write.
+        const MAP_PRIVATE = 1 << 1;
+        /// Map address must be exactly as requested, no matter whether it is available.
+        const MAP_FIXED = 1 << 4;
+        /// Don't use a file.
+        const MAP_ANONYMOUS = 1 << 5;
+        /// Stack-like segment.
+        const MAP_GROWSDOWN = 0x100;
+        /// Ignored.
+        const MAP_DENYWRITE = 0x800;
+        /// Ignored.
+        const MAP_EXECUTABLE = 0x1000;
+        /// Lock the mapping.
+        const MAP_LOCKED = 0x2000;
+        /// Don't check for reservations.
+        const MAP_NORESERVE = 1 << 14;
+        /// Populate (prefault) the page tables.
+        const MAP_POPULATE = 0x8000;
+        /// Do not block on IO.
+        const MAP_NONBLOCK = 0x10000;
+        /// Allocation is for a stack.
+        const MAP_STACK = 0x20000;
+        /// Create a mapping backed by huge pages.
+        const MAP_HUGETLB = 0x40000;
+        /// Like `MAP_FIXED`, but fails if the range is not free.
+        const MAP_FIXED_NOREPLACE = 0x100000;
+    }
+}
+
+/// Where the pages of a new mapping come from.
+enum MmapSource {
+    /// Zero pages allocated on demand.
+    Anonymous,
+    /// Pages shared with other mappings, from the byte offset.
+    Shared(Arc<SharedPages>, usize),
+    /// A copy of the file contents, followed by zero pages on demand.
+    File(SharedPages),
+}
+
+/// Returns the range `[start, start + size)` if it is within `aspace`.
+fn user_range(aspace: &AddrSpace, start: usize, size: usize) -> Option<VirtAddr> {
+    start.checked_add(size)?;
+    let start = VirtAddr::from(start);
+    aspace.contains_range(start, size).then_some(start)
+}
+
+/// Whether there are no mappings in `[start, start + size)`.
+fn is_free(aspace: &AddrSpace, start: VirtAddr, size: usize) -> bool {
+    let limit = VirtAddrRange::from_start_size(aspace.base(), aspace.size());
+    aspace.find_free_area(start, size, limit) == Some(start)
+}
+
+/// Reads `buf.len()` bytes of the file from `offset`, or until the end of it.
+fn read_file(file: &dyn FileLike, offset: u64, buf: &mut [u8]) -> LinuxResult<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        let n = match file.read_at(offset + read as u64, &mut buf[read..]) {
+            Ok(0) => break,
+            Ok(n) => n,
+            // The file cannot be mapped.
+            Err(LinuxError::ESPIPE) => return Err(LinuxError::ENODEV),
+            Err(err) => return Err(err),
+        };
+        read += n;
+    }
+    Ok(read)
+}
+
+/// Reads at most `size` bytes of the file from `offset` into new pages, for a
+/// private copy of the file contents.
+///
+/// The file is read in chunks, and only the pages holding its contents are
+/// allocated.
+fn read_file_pages(file: &dyn FileLike, offset: u64, size: usize) -> LinuxResult<SharedPages> {
+    let pages = SharedPages::new(0).map_err(|_| LinuxError::ENOMEM)?;
+    let mut buf = vec![0u8; FILE_CHUNK_SIZE.min(size)];
+    let mut copied = 0;
+    while copied < size {
+        let chunk = (size - copied).min(buf.len());
+        let n = read_file(file, offset + copied as u64, &mut buf[..chunk])?;
+        if n == 0 {
+            break;
+        }
+        pages
+            .grow((copied + n).div_ceil(PAGE_SIZE_4K))
+            .map_err(|_| LinuxError::ENOMEM)?;
+        pages.write(copied, &buf[..n]);
+        if n < chunk {
+            break;
+        }
+        copied += n;
+    }
+    Ok(pages)
+}
+
+/// Finds a free area of `size` bytes aligned to `align`, starting from `hint`.
+fn find_free_area(
+    aspace: &AddrSpace,
+    hint: VirtAddr,
+    size: usize,
+    align: usize,
+) -> Option<VirtAddr> {
+    let limit = VirtAddrRange::from_start_size(aspace.base(), aspace.size());
+    let search_size = size.checked_add(align - PAGE_SIZE_4K)?;
+    aspace
+        .find_free_area(hint.align_up(align), search_size, limit)
+        .map(|start| start.align_up(align))
+}
+
+fn mmap(
+    addr: usize,
+    length: usize,
+    prot: i32,
+    flags: i32,
+    fd: c_int,
+    offset: isize,
+) -> LinuxResult<usize> {
+    let prot = MmapProt::from_bits(prot).ok_or(LinuxError::EINVAL)?;
+    let shared = match flags & MAP_TYPE {
+        MAP_SHARED_VALIDATE => {
+            if MmapFlags::from_bits(flags).is_none() {
+                return Err(LinuxError::EOPNOTSUPP);
+            }
+            true
+        }
+        ty if ty == MmapFlags::MAP_SHARED.bits() => true,
+        ty if ty == MmapFlags::MAP_PRIVATE.bits() => false,
+        _ => return Err(LinuxError::EINVAL),
+    };
+    let flags = MmapFlags::from_bits_truncate(flags);
+    let anonymous = flags.contains(MmapFlags::MAP_ANONYMOUS);
+    let fixed = flags.intersects(MmapFlags::MAP_FIXED | MmapFlags::MAP_FIXED_NOREPLACE);
+    let page_size = if flags.contains(MmapFlags::MAP_HUGETLB) {
+        // Only private anonymous mappings can use huge pages.
+        if shared || !anonymous {
+            return Err(LinuxError::EINVAL);
+        }
+        PageSize::Size2M
+    } else {
+        PageSize::Size4K
+    };
+    let align: usize = page_size.into();
+
+    if length == 0 || offset < 0 || offset as usize % PAGE_SIZE_4K != 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    if fixed && addr % align != 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    let len = length
+        .checked_next_multiple_of(align)
+        .ok_or(LinuxError::ENOMEM)?;
+
+    // Prepare the pages before touching the address space, so an existing
+    // mapping is not replaced if it fails.
+    let source = if anonymous {
+        if shared {
+            let pages = SharedPages::new(len / PAGE_SIZE_4K).map_err(|_| LinuxError::ENOMEM)?;
+            MmapSource::Shared(Arc::new(pages), 0)
+        } else {
+            MmapSource::Anonymous
+        }
+    } else {
+        let file = api::get_file_like(fd)?;
+        match file.clone().into_any().downcast::<MemFd>() {
+            Ok(memfd) if shared => {
+                let offset = offset as usize;
+                let num_pages =
+                    offset.checked_add(len).ok_or(LinuxError::EOVERFLOW)? / PAGE_SIZE_4K;
+                let pages = memfd.pages();
+                pages.grow(num_pages).map_err(|_| LinuxError::ENOMEM)?;
+                MmapSource::Shared(pages, offset)
+            }
+            _ => {
+                // A read-only shared mapping is the same as a copy, as long as
+                // the file does not change.
+                if shared && prot.contains(MmapProt::PROT_WRITE) {
+                    warn!("mmap: writable shared mappings of files are not supported");
+                    return Err(LinuxError::ENODEV);
+                }
+                MmapSource::File(read_file_pages(file.as_ref(), offset as u64, len)?)
+            }
+        }
+    };
+
+    let curr = current();
+    let mut aspace = curr.task_ext().aspace.lock();
+    let start = if fixed {
+        let start = user_range(&aspace, addr, len).ok_or(LinuxError::ENOMEM)?;
+        if !is_free(&aspace, start, len) {
+            if flags.contains(MmapFlags::MAP_FIXED_NOREPLACE) {
+                return Err(LinuxError::EEXIST);
+            }
+            aspace.unmap(start, len)?;
+        }
+        start
+    } else {
+        // Use the hint if the range is free, otherwise search from the mmap
+        // base, like Linux does.
+        addr.checked_next_multiple_of(align)
+            .filter(|&hint| hint != 0)
+            .and_then(|hint| user_range(&aspace, hint, len))
+            .filter(|&hint| is_free(&aspace, hint, len))
+            .or_else(|| find_free_area(&aspace, aspace.layout().mmap_base, len, align))
+            .ok_or(LinuxError::ENOMEM)?
+    };
+
+    let mapping_flags = MappingFlags::from(prot);
+    let populate = flags.contains(MmapFlags::MAP_POPULATE);
+    match source {
+        MmapSource::Anonymous => {
+            aspace.map_alloc_huge(start, len, mapping_flags, populate, page_size)?
+        }
+        MmapSource::Shared(pages, offset) => {
+            aspace.map_shared(start, len, mapping_flags, pages, offset)?
+        }
+        MmapSource::File(pages) => aspace.map_alloc_from(start, len, mapping_flags, pages)?,
+    }
+    Ok(start.as_usize())
+}
+
+fn munmap(addr: usize, length: usize) -> LinuxResult<isize> {
+    if addr % PAGE_SIZE_4K != 0 || length == 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    let len = length
+        .checked_next_multiple_of(PAGE_SIZE_4K)
+        .ok_or(LinuxError::EINVAL)?;
+    let curr = current();
+    let mut aspace = curr.task_ext().aspace.lock();
+    let start = user_range(&aspace, addr, len).ok_or(LinuxError::EINVAL)?;
+    aspace.unmap(start, len)?;
+    Ok(0)
+}
+
+fn mprotect(addr: usize, length: usize, prot: i32) -> LinuxResult<isize> {
+    let prot = MmapProt::from_bits(prot).ok_or(LinuxError::EINVAL)?;
+    if addr % PAGE_SIZE_4K != 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    if length == 0 {
+        return Ok(0);
+    }
+    let len = length
+        .checked_next_multiple_of(PAGE_SIZE_4K)
+        .ok_or(LinuxError::ENOMEM)?;
+    let curr = current();
+    let mut aspace = curr.task_ext().aspace.lock();
+    let start = user_range(&aspace, addr, len).ok_or(LinuxError::ENOMEM)?;
+    if !aspace.is_mapped(start, len) {
+        return Err(LinuxError::ENOMEM);
+    }
+    aspace.protect(start, len, prot.into())?;
+    Ok(0)
+}
+
+fn madvise(addr: usize, length: usize, advice: i32) -> LinuxResult<isize> {
+    let discard = match advice {
+        MADV_NORMAL | MADV_RANDOM | MADV_SEQUENTIAL | MADV_WILLNEED => false,
+        // The pages can be freed right away, as there is no memory pressure
+        // tracking to defer it.
+        MADV_DONTNEED | MADV_FREE => true,
+        _ => return Err(LinuxError::EINVAL),
+    };
+    if addr % PAGE_SIZE_4K != 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    if length == 0 {
+        return Ok(0);
+    }
+    let len = length
+        .checked_next_multiple_of(PAGE_SIZE_4K)
+        .ok_or(LinuxError::EINVAL)?;
+    let curr = current();
+    let mut aspace = curr.task_ext().aspace.lock();
+    let start = user_range(&aspace, addr, len).ok_or(LinuxError::ENOMEM)?;
+    if !aspace.is_mapped(start, len) {
+        return Err(LinuxError::ENOMEM);
+    }
+    if discard {
+        aspace.discard(start, len)?;
+    }
+    Ok(0)
+}
+
+fn brk(addr: usize) -> LinuxResult<usize> {
+    let curr = current();
+    let ext = curr.task_ext();
+    // The address space lock also serializes the updates of the break.
+    let mut aspace = ext.aspace.lock();
+    let (start, end) = ext.process.brk();
+    let new_end = VirtAddr::from(addr);
+    if new_end < start {
+        // Also for `brk(0)`, which queries the current break.
+        return Ok(end.as_usize());
+    }
+
+    let old_top = end.align_up_4k();
+    let new_top = new_end.align_up_4k();
+    let res = if new_top > old_top {
+        let flags = MappingFlags::READ | MappingFlags::WRITE | MappingFlags::USER;
+        if aspace.contains_range(old_top, new_top - old_top) {
+            aspace.map_alloc(old_top, new_top - old_top, flags, false)
+        } else {
+            Err(axerrno::AxError::NoMemory)
+        }
+    } else if new_top < old_top {
+        aspace.unmap(new_top, old_top - new_top)
+    } else {
+        Ok(())
+    };
+    // On failure, the current break is returned, like Linux does.
+    match res {
+        Ok(()) => {
+            ext.process.set_brk(start, new_end);
+            Ok(new_end.as_usize())
+        }
+        Err(err) => {
+            debug!("brk: cannot move the break to {:#x}: {:?}", new_end, err);
+            Ok(end.as_usize())
+        }
+    }
+}
+
+pub(crate) fn sys_mmap(
+    addr: usize,
+    length: usize,
+    prot: i32,
+    flags: i32,
+    fd: c_int,
+    offset: isize,
+) -> isize {
+    syscall_body!(sys_mmap, mmap(addr, length, prot, flags, fd, offset))
+}
+
+pub(crate) fn sys_munmap(addr: usize, length: usize) -> isize {
+    syscall_body!(sys_munmap, munmap(addr, length))
+}
+
+pub(crate) fn sys_mprotect(addr: usize, length: usize, prot: i32) -> isize {
+    syscall_body!(sys_mprotect, mprotect(addr, length, prot))
+}
+
+pub(crate) fn sys_madvise(addr: usize, length: usize, advice: i32) -> isize {
+    syscall_body!(sys_madvise, madvise(addr, length, advice))
+}
+
+pub(crate) fn sys_brk(addr: usize) -> isize {
+    syscall_body!(sys_brk, brk(addr))
+}
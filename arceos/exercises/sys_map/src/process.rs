@@ -0,0 +1,674 @@
+//! Processes and the process-related syscalls.
+//!
+//! A process is a thread group sharing an address space. Its pid is the task
+//! ID of the first thread, and the task ID of each thread is its tid, like in
+//! Linux. An exited process stays as a zombie in the children of its parent,
+//! until the parent reaps it with `wait4`.
+
+use core::ffi::c_char;
+use core::mem::size_of;
+use core::sync::atomic::{AtomicBool, AtomicI32, AtomicU32, Ordering};
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec;
+use alloc::vec::Vec;
+use axerrno::{LinuxError, LinuxResult};
+use axhal::arch::{TrapFrame, UspaceContext};
+use axhal::mem::VirtAddr;
+use axhal::paging::MappingFlags;
+use axmm::{strncpy_from_user, AddrSpace, UserPtr};
+use axsync::Mutex;
+use axtask::{current, TaskExtRef, WaitQueue};
+use kspin::SpinNoIrq;
+
+use crate::signal::{self, ProcessSignal, SigInfo, ThreadSignal};
+use axsyscall::syscall_body;
+use crate::task::{self, TaskExt};
+
+/// Process ID, which is also used as thread ID.
+pub type Pid = u64;
+
+/// Maximum length of a path, including the terminating NUL.
+const PATH_MAX: usize = 4096;
+/// Maximum number of arguments and environment variables of `execve`.
+const ARG_MAX: usize = 1024;
+
+/// Return immediately if no child has exited.
+const WNOHANG: i32 = 1;
+
+bitflags::bitflags! {
+    #[derive(Debug, Clone, Copy)]
+    /// Flags for sys_clone.
+    ///
+    /// See <https://github.com/torvalds/linux/blob/master/include/uapi/linux/sched.h>
+    struct CloneFlags: usize {
+        /// Share the address space.
+        const CLONE_VM = 0x100;
+        /// Share the file system information.
+        const CLONE_FS = 0x200;
+        /// Share the file descriptor table.
+        const CLONE_FILES = 0x400;
+        /// Share the signal handlers.
+        const CLONE_SIGHAND = 0x800;
+        /// The parent sleeps until the child execs or exits.
+        const CLONE_VFORK = 0x4000;
+        /// The child has the same parent as the caller.
+        const CLONE_PARENT = 0x8000;
+        /// The child is in the same thread group.
+        const CLONE_THREAD = 0x10000;
+        /// Set the TLS of the child.
+        const CLONE_SETTLS = 0x80000;
+        /// Store the child tid in the parent memory.
+        const CLONE_PARENT_SETTID = 0x100000;
+        /// Clear the child tid in the child memory when it exits.
+        const CLONE_CHILD_CLEARTID = 0x200000;
+        /// Store the child tid in the child memory.
+        const CLONE_CHILD_SETTID = 0x1000000;
+    }
+}
+
+/// The bits of the clone flags for the signal sent to the parent on exit.
+const CSIGNAL: usize = 0xff;
+
+/// The first process, which adopts the orphans.
+static INIT_PROCESS: SpinNoIrq<Weak<Process>> = SpinNoIrq::new(Weak::new());
+/// All processes, including the zombies.
+static PROCESSES: SpinNoIrq<BTreeMap<Pid, Weak<Process>>> = SpinNoIrq::new(BTreeMap::new());
+
+/// The completion that the parent of `vfork` waits for, signaled when the
+/// child execs or exits.
+pub struct VforkDone {
+    done: AtomicBool,
+    wq: WaitQueue,
+}
+
+impl VforkDone {
+    fn new() -> Self {
+        Self {
+            done: AtomicBool::new(false),
+            wq: WaitQueue::new(),
+        }
+    }
+
+    fn complete(&self) {
+        self.done.store(true, Ordering::Release);
+        self.wq.notify_all(false);
+    }
+
+    fn is_done(&self) -> bool {
+        self.done.load(Ordering::Acquire)
+    }
+}
+
+/// A process, i.e., a group of threads.
+pub struct Process {
+    pid: Pid,
+    parent: SpinNoIrq<Weak<Process>>,
+    children: SpinNoIrq<BTreeMap<Pid, Arc<Process>>>,
+    /// Live threads, with their signal states.
+    threads: SpinNoIrq<BTreeMap<Pid, Arc<ThreadSignal>>>,
+    exit_code: AtomicI32,
+    /// The signal that terminated the process, or 0.
+    term_signal: AtomicU32,
+    /// The signal sent to the parent when the process exits, or 0.
+    exit_signal: u32,
+    zombie: AtomicBool,
+    /// Set when the threads are asked to exit, by `exit_group` or `execve`.
+    /// The thread with the given tid (if any) keeps running.
+    group_exit: SpinNoIrq<Option<Option<Pid>>>,
+    /// Notified when a child becomes a zombie.
+    child_exit: WaitQueue,
+    /// Notified when a thread exits.
+    thread_exit: WaitQueue,
+    /// The start and the current end of the heap, i.e., the program break.
+    brk: SpinNoIrq<(VirtAddr, VirtAddr)>,
+    /// The signal state shared by the threads.
+    pub signal: ProcessSignal,
+    /// The absolute path of the current directory, shared by the threads and
+    /// copied by `fork`.
+    cwd: Arc<Mutex<String>>,
+}
+
+impl Process {
+    fn new(
+        pid: Pid,
+        parent: Weak<Process>,
+        thread: Arc<ThreadSignal>,
+        exit_signal: u32,
+    ) -> Arc<Self> {
+        let process = Arc::new(Self {
+            pid,
+            parent: SpinNoIrq::new(parent),
+            children: SpinNoIrq::new(BTreeMap::new()),
+            threads: SpinNoIrq::new(BTreeMap::from([(pid, thread)])),
+            exit_code: AtomicI32::new(0),
+            term_signal: AtomicU32::new(0),
+            exit_signal,
+            zombie: AtomicBool::new(false),
+            group_exit: SpinNoIrq::new(None),
+            child_exit: WaitQueue::new(),
+            thread_exit: WaitQueue::new(),
+            brk: SpinNoIrq::new((VirtAddr::from(0), VirtAddr::from(0))),
+            signal: ProcessSignal::new(),
+            cwd: Arc::new(Mutex::new(String::from("/"))),
+        });
+        PROCESSES.lock().insert(pid, Arc::downgrade(&process));
+        process
+    }
+
+    /// Creates the first process, whose main thread is `pid`.
+    pub fn new_init(pid: Pid, thread: Arc<ThreadSignal>) -> Arc<Self> {
+        let init = Self::new(pid, Weak::new(), thread, 0);
+        *INIT_PROCESS.lock() = Arc::downgrade(&init);
+        init
+    }
+
+    /// Creates a child process, whose main thread is `pid`. The parent is
+    /// sent `exit_signal` when the child exits.
+    pub fn new_child(
+        self: &Arc<Self>,
+        pid: Pid,
+        thread: Arc<ThreadSignal>,
+        exit_signal: u32,
+    ) -> Arc<Self> {
+        let child = Self::new(pid, Arc::downgrade(self), thread, exit_signal);
+        *child.brk.lock() = self.brk();
+        child.signal.inherit(&self.signal);
+        *child.cwd.lock() = self.cwd.lock().clone();
+        self.children.lock().insert(pid, child.clone());
+        child
+    }
+
+    /// Returns the process ID.
+    pub const fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    /// Returns the parent process, `None` for the init process.
+    pub fn parent(&self) -> Option<Arc<Process>> {
+        self.parent.lock().upgrade()
+    }
+
+    /// Returns the current directory, see [`arceos_posix_api::CURRENT_DIR`].
+    pub fn cwd(&self) -> Arc<Mutex<String>> {
+        self.cwd.clone()
+    }
+
+    /// Whether the process has exited and waits to be reaped.
+    pub fn is_zombie(&self) -> bool {
+        self.zombie.load(Ordering::Acquire)
+    }
+
+    /// Returns the start and the current end of the heap.
+    pub fn brk(&self) -> (VirtAddr, VirtAddr) {
+        *self.brk.lock()
+    }
+
+    /// Sets the start and the current end of the heap.
+    pub fn set_brk(&self, start: VirtAddr, end: VirtAddr) {
+        *self.brk.lock() = (start, end);
+    }
+
+    /// Adds a new thread to the group.
+    pub fn add_thread(&self, tid: Pid, thread: Arc<ThreadSignal>) {
+        self.threads.lock().insert(tid, thread);
+    }
+
+    /// Returns the signal state of the thread `tid` in the group.
+    pub fn thread(&self, tid: Pid) -> Option<Arc<ThreadSignal>> {
+        self.threads.lock().get(&tid).cloned()
+    }
+
+    /// Returns the signal states of all threads in the group.
+    pub fn threads(&self) -> Vec<Arc<ThreadSignal>> {
+        self.threads.lock().values().cloned().collect()
+    }
+
+    /// Whether the current thread `tid` should exit, because another thread
+    /// calls `exit_group` or `execve`.
+    pub fn should_exit(&self, tid: Pid) -> bool {
+        matches!(*self.group_exit.lock(), Some(survivor) if survivor != Some(tid))
+    }
+
+    /// Removes a thread from the group. The process exits with `exit_code`
+    /// if it is the last one.
+    fn exit_thread(&self, tid: Pid, exit_code: i32) {
+        let mut threads = self.threads.lock();
+        threads.remove(&tid);
+        let last = threads.is_empty();
+        drop(threads);
+        self.thread_exit.notify_all(false);
+        if last {
+            if self.group_exit.lock().is_none() {
+                self.exit_code.store(exit_code, Ordering::Release);
+            }
+            self.exit();
+        }
+    }
+
+    /// Turns the process into a zombie, and passes its children to the init
+    /// process.
+    fn exit(&self) {
+        let init = INIT_PROCESS.lock().upgrade();
+        let children = core::mem::take(&mut *self.children.lock());
+        if let Some(init) = init.filter(|init| init.pid != self.pid) {
+            for (pid, child) in children {
+                *child.parent.lock() = Arc::downgrade(&init);
+                init.children.lock().insert(pid, child);
+            }
+            init.child_exit.notify_all(false);
+        }
+        self.zombie.store(true, Ordering::Release);
+        if let Some(parent) = self.parent() {
+            if self.exit_signal != 0 {
+                let info = SigInfo::child(
+                    self.exit_signal,
+                    self.pid,
+                    self.exit_code.load(Ordering::Acquire),
+                    self.term_signal.load(Ordering::Acquire),
+                );
+                signal::send_to_process(&parent, info);
+            }
+            parent.child_exit.notify_all(false);
+        }
+    }
+
+    /// Returns the status reported by `wait4`.
+    fn wait_status(&self) -> i32 {
+        match self.term_signal.load(Ordering::Acquire) {
+            0 => (self.exit_code.load(Ordering::Acquire) & 0xff) << 8,
+            sig => sig as i32,
+        }
+    }
+
+    /// Asks the other threads to exit with `exit_code`, and the current
+    /// thread `tid` as well if `keep_current` is false. `term_signal` is the
+    /// signal that terminates the process, or 0.
+    ///
+    /// The exit of the whole group overrides a pending `execve`, whose
+    /// thread exits as well.
+    fn exit_group(&self, tid: Pid, exit_code: i32, term_signal: u32, keep_current: bool) {
+        let mut group_exit = self.group_exit.lock();
+        let overrides = match *group_exit {
+            None => true,
+            Some(Some(_)) => !keep_current,
+            Some(None) => false,
+        };
+        if overrides {
+            self.exit_code.store(exit_code, Ordering::Release);
+            self.term_signal.store(term_signal, Ordering::Release);
+            *group_exit = Some(keep_current.then_some(tid));
+        }
+        drop(group_exit);
+        for thread in self.threads() {
+            thread.wake();
+        }
+    }
+
+    /// Terminates all threads because of the signal `sig`.
+    pub fn terminate(&self, sig: u32) {
+        self.exit_group(0, 128 + sig as i32, sig, false);
+    }
+
+    /// Kills the other threads and waits for them to exit, like `de_thread`
+    /// in Linux. The current thread `tid` is the only one left.
+    ///
+    /// The current thread exits instead if another thread exits the group or
+    /// execs first.
+    fn kill_other_threads(&self, tid: Pid) {
+        self.exit_group(tid, 0, 0, true);
+        let alone = || self.threads.lock().keys().all(|&t| t == tid);
+        if signal::wait_killable(&self.thread_exit, alone).is_err() {
+            exit_current(self.exit_code.load(Ordering::Acquire));
+        }
+        let mut group_exit = self.group_exit.lock();
+        if *group_exit == Some(Some(tid)) {
+            *group_exit = None;
+        }
+    }
+
+    /// Whether a child matches the `pid` argument of `wait4`.
+    fn is_waited(child: &Process, pid: Option<Pid>) -> bool {
+        pid.map_or(true, |pid| child.pid == pid)
+    }
+
+    /// Whether there is a zombie child to reap, or no child to wait for.
+    fn can_wait(&self, pid: Option<Pid>) -> bool {
+        let children = self.children.lock();
+        let mut waited = children
+            .values()
+            .filter(|child| Self::is_waited(child, pid))
+            .peekable();
+        waited.peek().is_none() || waited.any(|child| child.is_zombie())
+    }
+
+    /// Removes a zombie child that matches `pid`, returns its pid and wait
+    /// status.
+    fn reap_child(&self, pid: Option<Pid>) -> LinuxResult<Option<(Pid, i32)>> {
+        let mut children = self.children.lock();
+        if !children.values().any(|child| Self::is_waited(child, pid)) {
+            return Err(LinuxError::ECHILD);
+        }
+        let zombie = children
+            .values()
+            .find(|child| Self::is_waited(child, pid) && child.zombie.load(Ordering::Acquire))
+            .map(|child| child.pid);
+        Ok(zombie.map(|pid| {
+            let child = children.remove(&pid).unwrap();
+            (pid, child.wait_status())
+        }))
+    }
+
+    /// Waits for a child that matches `pid` to exit and reaps it.
+    ///
+    /// Returns `None` if `nohang` and no such child has exited yet.
+    fn wait_child(&self, pid: Option<Pid>, nohang: bool) -> LinuxResult<Option<(Pid, i32)>> {
+        loop {
+            if let Some(res) = self.reap_child(pid)? {
+                return Ok(Some(res));
+            }
+            if nohang {
+                return Ok(None);
+            }
+            signal::wait_interruptible(&self.child_exit, || self.can_wait(pid))?;
+        }
+    }
+}
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        PROCESSES.lock().remove(&self.pid);
+    }
+}
+
+/// Finds a process by its pid.
+pub fn find_process(pid: Pid) -> Option<Arc<Process>> {
+    PROCESSES.lock().get(&pid)?.upgrade()
+}
+
+/// Returns all processes.
+pub fn all_processes() -> Vec<Arc<Process>> {
+    PROCESSES
+        .lock()
+        .values()
+        .filter_map(Weak::upgrade)
+        .collect()
+}
+
+/// Finds a thread by its tid, returns its process and signal state.
+pub fn find_thread(tid: Pid) -> Option<(Arc<Process>, Arc<ThreadSignal>)> {
+    all_processes()
+        .into_iter()
+        .find_map(|process| process.thread(tid).map(|thread| (process, thread)))
+}
+
+/// Exits the current thread, and the process if it is the last thread.
+pub fn exit_current(exit_code: i32) -> ! {
+    let curr = current();
+    let ext = curr.task_ext();
+    let clear_child_tid = ext.clear_child_tid() as usize;
+    if clear_child_tid != 0 {
+        // Errors are ignored, as there is nowhere to report them.
+        let _ = UserPtr::<i32>::from(clear_child_tid).write(&mut ext.aspace.lock(), 0);
+    }
+    wake_vfork_parent(ext);
+    ext.process.exit_thread(curr.id().as_u64(), exit_code);
+    axtask::exit(exit_code)
+}
+
+/// Exits all threads of the current process.
+pub fn exit_group(exit_code: i32) -> ! {
+    let curr = current();
+    curr.task_ext()
+        .process
+        .exit_group(curr.id().as_u64(), exit_code, 0, false);
+    exit_current(exit_code)
+}
+
+/// Exits the current thread if another thread calls `exit_group` or
+/// `execve`. It is checked at the entry of every syscall.
+pub fn check_group_exit() {
+    let curr = current();
+    let process = &curr.task_ext().process;
+    if process.should_exit(curr.id().as_u64()) {
+        exit_current(process.exit_code.load(Ordering::Acquire));
+    }
+}
+
+/// Resumes the parent blocked in `vfork`, if the current thread is the child.
+fn wake_vfork_parent(ext: &TaskExt) {
+    if let Some(done) = ext.vfork_done.lock().take() {
+        done.complete();
+    }
+}
+
+/// Writes `tid` to the user address `ptr` of `aspace`, which may not be the
+/// current address space.
+fn put_tid(aspace: &mut AddrSpace, ptr: usize, tid: Pid) -> LinuxResult {
+    let vaddr = VirtAddr::from(ptr);
+    aspace.check_user_access(vaddr, size_of::<i32>(), MappingFlags::WRITE)?;
+    aspace.write(vaddr, &(tid as i32).to_ne_bytes())?;
+    Ok(())
+}
+
+pub(crate) fn sys_clone(
+    tf: &TrapFrame,
+    flags: usize,
+    stack: usize,
+    ptid: usize,
+    ctid: usize,
+    tls: usize,
+) -> isize {
+    syscall_body!(sys_clone, {
+        let exit_signal = (flags & CSIGNAL) as u32;
+        let flags = CloneFlags::from_bits(flags & !CSIGNAL).ok_or(LinuxError::EINVAL)?;
+        let is_thread = flags.contains(CloneFlags::CLONE_THREAD);
+        if is_thread && !flags.contains(CloneFlags::CLONE_VM | CloneFlags::CLONE_SIGHAND) {
+            return Err(LinuxError::EINVAL);
+        }
+        if flags.contains(CloneFlags::CLONE_VM) && !is_thread {
+            // A process sharing the address space is not supported. It is
+            // fine for `vfork`, where the child gets a copy instead.
+            if !flags.contains(CloneFlags::CLONE_VFORK) {
+                return Err(LinuxError::EINVAL);
+            }
+        }
+
+        // The trap frame already points past the syscall instruction. Return 0
+        // in the child.
+        let mut uctx = UspaceContext::from(tf);
+        uctx.set_retval(0);
+        if stack != 0 {
+            uctx.set_sp(stack);
+        }
+        if flags.contains(CloneFlags::CLONE_SETTLS) {
+            uctx.set_tls(tls);
+        }
+
+        let curr = current();
+        let ext = curr.task_ext();
+        let aspace = if is_thread {
+            ext.aspace.clone()
+        } else {
+            let parent_aspace = ext.aspace.lock();
+            let mut aspace = axmm::new_user_aspace()?;
+            aspace.copy_areas_from(&parent_aspace)?;
+            task::share_aspace(aspace)
+        };
+
+        let task = task::new_user_task(curr.name());
+        let tid = task.id().as_u64();
+        if flags.contains(CloneFlags::CLONE_PARENT_SETTID) {
+            put_tid(&mut ext.aspace.lock(), ptid, tid)?;
+        }
+        if flags.contains(CloneFlags::CLONE_CHILD_SETTID) {
+            put_tid(&mut aspace.lock(), ctid, tid)?;
+        }
+
+        let signal = Arc::new(ThreadSignal::new(ext.signal.blocked()));
+        let process = if is_thread {
+            ext.process.add_thread(tid, signal.clone());
+            ext.process.clone()
+        } else if flags.contains(CloneFlags::CLONE_PARENT) {
+            let parent = ext.process.parent().ok_or(LinuxError::EINVAL)?;
+            parent.new_child(tid, signal.clone(), exit_signal)
+        } else {
+            ext.process.new_child(tid, signal.clone(), exit_signal)
+        };
+        let shm_attaches = if is_thread {
+            BTreeMap::new()
+        } else {
+            ext.shm_attaches.lock().clone()
+        };
+        let fd_table = if flags.contains(CloneFlags::CLONE_FILES) {
+            ext.fd_table()
+        } else {
+            Arc::new(ext.fd_table().fork())
+        };
+        let new_ext = TaskExt::new(uctx, aspace, process, signal, fd_table);
+        *new_ext.shm_attaches.lock() = shm_attaches;
+        if flags.contains(CloneFlags::CLONE_CHILD_CLEARTID) {
+            new_ext.set_clear_child_tid(ctid as _);
+        }
+        let vfork_done = flags
+            .contains(CloneFlags::CLONE_VFORK)
+            .then(|| Arc::new(VforkDone::new()));
+        *new_ext.vfork_done.lock() = vfork_done.clone();
+        task::spawn_user_task(task, new_ext);
+        if let Some(done) = vfork_done {
+            // Only a group exit interrupts it, which is handled on the way back
+            // to user space.
+            let _ = signal::wait_killable(&done.wq, || done.is_done());
+        }
+        Ok(tid)
+    })
+}
+
+/// Reads a NULL-terminated array of strings, i.e., `argv` or `envp`.
+fn read_str_array(aspace: &mut AddrSpace, array: usize) -> LinuxResult<Vec<String>> {
+    let mut strs = Vec::new();
+    if array == 0 {
+        return Ok(strs);
+    }
+    let mut buf = vec![0u8; PATH_MAX];
+    for i in 0.. {
+        if i >= ARG_MAX {
+            return Err(LinuxError::E2BIG);
+        }
+        let ptr = UserPtr::<usize>::from(array).add(i)?.read(aspace)?;
+        if ptr == 0 {
+            break;
+        }
+        let len = strncpy_from_user(aspace, &mut buf, VirtAddr::from(ptr))?;
+        if len == buf.len() {
+            return Err(LinuxError::E2BIG);
+        }
+        let s = core::str::from_utf8(&buf[..len]).map_err(|_| LinuxError::EINVAL)?;
+        strs.push(String::from(s));
+    }
+    Ok(strs)
+}
+
+/// Replaces the image of the current process, returns the context to enter
+/// the new one.
+fn execve(path: *const c_char, argv: usize, envp: usize) -> LinuxResult<UspaceContext> {
+    let curr = current();
+    let ext = curr.task_ext();
+    let mut aspace = ext.aspace.lock();
+    let mut buf = vec![0u8; PATH_MAX];
+    let len = strncpy_from_user(&mut aspace, &mut buf, VirtAddr::from(path as usize))?;
+    if len == PATH_MAX {
+        return Err(LinuxError::ENAMETOOLONG);
+    }
+    let path = core::str::from_utf8(&buf[..len]).map_err(|_| LinuxError::EINVAL)?;
+    let path = if path.starts_with('/') {
+        String::from(path)
+    } else {
+        axfs::api::canonicalize(&alloc::format!("{}/{}", ext.process.cwd().lock(), path))?
+    };
+    let args = read_str_array(&mut aspace, argv)?;
+    let envs = read_str_array(&mut aspace, envp)?;
+    drop(aspace);
+    // Fail before destroying the old image if the file cannot be read.
+    let data = std::fs::read(&path)?;
+
+    ext.process.kill_other_threads(curr.id().as_u64());
+
+    // The point of no return: the old image is gone.
+    let mut aspace = ext.aspace.lock();
+    aspace.clear();
+    aspace.randomize_layout();
+    ext.shm_attaches.lock().clear();
+    ext.set_clear_child_tid(0);
+    ext.process.signal.reset_for_exec();
+    let loaded = axloader::load_elf(&mut aspace, &data, std::fs::read).and_then(|app| {
+        let ustack_top = crate::init_user_stack(&mut aspace, &args, &envs, &app.auxv, true)?;
+        ext.process.set_brk(app.brk_start, app.brk_start);
+        Ok((app.entry.as_usize(), ustack_top))
+    });
+    drop(aspace);
+    match loaded {
+        Ok((entry, ustack_top)) => {
+            info!("execve: {} entry={:#x}", path, entry);
+            // Only the new image loses the close-on-exec files, and they must
+            // stay open in the tasks that shared the table.
+            ext.unshare_fd_table().close_on_exec();
+            wake_vfork_parent(ext);
+            Ok(UspaceContext::new(entry, ustack_top))
+        }
+        Err(err) => {
+            warn!("execve: failed to load {}: {:?}", path, err);
+            exit_group(-1);
+        }
+    }
+}
+
+pub(crate) fn sys_execve(path: *const c_char, argv: usize, envp: usize) -> isize {
+    match execve(path, argv, envp) {
+        Ok(uctx) => {
+            let kstack_top = current().kernel_stack_top().unwrap();
+            unsafe { uctx.enter_uspace(kstack_top) }
+        }
+        Err(err) => {
+            info!("sys_execve => {:?}", err);
+            -err.code() as _
+        }
+    }
+}
+
+pub(crate) fn sys_wait4(pid: i32, wstatus: *mut i32, options: i32, _rusage: usize) -> isize {
+    syscall_body!(sys_wait4, {
+        // There are no process groups, so `0` and `-pgid` wait for any child,
+        // like `-1`.
+        let pid = if pid > 0 { Some(pid as Pid) } else { None };
+        let curr = current();
+        let process = &curr.task_ext().process;
+        match process.wait_child(pid, options & WNOHANG != 0)? {
+            Some((pid, status)) => {
+                if !wstatus.is_null() {
+                    UserPtr::from(wstatus).write(&mut curr.task_ext().aspace.lock(), status)?;
+                }
+                Ok(pid)
+            }
+            None => Ok(0),
+        }
+    })
+}
+
+pub(crate) fn sys_getpid() -> isize {
+    current().task_ext().process.pid() as isize
+}
+
+pub(crate) fn sys_getppid() -> isize {
+    current()
+        .task_ext()
+        .process
+        .parent()
+        .map_or(0, |parent| parent.pid() as isize)
+}
+
+pub(crate) fn sys_gettid() -> isize {
+    current().id().as_u64() as isize
+}
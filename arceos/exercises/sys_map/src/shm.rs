@@ -0,0 +1,286 @@
+//! System V shared memory and `memfd_create` anonymous shared files.
+//!
+//! Both are built on [`SharedPages`], so the same physical frames can be
+//! mapped into several address spaces.
+
+use core::ffi::{c_char, c_int, c_uint};
+use core::sync::atomic::{AtomicI32, Ordering};
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::Arc;
+use arceos_posix_api::{self as api, ctypes, FileLike};
+use axerrno::{LinuxError, LinuxResult};
+use axhal::mem::{MemoryAddr, VirtAddr, PAGE_SIZE_4K};
+use axhal::paging::MappingFlags;
+use axio::PollState;
+use axmm::{strncpy_from_user, SharedPages};
+use axsync::Mutex;
+use axtask::{current, TaskExtRef};
+use memory_addr::VirtAddrRange;
+
+use axsyscall::syscall_body;
+
+/// Private key, always creates a new segment.
+const IPC_PRIVATE: i32 = 0;
+/// Create the segment if the key does not exist.
+const IPC_CREAT: i32 = 0o1000;
+/// Fail if the key exists.
+const IPC_EXCL: i32 = 0o2000;
+/// Remove the identifier.
+const IPC_RMID: i32 = 0;
+/// Attach the segment for read-only access.
+const SHM_RDONLY: i32 = 0o10000;
+/// Round the attach address down to SHMLBA.
+const SHM_RND: i32 = 0o20000;
+/// Segment low boundary address multiple.
+const SHMLBA: usize = PAGE_SIZE_4K;
+
+/// Maximum length of the name given to `memfd_create`.
+const MFD_NAME_MAX: usize = 249;
+
+/// Set the close-on-exec flag of the new file descriptor.
+const MFD_CLOEXEC: c_uint = 1;
+
+/// A System V shared memory segment.
+struct ShmSegment {
+    key: i32,
+    size: usize,
+    pages: Arc<SharedPages>,
+}
+
+static SHM_SEGMENTS: Mutex<BTreeMap<i32, ShmSegment>> = Mutex::new(BTreeMap::new());
+static NEXT_SHMID: AtomicI32 = AtomicI32::new(1);
+
+fn shmget(key: i32, size: usize, shmflg: i32) -> LinuxResult<i32> {
+    let mut segments = SHM_SEGMENTS.lock();
+    if key != IPC_PRIVATE {
+        if let Some((&shmid, seg)) = segments.iter().find(|(_, seg)| seg.key == key) {
+            if shmflg & IPC_CREAT != 0 && shmflg & IPC_EXCL != 0 {
+                return Err(LinuxError::EEXIST);
+            }
+            if size > seg.size {
+                return Err(LinuxError::EINVAL);
+            }
+            return Ok(shmid);
+        }
+        if shmflg & IPC_CREAT == 0 {
+            return Err(LinuxError::ENOENT);
+        }
+    }
+    if size == 0 {
+        return Err(LinuxError::EINVAL);
+    }
+
+    let size = size.align_up_4k();
+    let pages = SharedPages::new(size / PAGE_SIZE_4K).map_err(|_| LinuxError::ENOMEM)?;
+    let shmid = NEXT_SHMID.fetch_add(1, Ordering::Relaxed);
+    segments.insert(
+        shmid,
+        ShmSegment {
+            key,
+            size,
+            pages: Arc::new(pages),
+        },
+    );
+    Ok(shmid)
+}
+
+fn shmat(shmid: i32, shmaddr: usize, shmflg: i32) -> LinuxResult<usize> {
+    let (size, pages) = {
+        let segments = SHM_SEGMENTS.lock();
+        let seg = segments.get(&shmid).ok_or(LinuxError::EINVAL)?;
+        (seg.size, seg.pages.clone())
+    };
+
+    let mut flags = MappingFlags::READ | MappingFlags::USER;
+    if shmflg & SHM_RDONLY == 0 {
+        flags |= MappingFlags::WRITE;
+    }
+
+    let curr = current();
+    let mut aspace = curr.task_ext().aspace.lock();
+    let limit = VirtAddrRange::from_start_size(aspace.base(), aspace.size());
+    let start = if shmaddr == 0 {
+        // Search from the mmap base, like `mmap` does.
+        aspace
+            .find_free_area(aspace.layout().mmap_base, size, limit)
+            .ok_or(LinuxError::ENOMEM)?
+    } else {
+        let start = if shmflg & SHM_RND != 0 {
+            VirtAddr::from(shmaddr).align_down(SHMLBA)
+        } else if shmaddr % SHMLBA == 0 {
+            VirtAddr::from(shmaddr)
+        } else {
+            return Err(LinuxError::EINVAL);
+        };
+        // The segment is attached at exactly the given address, which must
+        // be free.
+        if start.as_usize().checked_add(size).is_none()
+            || !aspace.contains_range(start, size)
+            || aspace.find_free_area(start, size, limit) != Some(start)
+        {
+            return Err(LinuxError::EINVAL);
+        }
+        start
+    };
+    aspace.map_shared(start, size, flags, pages, 0)?;
+    curr.task_ext()
+        .shm_attaches
+        .lock()
+        .insert(start.as_usize(), size);
+    Ok(start.as_usize())
+}
+
+fn shmdt(shmaddr: usize) -> LinuxResult<isize> {
+    let curr = current();
+    let mut aspace = curr.task_ext().aspace.lock();
+    let mut attaches = curr.task_ext().shm_attaches.lock();
+    let size = *attaches.get(&shmaddr).ok_or(LinuxError::EINVAL)?;
+    // Keep the record if the segment cannot be detached.
+    aspace.unmap(VirtAddr::from(shmaddr), size)?;
+    attaches.remove(&shmaddr);
+    Ok(0)
+}
+
+fn shmctl(shmid: i32, cmd: i32) -> LinuxResult<isize> {
+    match cmd {
+        IPC_RMID => {
+            // The frames stay alive until every attachment is detached.
+            SHM_SEGMENTS
+                .lock()
+                .remove(&shmid)
+                .ok_or(LinuxError::EINVAL)?;
+            Ok(0)
+        }
+        _ => {
+            warn!("unsupported shmctl command: {}", cmd);
+            Err(LinuxError::EINVAL)
+        }
+    }
+}
+
+/// An anonymous file living in shared memory, created by `memfd_create`.
+///
+/// Writing beyond the end grows the file. Its pages can be mapped with
+/// [`MemFd::pages`].
+pub struct MemFd {
+    name: String,
+    pages: Arc<SharedPages>,
+    /// The current file size and the file offset.
+    state: Mutex<(usize, usize)>,
+}
+
+impl MemFd {
+    fn new(name: String) -> LinuxResult<Self> {
+        Ok(Self {
+            name,
+            pages: Arc::new(SharedPages::new(0).map_err(|_| LinuxError::ENOMEM)?),
+            state: Mutex::new((0, 0)),
+        })
+    }
+
+    /// Returns the name given to `memfd_create`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the shared pages backing this file.
+    pub fn pages(&self) -> Arc<SharedPages> {
+        self.pages.clone()
+    }
+}
+
+impl FileLike for MemFd {
+    fn read(&self, buf: &mut [u8]) -> LinuxResult<usize> {
+        let mut state = self.state.lock();
+        let (size, pos) = *state;
+        let len = buf.len().min(size.saturating_sub(pos));
+        let n = self.pages.read(pos, &mut buf[..len]);
+        state.1 += n;
+        Ok(n)
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> LinuxResult<usize> {
+        let size = self.state.lock().0;
+        let offset = offset as usize;
+        let len = buf.len().min(size.saturating_sub(offset));
+        Ok(self.pages.read(offset, &mut buf[..len]))
+    }
+
+    fn write(&self, buf: &[u8]) -> LinuxResult<usize> {
+        let mut state = self.state.lock();
+        let (size, pos) = *state;
+        let end = pos + buf.len();
+        self.pages
+            .grow(end.div_ceil(PAGE_SIZE_4K))
+            .map_err(|_| LinuxError::ENOSPC)?;
+        let n = self.pages.write(pos, buf);
+        *state = (size.max(pos + n), pos + n);
+        Ok(n)
+    }
+
+    fn stat(&self) -> LinuxResult<ctypes::stat> {
+        let size = self.state.lock().0;
+        Ok(ctypes::stat {
+            st_ino: 1,
+            st_nlink: 1,
+            st_mode: 0o100000 | 0o777, // S_IFREG | rwxrwxrwx
+            st_size: size as _,
+            st_blocks: size.div_ceil(512) as _,
+            st_blksize: PAGE_SIZE_4K as _,
+            ..Default::default()
+        })
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
+        self
+    }
+
+    fn poll(&self) -> LinuxResult<PollState> {
+        Ok(PollState {
+            readable: true,
+            writable: true,
+        })
+    }
+
+    fn set_nonblocking(&self, _nonblocking: bool) -> LinuxResult {
+        Ok(())
+    }
+}
+
+fn memfd_create(name: *const c_char, flags: c_uint) -> LinuxResult<c_int> {
+    let mut buf = [0u8; MFD_NAME_MAX + 1];
+    let len = strncpy_from_user(
+        &mut current().task_ext().aspace.lock(),
+        &mut buf,
+        VirtAddr::from(name as usize),
+    )?;
+    if len > MFD_NAME_MAX {
+        return Err(LinuxError::EINVAL);
+    }
+    let name = core::str::from_utf8(&buf[..len]).map_err(|_| LinuxError::EINVAL)?;
+    let file = MemFd::new(format!("memfd:{}", name))?;
+    api::current_fd_table().add(Arc::new(file), flags & MFD_CLOEXEC != 0)
+}
+
+pub(crate) fn sys_shmget(key: i32, size: usize, shmflg: i32) -> isize {
+    syscall_body!(sys_shmget, shmget(key, size, shmflg))
+}
+
+pub(crate) fn sys_shmat(shmid: i32, shmaddr: usize, shmflg: i32) -> isize {
+    syscall_body!(sys_shmat, shmat(shmid, shmaddr, shmflg))
+}
+
+pub(crate) fn sys_shmdt(shmaddr: usize) -> isize {
+    syscall_body!(sys_shmdt, shmdt(shmaddr))
+}
+
+pub(crate) fn sys_shmctl(shmid: i32, cmd: i32, _buf: usize) -> isize {
+    syscall_body!(sys_shmctl, shmctl(shmid, cmd))
+}
+
+pub(crate) fn sys_memfd_create(name: *const c_char, flags: c_uint) -> isize {
+    syscall_body!(sys_memfd_create, memfd_create(name, flags))
+}
@@ -0,0 +1,737 @@
+//! POSIX signals.
+//!
+//! Each thread has its own pending and blocked signals, and the signals sent
+//! to the process (e.g., by `kill`) are pending in the process until one of
+//! its threads takes them. The signal actions are shared by the threads of a
+//! process.
+//!
+//! Pending signals are delivered just before returning to user space: a
+//! signal frame with the saved registers is pushed onto the user stack, and
+//! the handler returns to a trampoline page that calls `rt_sigreturn`. The
+//! frame layout and the trampoline code follow the Linux ABI of each
+//! architecture, in the `arch` submodule. Real-time signals are not queued,
+//! i.e., they are pending at most once like the standard ones. Stopping and
+//! continuing processes is not supported.
+
+#![allow(dead_code)]
+
+use core::mem::{offset_of, size_of};
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use core::time::Duration;
+
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use axerrno::{AxResult, LinuxError, LinuxResult};
+use axhal::arch::TrapFrame;
+use axhal::mem::{MemoryAddr, PAGE_SIZE_4K};
+use axhal::paging::MappingFlags;
+use axhal::trap::{register_trap_handler, UserFault, RETURN_TO_USER, USER_FAULT};
+use axmm::{AddrSpace, UserPtr};
+use axtask::{current, TaskExtRef, WaitQueue};
+use kspin::SpinNoIrq;
+use memory_addr::VirtAddrRange;
+
+use crate::process::{self, Pid, Process};
+use axsyscall::syscall_body;
+use crate::task::TaskExt;
+
+#[cfg(target_arch = "aarch64")]
+mod aarch64;
+#[cfg(target_arch = "riscv64")]
+mod riscv;
+#[cfg(target_arch = "x86_64")]
+mod x86_64;
+
+#[cfg(target_arch = "aarch64")]
+use self::aarch64 as arch;
+#[cfg(target_arch = "riscv64")]
+use self::riscv as arch;
+#[cfg(target_arch = "x86_64")]
+use self::x86_64 as arch;
+
+/// Number of signals.
+const NSIG: u32 = 64;
+
+pub const SIGHUP: u32 = 1;
+pub const SIGINT: u32 = 2;
+pub const SIGQUIT: u32 = 3;
+pub const SIGILL: u32 = 4;
+pub const SIGTRAP: u32 = 5;
+pub const SIGABRT: u32 = 6;
+pub const SIGBUS: u32 = 7;
+pub const SIGFPE: u32 = 8;
+pub const SIGKILL: u32 = 9;
+pub const SIGUSR1: u32 = 10;
+pub const SIGSEGV: u32 = 11;
+pub const SIGUSR2: u32 = 12;
+pub const SIGPIPE: u32 = 13;
+pub const SIGALRM: u32 = 14;
+pub const SIGTERM: u32 = 15;
+pub const SIGCHLD: u32 = 17;
+pub const SIGCONT: u32 = 18;
+pub const SIGSTOP: u32 = 19;
+pub const SIGTSTP: u32 = 20;
+pub const SIGTTIN: u32 = 21;
+pub const SIGTTOU: u32 = 22;
+pub const SIGURG: u32 = 23;
+pub const SIGWINCH: u32 = 28;
+
+/// Default action.
+const SIG_DFL: usize = 0;
+/// Ignore the signal.
+const SIG_IGN: usize = 1;
+
+/// Pass the `siginfo` and `ucontext` to the handler. They are always passed.
+const SA_SIGINFO: usize = 0x4;
+/// Use the alternate signal stack. Ignored.
+const SA_ONSTACK: usize = 0x0800_0000;
+/// Restart the interrupted syscalls. Ignored, they fail with `EINTR`.
+const SA_RESTART: usize = 0x1000_0000;
+/// Do not block the signal in its handler.
+const SA_NODEFER: usize = 0x4000_0000;
+/// Reset the action to the default one on delivery.
+const SA_RESETHAND: usize = 0x8000_0000;
+
+/// `how` argument of `rt_sigprocmask`.
+const SIG_BLOCK: i32 = 0;
+const SIG_UNBLOCK: i32 = 1;
+const SIG_SETMASK: i32 = 2;
+
+/// `si_code` values.
+const SI_USER: i32 = 0;
+const SI_TKILL: i32 = -6;
+const SI_KERNEL: i32 = 0x80;
+const SEGV_MAPERR: i32 = 1;
+const SEGV_ACCERR: i32 = 2;
+const ILL_ILLOPC: i32 = 1;
+const BUS_ADRALN: i32 = 1;
+const TRAP_BRKPT: i32 = 1;
+const CLD_EXITED: i32 = 1;
+const CLD_KILLED: i32 = 2;
+
+/// A set of signals, where signal `n` is bit `n - 1`.
+pub type SigSet = u64;
+
+const fn sig_bit(sig: u32) -> SigSet {
+    1 << (sig - 1)
+}
+
+/// Signals that can be neither blocked nor handled.
+const UNBLOCKABLE: SigSet = sig_bit(SIGKILL) | sig_bit(SIGSTOP);
+
+fn is_valid(sig: u32) -> bool {
+    (1..=NSIG).contains(&sig)
+}
+
+enum DefaultAction {
+    Terminate,
+    Ignore,
+    Stop,
+    Continue,
+}
+
+fn default_action(sig: u32) -> DefaultAction {
+    match sig {
+        SIGCHLD | SIGURG | SIGWINCH => DefaultAction::Ignore,
+        SIGCONT => DefaultAction::Continue,
+        SIGSTOP | SIGTSTP | SIGTTIN | SIGTTOU => DefaultAction::Stop,
+        _ => DefaultAction::Terminate,
+    }
+}
+
+/// A signal action, with the same layout as `struct sigaction` of the Linux
+/// syscall.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct SigAction {
+    handler: usize,
+    flags: usize,
+    mask: SigSet,
+}
+
+impl SigAction {
+    const DEFAULT: Self = Self {
+        handler: SIG_DFL,
+        flags: 0,
+        mask: 0,
+    };
+
+    fn is_ignored(&self, sig: u32) -> bool {
+        match self.handler {
+            SIG_IGN => true,
+            SIG_DFL => matches!(
+                default_action(sig),
+                DefaultAction::Ignore | DefaultAction::Continue
+            ),
+            _ => false,
+        }
+    }
+}
+
+/// Information about a signal, with the same layout as `siginfo_t`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SigInfo {
+    signo: i32,
+    errno: i32,
+    code: i32,
+    _pad: i32,
+    fields: [usize; 14],
+}
+
+impl SigInfo {
+    fn new(signo: u32, code: i32) -> Self {
+        Self {
+            signo: signo as _,
+            errno: 0,
+            code,
+            _pad: 0,
+            fields: [0; 14],
+        }
+    }
+
+    /// A signal sent by the process `pid`, e.g., by `kill`.
+    fn user(signo: u32, code: i32, pid: Pid) -> Self {
+        let mut info = Self::new(signo, code);
+        info.fields[0] = pid as u32 as usize; // si_pid, si_uid = 0
+        info
+    }
+
+    /// A signal caused by a fault at `addr`.
+    fn fault(signo: u32, code: i32, addr: usize) -> Self {
+        let mut info = Self::new(signo, code);
+        info.fields[0] = addr; // si_addr
+        info
+    }
+
+    /// The signal sent to the parent when the child `pid` exits.
+    pub fn child(signo: u32, pid: Pid, exit_code: i32, term_signal: u32) -> Self {
+        let (code, status) = if term_signal != 0 {
+            (CLD_KILLED, term_signal as i32)
+        } else {
+            (CLD_EXITED, exit_code)
+        };
+        let mut info = Self::new(signo, code);
+        info.fields[0] = pid as u32 as usize; // si_pid, si_uid = 0
+        info.fields[1] = status as u32 as usize; // si_status
+        info
+    }
+
+    fn signo(&self) -> u32 {
+        self.signo as u32
+    }
+}
+
+/// Pending signals, with the information of each one.
+struct PendingSignals(BTreeMap<u32, SigInfo>);
+
+impl PendingSignals {
+    const fn new() -> Self {
+        Self(BTreeMap::new())
+    }
+
+    fn set(&self) -> SigSet {
+        self.0.keys().fold(0, |set, &sig| set | sig_bit(sig))
+    }
+
+    /// Adds a signal. It is ignored if the same signal is already pending.
+    fn add(&mut self, info: SigInfo) {
+        self.0.entry(info.signo()).or_insert(info);
+    }
+
+    /// Removes the lowest pending signal not in `blocked`.
+    fn take(&mut self, blocked: SigSet) -> Option<SigInfo> {
+        let sig = *self.0.keys().find(|&&sig| sig_bit(sig) & blocked == 0)?;
+        self.0.remove(&sig)
+    }
+}
+
+/// The signal state of a thread.
+pub struct ThreadSignal {
+    pending: SpinNoIrq<PendingSignals>,
+    blocked: AtomicU64,
+    /// The registers restored by `rt_sigreturn`, which take effect when
+    /// returning to user space.
+    restore: SpinNoIrq<Option<TrapFrame>>,
+    /// The address of the wait queue the thread is interruptibly blocked in,
+    /// or 0.
+    waiting: SpinNoIrq<usize>,
+}
+
+impl ThreadSignal {
+    /// Creates the state of a new thread, with the given blocked signals.
+    pub fn new(blocked: SigSet) -> Self {
+        Self {
+            pending: SpinNoIrq::new(PendingSignals::new()),
+            blocked: AtomicU64::new(blocked),
+            restore: SpinNoIrq::new(None),
+            waiting: SpinNoIrq::new(0),
+        }
+    }
+
+    /// Returns the blocked signals.
+    pub fn blocked(&self) -> SigSet {
+        self.blocked.load(Ordering::Acquire)
+    }
+
+    fn set_blocked(&self, blocked: SigSet) {
+        self.blocked
+            .store(blocked & !UNBLOCKABLE, Ordering::Release);
+    }
+
+    /// Wakes up the thread if it is interruptibly blocked.
+    pub fn wake(&self) {
+        let waiting = self.waiting.lock();
+        if *waiting != 0 {
+            // SAFETY: the waiting thread clears it before the wait queue goes
+            // away, which cannot happen while the lock is held.
+            unsafe { (*(*waiting as *const WaitQueue)).notify_all(false) };
+        }
+    }
+}
+
+/// The signal state shared by the threads of a process.
+pub struct ProcessSignal {
+    pending: SpinNoIrq<PendingSignals>,
+    actions: SpinNoIrq<[SigAction; NSIG as usize]>,
+    /// The address of the page calling `rt_sigreturn`, or 0 if it is not
+    /// mapped yet.
+    trampoline: AtomicUsize,
+}
+
+impl ProcessSignal {
+    pub const fn new() -> Self {
+        Self {
+            pending: SpinNoIrq::new(PendingSignals::new()),
+            actions: SpinNoIrq::new([SigAction::DEFAULT; NSIG as usize]),
+            trampoline: AtomicUsize::new(0),
+        }
+    }
+
+    /// Copies the actions and the trampoline of the parent, for `fork`.
+    pub fn inherit(&self, parent: &ProcessSignal) {
+        *self.actions.lock() = *parent.actions.lock();
+        self.trampoline
+            .store(parent.trampoline.load(Ordering::Acquire), Ordering::Release);
+    }
+
+    /// Resets the handled signals to the default action, and forgets the
+    /// trampoline, for `execve`. Ignored signals stay ignored.
+    pub fn reset_for_exec(&self) {
+        for action in self.actions.lock().iter_mut() {
+            if action.handler != SIG_IGN {
+                *action = SigAction::DEFAULT;
+            }
+        }
+        self.trampoline.store(0, Ordering::Release);
+    }
+
+    fn action(&self, sig: u32) -> SigAction {
+        self.actions.lock()[sig as usize - 1]
+    }
+
+    /// Returns the trampoline page, mapping it into `aspace` if needed.
+    fn trampoline(&self, aspace: &mut AddrSpace) -> AxResult<usize> {
+        let addr = self.trampoline.load(Ordering::Acquire);
+        if addr != 0 {
+            return Ok(addr);
+        }
+        let limit = VirtAddrRange::from_start_size(aspace.base(), aspace.size());
+        let start = aspace
+            .find_free_area(aspace.layout().mmap_base, PAGE_SIZE_4K, limit)
+            .ok_or(axerrno::AxError::NoMemory)?;
+        let flags = MappingFlags::READ | MappingFlags::EXECUTE | MappingFlags::USER;
+        aspace.map_alloc(start, PAGE_SIZE_4K, flags, true)?;
+        aspace.write(start, &arch::trampoline_code())?;
+        self.trampoline.store(start.as_usize(), Ordering::Release);
+        Ok(start.as_usize())
+    }
+}
+
+/// The frame pushed onto the user stack to deliver a signal.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct SignalFrame {
+    info: SigInfo,
+    uc: arch::UContext,
+}
+
+/// Whether the current thread has a signal to handle, or should exit.
+fn signal_pending(ext: &TaskExt, tid: Pid) -> bool {
+    let blocked = ext.signal.blocked();
+    let pending = ext.signal.pending.lock().set() | ext.process.signal.pending.lock().set();
+    pending & !blocked != 0 || ext.process.should_exit(tid)
+}
+
+/// Blocks the current thread in `wq` until `condition` becomes true.
+///
+/// Returns `EINTR` if it is interrupted by a signal before that.
+pub fn wait_interruptible<F>(wq: &WaitQueue, condition: F) -> LinuxResult
+where
+    F: Fn() -> bool,
+{
+    let curr = current();
+    let ext = curr.task_ext();
+    let tid = curr.id().as_u64();
+    *ext.signal.waiting.lock() = wq as *const _ as usize;
+    wq.wait_until(|| condition() || signal_pending(ext, tid));
+    *ext.signal.waiting.lock() = 0;
+    if condition() {
+        Ok(())
+    } else {
+        Err(LinuxError::EINTR)
+    }
+}
+
+/// Blocks the current thread in `wq` until `condition` becomes true, like
+/// [`wait_interruptible`], but only interrupted if the thread should exit.
+///
+/// Returns `EINTR` if it is interrupted before that.
+pub fn wait_killable<F>(wq: &WaitQueue, condition: F) -> LinuxResult
+where
+    F: Fn() -> bool,
+{
+    let curr = current();
+    let ext = curr.task_ext();
+    let tid = curr.id().as_u64();
+    *ext.signal.waiting.lock() = wq as *const _ as usize;
+    wq.wait_until(|| condition() || ext.process.should_exit(tid));
+    *ext.signal.waiting.lock() = 0;
+    if condition() {
+        Ok(())
+    } else {
+        Err(LinuxError::EINTR)
+    }
+}
+
+/// Sleeps for `dur`.
+///
+/// Returns `EINTR` if it is interrupted by a signal before that.
+pub fn sleep_interruptible(dur: Duration) -> LinuxResult {
+    let curr = current();
+    let ext = curr.task_ext();
+    let tid = curr.id().as_u64();
+    let wq = WaitQueue::new();
+    *ext.signal.waiting.lock() = &wq as *const _ as usize;
+    let timeout = wq.wait_timeout_until(dur, || signal_pending(ext, tid));
+    *ext.signal.waiting.lock() = 0;
+    if timeout {
+        Ok(())
+    } else {
+        Err(LinuxError::EINTR)
+    }
+}
+
+/// Sends a signal to the process. `SIGKILL` terminates it right away.
+pub fn send_to_process(process: &Process, info: SigInfo) {
+    let sig = info.signo();
+    if sig == SIGKILL {
+        process.terminate(sig);
+        return;
+    }
+    if process.signal.action(sig).is_ignored(sig) {
+        return;
+    }
+    process.signal.pending.lock().add(info);
+    for thread in process.threads() {
+        thread.wake();
+    }
+}
+
+/// Sends a signal to a thread of the process.
+fn send_to_thread(process: &Process, thread: &ThreadSignal, info: SigInfo) {
+    let sig = info.signo();
+    if sig == SIGKILL {
+        process.terminate(sig);
+        return;
+    }
+    if process.signal.action(sig).is_ignored(sig) {
+        return;
+    }
+    thread.pending.lock().add(info);
+    thread.wake();
+}
+
+/// Sends a signal caused by the current thread, which can be neither blocked
+/// nor ignored.
+fn force_signal(ext: &TaskExt, info: SigInfo) {
+    let sig = info.signo();
+    let mut actions = ext.process.signal.actions.lock();
+    if actions[sig as usize - 1].handler == SIG_IGN {
+        actions[sig as usize - 1] = SigAction::DEFAULT;
+    }
+    drop(actions);
+    ext.signal.set_blocked(ext.signal.blocked() & !sig_bit(sig));
+    ext.signal.pending.lock().add(info);
+}
+
+/// Pushes the signal frame and makes the user context enter the handler.
+fn setup_frame(tf: &mut TrapFrame, ext: &TaskExt, action: &SigAction, info: &SigInfo) -> AxResult {
+    let mut aspace = ext.aspace.lock();
+    let trampoline = ext.process.signal.trampoline(&mut aspace)?;
+    let frame_addr =
+        arch::user_sp(tf).wrapping_sub(arch::RED_ZONE + size_of::<SignalFrame>()) & !0xf;
+    let frame = SignalFrame {
+        info: *info,
+        uc: arch::UContext::new(tf, ext.signal.blocked()),
+    };
+    UserPtr::<SignalFrame>::from(frame_addr).write(&mut aspace, frame)?;
+
+    let args = [
+        info.signo() as usize,
+        frame_addr + offset_of!(SignalFrame, info),
+        frame_addr + offset_of!(SignalFrame, uc),
+    ];
+    arch::enter_handler(
+        tf,
+        &mut aspace,
+        action.handler,
+        trampoline,
+        frame_addr,
+        args,
+    )
+}
+
+/// Delivers the pending signals of the current thread before returning to
+/// user space, and applies the context restored by `rt_sigreturn`.
+#[register_trap_handler(RETURN_TO_USER)]
+fn handle_signals(tf: &mut TrapFrame) {
+    let curr = current();
+    if unsafe { curr.task_ext_ptr() }.is_null() {
+        return; // not a user task
+    }
+    let ext = curr.task_ext();
+    if let Some(restored) = ext.signal.restore.lock().take() {
+        *tf = restored;
+    }
+    process::check_group_exit();
+
+    loop {
+        let blocked = ext.signal.blocked();
+        let taken = ext.signal.pending.lock().take(blocked);
+        let Some(info) = taken.or_else(|| ext.process.signal.pending.lock().take(blocked)) else {
+            return;
+        };
+        let sig = info.signo();
+        let action = ext.process.signal.action(sig);
+        match action.handler {
+            SIG_IGN => continue,
+            SIG_DFL => match default_action(sig) {
+                DefaultAction::Terminate => {
+                    ext.process.terminate(sig);
+                    process::check_group_exit();
+                }
+                DefaultAction::Stop => warn!("signal {}: stopping is not supported", sig),
+                DefaultAction::Ignore | DefaultAction::Continue => {}
+            },
+            _ => {
+                if let Err(err) = setup_frame(tf, ext, &action, &info) {
+                    warn!("signal {}: cannot set up the frame: {:?}", sig, err);
+                    ext.process.terminate(SIGSEGV);
+                    process::check_group_exit();
+                }
+                let mut blocked = blocked | action.mask;
+                if action.flags & SA_NODEFER == 0 {
+                    blocked |= sig_bit(sig);
+                }
+                ext.signal.set_blocked(blocked);
+                if action.flags & SA_RESETHAND != 0 {
+                    ext.process.signal.actions.lock()[sig as usize - 1] = SigAction::DEFAULT;
+                }
+                // The other signals are delivered after the handler returns.
+                return;
+            }
+        }
+    }
+}
+
+/// Turns the faults of user code into signals to the current thread.
+#[register_trap_handler(USER_FAULT)]
+fn handle_user_fault(fault: UserFault) -> bool {
+    let curr = current();
+    if unsafe { curr.task_ext_ptr() }.is_null() {
+        return false;
+    }
+    let ext = curr.task_ext();
+    let info = match fault {
+        UserFault::PageFault(vaddr, _) => {
+            // The address space may be locked by the faulting access.
+            let mapped = ext
+                .aspace
+                .try_lock()
+                .is_some_and(|aspace| aspace.is_mapped(vaddr.align_down_4k(), PAGE_SIZE_4K));
+            let code = if mapped { SEGV_ACCERR } else { SEGV_MAPERR };
+            SigInfo::fault(SIGSEGV, code, vaddr.as_usize())
+        }
+        UserFault::IllegalInstruction => SigInfo::fault(SIGILL, ILL_ILLOPC, 0),
+        UserFault::Misaligned(vaddr) => SigInfo::fault(SIGBUS, BUS_ADRALN, vaddr.as_usize()),
+        UserFault::Breakpoint => SigInfo::fault(SIGTRAP, TRAP_BRKPT, 0),
+    };
+    force_signal(ext, info);
+    true
+}
+
+fn rt_sigaction(sig: u32, act: usize, oldact: usize, sigsetsize: usize) -> LinuxResult<isize> {
+    if sigsetsize != size_of::<SigSet>() || !is_valid(sig) {
+        return Err(LinuxError::EINVAL);
+    }
+    if act != 0 && sig_bit(sig) & UNBLOCKABLE != 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    let curr = current();
+    let ext = curr.task_ext();
+    let mut aspace = ext.aspace.lock();
+    let new = if act != 0 {
+        Some(UserPtr::<SigAction>::from(act).read(&mut aspace)?)
+    } else {
+        None
+    };
+    let old = ext.process.signal.action(sig);
+    if oldact != 0 {
+        UserPtr::<SigAction>::from(oldact).write(&mut aspace, old)?;
+    }
+    drop(aspace);
+    if let Some(mut new) = new {
+        new.mask &= !UNBLOCKABLE;
+        ext.process.signal.actions.lock()[sig as usize - 1] = new;
+        if new.is_ignored(sig) {
+            // Discard the pending ones, like Linux.
+            ext.process.signal.pending.lock().0.remove(&sig);
+            for thread in ext.process.threads() {
+                thread.pending.lock().0.remove(&sig);
+            }
+        }
+    }
+    Ok(0)
+}
+
+fn rt_sigprocmask(how: i32, set: usize, oldset: usize, sigsetsize: usize) -> LinuxResult<isize> {
+    if sigsetsize != size_of::<SigSet>() {
+        return Err(LinuxError::EINVAL);
+    }
+    let curr = current();
+    let ext = curr.task_ext();
+    let mut aspace = ext.aspace.lock();
+    let old = ext.signal.blocked();
+    if set != 0 {
+        let set = UserPtr::<SigSet>::from(set).read(&mut aspace)?;
+        let blocked = match how {
+            SIG_BLOCK => old | set,
+            SIG_UNBLOCK => old & !set,
+            SIG_SETMASK => set,
+            _ => return Err(LinuxError::EINVAL),
+        };
+        ext.signal.set_blocked(blocked);
+    }
+    if oldset != 0 {
+        UserPtr::<SigSet>::from(oldset).write(&mut aspace, old)?;
+    }
+    Ok(0)
+}
+
+fn rt_sigpending(set: usize, sigsetsize: usize) -> LinuxResult<isize> {
+    if sigsetsize != size_of::<SigSet>() {
+        return Err(LinuxError::EINVAL);
+    }
+    let curr = current();
+    let ext = curr.task_ext();
+    let pending = ext.signal.pending.lock().set() | ext.process.signal.pending.lock().set();
+    UserPtr::<SigSet>::from(set).write(&mut ext.aspace.lock(), pending & ext.signal.blocked())?;
+    Ok(0)
+}
+
+fn kill(pid: i32, sig: u32) -> LinuxResult<isize> {
+    if sig != 0 && !is_valid(sig) {
+        return Err(LinuxError::EINVAL);
+    }
+    let curr = current();
+    let sender = curr.task_ext().process.pid();
+    // There are no process groups, so each process is in its own group.
+    let targets = match pid {
+        0 => alloc::vec![curr.task_ext().process.clone()],
+        // All processes except the init process and the caller.
+        -1 => process::all_processes()
+            .into_iter()
+            .filter(|process| {
+                process.pid() != sender && process.parent().is_some() && !process.is_zombie()
+            })
+            .collect(),
+        _ => {
+            let process = process::find_process(pid.unsigned_abs() as Pid);
+            alloc::vec![process.ok_or(LinuxError::ESRCH)?]
+        }
+    };
+    if targets.is_empty() {
+        return Err(LinuxError::ESRCH);
+    }
+    if sig != 0 {
+        for process in targets {
+            send_to_process(&process, SigInfo::user(sig, SI_USER, sender));
+        }
+    }
+    Ok(0)
+}
+
+fn tgkill(tgid: Option<Pid>, tid: Pid, sig: u32) -> LinuxResult<isize> {
+    if sig != 0 && !is_valid(sig) {
+        return Err(LinuxError::EINVAL);
+    }
+    let (process, thread) = process::find_thread(tid).ok_or(LinuxError::ESRCH)?;
+    if tgid.is_some_and(|tgid| tgid != process.pid()) {
+        return Err(LinuxError::ESRCH);
+    }
+    if sig != 0 {
+        let sender = current().task_ext().process.pid();
+        send_to_thread(&process, &thread, SigInfo::user(sig, SI_TKILL, sender));
+    }
+    Ok(0)
+}
+
+fn rt_sigreturn(tf: &TrapFrame) -> LinuxResult<isize> {
+    let curr = current();
+    let ext = curr.task_ext();
+    let frame = UserPtr::<SignalFrame>::from(arch::user_sp(tf)).read(&mut ext.aspace.lock());
+    let frame = match frame {
+        Ok(frame) => frame,
+        Err(err) => {
+            force_signal(ext, SigInfo::new(SIGSEGV, SI_KERNEL));
+            return Err(err.into());
+        }
+    };
+    let mut restored = *tf;
+    frame.uc.restore(&mut restored);
+    ext.signal.set_blocked(frame.uc.sigmask());
+    *ext.signal.restore.lock() = Some(restored);
+    Ok(arch::retval(&restored) as isize)
+}
+
+pub(crate) fn sys_rt_sigaction(sig: u32, act: usize, oldact: usize, sigsetsize: usize) -> isize {
+    syscall_body!(sys_rt_sigaction, rt_sigaction(sig, act, oldact, sigsetsize))
+}
+
+pub(crate) fn sys_rt_sigprocmask(how: i32, set: usize, oldset: usize, sigsetsize: usize) -> isize {
+    syscall_body!(
+        sys_rt_sigprocmask,
+        rt_sigprocmask(how, set, oldset, sigsetsize)
+    )
+}
+
+pub(crate) fn sys_rt_sigpending(set: usize, sigsetsize: usize) -> isize {
+    syscall_body!(sys_rt_sigpending, rt_sigpending(set, sigsetsize))
+}
+
+pub(crate) fn sys_kill(pid: i32, sig: u32) -> isize {
+    syscall_body!(sys_kill, kill(pid, sig))
+}
+
+pub(crate) fn sys_tkill(tid: Pid, sig: u32) -> isize {
+    syscall_body!(sys_tkill, tgkill(None, tid, sig))
+}
+
+pub(crate) fn sys_tgkill(tgid: Pid, tid: Pid, sig: u32) -> isize {
+    syscall_body!(sys_tgkill, tgkill(Some(tgid), tid, sig))
+}
+
+pub(crate) fn sys_rt_sigreturn(tf: &TrapFrame) -> isize {
+    syscall_body!(sys_rt_sigreturn, rt_sigreturn(tf))
+}
@@ -0,0 +1,115 @@
+//! The signal frame and the trampoline of aarch64.
+
+use axerrno::AxResult;
+use axhal::arch::TrapFrame;
+use axmm::AddrSpace;
+use axsyscall::Sysno;
+
+use super::SigSet;
+
+/// Bytes below the stack pointer that the signal frame must not overwrite.
+pub(super) const RED_ZONE: usize = 0;
+
+/// `mov x8, #__NR_rt_sigreturn; svc #0`
+pub(super) fn trampoline_code() -> [u8; 8] {
+    let mov = 0xd280_0000 | ((Sysno::rt_sigreturn.number() as u32) << 5) | 8;
+    let svc = 0xd400_0001u32;
+    let mut code = [0; 8];
+    code[..4].copy_from_slice(&mov.to_le_bytes());
+    code[4..].copy_from_slice(&svc.to_le_bytes());
+    code
+}
+
+/// The user context saved in the signal frame, with the same layout as
+/// `struct sigcontext` of aarch64.
+#[repr(C, align(16))]
+#[derive(Clone, Copy)]
+struct MContext {
+    fault_address: u64,
+    regs: [u64; 31],
+    sp: u64,
+    pc: u64,
+    pstate: u64,
+    _pad: u64,
+    /// The extra contexts (e.g., the FP/SIMD registers), which are not saved.
+    /// It ends with a zero header.
+    reserved: [u8; 4096],
+}
+
+/// The `ucontext_t` in the signal frame.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(super) struct UContext {
+    flags: usize,
+    link: usize,
+    stack: [usize; 3],
+    sigmask: SigSet,
+    _unused: [u8; 120],
+    mcontext: MContext,
+}
+
+impl UContext {
+    /// Saves the user context in `tf`, with the signal mask to restore.
+    pub(super) fn new(tf: &TrapFrame, sigmask: SigSet) -> Self {
+        Self {
+            flags: 0,
+            link: 0,
+            stack: [0; 3],
+            sigmask,
+            _unused: [0; 120],
+            mcontext: MContext {
+                fault_address: 0,
+                regs: tf.r,
+                sp: tf.usp,
+                pc: tf.elr,
+                pstate: tf.spsr,
+                _pad: 0,
+                reserved: [0; 4096],
+            },
+        }
+    }
+
+    /// Returns the saved signal mask.
+    pub(super) fn sigmask(&self) -> SigSet {
+        self.sigmask
+    }
+
+    /// Restores the saved user context into `tf`.
+    ///
+    /// `pstate` is not restored, so the user cannot change the exception
+    /// level.
+    pub(super) fn restore(&self, tf: &mut TrapFrame) {
+        tf.r = self.mcontext.regs;
+        tf.usp = self.mcontext.sp;
+        tf.elr = self.mcontext.pc;
+    }
+}
+
+/// Returns the user stack pointer.
+pub(super) fn user_sp(tf: &TrapFrame) -> usize {
+    tf.usp as usize
+}
+
+/// Returns the syscall return value, i.e., `x0`.
+pub(super) fn retval(tf: &TrapFrame) -> usize {
+    tf.r[0] as usize
+}
+
+/// Makes the user context call `handler` with `args` on the stack at `sp`,
+/// returning to `trampoline`.
+pub(super) fn enter_handler(
+    tf: &mut TrapFrame,
+    _aspace: &mut AddrSpace,
+    handler: usize,
+    trampoline: usize,
+    sp: usize,
+    args: [usize; 3],
+) -> AxResult {
+    tf.elr = handler as u64;
+    tf.r[30] = trampoline as u64;
+    tf.usp = sp as u64;
+    for (reg, arg) in tf.r.iter_mut().zip(args) {
+        *reg = arg as u64;
+    }
+    Ok(())
+}
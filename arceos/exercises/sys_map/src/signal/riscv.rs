@@ -0,0 +1,108 @@
+//! The signal frame and the trampoline of riscv64.
+
+use axerrno::AxResult;
+use axhal::arch::{GeneralRegisters, TrapFrame};
+use axmm::AddrSpace;
+use axsyscall::Sysno;
+
+use super::SigSet;
+
+/// Bytes below the stack pointer that the signal frame must not overwrite.
+pub(super) const RED_ZONE: usize = 0;
+
+/// `li a7, __NR_rt_sigreturn; ecall`
+pub(super) fn trampoline_code() -> [u8; 8] {
+    let li = ((Sysno::rt_sigreturn.number() as u32) << 20) | (17 << 7) | 0x13;
+    let ecall = 0x73u32;
+    let mut code = [0; 8];
+    code[..4].copy_from_slice(&li.to_le_bytes());
+    code[4..].copy_from_slice(&ecall.to_le_bytes());
+    code
+}
+
+/// The user context saved in the signal frame, with the same layout as
+/// `struct sigcontext` of riscv64.
+#[repr(C, align(16))]
+#[derive(Clone, Copy)]
+struct MContext {
+    /// `pc` and the general registers except `zero`.
+    regs: [usize; 32],
+    /// The floating-point registers, which are not saved.
+    fpregs: [u64; 66],
+}
+
+/// The `ucontext_t` in the signal frame.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(super) struct UContext {
+    flags: usize,
+    link: usize,
+    stack: [usize; 3],
+    sigmask: SigSet,
+    _unused: [u8; 120],
+    mcontext: MContext,
+}
+
+impl UContext {
+    /// Saves the user context in `tf`, with the signal mask to restore.
+    pub(super) fn new(tf: &TrapFrame, sigmask: SigSet) -> Self {
+        // SAFETY: `GeneralRegisters` is 31 `usize`s in the same order.
+        let regs: [usize; 31] = unsafe { core::mem::transmute(tf.regs) };
+        let mut mcontext = MContext {
+            regs: [0; 32],
+            fpregs: [0; 66],
+        };
+        mcontext.regs[0] = tf.sepc;
+        mcontext.regs[1..].copy_from_slice(&regs);
+        Self {
+            flags: 0,
+            link: 0,
+            stack: [0; 3],
+            sigmask,
+            _unused: [0; 120],
+            mcontext,
+        }
+    }
+
+    /// Returns the saved signal mask.
+    pub(super) fn sigmask(&self) -> SigSet {
+        self.sigmask
+    }
+
+    /// Restores the saved user context into `tf`.
+    pub(super) fn restore(&self, tf: &mut TrapFrame) {
+        let regs: [usize; 31] = self.mcontext.regs[1..].try_into().unwrap();
+        // SAFETY: `GeneralRegisters` is 31 `usize`s in the same order.
+        tf.regs = unsafe { core::mem::transmute::<[usize; 31], GeneralRegisters>(regs) };
+        tf.sepc = self.mcontext.regs[0];
+    }
+}
+
+/// Returns the user stack pointer.
+pub(super) fn user_sp(tf: &TrapFrame) -> usize {
+    tf.regs.sp
+}
+
+/// Returns the syscall return value, i.e., `a0`.
+pub(super) fn retval(tf: &TrapFrame) -> usize {
+    tf.regs.a0
+}
+
+/// Makes the user context call `handler` with `args` on the stack at `sp`,
+/// returning to `trampoline`.
+pub(super) fn enter_handler(
+    tf: &mut TrapFrame,
+    _aspace: &mut AddrSpace,
+    handler: usize,
+    trampoline: usize,
+    sp: usize,
+    args: [usize; 3],
+) -> AxResult {
+    tf.sepc = handler;
+    tf.regs.ra = trampoline;
+    tf.regs.sp = sp;
+    tf.regs.a0 = args[0];
+    tf.regs.a1 = args[1];
+    tf.regs.a2 = args[2];
+    Ok(())
+}
@@ -0,0 +1,170 @@
+//! The signal frame and the trampoline of x86_64.
+
+use axerrno::AxResult;
+use axhal::arch::TrapFrame;
+use axmm::{AddrSpace, UserPtr};
+use axsyscall::Sysno;
+
+use super::SigSet;
+
+/// Bytes below the stack pointer that the signal frame must not overwrite,
+/// i.e., the red zone of the System V ABI.
+pub(super) const RED_ZONE: usize = 128;
+
+/// `mov eax, __NR_rt_sigreturn; syscall; nop`
+pub(super) fn trampoline_code() -> [u8; 8] {
+    let mut code = [0xb8, 0, 0, 0, 0, 0x0f, 0x05, 0x90];
+    code[1..5].copy_from_slice(&(Sysno::rt_sigreturn.number() as u32).to_le_bytes());
+    code
+}
+
+/// The user context saved in the signal frame, with the same layout as
+/// `struct sigcontext` of x86_64.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct MContext {
+    r8: u64,
+    r9: u64,
+    r10: u64,
+    r11: u64,
+    r12: u64,
+    r13: u64,
+    r14: u64,
+    r15: u64,
+    rdi: u64,
+    rsi: u64,
+    rbp: u64,
+    rbx: u64,
+    rdx: u64,
+    rax: u64,
+    rcx: u64,
+    rsp: u64,
+    rip: u64,
+    eflags: u64,
+    cs: u16,
+    gs: u16,
+    fs: u16,
+    ss: u16,
+    err: u64,
+    trapno: u64,
+    oldmask: u64,
+    cr2: u64,
+    /// The floating-point state, which is not saved.
+    fpstate: u64,
+    _reserved: [u64; 8],
+}
+
+/// The `struct ucontext` in the signal frame.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(super) struct UContext {
+    flags: usize,
+    link: usize,
+    stack: [usize; 3],
+    mcontext: MContext,
+    sigmask: SigSet,
+}
+
+impl UContext {
+    /// Saves the user context in `tf`, with the signal mask to restore.
+    pub(super) fn new(tf: &TrapFrame, sigmask: SigSet) -> Self {
+        Self {
+            flags: 0,
+            link: 0,
+            stack: [0; 3],
+            mcontext: MContext {
+                r8: tf.r8,
+                r9: tf.r9,
+                r10: tf.r10,
+                r11: tf.r11,
+                r12: tf.r12,
+                r13: tf.r13,
+                r14: tf.r14,
+                r15: tf.r15,
+                rdi: tf.rdi,
+                rsi: tf.rsi,
+                rbp: tf.rbp,
+                rbx: tf.rbx,
+                rdx: tf.rdx,
+                rax: tf.rax,
+                rcx: tf.rcx,
+                rsp: tf.rsp,
+                rip: tf.rip,
+                eflags: tf.rflags,
+                cs: tf.cs as u16,
+                gs: 0,
+                fs: 0,
+                ss: tf.ss as u16,
+                err: 0,
+                trapno: 0,
+                oldmask: 0,
+                cr2: 0,
+                fpstate: 0,
+                _reserved: [0; 8],
+            },
+            sigmask,
+        }
+    }
+
+    /// Returns the saved signal mask.
+    pub(super) fn sigmask(&self) -> SigSet {
+        self.sigmask
+    }
+
+    /// Restores the saved user context into `tf`.
+    ///
+    /// The segment registers and the flags are not restored, so the user
+    /// cannot change the privilege level.
+    pub(super) fn restore(&self, tf: &mut TrapFrame) {
+        let mc = &self.mcontext;
+        tf.r8 = mc.r8;
+        tf.r9 = mc.r9;
+        tf.r10 = mc.r10;
+        tf.r11 = mc.r11;
+        tf.r12 = mc.r12;
+        tf.r13 = mc.r13;
+        tf.r14 = mc.r14;
+        tf.r15 = mc.r15;
+        tf.rdi = mc.rdi;
+        tf.rsi = mc.rsi;
+        tf.rbp = mc.rbp;
+        tf.rbx = mc.rbx;
+        tf.rdx = mc.rdx;
+        tf.rax = mc.rax;
+        tf.rcx = mc.rcx;
+        tf.rsp = mc.rsp;
+        tf.rip = mc.rip;
+    }
+}
+
+/// Returns the user stack pointer.
+pub(super) fn user_sp(tf: &TrapFrame) -> usize {
+    tf.rsp as usize
+}
+
+/// Returns the syscall return value, i.e., `rax`.
+pub(super) fn retval(tf: &TrapFrame) -> usize {
+    tf.rax as usize
+}
+
+/// Makes the user context call `handler` with `args` on the stack at `sp`,
+/// returning to `trampoline`.
+///
+/// The return address is pushed onto the stack, as a `call` does.
+pub(super) fn enter_handler(
+    tf: &mut TrapFrame,
+    aspace: &mut AddrSpace,
+    handler: usize,
+    trampoline: usize,
+    sp: usize,
+    args: [usize; 3],
+) -> AxResult {
+    let sp = sp.wrapping_sub(8);
+    UserPtr::<usize>::from(sp).write(aspace, trampoline)?;
+    tf.rip = handler as u64;
+    tf.rsp = sp as u64;
+    tf.rdi = args[0] as u64;
+    tf.rsi = args[1] as u64;
+    tf.rdx = args[2] as u64;
+    Ok(())
+}
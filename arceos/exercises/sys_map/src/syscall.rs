@@ -1,151 +1,122 @@
 #![allow(dead_code)]
 
 use core::ffi::{c_void, c_char, c_int};
+use core::time::Duration;
 use axhal::arch::TrapFrame;
 use axhal::trap::{register_trap_handler, SYSCALL};
 use axerrno::LinuxError;
+use axsyscall::{register_user_reader, syscall_body, Sysno, USER_READER};
 use axtask::current;
 use axtask::TaskExtRef;
-use axhal::paging::MappingFlags;
 use arceos_posix_api as api;
+use axhal::mem::VirtAddr;
+use axmm::{
+    copy_from_user, read_iovecs_from_user, read_to_user, write_from_user, UserIoVec, UserPtr,
+};
+use crate::fs;
+use crate::mm;
+use crate::process;
+use crate::shm;
+use crate::signal;
 
-const SYS_IOCTL: usize = 29;
-const SYS_OPENAT: usize = 56;
-const SYS_CLOSE: usize = 57;
-const SYS_READ: usize = 63;
-const SYS_WRITE: usize = 64;
-const SYS_WRITEV: usize = 66;
-const SYS_EXIT: usize = 93;
-const SYS_EXIT_GROUP: usize = 94;
-const SYS_SET_TID_ADDRESS: usize = 96;
-const SYS_MMAP: usize = 222;
-
-const AT_FDCWD: i32 = -100;
-
-/// Macro to generate syscall body
-///
-/// It will receive a function which return Result<_, LinuxError> and convert it to
-/// the type which is specified by the caller.
-#[macro_export]
-macro_rules! syscall_body {
-    ($fn: ident, $($stmt: tt)*) => {{
-        #[allow(clippy::redundant_closure_call)]
-        let res = (|| -> axerrno::LinuxResult<_> { $($stmt)* })();
-        match res {
-            Ok(_) | Err(axerrno::LinuxError::EAGAIN) => debug!(concat!(stringify!($fn), " => {:?}"),  res),
-            Err(_) => info!(concat!(stringify!($fn), " => {:?}"), res),
+#[register_trap_handler(SYSCALL)]
+fn handle_syscall(tf: &TrapFrame, syscall_num: usize) -> isize {
+    process::check_group_exit();
+    axsyscall::dispatch(tf, syscall_num, |sysno, args| match sysno {
+        Sysno::ioctl => sys_ioctl(args.get(0), args.get(1), args.get(2)) as _,
+        Sysno::set_tid_address => sys_set_tid_address(args.get(0)),
+        Sysno::openat => fs::sys_openat(args.get(0), args.get(1), args.get(2), args.get(3)),
+        Sysno::close => sys_close(args.get(0)),
+        Sysno::read => sys_read(args.get(0), args.get(1), args.get(2)),
+        Sysno::write => sys_write(args.get(0), args.get(1), args.get(2)),
+        Sysno::writev => sys_writev(args.get(0), args.get(1), args.get(2)),
+        Sysno::getdents64 => fs::sys_getdents64(args.get(0), args.get(1), args.get(2)),
+        Sysno::newfstatat => {
+            fs::sys_newfstatat(args.get(0), args.get(1), args.get(2), args.get(3))
         }
-        match res {
-            Ok(v) => v as _,
-            Err(e) => {
-                -e.code() as _
-            }
+        Sysno::fstat => fs::sys_fstat(args.get(0), args.get(1)),
+        Sysno::mkdirat => fs::sys_mkdirat(args.get(0), args.get(1), args.get(2)),
+        Sysno::unlinkat => fs::sys_unlinkat(args.get(0), args.get(1), args.get(2)),
+        Sysno::renameat2 => fs::sys_renameat2(
+            args.get(0),
+            args.get(1),
+            args.get(2),
+            args.get(3),
+            args.get(4),
+        ),
+        Sysno::ftruncate => fs::sys_ftruncate(args.get(0), args.get(1)),
+        Sysno::fsync => fs::sys_fsync(args.get(0)),
+        Sysno::getcwd => fs::sys_getcwd(args.get(0), args.get(1)),
+        Sysno::chdir => fs::sys_chdir(args.get(0)),
+        Sysno::fchdir => fs::sys_fchdir(args.get(0)),
+        Sysno::faccessat => fs::sys_faccessat(args.get(0), args.get(1), args.get(2)),
+        Sysno::faccessat2 => {
+            fs::sys_faccessat2(args.get(0), args.get(1), args.get(2), args.get(3))
         }
-    }};
-}
-
-bitflags::bitflags! {
-    #[derive(Debug)]
-    /// permissions for sys_mmap
-    ///
-    /// See <https://github.com/bminor/glibc/blob/master/bits/mman.h>
-    struct MmapProt: i32 {
-        /// Page can be read.
-        const PROT_READ = 1 << 0;
-        /// Page can be written.
-        const PROT_WRITE = 1 << 1;
-        /// Page can be executed.
-        const PROT_EXEC = 1 << 2;
-    }
-}
-
-impl From<MmapProt> for MappingFlags {
-    fn from(value: MmapProt) -> Self {
-        let mut flags = MappingFlags::USER;
-        if value.contains(MmapProt::PROT_READ) {
-            flags |= MappingFlags::READ;
+        Sysno::utimensat => {
+            fs::sys_utimensat(args.get(0), args.get(1), args.get(2), args.get(3))
         }
-        if value.contains(MmapProt::PROT_WRITE) {
-            flags |= MappingFlags::WRITE;
+        Sysno::exit_group => {
+            ax_println!("[SYS_EXIT_GROUP]: process is exiting ..");
+            process::exit_group(args.get(0))
         }
-        if value.contains(MmapProt::PROT_EXEC) {
-            flags |= MappingFlags::EXECUTE;
+        Sysno::exit => {
+            ax_println!("[SYS_EXIT]: thread is exiting ..");
+            process::exit_current(args.get(0))
         }
-        flags
-    }
-}
-
-bitflags::bitflags! {
-    #[derive(Debug)]
-    /// flags for sys_mmap
-    ///
-    /// See <https://github.com/bminor/glibc/blob/master/bits/mman.h>
-    struct MmapFlags: i32 {
-        /// Share changes
-        const MAP_SHARED = 1 << 0;
-        /// Changes private; copy pages on write.
-        const MAP_PRIVATE = 1 << 1;
-        /// Map address must be exactly as requested, no matter whether it is available.
-        const MAP_FIXED = 1 << 4;
-        /// Don't use a file.
-        const MAP_ANONYMOUS = 1 << 5;
-        /// Don't check for reservations.
-        const MAP_NORESERVE = 1 << 14;
-        /// Allocation is for a stack.
-        const MAP_STACK = 0x20000;
-    }
-}
-
-#[register_trap_handler(SYSCALL)]
-fn handle_syscall(tf: &TrapFrame, syscall_num: usize) -> isize {
-    ax_println!("handle_syscall [{}] ...", syscall_num);
-    let ret = match syscall_num {
-         SYS_IOCTL => sys_ioctl(tf.arg0() as _, tf.arg1() as _, tf.arg2() as _) as _,
-        SYS_SET_TID_ADDRESS => sys_set_tid_address(tf.arg0() as _),
-        SYS_OPENAT => sys_openat(tf.arg0() as _, tf.arg1() as _, tf.arg2() as _, tf.arg3() as _),
-        SYS_CLOSE => sys_close(tf.arg0() as _),
-        SYS_READ => sys_read(tf.arg0() as _, tf.arg1() as _, tf.arg2() as _),
-        SYS_WRITE => sys_write(tf.arg0() as _, tf.arg1() as _, tf.arg2() as _),
-        SYS_WRITEV => sys_writev(tf.arg0() as _, tf.arg1() as _, tf.arg2() as _),
-        SYS_EXIT_GROUP => {
-            ax_println!("[SYS_EXIT_GROUP]: system is exiting ..");
-            axtask::exit(tf.arg0() as _)
-        },
-        SYS_EXIT => {
-            ax_println!("[SYS_EXIT]: system is exiting ..");
-            axtask::exit(tf.arg0() as _)
-        },
-        SYS_MMAP => sys_mmap(
-            tf.arg0() as _,
-            tf.arg1() as _,
-            tf.arg2() as _,
-            tf.arg3() as _,
-            tf.arg4() as _,
-            tf.arg5() as _,
+        Sysno::nanosleep => sys_nanosleep(args.get(0), args.get(1)),
+        Sysno::kill => signal::sys_kill(args.get(0), args.get(1)),
+        Sysno::tkill => signal::sys_tkill(args.get(0), args.get(1)),
+        Sysno::tgkill => signal::sys_tgkill(args.get(0), args.get(1), args.get(2)),
+        Sysno::rt_sigaction => {
+            signal::sys_rt_sigaction(args.get(0), args.get(1), args.get(2), args.get(3))
+        }
+        Sysno::rt_sigprocmask => {
+            signal::sys_rt_sigprocmask(args.get(0), args.get(1), args.get(2), args.get(3))
+        }
+        Sysno::rt_sigpending => signal::sys_rt_sigpending(args.get(0), args.get(1)),
+        Sysno::rt_sigreturn => signal::sys_rt_sigreturn(tf),
+        Sysno::getpid => process::sys_getpid(),
+        Sysno::getppid => process::sys_getppid(),
+        Sysno::gettid => process::sys_gettid(),
+        Sysno::clone => process::sys_clone(
+            tf,
+            args.get(0),
+            args.get(1),
+            args.get(2),
+            args.get(3),
+            args.get(4),
+        ),
+        Sysno::execve => process::sys_execve(args.get(0), args.get(1), args.get(2)),
+        Sysno::wait4 => process::sys_wait4(args.get(0), args.get(1), args.get(2), args.get(3)),
+        Sysno::brk => mm::sys_brk(args.get(0)),
+        Sysno::munmap => mm::sys_munmap(args.get(0), args.get(1)),
+        Sysno::mmap => mm::sys_mmap(
+            args.get(0),
+            args.get(1),
+            args.get(2),
+            args.get(3),
+            args.get(4),
+            args.get(5),
         ),
+        Sysno::mprotect => mm::sys_mprotect(args.get(0), args.get(1), args.get(2)),
+        Sysno::madvise => mm::sys_madvise(args.get(0), args.get(1), args.get(2)),
+        Sysno::shmget => shm::sys_shmget(args.get(0), args.get(1), args.get(2)),
+        Sysno::shmctl => shm::sys_shmctl(args.get(0), args.get(1), args.get(2)),
+        Sysno::shmat => shm::sys_shmat(args.get(0), args.get(1), args.get(2)),
+        Sysno::shmdt => shm::sys_shmdt(args.get(0)),
+        Sysno::memfd_create => shm::sys_memfd_create(args.get(0), args.get(1)),
         _ => {
-            ax_println!("Unimplemented syscall: {}", syscall_num);
+            ax_println!("Unimplemented syscall: {}", sysno.name());
             -LinuxError::ENOSYS.code() as _
         }
-    };
-    ret
+    })
 }
 
-#[allow(unused_variables)]
-fn sys_mmap(
-    addr: *mut usize,
-    length: usize,
-    prot: i32,
-    flags: i32,
-    fd: i32,
-    _offset: isize,
-) -> isize {
-    unimplemented!("no sys_mmap!");
-}
-
-fn sys_openat(dfd: c_int, fname: *const c_char, flags: c_int, mode: api::ctypes::mode_t) -> isize {
-    assert_eq!(dfd, AT_FDCWD);
-    api::sys_open(fname, flags, mode) as isize
+/// Reads user memory for the syscall tracer.
+#[register_user_reader(USER_READER)]
+fn read_user(addr: usize, buf: &mut [u8]) -> bool {
+    copy_from_user(&mut current().task_ext().aspace.lock(), buf, addr.into()).is_ok()
 }
 
 fn sys_close(fd: i32) -> isize {
@@ -153,15 +124,38 @@ fn sys_close(fd: i32) -> isize {
 }
 
 fn sys_read(fd: i32, buf: *mut c_void, count: usize) -> isize {
-    api::sys_read(fd, buf, count)
+    syscall_body!(sys_read, {
+        let curr = current();
+        let file = api::get_file_like(fd)?;
+        read_to_user(
+            || curr.task_ext().aspace.lock(),
+            VirtAddr::from(buf as usize),
+            count,
+            |kbuf| file.read(kbuf),
+        )
+    })
 }
 
 fn sys_write(fd: i32, buf: *const c_void, count: usize) -> isize {
-    api::sys_write(fd, buf, count)
+    syscall_body!(sys_write, {
+        let curr = current();
+        let file = api::get_file_like(fd)?;
+        write_from_user(
+            || curr.task_ext().aspace.lock(),
+            VirtAddr::from(buf as usize),
+            count,
+            |kbuf| file.write(kbuf),
+        )
+    })
 }
 
-fn sys_writev(fd: i32, iov: *const api::ctypes::iovec, iocnt: i32) -> isize {
-    unsafe { api::sys_writev(fd, iov, iocnt) }
+fn sys_writev(fd: i32, iov: *const UserIoVec, iocnt: i32) -> isize {
+    syscall_body!(sys_writev, {
+        let iocnt = usize::try_from(iocnt).map_err(|_| LinuxError::EINVAL)?;
+        let kbuf =
+            read_iovecs_from_user(&mut current().task_ext().aspace.lock(), iov.into(), iocnt)?;
+        Ok(api::sys_write(fd, kbuf.as_ptr() as _, kbuf.len()))
+    })
 }
 
 fn sys_set_tid_address(tid_ptd: *const i32) -> isize {
@@ -170,6 +164,28 @@ fn sys_set_tid_address(tid_ptd: *const i32) -> isize {
     curr.id().as_u64() as isize
 }
 
+fn sys_nanosleep(req: *const api::ctypes::timespec, rem: *mut api::ctypes::timespec) -> isize {
+    syscall_body!(sys_nanosleep, {
+        let curr = current();
+        let ts = UserPtr::from(req).read(&mut curr.task_ext().aspace.lock())?;
+        if ts.tv_sec < 0 || !(0..1_000_000_000).contains(&ts.tv_nsec) {
+            return Err(LinuxError::EINVAL);
+        }
+        let dur = Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32);
+        let deadline = axhal::time::wall_time() + dur;
+        let res = signal::sleep_interruptible(dur);
+        if res.is_err() && !rem.is_null() {
+            let left = deadline.saturating_sub(axhal::time::wall_time());
+            let ts = api::ctypes::timespec {
+                tv_sec: left.as_secs() as _,
+                tv_nsec: left.subsec_nanos() as _,
+            };
+            UserPtr::from(rem).write(&mut curr.task_ext().aspace.lock(), ts)?;
+        }
+        res.map(|_| 0)
+    })
+}
+
 fn sys_ioctl(_fd: i32, _op: usize, _argp: *mut c_void) -> i32 {
     ax_println!("Ignore SYS_IOCTL");
     0
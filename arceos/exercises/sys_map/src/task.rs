@@ -2,17 +2,33 @@
 
 use core::sync::atomic::AtomicU64;
 
-use alloc::sync::Arc;
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
 
+use axfs::{ProcTaskInfo, PROC_TASK_INFO};
 use axhal::arch::UspaceContext;
+use axhal::mem::VirtAddr;
 use axmm::AddrSpace;
 use axsync::Mutex;
 use axtask::{AxTaskRef, TaskExtRef, TaskInner};
+use kspin::SpinNoIrq;
+use arceos_posix_api::{FdTable, CURRENT_DIR, CURRENT_FD_TABLE};
+use linkme::distributed_slice;
+
+use crate::process::{Process, VforkDone};
+use crate::signal::ThreadSignal;
+
+/// The address spaces of all the processes, to reclaim pages from.
+static USER_ASPACES: SpinNoIrq<Vec<Weak<Mutex<AddrSpace>>>> = SpinNoIrq::new(Vec::new());
 
 /// Task extended data for the monolithic kernel.
 pub struct TaskExt {
-    /// The process ID.
-    pub proc_id: usize,
+    /// The process that the task belongs to.
+    pub process: Arc<Process>,
+    /// The signal state of the thread.
+    pub signal: Arc<ThreadSignal>,
     /// The clear thread tid field
     ///
     /// See <https://manpages.debian.org/unstable/manpages-dev/set_tid_address.2.en.html#clear_child_tid>
@@ -23,16 +39,50 @@ pub struct TaskExt {
     pub uctx: UspaceContext,
     /// The virtual memory address space.
     pub aspace: Arc<Mutex<AddrSpace>>,
+    /// Attached System V shared memory segments, mapping from the attach
+    /// address to the segment size.
+    pub shm_attaches: Mutex<BTreeMap<usize, usize>>,
+    /// The file descriptor table, shared by the tasks cloned with
+    /// `CLONE_FILES` until one of them calls `execve`.
+    fd_table: SpinNoIrq<Arc<FdTable>>,
+    /// The completion that the parent waits for, if the task is the child of
+    /// `vfork` and has not exec'd or exited yet.
+    pub vfork_done: SpinNoIrq<Option<Arc<VforkDone>>>,
 }
 
 impl TaskExt {
-    pub const fn new(uctx: UspaceContext, aspace: Arc<Mutex<AddrSpace>>) -> Self {
+    pub const fn new(
+        uctx: UspaceContext,
+        aspace: Arc<Mutex<AddrSpace>>,
+        process: Arc<Process>,
+        signal: Arc<ThreadSignal>,
+        fd_table: Arc<FdTable>,
+    ) -> Self {
         Self {
-            proc_id: 233,
+            process,
+            signal,
             uctx,
             clear_child_tid: AtomicU64::new(0),
             aspace,
+            shm_attaches: Mutex::new(BTreeMap::new()),
+            fd_table: SpinNoIrq::new(fd_table),
+            vfork_done: SpinNoIrq::new(None),
+        }
+    }
+
+    /// Returns the file descriptor table of the task.
+    pub(crate) fn fd_table(&self) -> Arc<FdTable> {
+        self.fd_table.lock().clone()
+    }
+
+    /// Gives the task a private copy of its file descriptor table if it is
+    /// shared with other tasks, then returns the table.
+    pub(crate) fn unshare_fd_table(&self) -> Arc<FdTable> {
+        let mut fd_table = self.fd_table.lock();
+        if Arc::strong_count(&fd_table) > 1 {
+            *fd_table = Arc::new(fd_table.fork());
         }
+        fd_table.clone()
     }
 
     pub(crate) fn clear_child_tid(&self) -> u64 {
@@ -48,8 +98,79 @@ impl TaskExt {
 
 axtask::def_task_ext!(TaskExt);
 
-pub fn spawn_user_task(aspace: Arc<Mutex<AddrSpace>>, uctx: UspaceContext) -> AxTaskRef {
-    let mut task = TaskInner::new(
+/// Resolves the file descriptors of the POSIX API against the table of the
+/// current user task. Kernel tasks have no extended data and use the
+/// default table.
+#[distributed_slice(CURRENT_FD_TABLE)]
+fn current_fd_table() -> Option<Arc<FdTable>> {
+    let curr = axtask::current();
+    if unsafe { curr.task_ext_ptr() }.is_null() {
+        return None;
+    }
+    Some(curr.task_ext().fd_table())
+}
+
+/// Resolves the relative paths of the POSIX API against the current directory
+/// of the process of the current user task.
+#[distributed_slice(CURRENT_DIR)]
+fn current_dir() -> Option<Arc<Mutex<String>>> {
+    let curr = axtask::current();
+    if unsafe { curr.task_ext_ptr() }.is_null() {
+        return None;
+    }
+    Some(curr.task_ext().process.cwd())
+}
+
+/// Shows the process and the address space of user tasks in
+/// `/proc/<tid>`.
+#[distributed_slice(PROC_TASK_INFO)]
+fn proc_task_info(task: &AxTaskRef) -> Option<ProcTaskInfo> {
+    if unsafe { task.task_ext_ptr() }.is_null() {
+        return None;
+    }
+    let ext = task.task_ext();
+    Some(ProcTaskInfo {
+        pid: ext.process.pid(),
+        ppid: ext.process.parent().map_or(0, |p| p.pid()),
+        maps: ext.aspace.lock().maps().to_string(),
+    })
+}
+
+/// Wraps the address space of a new process, to be shared by its threads.
+///
+/// Its pages can be reclaimed when other processes run out of memory.
+pub fn share_aspace(aspace: AddrSpace) -> Arc<Mutex<AddrSpace>> {
+    let aspace = Arc::new(Mutex::new(aspace));
+    let mut aspaces = USER_ASPACES.lock();
+    aspaces.retain(|aspace| aspace.strong_count() > 0);
+    aspaces.push(Arc::downgrade(&aspace));
+    aspace
+}
+
+/// Reclaims at most `count` pages from the address spaces that are not
+/// locked, see [`axmm::set_reclaim_handler`].
+pub fn reclaim_user_pages(count: usize) -> usize {
+    let aspaces: Vec<_> = USER_ASPACES
+        .lock()
+        .iter()
+        .filter_map(Weak::upgrade)
+        .collect();
+    let mut reclaimed = 0;
+    for aspace in aspaces {
+        if reclaimed >= count {
+            break;
+        }
+        if let Some(mut aspace) = aspace.try_lock() {
+            reclaimed += aspace.reclaim(count - reclaimed);
+        }
+    }
+    reclaimed
+}
+
+/// Creates a task that enters user space with the context in its
+/// [`TaskExt`], which is set by [`spawn_user_task`].
+pub fn new_user_task(name: &str) -> TaskInner {
+    TaskInner::new(
         || {
             let curr = axtask::current();
             let kstack_top = curr.kernel_stack_top().unwrap();
@@ -61,11 +182,29 @@ pub fn spawn_user_task(aspace: Arc<Mutex<AddrSpace>>, uctx: UspaceContext) -> Ax
             );
             unsafe { curr.task_ext().uctx.enter_uspace(kstack_top) };
         },
-        "userboot".into(),
+        name.into(),
         crate::KERNEL_STACK_SIZE,
-    );
+    )
+}
+
+/// Spawns a task created by [`new_user_task`] with the extended data.
+pub fn spawn_user_task(mut task: TaskInner, ext: TaskExt) -> AxTaskRef {
     task.ctx_mut()
-        .set_page_table_root(aspace.lock().page_table_root());
-    task.init_task_ext(TaskExt::new(uctx, aspace));
+        .set_page_table_root(ext.aspace.lock().page_table_root());
+    task.init_task_ext(ext);
     axtask::spawn_task(task)
 }
+
+/// Spawns the first user task, i.e., the main thread of the init process.
+pub fn spawn_init_task(
+    aspace: Arc<Mutex<AddrSpace>>,
+    uctx: UspaceContext,
+    brk_start: VirtAddr,
+) -> AxTaskRef {
+    let task = new_user_task("userboot");
+    let signal = Arc::new(ThreadSignal::new(0));
+    let process = Process::new_init(task.id().as_u64(), signal.clone());
+    process.set_brk(brk_start, brk_start);
+    let fd_table = Arc::new(FdTable::with_stdio());
+    spawn_user_task(task, TaskExt::new(uctx, aspace, process, signal, fd_table))
+}
@@ -127,6 +127,14 @@ fn gen_config_rs(config_path: &Path) -> Result<Vec<u8>> {
                         writeln!(output, "pub const {var_name}: &str = \"{s}\";")?;
                     }
                 }
+                Value::Array(irqs) if key == "virtio-mmio-irqs" => {
+                    writeln!(output, "{comments}")?;
+                    writeln!(output, "pub const {var_name}: &[usize] = &[")?;
+                    for irq in irqs.iter() {
+                        writeln!(output, "    {},", irq.as_str().unwrap())?;
+                    }
+                    writeln!(output, "];")?;
+                }
                 Value::Array(regions) => {
                     if key != "mmio-regions" && key != "virtio-mmio-regions" && key != "pci-ranges"
                     {
@@ -1,11 +1,12 @@
 #[allow(unused_imports)]
-use crate::{prelude::*, AllDevices};
+use crate::{prelude::*, AllDevices, DeviceBus};
 
 impl AllDevices {
     pub(crate) fn probe_bus_devices(&mut self) {
         // TODO: parse device tree
         #[cfg(feature = "virtio")]
-        for reg in axconfig::VIRTIO_MMIO_REGIONS {
+        for (i, reg) in axconfig::VIRTIO_MMIO_REGIONS.iter().enumerate() {
+            let irq = axconfig::VIRTIO_MMIO_IRQS.get(i).copied();
             for_each_drivers!(type Driver, {
                 if let Some(dev) = Driver::probe_mmio(reg.0, reg.1) {
                     info!(
@@ -14,7 +15,7 @@ impl AllDevices {
                         reg.0, reg.0 + reg.1,
                         dev.device_name(),
                     );
-                    self.add_device(dev);
+                    self.add_device(dev, DeviceBus::Mmio, irq, Some(*reg));
                     continue; // skip to the next device
                 }
             });
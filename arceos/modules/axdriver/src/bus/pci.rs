@@ -1,4 +1,4 @@
-use crate::{prelude::*, AllDevices};
+use crate::{prelude::*, AllDevices, DeviceBus};
 use axdriver_pci::{
     BarInfo, Cam, Command, DeviceFunction, HeaderType, MemoryBarType, PciRangeAllocator, PciRoot,
 };
@@ -6,11 +6,14 @@ use axhal::mem::phys_to_virt;
 
 const PCI_BAR_NUM: u8 = 6;
 
+/// Assigns the BARs and enables the device. Returns the address and the size
+/// of the first memory BAR, if any.
 fn config_pci_device(
     root: &mut PciRoot,
     bdf: DeviceFunction,
     allocator: &mut Option<PciRangeAllocator>,
-) -> DevResult {
+) -> DevResult<Option<(usize, usize)>> {
+    let mut mmio = None;
     let mut bar = 0;
     while bar < PCI_BAR_NUM {
         let info = root.bar_info(bdf, bar).unwrap();
@@ -51,6 +54,7 @@ fn config_pci_device(
                 size,
             } => {
                 if address > 0 && size > 0 {
+                    mmio.get_or_insert((address as usize, size as usize));
                     debug!(
                         "  BAR {}: MEM [{:#x}, {:#x}){}{}",
                         bar,
@@ -79,7 +83,7 @@ fn config_pci_device(
         bdf,
         cmd | Command::IO_SPACE | Command::MEMORY_SPACE | Command::BUS_MASTER,
     );
-    Ok(())
+    Ok(mmio)
 }
 
 impl AllDevices {
@@ -99,7 +103,7 @@ impl AllDevices {
                     continue;
                 }
                 match config_pci_device(&mut root, bdf, &mut allocator) {
-                    Ok(_) => for_each_drivers!(type Driver, {
+                    Ok(mmio) => for_each_drivers!(type Driver, {
                         if let Some(dev) = Driver::probe_pci(&mut root, bdf, &dev_info) {
                             info!(
                                 "registered a new {:?} device at {}: {:?}",
@@ -107,7 +111,7 @@ impl AllDevices {
                                 bdf,
                                 dev.device_name(),
                             );
-                            self.add_device(dev);
+                            self.add_device(dev, DeviceBus::Pci, None, mmio);
                             continue; // skip to the next device
                         }
                     }),
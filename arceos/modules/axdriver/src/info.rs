@@ -0,0 +1,76 @@
+//! Information of the probed devices.
+
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+
+use axdriver_base::{BaseDriverOps, DeviceType};
+use kspin::SpinNoIrq;
+
+use crate::AxDeviceEnum;
+
+static DEVICES: SpinNoIrq<Vec<DeviceInfo>> = SpinNoIrq::new(Vec::new());
+
+/// The bus on which a device is found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceBus {
+    /// Not on a bus, e.g., a RAM disk or an on-chip controller.
+    Platform,
+    /// A memory-mapped device, e.g., a VirtIO MMIO device.
+    Mmio,
+    /// A PCI device.
+    Pci,
+}
+
+impl DeviceBus {
+    /// Returns the name of the bus.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Platform => "platform",
+            Self::Mmio => "mmio",
+            Self::Pci => "pci",
+        }
+    }
+}
+
+impl fmt::Display for DeviceBus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Information of a device probed by [`init_drivers`](crate::init_drivers).
+#[derive(Debug, Clone)]
+pub struct DeviceInfo {
+    /// The bus on which the device is found.
+    pub bus: DeviceBus,
+    /// The category of the device.
+    pub ty: DeviceType,
+    /// The name of the device driver.
+    pub name: String,
+    /// The IRQ number of the device, if known.
+    pub irq: Option<usize>,
+    /// The physical address and the size of the MMIO registers, if any.
+    pub mmio: Option<(usize, usize)>,
+}
+
+/// Records the information of a newly probed device.
+pub(crate) fn add_device_info(
+    dev: &AxDeviceEnum,
+    bus: DeviceBus,
+    irq: Option<usize>,
+    mmio: Option<(usize, usize)>,
+) {
+    DEVICES.lock().push(DeviceInfo {
+        bus,
+        ty: dev.device_type(),
+        name: dev.device_name().into(),
+        irq,
+        mmio,
+    });
+}
+
+/// Returns the information of all probed devices, in the order they are
+/// probed.
+pub fn probed_devices() -> Vec<DeviceInfo> {
+    DEVICES.lock().clone()
+}
@@ -61,7 +61,6 @@
 #[macro_use]
 extern crate log;
 
-#[cfg(feature = "dyn")]
 extern crate alloc;
 
 #[macro_use]
@@ -70,6 +69,7 @@ mod macros;
 mod bus;
 mod drivers;
 mod dummy;
+mod info;
 mod structs;
 
 #[cfg(feature = "virtio")]
@@ -80,6 +80,7 @@ mod ixgbe;
 
 pub mod prelude;
 
+pub use self::info::{probed_devices, DeviceBus, DeviceInfo};
 #[allow(unused_imports)]
 use self::prelude::*;
 pub use self::structs::{AxDeviceContainer, AxDeviceEnum};
@@ -126,16 +127,24 @@ impl AllDevices {
                     dev.device_type(),
                     dev.device_name(),
                 );
-                self.add_device(dev);
+                self.add_device(dev, DeviceBus::Platform, None, None);
             }
         });
 
         self.probe_bus_devices();
     }
 
-    /// Adds one device into the corresponding container, according to its device category.
+    /// Adds one device into the corresponding container, according to its device category,
+    /// and records its information.
     #[allow(dead_code)]
-    fn add_device(&mut self, dev: AxDeviceEnum) {
+    fn add_device(
+        &mut self,
+        dev: AxDeviceEnum,
+        bus: DeviceBus,
+        irq: Option<usize>,
+        mmio: Option<(usize, usize)>,
+    ) {
+        self::info::add_device_info(&dev, bus, irq, mmio);
         match dev {
             #[cfg(feature = "net")]
             AxDeviceEnum::Net(dev) => self.net.push(dev),
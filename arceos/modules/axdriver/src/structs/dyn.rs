@@ -59,6 +59,12 @@ impl<D> AxDeviceContainer<D> {
         }
     }
 
+    /// Takes the last device out of the container (will remove it from the
+    /// container).
+    pub fn take_last(&mut self) -> Option<D> {
+        self.0.pop()
+    }
+
     /// Constructs the container from one device.
     pub fn from_one(dev: D) -> Self {
         Self(vec![dev])
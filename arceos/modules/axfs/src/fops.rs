@@ -338,6 +338,25 @@ impl Directory {
         Ok(n)
     }
 
+    /// Returns the index of the next entry to be read by [`read_dir`].
+    ///
+    /// [`read_dir`]: Directory::read_dir
+    pub fn entry_idx(&self) -> usize {
+        self.entry_idx
+    }
+
+    /// Sets the index of the next entry to be read by [`read_dir`].
+    ///
+    /// [`read_dir`]: Directory::read_dir
+    pub fn set_entry_idx(&mut self, idx: usize) {
+        self.entry_idx = idx;
+    }
+
+    /// Gets the directory attributes.
+    pub fn get_attr(&self) -> AxResult<FileAttr> {
+        self.access_node(Cap::empty())?.get_attr()
+    }
+
     /// Rename a file or directory to a new name.
     /// Delete the original file if `old` already exists.
     ///
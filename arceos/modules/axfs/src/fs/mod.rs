@@ -11,3 +11,12 @@ pub use axfs_devfs as devfs;
 
 #[cfg(feature = "ramfs")]
 pub use axfs_ramfs as ramfs;
+
+#[cfg(any(feature = "procfs", feature = "sysfs"))]
+pub mod pseudo;
+
+#[cfg(feature = "procfs")]
+pub mod procfs;
+
+#[cfg(feature = "sysfs")]
+pub mod sysfs;
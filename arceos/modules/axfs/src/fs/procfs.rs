@@ -0,0 +1,243 @@
+//! The process filesystem mounted on `/proc`.
+//!
+//! All files are generated from the kernel state when they are read:
+//!
+//! - `/proc/meminfo`: memory usage of the global allocator.
+//! - `/proc/cpuinfo`: one entry for each CPU.
+//! - `/proc/uptime`: time since boot.
+//! - `/proc/mounts`: mounted filesystems.
+//! - `/proc/interrupts`: the number of each IRQ handled on each CPU.
+//! - `/proc/<tid>/{stat,status,maps}`: information of each task (requires the
+//!   `multitask` feature). `/proc/self` refers to the current task.
+//!
+//! The kernel can provide the process and the address space of tasks by
+//! registering a function in [`PROC_TASK_INFO`].
+
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::Arc;
+use core::fmt::Write;
+
+use axhal::mem::PAGE_SIZE_4K;
+
+use super::pseudo::{PseudoDir, PseudoFile, PseudoFileSystem};
+
+/// Creates the procfs.
+pub fn new_procfs() -> PseudoFileSystem {
+    let root = new_root();
+    root.add("meminfo", PseudoFile::new(meminfo));
+    root.add("cpuinfo", PseudoFile::new(cpuinfo));
+    root.add("uptime", PseudoFile::new(uptime));
+    root.add("mounts", PseudoFile::new(mounts));
+    root.add("interrupts", PseudoFile::new(interrupts));
+
+    let sys = root.mkdir("sys");
+    let core = sys.mkdir("net").mkdir("core");
+    core.add("somaxconn", PseudoFile::new_const("4096\n"));
+    let vm = sys.mkdir("vm");
+    vm.add("overcommit_memory", PseudoFile::new_const("0\n"));
+
+    PseudoFileSystem::new(root)
+}
+
+fn meminfo() -> String {
+    let alloc = axalloc::global_allocator();
+    let free_pages = alloc.available_pages();
+    let total = (alloc.used_pages() + free_pages) * PAGE_SIZE_4K;
+    let free = free_pages * PAGE_SIZE_4K + alloc.available_bytes();
+    let mut s = String::new();
+    let mut line = |name: &str, bytes: usize| {
+        writeln!(s, "{:<16}{:>8} kB", format!("{}:", name), bytes / 1024).ok();
+    };
+    line("MemTotal", total);
+    line("MemFree", free);
+    line("MemAvailable", free);
+    line("HeapUsed", alloc.used_bytes());
+    line("HeapFree", alloc.available_bytes());
+    s
+}
+
+fn cpuinfo() -> String {
+    let mut s = String::new();
+    for cpu in 0..axconfig::SMP {
+        writeln!(s, "processor\t: {}", cpu).ok();
+        writeln!(s, "arch\t\t: {}", axconfig::ARCH).ok();
+        writeln!(s, "platform\t: {}", axconfig::PLATFORM).ok();
+        writeln!(s).ok();
+    }
+    s
+}
+
+fn uptime() -> String {
+    let now = axhal::time::monotonic_time();
+    // Idle time is not accounted.
+    format!("{}.{:02} 0.00\n", now.as_secs(), now.subsec_millis() / 10)
+}
+
+fn mounts() -> String {
+    let mut s = String::new();
+    for (path, fstype) in crate::root::mounts() {
+        writeln!(s, "{} {} {} rw 0 0", fstype, path, fstype).ok();
+    }
+    s
+}
+
+fn interrupts() -> String {
+    let mut s = String::from("     ");
+    for cpu in 0..axconfig::SMP {
+        write!(s, "{:>11}", format!("CPU{}", cpu)).ok();
+    }
+    writeln!(s).ok();
+    #[cfg(feature = "irq")]
+    for (irq, counts) in axhal::irq::irq_stats() {
+        // Show trap causes with the interrupt bit set (e.g., on riscv64) in hex.
+        if irq < 0x10000 {
+            write!(s, "{:>4}:", irq).ok();
+        } else {
+            write!(s, "{:#x}:", irq).ok();
+        }
+        for count in counts {
+            write!(s, " {:>10}", count).ok();
+        }
+        writeln!(s).ok();
+    }
+    s
+}
+
+#[cfg(not(feature = "multitask"))]
+fn new_root() -> Arc<PseudoDir> {
+    PseudoDir::new()
+}
+
+#[cfg(feature = "multitask")]
+fn new_root() -> Arc<PseudoDir> {
+    use alloc::{string::ToString, vec::Vec};
+    use axfs_vfs::VfsNodeRef;
+
+    PseudoDir::new_dynamic(|root| {
+        let mut entries: Vec<(String, VfsNodeRef)> = axtask::tasks()
+            .into_iter()
+            .map(|task| {
+                let tid = task.id().as_u64();
+                (tid.to_string(), task_dir(root, tid))
+            })
+            .collect();
+        if let Some(curr) = axtask::current_may_uninit() {
+            entries.push(("self".into(), task_dir(root, curr.id().as_u64())));
+        }
+        entries
+    })
+}
+
+#[cfg(feature = "multitask")]
+pub use self::task::{ProcTaskInfo, PROC_TASK_INFO};
+
+#[cfg(feature = "multitask")]
+use self::task::task_dir;
+
+#[cfg(feature = "multitask")]
+mod task {
+    use alloc::string::String;
+    use alloc::sync::Arc;
+    use core::fmt::Write;
+
+    use axtask::{AxTaskRef, TaskState};
+    use linkme::distributed_slice;
+
+    use super::super::pseudo::{PseudoDir, PseudoFile};
+
+    /// Information of a task that only the kernel knows, such as its process
+    /// and address space.
+    pub struct ProcTaskInfo {
+        /// The process (thread group) ID.
+        pub pid: u64,
+        /// The process ID of the parent.
+        pub ppid: u64,
+        /// The memory areas of the address space, in the format of
+        /// `/proc/<pid>/maps`.
+        pub maps: String,
+    }
+
+    /// Functions that return the [`ProcTaskInfo`] of a task, or [`None`] if
+    /// it is unknown, e.g., for kernel tasks.
+    ///
+    /// Only the first registered function is used. Without one, each task is
+    /// shown as a process of its own with no memory areas.
+    #[distributed_slice]
+    pub static PROC_TASK_INFO: [fn(&AxTaskRef) -> Option<ProcTaskInfo>];
+
+    fn task_info(task: &AxTaskRef) -> ProcTaskInfo {
+        PROC_TASK_INFO
+            .first()
+            .and_then(|f| f(task))
+            .unwrap_or_else(|| ProcTaskInfo {
+                pid: task.id().as_u64(),
+                ppid: 0,
+                maps: String::new(),
+            })
+    }
+
+    /// Creates the directory `/proc/<tid>` of the task `tid` in `root`. Its
+    /// files are empty once the task is dropped.
+    pub fn task_dir(root: &Arc<PseudoDir>, tid: u64) -> Arc<PseudoDir> {
+        let dir = PseudoDir::new_in(root);
+        dir.add("stat", PseudoFile::new(move || with_task(tid, stat)));
+        dir.add("status", PseudoFile::new(move || with_task(tid, status)));
+        dir.add("maps", PseudoFile::new(move || with_task(tid, maps)));
+        dir
+    }
+
+    fn with_task(tid: u64, f: fn(&AxTaskRef) -> String) -> String {
+        axtask::get_task(tid)
+            .map(|task| f(&task))
+            .unwrap_or_default()
+    }
+
+    fn state_char(state: TaskState) -> char {
+        match state {
+            TaskState::Running | TaskState::Ready => 'R',
+            TaskState::Blocked => 'S',
+            TaskState::Exited => 'Z',
+        }
+    }
+
+    fn stat(task: &AxTaskRef) -> String {
+        let info = task_info(task);
+        // pid (comm) state ppid pgrp session tty_nr tpgid flags minflt
+        // cminflt majflt cmajflt utime stime cutime cstime priority nice
+        // num_threads itrealvalue starttime vsize rss
+        let mut s = String::new();
+        writeln!(
+            s,
+            "{} ({}) {} {} {} {} 0 -1 0 0 0 0 0 0 0 0 0 20 0 1 0 0 0 0",
+            task.id().as_u64(),
+            task.name(),
+            state_char(task.state()),
+            info.ppid,
+            info.pid,
+            info.pid,
+        )
+        .ok();
+        s
+    }
+
+    fn status(task: &AxTaskRef) -> String {
+        let info = task_info(task);
+        let state = match task.state() {
+            TaskState::Running | TaskState::Ready => "R (running)",
+            TaskState::Blocked => "S (sleeping)",
+            TaskState::Exited => "Z (zombie)",
+        };
+        let mut s = String::new();
+        writeln!(s, "Name:\t{}", task.name()).ok();
+        writeln!(s, "State:\t{}", state).ok();
+        writeln!(s, "Tgid:\t{}", info.pid).ok();
+        writeln!(s, "Pid:\t{}", task.id().as_u64()).ok();
+        writeln!(s, "PPid:\t{}", info.ppid).ok();
+        s
+    }
+
+    fn maps(task: &AxTaskRef) -> String {
+        task_info(task).maps
+    }
+}
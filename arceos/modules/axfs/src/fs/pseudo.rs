@@ -0,0 +1,271 @@
+//! Building blocks of pseudo filesystems such as procfs and sysfs, whose files
+//! are generated from the kernel state each time they are read.
+
+use alloc::collections::BTreeMap;
+use alloc::sync::{Arc, Weak};
+use alloc::{boxed::Box, string::String, vec::Vec};
+
+use axfs_vfs::{VfsDirEntry, VfsError, VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType};
+use axfs_vfs::{VfsNodePerm, VfsOps, VfsResult};
+use axsync::Mutex;
+
+type ReadFn = Box<dyn Fn() -> String + Send + Sync>;
+type WriteFn = Box<dyn Fn(&str) -> VfsResult + Send + Sync>;
+type EntriesFn = Box<dyn Fn(&Arc<PseudoDir>) -> Vec<(String, VfsNodeRef)> + Send + Sync>;
+
+/// A file whose content is generated each time it is read.
+///
+/// If it is writable, each write passes the written text to a handler, which
+/// usually changes the kernel state that the file shows.
+///
+/// Like in Linux, its size is 0, as the content is unknown until it is read.
+pub struct PseudoFile {
+    read: ReadFn,
+    write: Option<WriteFn>,
+}
+
+impl PseudoFile {
+    /// Creates a read-only file whose content is generated by `read`.
+    pub fn new<R>(read: R) -> Arc<Self>
+    where
+        R: Fn() -> String + Send + Sync + 'static,
+    {
+        Arc::new(Self {
+            read: Box::new(read),
+            write: None,
+        })
+    }
+
+    /// Creates a read-only file with fixed content.
+    pub fn new_const(content: &'static str) -> Arc<Self> {
+        Self::new(move || content.into())
+    }
+
+    /// Creates a writable file whose content is generated by `read`, and
+    /// whose writes are handled by `write`.
+    pub fn new_rw<R, W>(read: R, write: W) -> Arc<Self>
+    where
+        R: Fn() -> String + Send + Sync + 'static,
+        W: Fn(&str) -> VfsResult + Send + Sync + 'static,
+    {
+        Arc::new(Self {
+            read: Box::new(read),
+            write: Some(Box::new(write)),
+        })
+    }
+}
+
+impl VfsNodeOps for PseudoFile {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let perm = if self.write.is_some() { 0o644 } else { 0o444 };
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(perm),
+            VfsNodeType::File,
+            0,
+            0,
+        ))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let content = (self.read)();
+        let content = content.as_bytes();
+        let start = content.len().min(offset as usize);
+        let end = content.len().min(start + buf.len());
+        let src = &content[start..end];
+        buf[..src.len()].copy_from_slice(src);
+        Ok(src.len())
+    }
+
+    fn write_at(&self, _offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        let write = self.write.as_ref().ok_or(VfsError::PermissionDenied)?;
+        let text = core::str::from_utf8(buf).map_err(|_| VfsError::InvalidInput)?;
+        write(text.trim_end())?;
+        Ok(buf.len())
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        // Opening with `O_TRUNC` truncates the file first, which is a no-op.
+        match self.write {
+            Some(_) => Ok(()),
+            None => Err(VfsError::PermissionDenied),
+        }
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
+
+/// A directory with fixed entries, and optionally entries generated each time
+/// it is looked up or listed, e.g., one per task.
+pub struct PseudoDir {
+    this: Weak<PseudoDir>,
+    parent: Mutex<Weak<dyn VfsNodeOps>>,
+    children: Mutex<BTreeMap<String, VfsNodeRef>>,
+    dynamic: Option<EntriesFn>,
+}
+
+impl PseudoDir {
+    fn new_with(dynamic: Option<EntriesFn>) -> Arc<Self> {
+        Arc::new_cyclic(|this| Self {
+            this: this.clone(),
+            parent: Mutex::new(Weak::<Self>::new()),
+            children: Mutex::new(BTreeMap::new()),
+            dynamic,
+        })
+    }
+
+    /// Creates an empty directory.
+    pub fn new() -> Arc<Self> {
+        Self::new_with(None)
+    }
+
+    /// Creates an empty directory whose `..` is `parent`, without adding it
+    /// to `parent`. It is used for the subdirectories generated by a
+    /// [dynamic](Self::new_dynamic) directory.
+    pub fn new_in(parent: &Arc<PseudoDir>) -> Arc<Self> {
+        let dir = Self::new();
+        dir.set_parent(Some(&(parent.clone() as VfsNodeRef)));
+        dir
+    }
+
+    /// Creates a directory whose entries, besides the ones added with
+    /// [`add`](Self::add), are generated by `entries`. It is passed the
+    /// directory itself, to create the subdirectories with
+    /// [`new_in`](Self::new_in).
+    pub fn new_dynamic<E>(entries: E) -> Arc<Self>
+    where
+        E: Fn(&Arc<PseudoDir>) -> Vec<(String, VfsNodeRef)> + Send + Sync + 'static,
+    {
+        Self::new_with(Some(Box::new(entries)))
+    }
+
+    fn set_parent(&self, parent: Option<&VfsNodeRef>) {
+        *self.parent.lock() = parent.map_or(Weak::<Self>::new() as _, Arc::downgrade);
+    }
+
+    /// Adds the file or directory `node` named `name` to this directory.
+    pub fn add(&self, name: &str, node: VfsNodeRef) {
+        self.children.lock().insert(name.into(), node);
+    }
+
+    /// Adds the subdirectory `dir` named `name` to this directory.
+    pub fn add_dir(self: &Arc<Self>, name: &str, dir: Arc<PseudoDir>) -> Arc<PseudoDir> {
+        dir.set_parent(Some(&(self.clone() as VfsNodeRef)));
+        self.add(name, dir.clone());
+        dir
+    }
+
+    /// Creates an empty subdirectory named `name`, and returns it.
+    pub fn mkdir(self: &Arc<Self>, name: &str) -> Arc<PseudoDir> {
+        self.add_dir(name, Self::new())
+    }
+
+    fn entries(&self) -> Vec<(String, VfsNodeRef)> {
+        let mut entries: Vec<_> = self
+            .children
+            .lock()
+            .iter()
+            .map(|(name, node)| (name.clone(), node.clone()))
+            .collect();
+        if let (Some(dynamic), Some(this)) = (&self.dynamic, self.this.upgrade()) {
+            entries.extend(dynamic(&this));
+        }
+        entries
+    }
+
+    fn find(&self, name: &str) -> Option<VfsNodeRef> {
+        if let Some(node) = self.children.lock().get(name) {
+            return Some(node.clone());
+        }
+        let this = self.this.upgrade()?;
+        self.dynamic.as_ref().and_then(|dynamic| {
+            dynamic(&this)
+                .into_iter()
+                .find_map(|(n, node)| (n == name).then_some(node))
+        })
+    }
+}
+
+impl VfsNodeOps for PseudoDir {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(0o555),
+            VfsNodeType::Dir,
+            0,
+            0,
+        ))
+    }
+
+    fn parent(&self) -> Option<VfsNodeRef> {
+        self.parent.lock().upgrade()
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        let path = path.trim_start_matches('/');
+        let (name, rest) = match path.find('/') {
+            Some(n) => (&path[..n], Some(&path[n + 1..])),
+            None => (path, None),
+        };
+        let node = match name {
+            "" | "." => self.clone() as VfsNodeRef,
+            ".." => self.parent().ok_or(VfsError::NotFound)?,
+            _ => self.find(name).ok_or(VfsError::NotFound)?,
+        };
+        match rest {
+            Some(rest) => node.lookup(rest),
+            None => Ok(node),
+        }
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        let entries = self.entries();
+        let mut entries = entries.iter().skip(start_idx.max(2) - 2);
+        for (i, ent) in dirents.iter_mut().enumerate() {
+            match i + start_idx {
+                0 => *ent = VfsDirEntry::new(".", VfsNodeType::Dir),
+                1 => *ent = VfsDirEntry::new("..", VfsNodeType::Dir),
+                _ => match entries.next() {
+                    Some((name, node)) => {
+                        *ent = VfsDirEntry::new(name, node.get_attr()?.file_type())
+                    }
+                    None => return Ok(i),
+                },
+            }
+        }
+        Ok(dirents.len())
+    }
+
+    axfs_vfs::impl_vfs_dir_default! {}
+}
+
+/// A pseudo filesystem made of [`PseudoDir`]s and [`PseudoFile`]s.
+pub struct PseudoFileSystem {
+    parent: Mutex<Option<VfsNodeRef>>,
+    root: Arc<PseudoDir>,
+}
+
+impl PseudoFileSystem {
+    /// Creates a filesystem with the given root directory.
+    pub fn new(root: Arc<PseudoDir>) -> Self {
+        Self {
+            parent: Mutex::new(None),
+            root,
+        }
+    }
+}
+
+impl VfsOps for PseudoFileSystem {
+    fn mount(&self, _path: &str, mount_point: VfsNodeRef) -> VfsResult {
+        let parent = mount_point.parent();
+        self.root.set_parent(parent.as_ref());
+        // The root only holds a weak reference to its parent.
+        *self.parent.lock() = parent;
+        Ok(())
+    }
+
+    fn root_dir(&self) -> VfsNodeRef {
+        self.root.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests;
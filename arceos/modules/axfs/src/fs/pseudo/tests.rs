@@ -0,0 +1,130 @@
+use std::sync::Once;
+
+use super::*;
+
+static INIT: Once = Once::new();
+
+fn init() {
+    // Required by `axsync::Mutex`.
+    INIT.call_once(axtask::init_scheduler);
+}
+
+fn is_same(node: &VfsNodeRef, dir: &Arc<PseudoDir>) -> bool {
+    Arc::as_ptr(node) as *const u8 == Arc::as_ptr(dir) as *const u8
+}
+
+fn read_all(node: &VfsNodeRef) -> String {
+    let mut buf = [0u8; 64];
+    let len = node.read_at(0, &mut buf).unwrap();
+    String::from_utf8(buf[..len].to_vec()).unwrap()
+}
+
+fn list(dir: &Arc<PseudoDir>) -> Vec<(String, VfsNodeType)> {
+    let mut dirents = [VfsDirEntry::default(), VfsDirEntry::default()];
+    let mut names = Vec::new();
+    loop {
+        let n = dir.read_dir(names.len(), &mut dirents).unwrap();
+        if n == 0 {
+            return names;
+        }
+        for ent in &dirents[..n] {
+            let name = String::from_utf8(ent.name_as_bytes().to_vec()).unwrap();
+            names.push((name, ent.entry_type()));
+        }
+    }
+}
+
+#[test]
+fn test_pseudo_file() {
+    init();
+    let file: VfsNodeRef = PseudoFile::new(|| "hello\n".into());
+    let mut buf = [0u8; 4];
+    assert_eq!(file.read_at(0, &mut buf).unwrap(), 4);
+    assert_eq!(&buf, b"hell");
+    assert_eq!(file.read_at(4, &mut buf).unwrap(), 2);
+    assert_eq!(&buf[..2], b"o\n");
+    assert_eq!(file.read_at(100, &mut buf).unwrap(), 0);
+
+    let attr = file.get_attr().unwrap();
+    assert_eq!(attr.size(), 0);
+    assert!(!attr.perm().owner_writable());
+    assert_eq!(file.write_at(0, b"1"), Err(VfsError::PermissionDenied));
+
+    let value = Arc::new(Mutex::new(String::from("0")));
+    let (r, w) = (value.clone(), value.clone());
+    let file: VfsNodeRef = PseudoFile::new_rw(
+        move || r.lock().clone(),
+        move |text| {
+            *w.lock() = text.into();
+            Ok(())
+        },
+    );
+    assert!(file.get_attr().unwrap().perm().owner_writable());
+    file.truncate(0).unwrap();
+    assert_eq!(file.write_at(0, b"42\n").unwrap(), 3);
+    assert_eq!(read_all(&file), "42");
+}
+
+#[test]
+fn test_pseudo_dir() {
+    init();
+    let root = PseudoDir::new();
+    let sub = root.mkdir("sub");
+    sub.add("file", PseudoFile::new_const("content"));
+
+    let file = root.clone().lookup("sub/file").unwrap();
+    assert_eq!(read_all(&file), "content");
+    assert!(root.clone().lookup("/sub/./file").is_ok());
+    assert!(is_same(&root.clone().lookup("sub/..").unwrap(), &root));
+    assert!(is_same(&root.clone().lookup("sub/../sub").unwrap(), &sub));
+    assert!(is_same(&root.clone().lookup(".").unwrap(), &root));
+    assert_eq!(
+        root.clone().lookup("sub/missing").err(),
+        Some(VfsError::NotFound)
+    );
+    assert_eq!(root.clone().lookup("..").err(), Some(VfsError::NotFound));
+
+    assert_eq!(
+        list(&sub),
+        [
+            (String::from("."), VfsNodeType::Dir),
+            (String::from(".."), VfsNodeType::Dir),
+            (String::from("file"), VfsNodeType::File),
+        ]
+    );
+}
+
+#[test]
+fn test_dynamic_dir() {
+    init();
+    let root = PseudoDir::new_dynamic(|root| {
+        ["1", "2"]
+            .into_iter()
+            .map(|name| {
+                let dir = PseudoDir::new_in(root);
+                dir.add("name", PseudoFile::new_const(name));
+                (String::from(name), dir as VfsNodeRef)
+            })
+            .collect()
+    });
+    root.add("fixed", PseudoFile::new_const(""));
+
+    let file = root.clone().lookup("2/name").unwrap();
+    assert_eq!(read_all(&file), "2");
+    // The generated directories have a parent.
+    assert!(is_same(&root.clone().lookup("1/..").unwrap(), &root));
+    let file = root.clone().lookup("1/../2/name").unwrap();
+    assert_eq!(read_all(&file), "2");
+    assert_eq!(root.clone().lookup("3").err(), Some(VfsError::NotFound));
+
+    assert_eq!(
+        list(&root),
+        [
+            (String::from("."), VfsNodeType::Dir),
+            (String::from(".."), VfsNodeType::Dir),
+            (String::from("fixed"), VfsNodeType::File),
+            (String::from("1"), VfsNodeType::Dir),
+            (String::from("2"), VfsNodeType::Dir),
+        ]
+    );
+}
@@ -0,0 +1,134 @@
+//! The system filesystem mounted on `/sys`.
+//!
+//! All files are generated from the kernel state when they are read:
+//!
+//! - `/sys/devices/<type><n>/{bus,type,name,irq,resource}`: each device probed
+//!   by [`axdriver::init_drivers`]. `irq` is empty if the IRQ is unknown, and
+//!   `resource` shows the MMIO range.
+//! - `/sys/kernel/scheduler`: the task scheduler (requires the `multitask`
+//!   feature).
+//! - `/sys/kernel/allocator`: the global memory allocator.
+//! - `/sys/kernel/log_level`: the maximum log level. Writing a level (`off`,
+//!   `error`, `warn`, `info`, `debug` or `trace`) to it changes the level.
+//! - `/sys/devices/system/clocksource/clocksource0/current_clocksource`: the
+//!   clock source of the architecture.
+
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::{format, sync::Arc};
+use core::fmt::Write;
+use core::str::FromStr;
+
+use axdriver::DeviceInfo;
+use axfs_vfs::{VfsError, VfsResult};
+use log::LevelFilter;
+
+use super::pseudo::{PseudoDir, PseudoFile, PseudoFileSystem};
+
+const LOG_LEVELS: [LevelFilter; 6] = [
+    LevelFilter::Off,
+    LevelFilter::Error,
+    LevelFilter::Warn,
+    LevelFilter::Info,
+    LevelFilter::Debug,
+    LevelFilter::Trace,
+];
+
+/// Creates the sysfs.
+pub fn new_sysfs() -> PseudoFileSystem {
+    let root = PseudoDir::new();
+
+    let devices = root.mkdir("devices");
+    let mut counts = BTreeMap::new();
+    for dev in axdriver::probed_devices() {
+        let ty = format!("{:?}", dev.ty).to_ascii_lowercase();
+        let count = counts.entry(ty.clone()).or_insert(0);
+        devices.add_dir(&format!("{}{}", ty, count), device_dir(ty, dev));
+        *count += 1;
+    }
+    let clocksource = devices
+        .mkdir("system")
+        .mkdir("clocksource")
+        .mkdir("clocksource0");
+    clocksource.add(
+        "current_clocksource",
+        PseudoFile::new_const(current_clocksource()),
+    );
+
+    let kernel = root.mkdir("kernel");
+    #[cfg(feature = "multitask")]
+    kernel.add(
+        "scheduler",
+        PseudoFile::new(|| format!("{}\n", axtask::scheduler_name())),
+    );
+    kernel.add(
+        "allocator",
+        PseudoFile::new(|| format!("{}\n", axalloc::global_allocator().name())),
+    );
+    kernel.add("log_level", PseudoFile::new_rw(log_level, set_log_level));
+    let thp = kernel.mkdir("mm").mkdir("transparent_hugepage");
+    thp.add("enabled", PseudoFile::new_const("always [madvise] never\n"));
+
+    PseudoFileSystem::new(root)
+}
+
+fn device_dir(ty: String, dev: DeviceInfo) -> Arc<PseudoDir> {
+    let DeviceInfo {
+        bus,
+        name,
+        irq,
+        mmio,
+        ..
+    } = dev;
+    let irq = irq.map(|irq| irq.to_string()).unwrap_or_default();
+    let resource = mmio
+        .map(|(base, size)| format!("{:#x}-{:#x}", base, base + size - 1))
+        .unwrap_or_default();
+    let dir = PseudoDir::new();
+    dir.add("bus", PseudoFile::new(move || format!("{}\n", bus)));
+    dir.add("type", PseudoFile::new(move || format!("{}\n", ty)));
+    dir.add("name", PseudoFile::new(move || format!("{}\n", name)));
+    dir.add("irq", PseudoFile::new(move || format!("{}\n", irq)));
+    dir.add(
+        "resource",
+        PseudoFile::new(move || format!("{}\n", resource)),
+    );
+    dir
+}
+
+/// Shows all log levels, with the current one in brackets.
+fn log_level() -> String {
+    let max_level = log::max_level();
+    let mut s = String::new();
+    for level in LOG_LEVELS {
+        let name = level.as_str().to_ascii_lowercase();
+        if level == max_level {
+            write!(s, "[{}] ", name).ok();
+        } else {
+            write!(s, "{} ", name).ok();
+        }
+    }
+    s.pop();
+    s.push('\n');
+    s
+}
+
+fn set_log_level(level: &str) -> VfsResult {
+    LevelFilter::from_str(level).map_err(|_| VfsError::InvalidInput)?;
+    axlog::set_max_level(level);
+    Ok(())
+}
+
+const fn current_clocksource() -> &'static str {
+    if cfg!(target_arch = "x86_64") {
+        "tsc\n"
+    } else if cfg!(target_arch = "aarch64") {
+        "arch_sys_counter\n"
+    } else if cfg!(any(target_arch = "riscv32", target_arch = "riscv64")) {
+        "riscv_clocksource\n"
+    } else if cfg!(target_arch = "loongarch64") {
+        "constant_timer\n"
+    } else {
+        "unknown\n"
+    }
+}
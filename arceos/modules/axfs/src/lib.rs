@@ -10,6 +10,13 @@
 //!    **enabled** by default.
 //! - `ramfs`: Mount [`axfs_ramfs::RamFileSystem`] on `/tmp`. This feature is
 //!    **enabled** by default.
+//! - `procfs`: Mount a process filesystem on `/proc`, whose files are
+//!    generated from the kernel state. This feature is **enabled** by default.
+//! - `sysfs`: Mount a system filesystem on `/sys`, which shows the devices and
+//!    the kernel settings. This feature is **enabled** by default.
+//! - `multitask`: Show each task in `/proc/<tid>`, and the scheduler in
+//!    `/sys/kernel/scheduler`.
+//! - `irq`: Show the IRQ statistics in `/proc/interrupts`.
 //! - `myfs`: Allow users to define their custom filesystems to override the
 //!    default. In this case, [`MyFileSystemIf`] is required to be implemented
 //!    to create and initialize other filesystems. This feature is **disabled** by
@@ -34,6 +41,9 @@ mod root;
 pub mod api;
 pub mod fops;
 
+#[cfg(all(feature = "procfs", feature = "multitask"))]
+pub use fs::procfs::{ProcTaskInfo, PROC_TASK_INFO};
+
 use axdriver::{prelude::*, AxDeviceContainer};
 
 /// Initializes filesystems by block devices.
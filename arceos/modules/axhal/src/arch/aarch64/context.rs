@@ -1,4 +1,6 @@
 use core::arch::asm;
+#[cfg(feature = "uspace")]
+use memory_addr::PhysAddr;
 use memory_addr::VirtAddr;
 
 /// Saved registers when a trap (exception) occurs.
@@ -15,6 +17,166 @@ pub struct TrapFrame {
     pub spsr: u64,
 }
 
+impl TrapFrame {
+    /// Whether the trap is from user mode (EL0).
+    pub const fn is_user(&self) -> bool {
+        self.spsr & 0b1111 == 0
+    }
+
+    /// Gets the 0th syscall argument.
+    pub const fn arg0(&self) -> usize {
+        self.r[0] as _
+    }
+
+    /// Gets the 1st syscall argument.
+    pub const fn arg1(&self) -> usize {
+        self.r[1] as _
+    }
+
+    /// Gets the 2nd syscall argument.
+    pub const fn arg2(&self) -> usize {
+        self.r[2] as _
+    }
+
+    /// Gets the 3rd syscall argument.
+    pub const fn arg3(&self) -> usize {
+        self.r[3] as _
+    }
+
+    /// Gets the 4th syscall argument.
+    pub const fn arg4(&self) -> usize {
+        self.r[4] as _
+    }
+
+    /// Gets the 5th syscall argument.
+    pub const fn arg5(&self) -> usize {
+        self.r[5] as _
+    }
+}
+
+/// Context to enter user space.
+#[cfg(feature = "uspace")]
+pub struct UspaceContext {
+    tf: TrapFrame,
+    tpidr_el0: u64,
+}
+
+#[cfg(feature = "uspace")]
+impl UspaceContext {
+    /// Creates an empty context with all registers set to zero.
+    pub const fn empty() -> Self {
+        unsafe { core::mem::MaybeUninit::zeroed().assume_init() }
+    }
+
+    /// Creates a new context with the given entry point and user stack
+    /// pointer.
+    pub fn new(entry: usize, ustack_top: VirtAddr) -> Self {
+        use aarch64_cpu::registers::SPSR_EL1;
+
+        // EL0 with IRQs enabled.
+        let spsr =
+            SPSR_EL1::M::EL0t + SPSR_EL1::D::Masked + SPSR_EL1::A::Masked + SPSR_EL1::F::Masked;
+        Self {
+            tf: TrapFrame {
+                usp: ustack_top.as_usize() as _,
+                elr: entry as _,
+                spsr: spsr.value,
+                ..Default::default()
+            },
+            tpidr_el0: 0,
+        }
+    }
+
+    /// Creates a new context from the given [`TrapFrame`].
+    ///
+    /// The thread pointer (`TPIDR_EL0`) is not in the trap frame, it is read
+    /// from the current CPU.
+    pub fn from(trap_frame: &TrapFrame) -> Self {
+        Self {
+            tf: *trap_frame,
+            tpidr_el0: super::read_thread_pointer() as _,
+        }
+    }
+
+    /// Gets the instruction pointer.
+    pub const fn get_ip(&self) -> usize {
+        self.tf.elr as _
+    }
+
+    /// Gets the stack pointer.
+    pub const fn get_sp(&self) -> usize {
+        self.tf.usp as _
+    }
+
+    /// Sets the instruction pointer.
+    pub const fn set_ip(&mut self, pc: usize) {
+        self.tf.elr = pc as _;
+    }
+
+    /// Sets the stack pointer.
+    pub const fn set_sp(&mut self, sp: usize) {
+        self.tf.usp = sp as _;
+    }
+
+    /// Sets the return value register.
+    pub const fn set_retval(&mut self, r0: usize) {
+        self.tf.r[0] = r0 as _;
+    }
+
+    /// Sets the thread pointer register (`TPIDR_EL0`) for thread-local
+    /// storage.
+    pub const fn set_tls(&mut self, tls: usize) {
+        self.tpidr_el0 = tls as _;
+    }
+
+    /// Enters user space.
+    ///
+    /// It restores the user registers and jumps to the user entry point
+    /// (saved in `ELR_EL1`).
+    /// When an exception or syscall occurs, the kernel stack pointer is
+    /// switched to `kstack_top`.
+    ///
+    /// # Safety
+    ///
+    /// This function is unsafe because it changes processor mode and the stack.
+    #[inline(never)]
+    #[no_mangle]
+    pub unsafe fn enter_uspace(&self, kstack_top: VirtAddr) -> ! {
+        super::disable_irqs();
+        super::write_thread_pointer(self.tpidr_el0 as _);
+        // `SP_EL1` stays at `kstack_top` while running in user space, so that
+        // the trap frame is saved on the top of the kernel stack.
+        asm!("
+            mov     sp, x1
+            ldp     x30, x9, [x0, 30 * 8]
+            ldp     x10, x11, [x0, 32 * 8]
+            msr     sp_el0, x9
+            msr     elr_el1, x10
+            msr     spsr_el1, x11
+
+            ldp     x28, x29, [x0, 28 * 8]
+            ldp     x26, x27, [x0, 26 * 8]
+            ldp     x24, x25, [x0, 24 * 8]
+            ldp     x22, x23, [x0, 22 * 8]
+            ldp     x20, x21, [x0, 20 * 8]
+            ldp     x18, x19, [x0, 18 * 8]
+            ldp     x16, x17, [x0, 16 * 8]
+            ldp     x14, x15, [x0, 14 * 8]
+            ldp     x12, x13, [x0, 12 * 8]
+            ldp     x10, x11, [x0, 10 * 8]
+            ldp     x8, x9, [x0, 8 * 8]
+            ldp     x6, x7, [x0, 6 * 8]
+            ldp     x4, x5, [x0, 4 * 8]
+            ldp     x2, x3, [x0, 2 * 8]
+            ldp     x0, x1, [x0]
+            eret",
+            in("x0") &self.tf,
+            in("x1") kstack_top.as_usize(),
+            options(noreturn),
+        )
+    }
+}
+
 /// FP & SIMD registers.
 #[repr(C, align(16))]
 #[derive(Debug, Default)]
@@ -63,14 +225,24 @@ pub struct TaskContext {
     pub r28: u64,
     pub r29: u64,
     pub lr: u64, // r30
+    /// The `TTBR0_EL1` register value, i.e., the page table root of the
+    /// user space.
+    #[cfg(feature = "uspace")]
+    pub ttbr0_el1: PhysAddr,
     #[cfg(feature = "fp_simd")]
     pub fp_state: FpState,
 }
 
 impl TaskContext {
     /// Creates a new default context for a new task.
-    pub const fn new() -> Self {
-        unsafe { core::mem::MaybeUninit::zeroed().assume_init() }
+    pub fn new() -> Self {
+        #[allow(unused_mut)]
+        let mut ctx: Self = unsafe { core::mem::MaybeUninit::zeroed().assume_init() };
+        #[cfg(feature = "uspace")]
+        {
+            ctx.ttbr0_el1 = crate::paging::kernel_page_table_root();
+        }
+        ctx
     }
 
     /// Initializes the context for a new task, with the given entry point and
@@ -81,6 +253,18 @@ impl TaskContext {
         self.tpidr_el0 = tls_area.as_usize() as u64;
     }
 
+    /// Changes the page table root of the user space (`TTBR0_EL1` register
+    /// for aarch64).
+    ///
+    /// If not set, the kernel page table root is used (obtained by
+    /// [`axhal::paging::kernel_page_table_root`][1]).
+    ///
+    /// [1]: crate::paging::kernel_page_table_root
+    #[cfg(feature = "uspace")]
+    pub fn set_page_table_root(&mut self, ttbr0_el1: PhysAddr) {
+        self.ttbr0_el1 = ttbr0_el1;
+    }
+
     /// Switches to another task.
     ///
     /// It first saves the current task's context from CPU to this place, and then
@@ -88,6 +272,12 @@ impl TaskContext {
     pub fn switch_to(&mut self, next_ctx: &Self) {
         #[cfg(feature = "fp_simd")]
         self.fp_state.switch_to(&next_ctx.fp_state);
+        #[cfg(feature = "uspace")]
+        unsafe {
+            if self.ttbr0_el1 != next_ctx.ttbr0_el1 {
+                super::write_page_table_root0(next_ctx.ttbr0_el1);
+            }
+        }
         unsafe { context_switch(self, next_ctx) }
     }
 }
@@ -5,8 +5,16 @@ use page_table_entry::MappingFlags;
 use tock_registers::interfaces::Readable;
 
 use super::TrapFrame;
+#[cfg(feature = "uspace")]
+use crate::trap::{handle_user_fault, UserFault};
 
-global_asm!(include_str!("trap.S"));
+global_asm!(
+    include_str!("trap.S"),
+    cache_current_task_ptr = sym crate::cpu::cache_current_task_ptr,
+);
+
+#[cfg(feature = "uspace")]
+global_asm!(include_str!("uaccess.S"));
 
 #[repr(u8)]
 #[derive(Debug)]
@@ -37,8 +45,25 @@ fn invalid_exception(tf: &TrapFrame, kind: TrapKind, source: TrapSource) {
 }
 
 #[no_mangle]
-fn handle_irq_exception(_tf: &TrapFrame) {
+fn handle_irq_exception(tf: &mut TrapFrame) {
     handle_trap!(IRQ, 0);
+    #[cfg(feature = "uspace")]
+    if tf.is_user() {
+        crate::trap::return_to_user(tf);
+    }
+    #[cfg(not(feature = "uspace"))]
+    let _ = tf;
+}
+
+/// Recovers from a fault at an instruction listed in the `.extable` section,
+/// e.g., a user memory access of the kernel, by resuming at the address in
+/// `x16`.
+fn fixup_extable(tf: &mut TrapFrame) -> bool {
+    if !crate::trap::in_extable(tf.elr as usize) {
+        return false;
+    }
+    tf.elr = tf.r[16];
+    true
 }
 
 fn handle_instruction_abort(tf: &TrapFrame, iss: u64, is_user: bool) {
@@ -52,6 +77,11 @@ fn handle_instruction_abort(tf: &TrapFrame, iss: u64, is_user: bool) {
     if !matches!(iss & 0b111100, 0b0100 | 0b1100) // IFSC or DFSC bits
         || !handle_trap!(PAGE_FAULT, vaddr, access_flags, is_user)
     {
+        crate::trap::report_unhandled_page_fault(vaddr, access_flags, is_user);
+        #[cfg(feature = "uspace")]
+        if is_user && handle_user_fault(UserFault::PageFault(vaddr, access_flags)) {
+            return;
+        }
         panic!(
             "Unhandled {} Instruction Abort @ {:#x}, fault_vaddr={:#x}, ISS={:#x} ({:?}):\n{:#x?}",
             if is_user { "EL0" } else { "EL1" },
@@ -64,7 +94,7 @@ fn handle_instruction_abort(tf: &TrapFrame, iss: u64, is_user: bool) {
     }
 }
 
-fn handle_data_abort(tf: &TrapFrame, iss: u64, is_user: bool) {
+fn handle_data_abort(tf: &mut TrapFrame, iss: u64, is_user: bool) {
     let wnr = (iss & (1 << 6)) != 0; // WnR: Write not Read
     let cm = (iss & (1 << 8)) != 0; // CM: Cache maintenance
     let mut access_flags = if wnr & !cm {
@@ -77,10 +107,26 @@ fn handle_data_abort(tf: &TrapFrame, iss: u64, is_user: bool) {
     }
     let vaddr = va!(FAR_EL1.get() as usize);
 
+    #[cfg(feature = "uspace")]
+    if is_user && iss & 0b111111 == 0b100001 {
+        // Alignment fault
+        if handle_user_fault(UserFault::Misaligned(vaddr)) {
+            return;
+        }
+    }
+
     // Only handle Translation fault and Permission fault
     if !matches!(iss & 0b111100, 0b0100 | 0b1100) // IFSC or DFSC bits
         || !handle_trap!(PAGE_FAULT, vaddr, access_flags, is_user)
     {
+        if !is_user && fixup_extable(tf) {
+            return;
+        }
+        crate::trap::report_unhandled_page_fault(vaddr, access_flags, is_user);
+        #[cfg(feature = "uspace")]
+        if is_user && handle_user_fault(UserFault::PageFault(vaddr, access_flags)) {
+            return;
+        }
         panic!(
             "Unhandled {} Data Abort @ {:#x}, fault_vaddr={:#x}, ISS=0b{:08b} ({:?}):\n{:#x?}",
             if is_user { "EL0" } else { "EL1" },
@@ -98,6 +144,11 @@ fn handle_sync_exception(tf: &mut TrapFrame) {
     let esr = ESR_EL1.extract();
     let iss = esr.read(ESR_EL1::ISS);
     match esr.read_as_enum(ESR_EL1::EC) {
+        #[cfg(feature = "uspace")]
+        Some(ESR_EL1::EC::Value::SVC64) => {
+            tf.r[0] = crate::trap::handle_syscall(tf, tf.r[8] as usize) as u64;
+        }
+        #[cfg(not(feature = "uspace"))]
         Some(ESR_EL1::EC::Value::SVC64) => {
             warn!("No syscall is supported currently!");
         }
@@ -105,10 +156,16 @@ fn handle_sync_exception(tf: &mut TrapFrame) {
         Some(ESR_EL1::EC::Value::InstrAbortCurrentEL) => handle_instruction_abort(tf, iss, false),
         Some(ESR_EL1::EC::Value::DataAbortLowerEL) => handle_data_abort(tf, iss, true),
         Some(ESR_EL1::EC::Value::DataAbortCurrentEL) => handle_data_abort(tf, iss, false),
+        #[cfg(feature = "uspace")]
+        Some(ESR_EL1::EC::Value::Brk64)
+            if tf.is_user() && handle_user_fault(UserFault::Breakpoint) => {}
         Some(ESR_EL1::EC::Value::Brk64) => {
             debug!("BRK #{:#x} @ {:#x} ", iss, tf.elr);
             tf.elr += 4;
         }
+        #[cfg(feature = "uspace")]
+        Some(ESR_EL1::EC::Value::Unknown)
+            if tf.is_user() && handle_user_fault(UserFault::IllegalInstruction) => {}
         _ => {
             panic!(
                 "Unhandled synchronous exception @ {:#x}: ESR={:#x} (EC {:#08b}, ISS {:#x})",
@@ -119,4 +176,8 @@ fn handle_sync_exception(tf: &mut TrapFrame) {
             );
         }
     }
+    #[cfg(feature = "uspace")]
+    if tf.is_user() {
+        crate::trap::return_to_user(tf);
+    }
 }
@@ -240,6 +240,11 @@ impl UspaceContext {
         self.0.regs.a0 = a0;
     }
 
+    /// Sets the thread pointer register (`tp`) for thread-local storage.
+    pub const fn set_tls(&mut self, tls: usize) {
+        self.0.regs.tp = tls;
+    }
+
     /// Enters user space.
     ///
     /// It restores the user registers and jumps to the user entry point
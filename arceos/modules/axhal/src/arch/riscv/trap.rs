@@ -3,6 +3,8 @@ use riscv::register::scause::{self, Exception as E, Trap};
 use riscv::register::stval;
 
 use super::TrapFrame;
+#[cfg(feature = "uspace")]
+use crate::trap::{handle_user_fault, UserFault};
 
 include_asm_marcos!();
 
@@ -11,17 +13,40 @@ core::arch::global_asm!(
     trapframe_size = const core::mem::size_of::<TrapFrame>(),
 );
 
+#[cfg(feature = "uspace")]
+core::arch::global_asm!(include_str!("uaccess.S"));
+
 fn handle_breakpoint(sepc: &mut usize) {
     debug!("Exception(Breakpoint) @ {:#x} ", sepc);
     *sepc += 2
 }
 
-fn handle_page_fault(tf: &TrapFrame, mut access_flags: MappingFlags, is_user: bool) {
+/// Recovers from a fault at an instruction listed in the `.extable` section,
+/// e.g., a guest memory access of the hypervisor, by resuming at the address
+/// in `t0` with `scause` in `t1`.
+fn fixup_extable(tf: &mut TrapFrame, scause: usize) -> bool {
+    if !crate::trap::in_extable(tf.sepc) {
+        return false;
+    }
+    tf.regs.t1 = scause;
+    tf.sepc = tf.regs.t0;
+    true
+}
+
+fn handle_page_fault(tf: &mut TrapFrame, mut access_flags: MappingFlags, is_user: bool) {
     if is_user {
         access_flags |= MappingFlags::USER;
     }
     let vaddr = va!(stval::read());
     if !handle_trap!(PAGE_FAULT, vaddr, access_flags, is_user) {
+        if !is_user && fixup_extable(tf, scause::read().bits()) {
+            return;
+        }
+        crate::trap::report_unhandled_page_fault(vaddr, access_flags, is_user);
+        #[cfg(feature = "uspace")]
+        if is_user && handle_user_fault(UserFault::PageFault(vaddr, access_flags)) {
+            return;
+        }
         panic!(
             "Unhandled {} Page Fault @ {:#x}, fault_vaddr={:#x} ({:?}):\n{:#x?}",
             if is_user { "User" } else { "Supervisor" },
@@ -37,27 +62,62 @@ fn handle_page_fault(tf: &TrapFrame, mut access_flags: MappingFlags, is_user: bo
 fn riscv_trap_handler(tf: &mut TrapFrame, from_user: bool) {
     let scause = scause::read();
     match scause.cause() {
+        // Page faults are passed to the handlers before the fixup, see
+        // `handle_page_fault`.
+        Trap::Exception(e)
+            if !from_user
+                && !matches!(
+                    e,
+                    E::LoadPageFault | E::StorePageFault | E::InstructionPageFault
+                )
+                && fixup_extable(tf, scause.bits()) => {}
         #[cfg(feature = "uspace")]
         Trap::Exception(E::UserEnvCall) => {
-            tf.regs.a0 = crate::trap::handle_syscall(tf, tf.regs.a7) as usize;
+            // Skip the `ecall` instruction first, so that the handler sees the
+            // same trap frame as on the other architectures.
             tf.sepc += 4;
+            tf.regs.a0 = crate::trap::handle_syscall(tf, tf.regs.a7) as usize;
         }
         Trap::Exception(E::LoadPageFault) => handle_page_fault(tf, MappingFlags::READ, from_user),
         Trap::Exception(E::StorePageFault) => handle_page_fault(tf, MappingFlags::WRITE, from_user),
         Trap::Exception(E::InstructionPageFault) => {
             handle_page_fault(tf, MappingFlags::EXECUTE, from_user)
         }
+        #[cfg(feature = "uspace")]
+        Trap::Exception(E::Breakpoint) if from_user => {
+            // Skipped as in the kernel if there is no handler.
+            if !handle_user_fault(UserFault::Breakpoint) {
+                handle_breakpoint(&mut tf.sepc);
+            }
+        }
+        #[cfg(feature = "uspace")]
+        Trap::Exception(E::IllegalInstruction) if from_user => {
+            handle_user_exception(tf, scause.cause(), UserFault::IllegalInstruction)
+        }
+        #[cfg(feature = "uspace")]
+        Trap::Exception(E::LoadMisaligned | E::StoreMisaligned) if from_user => {
+            let fault = UserFault::Misaligned(va!(stval::read()));
+            handle_user_exception(tf, scause.cause(), fault)
+        }
         Trap::Exception(E::Breakpoint) => handle_breakpoint(&mut tf.sepc),
         Trap::Interrupt(_) => {
             handle_trap!(IRQ, scause.bits());
         }
-        _ => {
-            panic!(
-                "Unhandled trap {:?} @ {:#x}:\n{:#x?}",
-                scause.cause(),
-                tf.sepc,
-                tf
-            );
-        }
+        _ => unhandled_trap(tf, scause.cause()),
+    }
+    #[cfg(feature = "uspace")]
+    if from_user {
+        crate::trap::return_to_user(tf);
+    }
+}
+
+fn unhandled_trap(tf: &TrapFrame, cause: Trap) -> ! {
+    panic!("Unhandled trap {:?} @ {:#x}:\n{:#x?}", cause, tf.sepc, tf);
+}
+
+#[cfg(feature = "uspace")]
+fn handle_user_exception(tf: &TrapFrame, cause: Trap, fault: UserFault) {
+    if !handle_user_fault(fault) {
+        unhandled_trap(tf, cause);
     }
 }
@@ -1,11 +1,17 @@
 use core::fmt;
 
+use lazyinit::LazyInit;
+
 use x86_64::instructions::tables::{lgdt, load_tss};
 use x86_64::registers::segmentation::{Segment, SegmentSelector, CS};
 use x86_64::structures::gdt::{Descriptor, DescriptorFlags};
 use x86_64::structures::{tss::TaskStateSegment, DescriptorTablePointer};
 use x86_64::{addr::VirtAddr, PrivilegeLevel};
 
+/// The Task State Segment (TSS) of each CPU.
+#[percpu::def_percpu]
+pub(crate) static TSS: LazyInit<TaskStateSegment> = LazyInit::new();
+
 /// A wrapper of the Global Descriptor Table (GDT) with maximum 16 entries.
 #[repr(align(16))]
 pub struct GdtStruct {
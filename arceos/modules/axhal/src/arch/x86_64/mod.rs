@@ -5,6 +5,9 @@ mod idt;
 #[cfg(target_os = "none")]
 mod trap;
 
+#[cfg(all(feature = "uspace", target_os = "none"))]
+mod syscall;
+
 use core::arch::asm;
 
 use memory_addr::{MemoryAddr, PhysAddr, VirtAddr};
@@ -13,9 +16,15 @@ use x86_64::instructions::interrupts;
 
 pub use self::context::{ExtendedState, FxsaveArea, TaskContext, TrapFrame};
 pub use self::gdt::GdtStruct;
+pub(crate) use self::gdt::TSS;
 pub use self::idt::IdtStruct;
 pub use x86_64::structures::tss::TaskStateSegment;
 
+#[cfg(feature = "uspace")]
+pub use self::context::UspaceContext;
+#[cfg(all(feature = "uspace", target_os = "none"))]
+pub(crate) use self::syscall::init_syscall;
+
 /// Allows the current CPU to respond to interrupts.
 #[inline]
 pub fn enable_irqs() {
@@ -108,3 +117,15 @@ pub fn read_thread_pointer() -> usize {
 pub unsafe fn write_thread_pointer(fs_base: usize) {
     unsafe { msr::wrmsr(msr::IA32_FS_BASE, fs_base as u64) }
 }
+
+/// Sets the kernel stack used on traps and syscalls from user space, i.e.,
+/// `RSP0` in the TSS and the stack of the `syscall` entry.
+#[cfg(feature = "uspace")]
+pub(crate) fn set_kernel_stack(kstack_top: VirtAddr) {
+    let tss = unsafe { TSS.current_ref_mut_raw() };
+    if tss.is_inited() {
+        tss.privilege_stack_table[0] = x86_64::VirtAddr::new(kstack_top.as_usize() as _);
+    }
+    #[cfg(target_os = "none")]
+    self::syscall::set_kernel_rsp(kstack_top.as_usize());
+}
@@ -0,0 +1,58 @@
+use x86_64::registers::model_specific::{Efer, EferFlags, LStar, SFMask, Star};
+use x86_64::registers::rflags::RFlags;
+
+use super::{GdtStruct, TrapFrame};
+
+/// The vector number in the trap frame of a syscall, as used by `int 0x80`.
+const SYSCALL_VECTOR: u8 = 0x80;
+
+#[percpu::def_percpu]
+static USER_RSP: usize = 0;
+
+#[percpu::def_percpu]
+static KERNEL_RSP: usize = 0;
+
+core::arch::global_asm!(
+    include_str!("syscall.S"),
+    udata = const GdtStruct::UDATA_SELECTOR.0,
+    ucode64 = const GdtStruct::UCODE64_SELECTOR.0,
+    vector = const SYSCALL_VECTOR,
+);
+
+extern "C" {
+    fn syscall_entry();
+}
+
+#[no_mangle]
+fn x86_syscall_handler(tf: &mut TrapFrame) {
+    tf.rax = crate::trap::handle_syscall(tf, tf.rax as usize) as u64;
+    crate::trap::return_to_user(tf);
+}
+
+/// Sets the kernel stack of the `syscall` entry.
+pub(super) fn set_kernel_rsp(kstack_top: usize) {
+    KERNEL_RSP.write_current(kstack_top);
+}
+
+/// Enables the `syscall` instruction on the current CPU.
+pub(crate) fn init_syscall() {
+    LStar::write(x86_64::VirtAddr::new(syscall_entry as usize as _));
+    Star::write(
+        GdtStruct::UCODE64_SELECTOR,
+        GdtStruct::UDATA_SELECTOR,
+        GdtStruct::KCODE64_SELECTOR,
+        GdtStruct::KDATA_SELECTOR,
+    )
+    .unwrap();
+    // Interrupts are disabled until the kernel stack is switched.
+    SFMask::write(
+        RFlags::TRAP_FLAG
+            | RFlags::INTERRUPT_FLAG
+            | RFlags::DIRECTION_FLAG
+            | RFlags::IOPL_LOW
+            | RFlags::IOPL_HIGH
+            | RFlags::NESTED_TASK
+            | RFlags::ALIGNMENT_CHECK,
+    );
+    unsafe { Efer::update(|efer| *efer |= EferFlags::SYSTEM_CALL_EXTENSIONS) };
+}
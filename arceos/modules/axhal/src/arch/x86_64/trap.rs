@@ -3,17 +3,41 @@ use x86::{controlregs::cr2, irq::*};
 use x86_64::structures::idt::PageFaultErrorCode;
 
 use super::context::TrapFrame;
+#[cfg(feature = "uspace")]
+use crate::trap::{handle_user_fault, UserFault};
 
 core::arch::global_asm!(include_str!("trap.S"));
 
+#[cfg(feature = "uspace")]
+core::arch::global_asm!(include_str!("uaccess.S"));
+
 const IRQ_VECTOR_START: u8 = 0x20;
 const IRQ_VECTOR_END: u8 = 0xff;
 
-fn handle_page_fault(tf: &TrapFrame) {
+/// Recovers from a fault at an instruction listed in the `.extable` section,
+/// e.g., a user memory access of the kernel, by resuming at the address in
+/// `r11`.
+fn fixup_extable(tf: &mut TrapFrame) -> bool {
+    if !crate::trap::in_extable(tf.rip as usize) {
+        return false;
+    }
+    tf.rip = tf.r11;
+    true
+}
+
+fn handle_page_fault(tf: &mut TrapFrame) {
     let access_flags = err_code_to_flags(tf.error_code)
         .unwrap_or_else(|e| panic!("Invalid #PF error code: {:#x}", e));
     let vaddr = va!(unsafe { cr2() });
     if !handle_trap!(PAGE_FAULT, vaddr, access_flags, tf.is_user()) {
+        if !tf.is_user() && fixup_extable(tf) {
+            return;
+        }
+        crate::trap::report_unhandled_page_fault(vaddr, access_flags, tf.is_user());
+        #[cfg(feature = "uspace")]
+        if tf.is_user() && handle_user_fault(UserFault::PageFault(vaddr, access_flags)) {
+            return;
+        }
         panic!(
             "Unhandled {} #PF @ {:#x}, fault_vaddr={:#x}, error_code={:#x} ({:?}):\n{:#x?}",
             if tf.is_user() { "user" } else { "kernel" },
@@ -27,10 +51,15 @@ fn handle_page_fault(tf: &TrapFrame) {
 }
 
 #[no_mangle]
-fn x86_trap_handler(tf: &TrapFrame) {
+fn x86_trap_handler(tf: &mut TrapFrame) {
     match tf.vector as u8 {
         PAGE_FAULT_VECTOR => handle_page_fault(tf),
+        #[cfg(feature = "uspace")]
+        BREAKPOINT_VECTOR if tf.is_user() && handle_user_fault(UserFault::Breakpoint) => {}
         BREAKPOINT_VECTOR => debug!("#BP @ {:#x} ", tf.rip),
+        #[cfg(feature = "uspace")]
+        INVALID_OPCODE_VECTOR
+            if tf.is_user() && handle_user_fault(UserFault::IllegalInstruction) => {}
         GENERAL_PROTECTION_FAULT_VECTOR => {
             panic!(
                 "#GP @ {:#x}, error_code={:#x}:\n{:#x?}",
@@ -51,6 +80,10 @@ fn x86_trap_handler(tf: &TrapFrame) {
             );
         }
     }
+    #[cfg(feature = "uspace")]
+    if tf.is_user() {
+        crate::trap::return_to_user(tf);
+    }
 }
 
 fn vec_to_str(vec: u64) -> &'static str {
@@ -69,10 +69,23 @@ pub unsafe fn set_current_task_ptr<T>(ptr: *const T) {
     #[cfg(target_arch = "aarch64")]
     {
         use tock_registers::interfaces::Writeable;
+        let _guard = kernel_guard::IrqSave::new();
+        // `SP_EL0` is the user stack pointer in user space, keep a copy to
+        // restore it on traps (see `cache_current_task_ptr`).
+        CURRENT_TASK_PTR.write_current_raw(ptr as usize);
         aarch64_cpu::registers::SP_EL0.set(ptr as u64)
     }
 }
 
+/// Restores the current task pointer in `SP_EL0` from the per-CPU copy, after
+/// a trap from user space saved the user stack pointer.
+#[cfg(target_arch = "aarch64")]
+pub(crate) extern "C" fn cache_current_task_ptr() {
+    use tock_registers::interfaces::Writeable;
+    let ptr = unsafe { CURRENT_TASK_PTR.read_current_raw() };
+    aarch64_cpu::registers::SP_EL0.set(ptr as u64)
+}
+
 #[allow(dead_code)]
 pub(crate) fn init_primary(cpu_id: usize) {
     percpu::init(axconfig::SMP);
@@ -1,5 +1,7 @@
 //! Interrupt management.
 
+use core::sync::atomic::{AtomicUsize, Ordering};
+
 use handler_table::HandlerTable;
 
 use crate::platform::irq::{dispatch_irq, MAX_IRQ_COUNT};
@@ -12,6 +14,67 @@ pub type IrqHandler = handler_table::Handler;
 
 static IRQ_HANDLER_TABLE: HandlerTable<MAX_IRQ_COUNT> = HandlerTable::new();
 
+/// The maximum number of distinct IRQs whose statistics are recorded.
+const MAX_IRQ_STATS: usize = 64;
+
+struct IrqStat {
+    /// The IRQ number plus one, or 0 if the slot is unused.
+    irq: AtomicUsize,
+    /// The number of times the IRQ is handled on each CPU.
+    counts: [AtomicUsize; axconfig::SMP],
+}
+
+impl IrqStat {
+    const fn new() -> Self {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const ZERO: AtomicUsize = AtomicUsize::new(0);
+        Self {
+            irq: AtomicUsize::new(0),
+            counts: [ZERO; axconfig::SMP],
+        }
+    }
+}
+
+static IRQ_STATS: [IrqStat; MAX_IRQ_STATS] = {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INIT: IrqStat = IrqStat::new();
+    [INIT; MAX_IRQ_STATS]
+};
+
+/// Counts one occurrence of the IRQ `irq_num` on the current CPU.
+fn record_irq(irq_num: usize) {
+    let key = irq_num.wrapping_add(1);
+    for stat in IRQ_STATS.iter() {
+        let irq = match stat
+            .irq
+            .compare_exchange(0, key, Ordering::Relaxed, Ordering::Relaxed)
+        {
+            Ok(_) => key,
+            Err(irq) => irq,
+        };
+        if irq == key {
+            stat.counts[crate::cpu::this_cpu_id()].fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+    }
+}
+
+/// Returns how many times each IRQ has been handled on each CPU, in the order
+/// the IRQs first occurred.
+///
+/// The IRQ numbers are the ones passed to the IRQ trap handler, e.g., the
+/// interrupt cause on riscv64 and the vector on x86_64.
+pub fn irq_stats() -> impl Iterator<Item = (usize, [usize; axconfig::SMP])> {
+    IRQ_STATS
+        .iter()
+        .take_while(|stat| stat.irq.load(Ordering::Relaxed) != 0)
+        .map(|stat| {
+            let irq = stat.irq.load(Ordering::Relaxed).wrapping_sub(1);
+            let counts = core::array::from_fn(|i| stat.counts[i].load(Ordering::Relaxed));
+            (irq, counts)
+        })
+}
+
 /// Platform-independent IRQ dispatching.
 #[allow(dead_code)]
 pub(crate) fn dispatch_irq_common(irq_num: usize) {
@@ -38,6 +101,7 @@ pub(crate) fn register_handler_common(irq_num: usize, handler: IrqHandler) -> bo
 #[register_trap_handler(IRQ)]
 fn handler_irq(irq_num: usize) -> bool {
     let guard = kernel_guard::NoPreempt::new();
+    record_irq(irq_num);
     dispatch_irq(irq_num);
     drop(guard); // rescheduling may occur when preemption is re-enabled.
     true
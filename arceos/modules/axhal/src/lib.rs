@@ -20,6 +20,8 @@
 //! - `fp_simd`: Enable floating-point and SIMD support.
 //! - `paging`: Enable page table manipulation.
 //! - `irq`: Enable interrupt handling support.
+//! - `uspace`: Enable user space support, including fault-tolerant access to
+//!   user memory.
 //!
 //! [ArceOS]: https://github.com/arceos-org/arceos
 //! [cargo test]: https://doc.rust-lang.org/cargo/guide/tests.html
@@ -47,6 +49,7 @@ pub mod trap;
 pub mod arch;
 pub mod cpu;
 pub mod mem;
+pub mod random;
 pub mod time;
 
 #[cfg(feature = "tls")]
@@ -58,6 +61,9 @@ pub mod irq;
 #[cfg(feature = "paging")]
 pub mod paging;
 
+#[cfg(feature = "uspace")]
+pub mod uaccess;
+
 /// Console input and output.
 pub mod console {
     pub use super::platform::console::*;
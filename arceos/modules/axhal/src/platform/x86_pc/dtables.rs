@@ -1,13 +1,10 @@
 //! Description tables (per-CPU GDT, per-CPU ISS, IDT)
 
-use crate::arch::{GdtStruct, IdtStruct, TaskStateSegment};
+use crate::arch::{GdtStruct, IdtStruct, TaskStateSegment, TSS};
 use lazyinit::LazyInit;
 
 static IDT: LazyInit<IdtStruct> = LazyInit::new();
 
-#[percpu::def_percpu]
-static TSS: LazyInit<TaskStateSegment> = LazyInit::new();
-
 #[percpu::def_percpu]
 static GDT: LazyInit<GdtStruct> = LazyInit::new();
 
@@ -21,6 +18,8 @@ fn init_percpu() {
         gdt.load();
         gdt.load_tss();
     }
+    #[cfg(feature = "uspace")]
+    crate::arch::init_syscall();
 }
 
 /// Initializes IDT, GDT on the primary CPU.
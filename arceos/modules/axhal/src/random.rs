@@ -0,0 +1,151 @@
+//! Kernel random number generator.
+//!
+//! It is a ChaCha20-based generator with fast key erasure: each output block
+//! also produces the key for the next one, so the previous outputs cannot be
+//! recovered from the current state.
+//!
+//! The key is seeded from the timer and the wall clock on first use, along
+//! with the hardware RNG (`RDRAND`) on x86_64 if it is present. More entropy
+//! can be mixed in at any time by [`add_entropy`], e.g., the timestamps of
+//! interrupts.
+
+use kspin::SpinNoIrq;
+
+use crate::time;
+
+/// Number of 32-bit words in a ChaCha20 block.
+const BLOCK_WORDS: usize = 16;
+/// Number of 32-bit words in the key.
+const KEY_WORDS: usize = 8;
+/// "expand 32-byte k"
+const CONSTANTS: [u32; 4] = [0x6170_7865, 0x3320_646e, 0x7962_2d32, 0x6b20_6574];
+
+struct ChaChaRng {
+    key: [u32; KEY_WORDS],
+    counter: u64,
+    seeded: bool,
+}
+
+static RNG: SpinNoIrq<ChaChaRng> = SpinNoIrq::new(ChaChaRng {
+    key: [0; KEY_WORDS],
+    counter: 0,
+    seeded: false,
+});
+
+#[inline(always)]
+fn quarter_round(s: &mut [u32; BLOCK_WORDS], a: usize, b: usize, c: usize, d: usize) {
+    s[a] = s[a].wrapping_add(s[b]);
+    s[d] = (s[d] ^ s[a]).rotate_left(16);
+    s[c] = s[c].wrapping_add(s[d]);
+    s[b] = (s[b] ^ s[c]).rotate_left(12);
+    s[a] = s[a].wrapping_add(s[b]);
+    s[d] = (s[d] ^ s[a]).rotate_left(8);
+    s[c] = s[c].wrapping_add(s[d]);
+    s[b] = (s[b] ^ s[c]).rotate_left(7);
+}
+
+fn chacha20_block(key: &[u32; KEY_WORDS], counter: u64, nonce: u64) -> [u32; BLOCK_WORDS] {
+    let mut init = [0; BLOCK_WORDS];
+    init[..4].copy_from_slice(&CONSTANTS);
+    init[4..12].copy_from_slice(key);
+    init[12] = counter as u32;
+    init[13] = (counter >> 32) as u32;
+    init[14] = nonce as u32;
+    init[15] = (nonce >> 32) as u32;
+
+    let mut s = init;
+    for _ in 0..10 {
+        quarter_round(&mut s, 0, 4, 8, 12);
+        quarter_round(&mut s, 1, 5, 9, 13);
+        quarter_round(&mut s, 2, 6, 10, 14);
+        quarter_round(&mut s, 3, 7, 11, 15);
+        quarter_round(&mut s, 0, 5, 10, 15);
+        quarter_round(&mut s, 1, 6, 11, 12);
+        quarter_round(&mut s, 2, 7, 8, 13);
+        quarter_round(&mut s, 3, 4, 9, 14);
+    }
+    for (x, y) in s.iter_mut().zip(init.iter()) {
+        *x = x.wrapping_add(*y);
+    }
+    s
+}
+
+impl ChaChaRng {
+    /// Mixes `data` into the key.
+    fn mix(&mut self, data: u64) {
+        let block = chacha20_block(&self.key, self.counter, data);
+        self.counter = self.counter.wrapping_add(1);
+        self.key.copy_from_slice(&block[..KEY_WORDS]);
+    }
+
+    fn seed(&mut self) {
+        self.mix(time::current_ticks());
+        self.mix(time::epochoffset_nanos());
+        if let Some(seed) = hw_random() {
+            self.mix(seed);
+        }
+        self.seeded = true;
+    }
+
+    /// Fills `buf` with random bytes, then replaces the key.
+    fn fill_bytes(&mut self, buf: &mut [u8]) {
+        if !self.seeded {
+            self.seed();
+        }
+        // The timing of the requests is also a source of entropy.
+        let nonce = time::current_ticks();
+        for chunk in buf.chunks_mut((BLOCK_WORDS - KEY_WORDS) * 4) {
+            let block = chacha20_block(&self.key, self.counter, nonce);
+            self.counter = self.counter.wrapping_add(1);
+            self.key.copy_from_slice(&block[..KEY_WORDS]);
+            for (dst, word) in chunk.chunks_mut(4).zip(&block[KEY_WORDS..]) {
+                dst.copy_from_slice(&word.to_le_bytes()[..dst.len()]);
+            }
+        }
+    }
+}
+
+/// Reads a random number from the hardware RNG, if there is one.
+fn hw_random() -> Option<u64> {
+    #[cfg(target_arch = "x86_64")]
+    {
+        let has_rdrand = raw_cpuid::CpuId::new()
+            .get_feature_info()
+            .is_some_and(|info| info.has_rdrand());
+        if has_rdrand {
+            for _ in 0..10 {
+                let value: u64;
+                let ok: u8;
+                unsafe {
+                    core::arch::asm!(
+                        "rdrand {value}",
+                        "setc {ok}",
+                        value = out(reg) value,
+                        ok = out(reg_byte) ok,
+                    )
+                };
+                if ok != 0 {
+                    return Some(value);
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Mixes some entropy into the generator.
+pub fn add_entropy(data: u64) {
+    RNG.lock().mix(data);
+}
+
+/// Fills `buf` with random bytes.
+pub fn fill_bytes(buf: &mut [u8]) {
+    RNG.lock().fill_bytes(buf);
+}
+
+/// Returns a random `u64`.
+pub fn random_u64() -> u64 {
+    let mut buf = [0; 8];
+    fill_bytes(&mut buf);
+    u64::from_le_bytes(buf)
+}
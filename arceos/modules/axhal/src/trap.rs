@@ -17,11 +17,50 @@ pub static IRQ: [fn(usize) -> bool];
 #[def_trap_handler]
 pub static PAGE_FAULT: [fn(VirtAddr, MappingFlags, bool) -> bool];
 
+/// A slice of functions called on unhandled page faults, just before the
+/// kernel panics.
+///
+/// They are used for reporting only, e.g., dumping the mappings of the current
+/// address space for debugging.
+#[def_trap_handler]
+pub static UNHANDLED_PAGE_FAULT: [fn(VirtAddr, MappingFlags, bool)];
+
 /// A slice of syscall handler functions.
+///
+/// The instruction pointer in the trap frame already points past the syscall
+/// instruction, i.e., where the user task resumes.
 #[cfg(feature = "uspace")]
 #[def_trap_handler]
 pub static SYSCALL: [fn(&TrapFrame, usize) -> isize];
 
+/// An exception raised by user code that the kernel cannot fix by itself.
+#[cfg(feature = "uspace")]
+#[derive(Debug, Clone, Copy)]
+pub enum UserFault {
+    /// An unhandled page fault at the address, with the access type.
+    PageFault(VirtAddr, MappingFlags),
+    /// An illegal instruction.
+    IllegalInstruction,
+    /// A misaligned memory access at the address.
+    Misaligned(VirtAddr),
+    /// A breakpoint instruction.
+    Breakpoint,
+}
+
+/// A slice of user fault handler functions, e.g., to send a signal to the
+/// current task.
+///
+/// The kernel panics if the fault is not handled (i.e., returns `false`).
+#[cfg(feature = "uspace")]
+#[def_trap_handler]
+pub static USER_FAULT: [fn(UserFault) -> bool];
+
+/// A slice of functions called just before returning to user space, e.g., to
+/// deliver pending signals by modifying the trap frame.
+#[cfg(feature = "uspace")]
+#[def_trap_handler]
+pub static RETURN_TO_USER: [fn(&mut TrapFrame)];
+
 #[allow(unused_macros)]
 macro_rules! handle_trap {
     ($trap:ident, $($args:tt)*) => {{
@@ -38,8 +77,60 @@ macro_rules! handle_trap {
     }}
 }
 
+/// Whether the instruction at `pc` is in the exception table (the `.extable`
+/// section), i.e., a fault there can be recovered.
+///
+/// How it resumes depends on the architecture, see `fixup_extable` in the
+/// trap handlers.
+#[cfg(any(not(target_arch = "x86_64"), target_os = "none"))]
+pub(crate) fn in_extable(pc: usize) -> bool {
+    extern "C" {
+        fn _sextable();
+        fn _eextable();
+    }
+    let start = _sextable as usize as *const usize;
+    let len = (_eextable as usize - _sextable as usize) / core::mem::size_of::<usize>();
+    let table = unsafe { core::slice::from_raw_parts(start, len) };
+    table.contains(&pc)
+}
+
+/// Call all the reporting functions for an unhandled page fault.
+#[cfg(any(not(target_arch = "x86_64"), target_os = "none"))]
+pub(crate) fn report_unhandled_page_fault(
+    vaddr: VirtAddr,
+    access_flags: MappingFlags,
+    is_user: bool,
+) {
+    for func in UNHANDLED_PAGE_FAULT.iter() {
+        func(vaddr, access_flags, is_user);
+    }
+}
+
 /// Call the external syscall handler.
-#[cfg(feature = "uspace")]
+#[cfg(all(
+    feature = "uspace",
+    any(not(target_arch = "x86_64"), target_os = "none")
+))]
 pub(crate) fn handle_syscall(tf: &TrapFrame, syscall_num: usize) -> isize {
     SYSCALL[0](tf, syscall_num)
 }
+
+/// Call the external user fault handler. Returns `false` if there is none.
+#[cfg(all(
+    feature = "uspace",
+    any(not(target_arch = "x86_64"), target_os = "none")
+))]
+pub(crate) fn handle_user_fault(fault: UserFault) -> bool {
+    USER_FAULT.first().is_some_and(|func| func(fault))
+}
+
+/// Call all the functions before returning to user space.
+#[cfg(all(
+    feature = "uspace",
+    any(not(target_arch = "x86_64"), target_os = "none")
+))]
+pub(crate) fn return_to_user(tf: &mut TrapFrame) {
+    for func in RETURN_TO_USER.iter() {
+        func(tf);
+    }
+}
@@ -0,0 +1,30 @@
+//! Fault-tolerant access to user memory.
+//!
+//! The kernel accesses user memory through [`copy_user`] only. If it faults
+//! and the [`PAGE_FAULT`](crate::trap::PAGE_FAULT) handlers cannot fix it
+//! (e.g., the user passes a bad pointer), the trap handler finds the faulting
+//! instruction in the exception table (the `.extable` section), and resumes
+//! the execution at the fixup code, instead of panicking.
+//!
+//! The copy routine is implemented in assembly for each architecture
+//! (`arch/*/uaccess.S`), so the faulting instructions are known.
+
+extern "C" {
+    fn __user_copy(dst: *mut u8, src: *const u8, len: usize) -> usize;
+}
+
+/// Copies `len` bytes from `src` to `dst`, either of which can be a user
+/// address.
+///
+/// Returns the number of bytes that are **not** copied, which is non-zero if a
+/// page fault occurs and is not handled.
+///
+/// # Safety
+///
+/// The kernel side of the copy must be valid. The user side must have been
+/// checked to be a user address of the current address space. Page faults
+/// here are passed to the [`PAGE_FAULT`](crate::trap::PAGE_FAULT) handlers
+/// first, as kernel faults.
+pub unsafe fn copy_user(dst: *mut u8, src: *const u8, len: usize) -> usize {
+    __user_copy(dst, src, len)
+}
@@ -0,0 +1,440 @@
+//! [ArceOS](https://github.com/arceos-org/arceos) ELF loader for user programs.
+//!
+//! It maps the loadable segments (`PT_LOAD`) of an ELF executable into a user
+//! [`AddrSpace`], with the permissions given by their `p_flags`.
+//!
+//! - Executables of type `ET_EXEC` are loaded at their fixed addresses.
+//! - Position-independent executables (`ET_DYN`) are loaded at
+//!   [`UserLayout::pie_base`](axmm::UserLayout::pie_base), which is
+//!   randomized if ASLR is enabled.
+//! - If there is a `PT_INTERP` segment, the dynamic linker it names (e.g.,
+//!   `/lib/ld-musl-riscv64.so.1`) is loaded into the mmap area as well, and
+//!   the execution starts from its entry instead.
+//!
+//! The auxiliary vector, which the dynamic linker and the C library use to
+//! find the program, is built along the way. [`build_user_stack`] places it on
+//! the user stack with the arguments, the environment variables and the random
+//! bytes of `AT_RANDOM`.
+//!
+//! # Cargo Features
+//!
+//! - `fs`: Load programs from the file system, see [`load_user_app`].
+
+#![cfg_attr(not(test), no_std)]
+
+#[macro_use]
+extern crate log;
+extern crate alloc;
+
+#[cfg(test)]
+mod tests;
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use axerrno::{ax_err, ax_err_type, AxError, AxResult};
+use axhal::paging::MappingFlags;
+use axmm::AddrSpace;
+use elf::abi::{ET_DYN, ET_EXEC, PF_R, PF_W, PF_X, PT_INTERP, PT_LOAD, PT_PHDR};
+use elf::endian::AnyEndian;
+use elf::file::Class;
+use elf::segment::ProgramHeader;
+use elf::ElfBytes;
+use memory_addr::{MemoryAddr, VirtAddr, VirtAddrRange, PAGE_SIZE_4K};
+
+/// End of the auxiliary vector.
+pub const AT_NULL: u8 = 0;
+/// Address of the program headers.
+pub const AT_PHDR: u8 = 3;
+/// Size of a program header entry.
+pub const AT_PHENT: u8 = 4;
+/// Number of program headers.
+pub const AT_PHNUM: u8 = 5;
+/// System page size.
+pub const AT_PAGESZ: u8 = 6;
+/// Base address of the dynamic linker.
+pub const AT_BASE: u8 = 7;
+/// Flags, always 0.
+pub const AT_FLAGS: u8 = 8;
+/// Entry point of the program.
+pub const AT_ENTRY: u8 = 9;
+/// Real user ID.
+pub const AT_UID: u8 = 11;
+/// Effective user ID.
+pub const AT_EUID: u8 = 12;
+/// Real group ID.
+pub const AT_GID: u8 = 13;
+/// Effective group ID.
+pub const AT_EGID: u8 = 14;
+/// CPU features, see [`hwcap`].
+pub const AT_HWCAP: u8 = 16;
+/// Frequency of `times()`.
+pub const AT_CLKTCK: u8 = 17;
+/// Whether the program runs in secure mode (e.g., setuid).
+pub const AT_SECURE: u8 = 23;
+/// Address of 16 random bytes, e.g., for the stack protector.
+pub const AT_RANDOM: u8 = 25;
+
+/// Size of the random bytes of `AT_RANDOM`.
+const RANDOM_SIZE: usize = 16;
+
+#[cfg(target_arch = "riscv64")]
+const EM_CURRENT: u16 = elf::abi::EM_RISCV;
+#[cfg(target_arch = "aarch64")]
+const EM_CURRENT: u16 = elf::abi::EM_AARCH64;
+#[cfg(target_arch = "x86_64")]
+const EM_CURRENT: u16 = elf::abi::EM_X86_64;
+#[cfg(not(any(
+    target_arch = "riscv64",
+    target_arch = "aarch64",
+    target_arch = "x86_64"
+)))]
+const EM_CURRENT: u16 = elf::abi::EM_NONE;
+
+/// Returns the CPU features in the format of `AT_HWCAP` in Linux.
+///
+/// - riscv64: one bit for each single-letter ISA extension, e.g., bit 0 for
+///   `A` and bit 2 for `C`.
+/// - aarch64: `HWCAP_FP` and `HWCAP_ASIMD`.
+/// - x86_64: the feature flags in `EDX` of the CPUID leaf 1.
+pub fn hwcap() -> usize {
+    #[cfg(target_arch = "riscv64")]
+    {
+        let ext = |letter: u8| 1 << (letter - b'a');
+        let mut hwcap = ext(b'i');
+        if cfg!(target_feature = "m") {
+            hwcap |= ext(b'm');
+        }
+        if cfg!(target_feature = "a") {
+            hwcap |= ext(b'a');
+        }
+        if cfg!(target_feature = "f") {
+            hwcap |= ext(b'f');
+        }
+        if cfg!(target_feature = "d") {
+            hwcap |= ext(b'd');
+        }
+        if cfg!(target_feature = "c") {
+            hwcap |= ext(b'c');
+        }
+        hwcap
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        const HWCAP_FP: usize = 1 << 0;
+        const HWCAP_ASIMD: usize = 1 << 1;
+        if cfg!(target_feature = "neon") {
+            HWCAP_FP | HWCAP_ASIMD
+        } else {
+            0
+        }
+    }
+    #[cfg(target_arch = "x86_64")]
+    {
+        unsafe { core::arch::x86_64::__cpuid(1) }.edx as usize
+    }
+    #[cfg(not(any(
+        target_arch = "riscv64",
+        target_arch = "aarch64",
+        target_arch = "x86_64"
+    )))]
+    {
+        0
+    }
+}
+
+/// An ELF program loaded into a user address space.
+#[derive(Debug)]
+pub struct LoadedElf {
+    /// Where the execution starts, i.e., the entry of the dynamic linker if
+    /// there is one, otherwise the entry of the program.
+    pub entry: VirtAddr,
+    /// The start of the heap (`brk`), after the program image.
+    pub brk_start: VirtAddr,
+    /// The path of the dynamic linker, if any.
+    pub interp: Option<String>,
+    /// The auxiliary vector, without `AT_RANDOM`, which is added by
+    /// [`build_user_stack`].
+    pub auxv: BTreeMap<u8, usize>,
+}
+
+/// An ELF file mapped into an address space.
+struct MappedImage {
+    /// The load bias, i.e., the difference between the runtime addresses and
+    /// the addresses in the file.
+    bias: usize,
+    /// The entry point, biased.
+    entry: usize,
+    /// The end of the last segment.
+    end: VirtAddr,
+    /// The address of the program headers, biased.
+    phdr: usize,
+    phent: usize,
+    phnum: usize,
+}
+
+fn parse_elf(data: &[u8]) -> AxResult<ElfBytes<'_, AnyEndian>> {
+    let elf = ElfBytes::<AnyEndian>::minimal_parse(data).map_err(|err| {
+        warn!("bad ELF file: {:?}", err);
+        AxError::InvalidData
+    })?;
+    let ehdr = &elf.ehdr;
+    if ehdr.class != Class::ELF64 || ehdr.e_machine != EM_CURRENT {
+        return ax_err!(InvalidData, "unsupported ELF class or machine");
+    }
+    Ok(elf)
+}
+
+/// Converts the `p_flags` of a segment into mapping flags.
+fn segment_flags(p_flags: u32) -> MappingFlags {
+    let mut flags = MappingFlags::USER;
+    if p_flags & PF_R != 0 {
+        flags |= MappingFlags::READ;
+    }
+    if p_flags & PF_W != 0 {
+        flags |= MappingFlags::WRITE;
+    }
+    if p_flags & PF_X != 0 {
+        flags |= MappingFlags::EXECUTE;
+    }
+    flags
+}
+
+/// Maps the loadable segments of `elf` into `uspace`.
+///
+/// `ET_DYN` files are loaded at `dyn_base`, or at a free area in the mmap
+/// area if it is `None`.
+fn map_image(
+    uspace: &mut AddrSpace,
+    elf: &ElfBytes<'_, AnyEndian>,
+    dyn_base: Option<VirtAddr>,
+) -> AxResult<MappedImage> {
+    let bad_elf = || ax_err_type!(InvalidData, "bad program headers");
+    let phdrs: Vec<ProgramHeader> = elf.segments().ok_or_else(bad_elf)?.iter().collect();
+    let mut loads: Vec<&ProgramHeader> = phdrs.iter().filter(|ph| ph.p_type == PT_LOAD).collect();
+    loads.sort_by_key(|ph| ph.p_vaddr);
+    let mut min_vaddr = usize::MAX;
+    let mut max_end = 0;
+    for ph in &loads {
+        let end = ph.p_vaddr.checked_add(ph.p_memsz).ok_or_else(bad_elf)?;
+        if ph.p_filesz > ph.p_memsz || ph.p_offset.checked_add(ph.p_filesz).is_none() {
+            return Err(bad_elf());
+        }
+        min_vaddr = min_vaddr.min(ph.p_vaddr as usize);
+        max_end = max_end.max(end as usize);
+    }
+    if loads.is_empty() {
+        return Err(bad_elf());
+    }
+    let min_vaddr = min_vaddr.align_down_4k();
+    let size = max_end.align_up_4k() - min_vaddr;
+
+    let bias = match elf.ehdr.e_type {
+        ET_EXEC => 0,
+        ET_DYN => {
+            let base = match dyn_base {
+                Some(base) => base,
+                None => {
+                    let limit = VirtAddrRange::new(uspace.base(), uspace.end());
+                    uspace
+                        .find_free_area(uspace.layout().mmap_base, size, limit)
+                        .ok_or_else(|| ax_err_type!(NoMemory, "no space for the ELF image"))?
+                }
+            };
+            base.as_usize().wrapping_sub(min_vaddr)
+        }
+        _ => return ax_err!(InvalidData, "not an executable"),
+    };
+
+    // Adjacent segments may share a page.
+    let mut mapped_end = VirtAddr::from(0);
+    let mut last_flags = MappingFlags::empty();
+    for ph in &loads {
+        let start = VirtAddr::from(bias.wrapping_add(ph.p_vaddr as usize));
+        let end = start + ph.p_memsz as usize;
+        let flags = segment_flags(ph.p_flags);
+        debug!(
+            "load segment: [{:#x}, {:#x}) {:?}, file offset {:#x}",
+            start, end, flags, ph.p_offset
+        );
+
+        let mut map_start = start.align_down_4k();
+        let map_end = end.align_up_4k();
+        if map_start < mapped_end {
+            // The shared page needs the permissions of both segments.
+            uspace.protect(map_start, mapped_end - map_start, last_flags | flags)?;
+            map_start = mapped_end;
+        }
+        if map_start < map_end {
+            uspace.map_alloc(map_start, map_end - map_start, flags, true)?;
+        }
+        // The frames are zeroed, so only the file data needs to be written.
+        let data = elf.segment_data(ph).map_err(|_| bad_elf())?;
+        uspace.write(start, data)?;
+        mapped_end = mapped_end.max(map_end);
+        last_flags = flags;
+    }
+
+    let phdr = match phdrs.iter().find(|ph| ph.p_type == PT_PHDR) {
+        Some(ph) => ph.p_vaddr as usize,
+        // Otherwise, find the segment that contains the program headers.
+        None => loads
+            .iter()
+            .find(|ph| (ph.p_offset..ph.p_offset + ph.p_filesz).contains(&elf.ehdr.e_phoff))
+            .map_or(0, |ph| {
+                (ph.p_vaddr + (elf.ehdr.e_phoff - ph.p_offset)) as usize
+            }),
+    };
+    Ok(MappedImage {
+        bias,
+        entry: bias.wrapping_add(elf.ehdr.e_entry as usize),
+        end: mapped_end,
+        phdr: bias.wrapping_add(phdr),
+        phent: elf.ehdr.e_phentsize as usize,
+        phnum: elf.ehdr.e_phnum as usize,
+    })
+}
+
+/// Returns the path of the dynamic linker in the `PT_INTERP` segment.
+fn interp_path(elf: &ElfBytes<'_, AnyEndian>) -> AxResult<Option<String>> {
+    let Some(ph) = elf
+        .segments()
+        .and_then(|phdrs| phdrs.iter().find(|ph| ph.p_type == PT_INTERP))
+    else {
+        return Ok(None);
+    };
+    let data = elf
+        .segment_data(&ph)
+        .map_err(|_| ax_err_type!(InvalidData, "bad PT_INTERP segment"))?;
+    let path = data.split(|&c| c == 0).next().unwrap_or_default();
+    let path = core::str::from_utf8(path)
+        .map_err(|_| ax_err_type!(InvalidData, "bad PT_INTERP segment"))?;
+    Ok(Some(String::from(path)))
+}
+
+/// Loads the ELF executable in `data` into `uspace`.
+///
+/// If the executable requests a dynamic linker, `read_file` is called with
+/// its path to read it.
+///
+/// The address space should be empty, except for the kernel mappings.
+pub fn load_elf<F>(uspace: &mut AddrSpace, data: &[u8], read_file: F) -> AxResult<LoadedElf>
+where
+    F: FnOnce(&str) -> AxResult<Vec<u8>>,
+{
+    let elf = parse_elf(data)?;
+    let pie_base = uspace.layout().pie_base;
+    let image = map_image(uspace, &elf, Some(pie_base))?;
+    let interp = interp_path(&elf)?;
+
+    let mut auxv = BTreeMap::new();
+    auxv.insert(AT_PHDR, image.phdr);
+    auxv.insert(AT_PHENT, image.phent);
+    auxv.insert(AT_PHNUM, image.phnum);
+    auxv.insert(AT_PAGESZ, PAGE_SIZE_4K);
+    auxv.insert(AT_FLAGS, 0);
+    auxv.insert(AT_ENTRY, image.entry);
+    auxv.insert(AT_UID, 0);
+    auxv.insert(AT_EUID, 0);
+    auxv.insert(AT_GID, 0);
+    auxv.insert(AT_EGID, 0);
+    auxv.insert(AT_HWCAP, hwcap());
+    auxv.insert(AT_CLKTCK, 100); // USER_HZ
+    auxv.insert(AT_SECURE, 0);
+
+    let mut entry = image.entry;
+    if let Some(path) = &interp {
+        info!("load dynamic linker: {}", path);
+        let interp_data = read_file(path)?;
+        let interp_elf = parse_elf(&interp_data)?;
+        if interp_elf.ehdr.e_type != ET_DYN {
+            return ax_err!(InvalidData, "dynamic linker is not ET_DYN");
+        }
+        let interp_image = map_image(uspace, &interp_elf, None)?;
+        auxv.insert(AT_BASE, interp_image.bias);
+        entry = interp_image.entry;
+    } else {
+        auxv.insert(AT_BASE, 0);
+    }
+
+    Ok(LoadedElf {
+        entry: VirtAddr::from(entry),
+        brk_start: uspace.layout().brk_start(image.end),
+        interp,
+        auxv,
+    })
+}
+
+/// Builds the initial user stack below `stack_top`, returns its content and the
+/// stack pointer, where the content is to be written.
+///
+/// From the stack pointer upwards, it holds `argc`, the `argv` and `envp`
+/// arrays (each terminated by a null pointer), the auxiliary vector with
+/// `AT_RANDOM` added (terminated by `AT_NULL`), then the strings and 16
+/// random bytes. The stack pointer is 16-byte aligned.
+pub fn build_user_stack(
+    args: &[String],
+    envs: &[String],
+    auxv: &BTreeMap<u8, usize>,
+    stack_top: VirtAddr,
+) -> (Vec<u8>, VirtAddr) {
+    let mut random = [0; RANDOM_SIZE];
+    axhal::random::fill_bytes(&mut random);
+    stack_image(args, envs, auxv, &random, stack_top)
+}
+
+fn stack_image(
+    args: &[String],
+    envs: &[String],
+    auxv: &BTreeMap<u8, usize>,
+    random: &[u8; RANDOM_SIZE],
+    stack_top: VirtAddr,
+) -> (Vec<u8>, VirtAddr) {
+    const WORD: usize = core::mem::size_of::<usize>();
+    let strings_size: usize = args.iter().chain(envs).map(|s| s.len() + 1).sum();
+    let info_start = stack_top.as_usize() - RANDOM_SIZE - strings_size;
+    let random_addr = stack_top.as_usize() - RANDOM_SIZE;
+    let mut auxv = auxv.clone();
+    auxv.insert(AT_RANDOM, random_addr);
+
+    // argc, argv, envp and auxv, with their terminators.
+    let num_words = 1 + (args.len() + 1) + (envs.len() + 1) + (auxv.len() + 1) * 2;
+    let sp = (info_start - num_words * WORD) & !0xf;
+    let mut data = vec![0; stack_top.as_usize() - sp];
+
+    let mut words = Vec::with_capacity(num_words);
+    words.push(args.len());
+    let mut str_addr = info_start;
+    for strs in [args, envs] {
+        for s in strs {
+            let offset = str_addr - sp;
+            data[offset..offset + s.len()].copy_from_slice(s.as_bytes());
+            words.push(str_addr);
+            str_addr += s.len() + 1;
+        }
+        words.push(0); // the end of argv or envp
+    }
+    for (&key, &value) in auxv.iter().filter(|&(&key, _)| key != AT_NULL) {
+        words.push(key as usize);
+        words.push(value);
+    }
+    words.push(AT_NULL as usize);
+    words.push(0);
+    for (i, word) in words.iter().enumerate() {
+        data[i * WORD..(i + 1) * WORD].copy_from_slice(&word.to_ne_bytes());
+    }
+    let random_offset = random_addr - sp;
+    data[random_offset..].copy_from_slice(random);
+    (data, VirtAddr::from(sp))
+}
+
+/// Loads the ELF executable at `path` in the file system into `uspace`.
+///
+/// See [`load_elf`] for details.
+#[cfg(feature = "fs")]
+pub fn load_user_app(path: &str, uspace: &mut AddrSpace) -> AxResult<LoadedElf> {
+    let data = axfs::api::read(path)?;
+    load_elf(uspace, &data, axfs::api::read)
+}
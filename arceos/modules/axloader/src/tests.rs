@@ -0,0 +1,187 @@
+use std::sync::Once;
+
+use memory_addr::va;
+
+use super::*;
+
+const POOL_SIZE: usize = 4 * 1024 * 1024;
+
+#[repr(align(4096))]
+struct Pool([u8; POOL_SIZE]);
+
+static mut POOL: Pool = Pool([0; POOL_SIZE]);
+static INIT: Once = Once::new();
+
+const EHDR_SIZE: usize = 64;
+const PHDR_SIZE: usize = 56;
+const CODE: [u8; 8] = [0x13, 0, 0, 0, 0x13, 0, 0, 0];
+const MEM_SIZE: usize = 0x2000;
+
+fn new_aspace() -> AddrSpace {
+    INIT.call_once(|| {
+        let start = core::ptr::addr_of_mut!(POOL) as usize;
+        axalloc::global_init(start, POOL_SIZE);
+    });
+    AddrSpace::new_empty(va!(0), 0x40_0000_0000).unwrap()
+}
+
+/// Builds an ELF file with a single `PT_LOAD` segment at `vaddr`, which holds
+/// the headers and `CODE`, followed by zeros up to `MEM_SIZE`. The entry is at
+/// the start of `CODE`.
+fn build_elf(e_type: u16, e_machine: u16, vaddr: u64) -> Vec<u8> {
+    let code_offset = (EHDR_SIZE + PHDR_SIZE) as u64;
+    let file_size = code_offset + CODE.len() as u64;
+    let mut data = Vec::new();
+    data.extend_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1, 1, 0]);
+    data.extend_from_slice(&[0; 8]);
+    data.extend_from_slice(&e_type.to_le_bytes());
+    data.extend_from_slice(&e_machine.to_le_bytes());
+    data.extend_from_slice(&1u32.to_le_bytes()); // e_version
+    data.extend_from_slice(&(vaddr + code_offset).to_le_bytes()); // e_entry
+    data.extend_from_slice(&(EHDR_SIZE as u64).to_le_bytes()); // e_phoff
+    data.extend_from_slice(&0u64.to_le_bytes()); // e_shoff
+    data.extend_from_slice(&0u32.to_le_bytes()); // e_flags
+    data.extend_from_slice(&(EHDR_SIZE as u16).to_le_bytes());
+    data.extend_from_slice(&(PHDR_SIZE as u16).to_le_bytes());
+    data.extend_from_slice(&1u16.to_le_bytes()); // e_phnum
+    data.extend_from_slice(&[0; 6]); // e_shentsize, e_shnum, e_shstrndx
+    assert_eq!(data.len(), EHDR_SIZE);
+
+    data.extend_from_slice(&PT_LOAD.to_le_bytes());
+    data.extend_from_slice(&(PF_R | PF_X).to_le_bytes());
+    data.extend_from_slice(&0u64.to_le_bytes()); // p_offset
+    data.extend_from_slice(&vaddr.to_le_bytes()); // p_vaddr
+    data.extend_from_slice(&vaddr.to_le_bytes()); // p_paddr
+    data.extend_from_slice(&file_size.to_le_bytes());
+    data.extend_from_slice(&(MEM_SIZE as u64).to_le_bytes());
+    data.extend_from_slice(&(PAGE_SIZE_4K as u64).to_le_bytes());
+    assert_eq!(data.len(), EHDR_SIZE + PHDR_SIZE);
+
+    data.extend_from_slice(&CODE);
+    data
+}
+
+fn no_file(path: &str) -> AxResult<Vec<u8>> {
+    panic!("unexpected read of {}", path);
+}
+
+fn read_word(data: &[u8], index: usize) -> usize {
+    const WORD: usize = core::mem::size_of::<usize>();
+    usize::from_ne_bytes(data[index * WORD..(index + 1) * WORD].try_into().unwrap())
+}
+
+fn read_str(data: &[u8], sp: VirtAddr, addr: usize) -> &str {
+    let bytes = &data[addr - sp.as_usize()..];
+    let len = bytes.iter().position(|&c| c == 0).unwrap();
+    core::str::from_utf8(&bytes[..len]).unwrap()
+}
+
+#[test]
+fn test_load_exec() {
+    let mut aspace = new_aspace();
+    let data = build_elf(ET_EXEC, EM_CURRENT, 0x10000);
+    let app = load_elf(&mut aspace, &data, no_file).unwrap();
+
+    let entry = 0x10000 + EHDR_SIZE + PHDR_SIZE;
+    assert_eq!(app.entry, va!(entry));
+    assert!(app.interp.is_none());
+    assert_eq!(app.brk_start, va!(0x10000 + MEM_SIZE));
+    assert_eq!(app.auxv[&AT_PHDR], 0x10000 + EHDR_SIZE);
+    assert_eq!(app.auxv[&AT_PHENT], PHDR_SIZE);
+    assert_eq!(app.auxv[&AT_PHNUM], 1);
+    assert_eq!(app.auxv[&AT_ENTRY], entry);
+    assert_eq!(app.auxv[&AT_BASE], 0);
+    assert_eq!(app.auxv[&AT_HWCAP], hwcap());
+    assert!(!app.auxv.contains_key(&AT_RANDOM));
+
+    let mut code = [0; CODE.len()];
+    aspace.read(va!(entry), &mut code).unwrap();
+    assert_eq!(code, CODE);
+    let mut bss = [0xff; 16];
+    aspace.read(va!(0x10000 + MEM_SIZE - 16), &mut bss).unwrap();
+    assert_eq!(bss, [0; 16]);
+}
+
+#[test]
+fn test_load_pie() {
+    let mut aspace = new_aspace();
+    let pie_base = aspace.layout().pie_base.as_usize();
+    let data = build_elf(ET_DYN, EM_CURRENT, 0);
+    let app = load_elf(&mut aspace, &data, no_file).unwrap();
+
+    let entry = pie_base + EHDR_SIZE + PHDR_SIZE;
+    assert_eq!(app.entry, va!(entry));
+    assert_eq!(app.auxv[&AT_ENTRY], entry);
+    assert_eq!(app.auxv[&AT_PHDR], pie_base + EHDR_SIZE);
+    let mut code = [0; CODE.len()];
+    aspace.read(va!(entry), &mut code).unwrap();
+    assert_eq!(code, CODE);
+}
+
+#[test]
+fn test_load_bad_elf() {
+    let mut aspace = new_aspace();
+    let other_machine = if EM_CURRENT == elf::abi::EM_RISCV {
+        elf::abi::EM_X86_64
+    } else {
+        elf::abi::EM_RISCV
+    };
+    let data = build_elf(ET_EXEC, other_machine, 0x10000);
+    let err = load_elf(&mut aspace, &data, no_file).unwrap_err();
+    assert_eq!(err, AxError::InvalidData);
+    assert_eq!(
+        load_elf(&mut aspace, &data[..EHDR_SIZE / 2], no_file).unwrap_err(),
+        AxError::InvalidData
+    );
+
+    // The end of the segment in the file overflows.
+    let mut data = build_elf(ET_EXEC, EM_CURRENT, 0x10000);
+    data[EHDR_SIZE + 8..EHDR_SIZE + 16].copy_from_slice(&u64::MAX.to_le_bytes());
+    let err = load_elf(&mut aspace, &data, no_file).unwrap_err();
+    assert_eq!(err, AxError::InvalidData);
+}
+
+#[test]
+fn test_user_stack() {
+    let args = [String::from("app"), String::from("-v")];
+    let envs = [String::from("HOME=/")];
+    let auxv = BTreeMap::from([(AT_PAGESZ, PAGE_SIZE_4K), (AT_HWCAP, 0x1234)]);
+    let random = [0x5a; RANDOM_SIZE];
+    let stack_top = va!(0x8000_0000);
+    let (data, sp) = stack_image(&args, &envs, &auxv, &random, stack_top);
+
+    assert_eq!(sp.as_usize() % 16, 0);
+    assert_eq!(sp + data.len(), stack_top);
+    assert_eq!(read_word(&data, 0), 2);
+    assert_eq!(read_str(&data, sp, read_word(&data, 1)), "app");
+    assert_eq!(read_str(&data, sp, read_word(&data, 2)), "-v");
+    assert_eq!(read_word(&data, 3), 0);
+    assert_eq!(read_str(&data, sp, read_word(&data, 4)), "HOME=/");
+    assert_eq!(read_word(&data, 5), 0);
+
+    let mut aux = BTreeMap::new();
+    for i in (6..).step_by(2) {
+        let (key, value) = (read_word(&data, i), read_word(&data, i + 1));
+        if key == AT_NULL as usize {
+            break;
+        }
+        aux.insert(key as u8, value);
+    }
+    assert_eq!(aux.len(), 3);
+    assert_eq!(aux[&AT_PAGESZ], PAGE_SIZE_4K);
+    assert_eq!(aux[&AT_HWCAP], 0x1234);
+    let random_addr = aux[&AT_RANDOM];
+    assert_eq!(random_addr, stack_top.as_usize() - RANDOM_SIZE);
+    assert_eq!(&data[random_addr - sp.as_usize()..], &random);
+}
+
+#[test]
+fn test_user_stack_empty() {
+    let stack_top = va!(0x8000_0000);
+    let (data, sp) = stack_image(&[], &[], &BTreeMap::new(), &[0; RANDOM_SIZE], stack_top);
+    assert_eq!(sp.as_usize() % 16, 0);
+    // argc, the ends of argv and envp, AT_RANDOM and AT_NULL.
+    let words: Vec<_> = (0..7).map(|i| read_word(&data, i)).collect();
+    let random_addr = stack_top.as_usize() - RANDOM_SIZE;
+    assert_eq!(words, [0, 0, 0, AT_RANDOM as usize, random_addr, 0, 0]);
+}
@@ -0,0 +1,97 @@
+//! Address space layout randomization (ASLR) for user address spaces.
+//!
+//! The layout of a user address space (from low to high addresses) is:
+//!
+//! - The program image, either at its fixed address, or at [`UserLayout::pie_base`]
+//!   for position-independent executables. The heap (`brk`) starts right after
+//!   the image, see [`UserLayout::brk_start`].
+//! - The mmap area, starting at [`UserLayout::mmap_base`] and growing upwards.
+//! - The user stack, growing downwards from [`UserLayout::stack_top`].
+//!
+//! When ASLR is enabled, each of them is moved by a random number of pages.
+//! ASLR is enabled by default, and is disabled at build time with `ASLR=n`
+//! (i.e., the `AX_ASLR` environment variable), at boot time with the
+//! `norandmaps` kernel option, or at runtime with [`set_aslr_enabled`], so
+//! that the layout is reproducible in tests.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use memory_addr::{MemoryAddr, VirtAddr, VirtAddrRange, PAGE_SIZE_4K};
+
+/// Maximum random offset of the PIE load base.
+const PIE_RND_SIZE: usize = 0x4000_0000; // 1 GiB
+/// Maximum random offset of the mmap base.
+const MMAP_RND_SIZE: usize = 0x4_0000_0000; // 16 GiB
+/// Maximum random offset of the stack top.
+const STACK_RND_SIZE: usize = 0x400_0000; // 64 MiB
+/// Maximum random offset of the heap start.
+const BRK_RND_SIZE: usize = 0x200_0000; // 32 MiB
+
+static ASLR_DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// Whether address space layout randomization is enabled.
+pub fn aslr_enabled() -> bool {
+    let disabled_at_build = matches!(option_env!("AX_ASLR"), Some("n" | "0" | "off"));
+    !disabled_at_build && !ASLR_DISABLED.load(Ordering::Relaxed)
+}
+
+/// Enables or disables address space layout randomization for the user
+/// address spaces created afterwards.
+///
+/// It cannot enable ASLR if it is disabled at build time.
+pub fn set_aslr_enabled(enabled: bool) {
+    ASLR_DISABLED.store(!enabled, Ordering::Relaxed);
+}
+
+/// Returns a random page-aligned offset less than `max`.
+fn random_offset(max: usize) -> usize {
+    let num_pages = max / PAGE_SIZE_4K;
+    if !aslr_enabled() || num_pages == 0 {
+        return 0;
+    }
+    (axhal::random::random_u64() as usize % num_pages) * PAGE_SIZE_4K
+}
+
+/// The layout of a user address space.
+#[derive(Debug, Clone, Copy)]
+pub struct UserLayout {
+    /// The top of the user stack.
+    pub stack_top: VirtAddr,
+    /// The lowest address to search for free areas for `mmap`.
+    pub mmap_base: VirtAddr,
+    /// The load bias of position-independent executables (`ET_DYN`).
+    pub pie_base: VirtAddr,
+    brk_offset: usize,
+}
+
+impl UserLayout {
+    /// Returns the layout for the given address range without randomization.
+    pub fn fixed(range: VirtAddrRange) -> Self {
+        let size = range.size();
+        Self {
+            stack_top: range.end,
+            mmap_base: range.start + size / 2,
+            pie_base: range.start + size / 8,
+            brk_offset: 0,
+        }
+    }
+
+    /// Returns a new randomized layout for the given address range.
+    ///
+    /// It is the same as [`UserLayout::fixed`] if ASLR is disabled.
+    pub fn random(range: VirtAddrRange) -> Self {
+        let size = range.size();
+        let fixed = Self::fixed(range);
+        Self {
+            stack_top: fixed.stack_top - random_offset(STACK_RND_SIZE.min(size / 16)),
+            mmap_base: fixed.mmap_base + random_offset(MMAP_RND_SIZE.min(size / 8)),
+            pie_base: fixed.pie_base + random_offset(PIE_RND_SIZE.min(size / 16)),
+            brk_offset: random_offset(BRK_RND_SIZE.min(size / 16)),
+        }
+    }
+
+    /// Returns the start of the heap, given the end of the program image.
+    pub fn brk_start(&self, image_end: VirtAddr) -> VirtAddr {
+        image_end.align_up_4k() + self.brk_offset
+    }
+}
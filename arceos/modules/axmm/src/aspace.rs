@@ -3,22 +3,27 @@ use core::fmt;
 use axerrno::{ax_err, AxError, AxResult};
 use axhal::{
     mem::phys_to_virt,
-    paging::{MappingFlags, PageTable},
+    paging::{MappingFlags, PageSize, PageTable},
 };
 use memory_addr::{
-    is_aligned_4k, pa, MemoryAddr, PageIter4K, PhysAddr, VirtAddr, VirtAddrRange, PAGE_SIZE_4K,
+    is_aligned, is_aligned_4k, MemoryAddr, PageIter4K, PhysAddr, VirtAddr, VirtAddrRange,
+    PAGE_SIZE_4K,
 };
 use memory_set::{MemoryArea, MemorySet};
-use crate::backend::Backend;
-use crate::paging_err_to_ax_err;
+use crate::aslr::UserLayout;
+use crate::backend::{dealloc_frame, Backend, SharedPages};
+use crate::swap::SwapState;
 use crate::mapping_err_to_ax_err;
+use alloc::sync::Arc;
 use alloc::vec::Vec;
 
 /// The virtual memory address space.
 pub struct AddrSpace {
     va_range: VirtAddrRange,
-    areas: MemorySet<Backend>,
-    pt: PageTable,
+    pub(crate) areas: MemorySet<Backend>,
+    pub(crate) pt: PageTable,
+    pub(crate) swap: SwapState,
+    layout: UserLayout,
 }
 
 impl AddrSpace {
@@ -37,6 +42,12 @@ impl AddrSpace {
         self.va_range.size()
     }
 
+    /// Returns the layout of the address space, i.e., where to place the user
+    /// stack, the mmap area, the heap and the program image.
+    pub const fn layout(&self) -> &UserLayout {
+        &self.layout
+    }
+
     /// Returns the reference to the inner page table.
     pub const fn page_table(&self) -> &PageTable {
         &self.pt
@@ -54,14 +65,27 @@ impl AddrSpace {
     }
 
     /// Creates a new empty address space.
+    ///
+    /// Its layout is not randomized, use [`AddrSpace::randomize_layout`] to
+    /// change it.
     pub fn new_empty(base: VirtAddr, size: usize) -> AxResult<Self> {
+        let va_range = VirtAddrRange::from_start_size(base, size);
         Ok(Self {
-            va_range: VirtAddrRange::from_start_size(base, size),
+            va_range,
             areas: MemorySet::new(),
             pt: PageTable::try_new().map_err(|_| AxError::NoMemory)?,
+            swap: SwapState::default(),
+            layout: UserLayout::fixed(va_range),
         })
     }
 
+    /// Chooses a new random layout for the address space if ASLR is enabled.
+    ///
+    /// It should be called before anything is mapped according to the layout.
+    pub fn randomize_layout(&mut self) {
+        self.layout = UserLayout::random(self.va_range);
+    }
+
     /// Copies page table mappings from another address space.
     ///
     /// It copies the page table entries only rather than the memory regions,
@@ -77,6 +101,32 @@ impl AddrSpace {
         Ok(())
     }
 
+    /// Copies the memory areas of `other` into this address space, like
+    /// `fork` does. The layout is copied as well.
+    ///
+    /// The pages of allocation mappings are copied eagerly (there is no
+    /// copy-on-write), including the swapped-out ones. Linear and shared
+    /// mappings are mapped to the same frames as in `other`.
+    ///
+    /// Returns an error if the areas overlap with the existing ones, or there
+    /// is not enough memory.
+    pub fn copy_areas_from(&mut self, other: &AddrSpace) -> AxResult {
+        for area in other.areas.iter() {
+            let backend = area.backend().clone();
+            let new_area = MemoryArea::new(area.start(), area.size(), area.flags(), backend.clone());
+            self.areas
+                .map(new_area, &mut self.pt, false)
+                .map_err(mapping_err_to_ax_err)?;
+            if matches!(backend, Backend::Alloc { .. }) {
+                for vaddr in PageIter4K::new(area.start(), area.end()).unwrap() {
+                    self.copy_page_from(other, vaddr, area.flags(), area.va_range(), &backend)?;
+                }
+            }
+        }
+        self.layout = other.layout;
+        Ok(())
+    }
+
     /// Finds a free area that can accommodate the given size.
     ///
     /// The search starts from the given hint address, and the area should be within the given limit range.
@@ -98,6 +148,9 @@ impl AddrSpace {
     ///
     /// The `flags` parameter indicates the mapping permissions and attributes.
     ///
+    /// Huge pages are used wherever both addresses and the remaining size
+    /// allow.
+    ///
     /// Returns an error if the address range is out of the address space or not
     /// aligned.
     pub fn map_linear(
@@ -114,18 +167,11 @@ impl AddrSpace {
             return ax_err!(InvalidInput, "address not aligned");
         }
 
-        let offset = start_vaddr.as_usize() - start_paddr.as_usize();
-        self.pt
-            .map_region(
-                start_vaddr,
-                |va| pa!(va.as_usize() - offset),
-                size,
-                flags,
-                false, // allow_huge
-                false, // flush_tlb_by_page
-            )
-            .map_err(paging_err_to_ax_err)?
-            .flush_all();
+        let offset = start_vaddr.as_usize().wrapping_sub(start_paddr.as_usize());
+        let area = MemoryArea::new(start_vaddr, size, flags, Backend::new_linear(offset));
+        self.areas
+            .map(area, &mut self.pt, false)
+            .map_err(mapping_err_to_ax_err)?;
         Ok(())
     }
 
@@ -143,15 +189,109 @@ impl AddrSpace {
         size: usize,
         flags: MappingFlags,
         populate: bool,
+    ) -> AxResult {
+        self.map_alloc_huge(start, size, flags, populate, PageSize::Size4K)
+    }
+
+    /// Add a new allocation mapping with the given page size, like
+    /// `MAP_HUGETLB` in Linux.
+    ///
+    /// The physical frames are allocated in units of `page_size`. If there is
+    /// no contiguous memory for a huge page when handling a page fault, a 4K
+    /// page is used instead.
+    ///
+    /// Returns an error if the address range is out of the address space or not
+    /// aligned to `page_size`.
+    pub fn map_alloc_huge(
+        &mut self,
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+        populate: bool,
+        page_size: PageSize,
     ) -> AxResult {
         if !self.contains_range(start, size) {
             return ax_err!(InvalidInput, "address out of range");
         }
-        if !start.is_aligned_4k() || !is_aligned_4k(size) {
+        if !start.is_aligned(page_size) || !is_aligned(size, page_size.into()) {
             return ax_err!(InvalidInput, "address not aligned");
         }
 
-        let area = MemoryArea::new(start, size, flags, Backend::new_alloc(populate));
+        let area = MemoryArea::new(
+            start,
+            size,
+            flags,
+            Backend::new_alloc_huge(populate, page_size),
+        );
+        self.areas
+            .map(area, &mut self.pt, false)
+            .map_err(mapping_err_to_ax_err)?;
+        Ok(())
+    }
+
+    /// Add a new allocation mapping like [`AddrSpace::map_alloc`], whose first
+    /// pages are the frames of `pages`, e.g., the contents of a file read in
+    /// advance. The others are allocated on demand.
+    ///
+    /// The frames are owned by the mapping afterwards.
+    ///
+    /// Returns an error if the address range is out of the address space, not
+    /// aligned, or smaller than `pages`.
+    pub fn map_alloc_from(
+        &mut self,
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+        pages: SharedPages,
+    ) -> AxResult {
+        if pages.size() > size {
+            return ax_err!(InvalidInput, "size exceeds the mapping");
+        }
+        self.map_alloc(start, size, flags, false)?;
+        let mut frames = pages.into_frames().into_iter();
+        let mut page = start;
+        for frame in frames.by_ref() {
+            if !self.map_resident_page(page, frame, flags) {
+                dealloc_frame(frame, PageSize::Size4K);
+                frames.for_each(|frame| dealloc_frame(frame, PageSize::Size4K));
+                self.unmap(start, size)?;
+                return ax_err!(BadState, "failed to map the pages");
+            }
+            page += PAGE_SIZE_4K;
+        }
+        Ok(())
+    }
+
+    /// Add a new shared mapping.
+    ///
+    /// `size` bytes of `pages` from the byte offset `offset` are mapped to
+    /// `start`. The same `pages` can be mapped into other address spaces as
+    /// well.
+    ///
+    /// The `flags` parameter indicates the mapping permissions and attributes.
+    ///
+    /// Returns an error if the address range is out of the address space, not
+    /// aligned, or beyond the end of `pages`.
+    pub fn map_shared(
+        &mut self,
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+        pages: Arc<SharedPages>,
+        offset: usize,
+    ) -> AxResult {
+        if !self.contains_range(start, size) {
+            return ax_err!(InvalidInput, "address out of range");
+        }
+        if !start.is_aligned_4k() || !is_aligned_4k(size) || !is_aligned_4k(offset) {
+            return ax_err!(InvalidInput, "address not aligned");
+        }
+        if offset > pages.size() || size > pages.size() - offset {
+            return ax_err!(InvalidInput, "size exceeds the shared pages");
+        }
+
+        let va_offset = start.as_usize().wrapping_sub(offset);
+        let area = MemoryArea::new(start, size, flags, Backend::new_shared(pages, va_offset));
         self.areas
             .map(area, &mut self.pt, false)
             .map_err(mapping_err_to_ax_err)?;
@@ -160,6 +300,10 @@ impl AddrSpace {
 
     /// Removes mappings within the specified virtual address range.
     ///
+    /// Memory areas partially covered by the range are shrunk or split, and
+    /// the physical frames of the removed part are released by their backends.
+    /// Huge pages partially covered by the range are split first.
+    ///
     /// Returns an error if the address range is out of the address space or not
     /// aligned.
     pub fn unmap(&mut self, start: VirtAddr, size: usize) -> AxResult {
@@ -170,13 +314,26 @@ impl AddrSpace {
             return ax_err!(InvalidInput, "address not aligned");
         }
 
-        self.pt
-            .unmap_region(start, size, true)
-            .map_err(paging_err_to_ax_err)?
-            .ignore();
+        self.restore_aged_pages(start, size);
+        self.forget_swap_pages(start, size);
+        self.areas
+            .unmap(start, size, &mut self.pt)
+            .map_err(mapping_err_to_ax_err)?;
         Ok(())
     }
 
+    /// Removes all mappings in the address space.
+    ///
+    /// The physical frames are released by the backends of the memory areas.
+    /// Mappings copied by [`AddrSpace::copy_mappings_from`] are not affected.
+    pub fn clear(&mut self) {
+        self.restore_aged_pages(self.base(), self.size());
+        self.forget_swap_pages(self.base(), self.size());
+        if let Err(err) = self.areas.clear(&mut self.pt) {
+            warn!("failed to clear the address space: {:?}", err);
+        }
+    }
+
     /// To process data in this area with the given function.
     ///
     /// Now it supports reading and writing data in the given interval.
@@ -237,6 +394,10 @@ impl AddrSpace {
 
     /// Updates mapping within the specified virtual address range.
     ///
+    /// Memory areas partially covered by the range are split, so that later
+    /// page faults in the range are resolved against the new flags. Huge pages
+    /// partially covered by the range are split first.
+    ///
     /// Returns an error if the address range is out of the address space or not
     /// aligned.
     pub fn protect(&mut self, start: VirtAddr, size: usize, flags: MappingFlags) -> AxResult {
@@ -247,10 +408,116 @@ impl AddrSpace {
             return ax_err!(InvalidInput, "address not aligned");
         }
 
-        self.pt
-            .protect_region(start, size, flags, true)
-            .map_err(paging_err_to_ax_err)?
-            .ignore();
+        self.restore_aged_pages(start, size);
+        self.areas
+            .protect(start, size, |_| Some(flags), &mut self.pt)
+            .map_err(mapping_err_to_ax_err)?;
+        Ok(())
+    }
+
+    /// Whether `[start, start + size)` is fully covered by memory areas.
+    pub fn is_mapped(&self, start: VirtAddr, size: usize) -> bool {
+        let Some(end) = start.as_usize().checked_add(size).map(VirtAddr::from) else {
+            return false;
+        };
+        let mut addr = start;
+        while addr < end {
+            match self.areas.find(addr) {
+                Some(area) => addr = area.end(),
+                None => return false,
+            }
+        }
+        true
+    }
+
+    /// Discards the pages of allocation mappings within the specified virtual
+    /// address range, like `madvise(MADV_DONTNEED)` in Linux.
+    ///
+    /// The frames (and the swapped-out pages) are released, and the range is
+    /// filled with zero pages again, on demand or right away for populated
+    /// mappings. The page size of the mappings is kept. Other mappings are not
+    /// affected.
+    ///
+    /// Returns an error if the address range is out of the address space, or
+    /// not aligned to the page size of the mappings it covers.
+    pub fn discard(&mut self, start: VirtAddr, size: usize) -> AxResult {
+        if !self.contains_range(start, size) {
+            return ax_err!(InvalidInput, "address out of range");
+        }
+        if !start.is_aligned_4k() || !is_aligned_4k(size) {
+            return ax_err!(InvalidInput, "address not aligned");
+        }
+
+        let end = start + size;
+        let discarded: Vec<_> = self
+            .areas
+            .iter()
+            .filter(|area| area.start() < end && start < area.end())
+            .filter_map(|area| match *area.backend() {
+                Backend::Alloc {
+                    populate,
+                    page_size,
+                } => {
+                    let range = (area.start().max(start), area.end().min(end));
+                    Some((range, area.flags(), populate, page_size))
+                }
+                _ => None,
+            })
+            .collect();
+        for &((area_start, area_end), _, _, page_size) in &discarded {
+            if !area_start.is_aligned(page_size) || !area_end.is_aligned(page_size) {
+                return ax_err!(InvalidInput, "address not aligned to the huge pages");
+            }
+        }
+        for ((area_start, area_end), flags, populate, page_size) in discarded {
+            let area_size = area_end - area_start;
+            self.unmap(area_start, area_size)?;
+            let backend = Backend::new_alloc_huge(populate, page_size);
+            let area = MemoryArea::new(area_start, area_size, flags, backend);
+            self.areas
+                .map(area, &mut self.pt, false)
+                .map_err(mapping_err_to_ax_err)?;
+        }
+        Ok(())
+    }
+
+    /// Allocates the pages of the lazy mappings within the specified virtual
+    /// address range, so that they can be accessed without page faults, like
+    /// `MAP_POPULATE` in Linux. Swapped-out pages are swapped in.
+    ///
+    /// The pages are allocated even if the mappings cannot be accessed with
+    /// their current permissions.
+    ///
+    /// Returns an error if the address range is out of the address space, not
+    /// aligned, not fully mapped, or there is no memory for the pages.
+    pub fn populate(&mut self, start: VirtAddr, size: usize) -> AxResult {
+        if !self.contains_range(start, size) {
+            return ax_err!(InvalidInput, "address out of range");
+        }
+        if !start.is_aligned_4k() || !is_aligned_4k(size) {
+            return ax_err!(InvalidInput, "address not aligned");
+        }
+
+        for vaddr in PageIter4K::new(start, start + size).unwrap() {
+            // Lazy mappings are mapped to 0 without flags before page faults.
+            match self.pt.query(vaddr) {
+                Ok((paddr, flags, _)) if !flags.is_empty() || paddr.as_usize() != 0 => continue,
+                _ => {}
+            }
+            let Some(area) = self.areas.find(vaddr) else {
+                return ax_err!(BadAddress, "address not mapped");
+            };
+            let (flags, area_range) = (area.flags(), area.va_range());
+            let populated = if area.backend().is_swappable() {
+                self.handle_swappable_page_fault(vaddr, flags, area_range)
+            } else {
+                area.backend()
+                    .handle_page_fault(vaddr, flags, area_range, &mut self.pt)
+            };
+            if !populated {
+                return ax_err!(NoMemory, "cannot populate the page");
+            }
+        }
         Ok(())
     }
 
@@ -264,15 +531,18 @@ impl AddrSpace {
         if !self.va_range.contains(vaddr) {
             return false;
         }
-        if let Some(area) = self.areas.find(vaddr) {
-            let orig_flags = area.flags();
-            if orig_flags.contains(access_flags) {
-                return area
-                    .backend()
-                    .handle_page_fault(vaddr, orig_flags, &mut self.pt);
-            }
+        let Some(area) = self.areas.find(vaddr) else {
+            return false;
+        };
+        let (orig_flags, area_range) = (area.flags(), area.va_range());
+        if !orig_flags.contains(access_flags) {
+            return false;
+        }
+        if area.backend().is_swappable() {
+            return self.handle_swappable_page_fault(vaddr, orig_flags, area_range);
         }
-        false
+        area.backend()
+            .handle_page_fault(vaddr, orig_flags, area_range, &mut self.pt)
     }
 
     pub fn translated_byte_buffer(
@@ -284,17 +554,19 @@ impl AddrSpace {
             return None;
         }
         if let Some(area) = self.areas.find(vaddr) {
-            if len > area.size() {
-                warn!(
-                    "AddrSpace translated_byte_buffer len {:#x} exceeds area length {:#x}",
-                    len,
-                    area.size()
-                );
-                return None;
-            }
-
+            let end = match vaddr.as_usize().checked_add(len) {
+                Some(end) if end <= area.end().as_usize() => VirtAddr::from(end),
+                _ => {
+                    warn!(
+                        "AddrSpace translated_byte_buffer [{:#x}, +{:#x}) exceeds area end {:#x}",
+                        vaddr,
+                        len,
+                        area.end()
+                    );
+                    return None;
+                }
+            };
             let mut start = vaddr;
-            let end = start + len;
 
             debug!(
                 "start {:?} end {:?} area size {:#x}",
@@ -305,7 +577,7 @@ impl AddrSpace {
 
             let mut v = Vec::new();
             while start < end {
-                let (start_paddr, _, page_size) = self.page_table().query(start).unwrap();
+                let (start_paddr, _, page_size) = self.page_table().query(start).ok()?;
                 let mut end_va = start.align_down(page_size) + page_size.into();
                 end_va = end_va.min(end);
 
@@ -324,11 +596,18 @@ impl AddrSpace {
     }
 }
 
+impl Drop for AddrSpace {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
 impl fmt::Debug for AddrSpace {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("AddrSpace")
             .field("va_range", &self.va_range)
             .field("areas", &self.areas)
+            .field("layout", &self.layout)
             .field("page_table_root", &self.pt.root_paddr())
             .finish()
     }
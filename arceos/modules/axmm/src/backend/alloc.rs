@@ -1,28 +1,46 @@
 use axalloc::global_allocator;
 use axhal::mem::{phys_to_virt, virt_to_phys};
 use axhal::paging::{MappingFlags, PageSize, PageTable};
-use memory_addr::{PageIter4K, PhysAddr, VirtAddr, PAGE_SIZE_4K};
+use memory_addr::{MemoryAddr, PhysAddr, VirtAddr, VirtAddrRange, PAGE_SIZE_4K};
 
-use super::Backend;
+use super::{split_huge_pages, Backend};
 
-fn alloc_frame(zeroed: bool) -> Option<PhysAddr> {
-    let vaddr = VirtAddr::from(global_allocator().alloc_pages(1, PAGE_SIZE_4K).ok()?);
+pub(crate) fn alloc_frame(zeroed: bool, page_size: PageSize) -> Option<PhysAddr> {
+    let size: usize = page_size.into();
+    let vaddr = VirtAddr::from(
+        global_allocator()
+            .alloc_pages(size / PAGE_SIZE_4K, size)
+            .ok()?,
+    );
     if zeroed {
-        unsafe { core::ptr::write_bytes(vaddr.as_mut_ptr(), 0, PAGE_SIZE_4K) };
+        unsafe { core::ptr::write_bytes(vaddr.as_mut_ptr(), 0, size) };
     }
     let paddr = virt_to_phys(vaddr);
     Some(paddr)
 }
 
-fn dealloc_frame(frame: PhysAddr) {
+pub(crate) fn dealloc_frame(frame: PhysAddr, page_size: PageSize) {
     let vaddr = phys_to_virt(frame);
-    global_allocator().dealloc_pages(vaddr.as_usize(), 1);
+    let size: usize = page_size.into();
+    global_allocator().dealloc_pages(vaddr.as_usize(), size / PAGE_SIZE_4K);
 }
 
 impl Backend {
     /// Creates a new allocation mapping backend.
     pub const fn new_alloc(populate: bool) -> Self {
-        Self::Alloc { populate }
+        Self::Alloc {
+            populate,
+            page_size: PageSize::Size4K,
+        }
+    }
+
+    /// Creates a new allocation mapping backend that uses pages of
+    /// `page_size` (e.g. 2M or 1G huge pages).
+    pub const fn new_alloc_huge(populate: bool, page_size: PageSize) -> Self {
+        Self::Alloc {
+            populate,
+            page_size,
+        }
     }
 
     pub(crate) fn map_alloc(
@@ -32,26 +50,35 @@ impl Backend {
         flags: MappingFlags,
         pt: &mut PageTable,
         populate: bool,
+        page_size: PageSize,
     ) -> bool {
         debug!(
-            "map_alloc: [{:#x}, {:#x}) {:?} (populate={})",
+            "map_alloc: [{:#x}, {:#x}) {:?} (populate={}, page_size={:?})",
             start,
             start + size,
             flags,
-            populate
+            populate,
+            page_size
         );
         if populate {
             // allocate all possible physical frames for populated mapping.
-            for addr in PageIter4K::new(start, start + size).unwrap() {
-                if let Some(frame) = alloc_frame(true) {
-                    if let Ok(tlb) = pt.map(addr, frame, PageSize::Size4K, flags) {
-                        tlb.ignore(); // TLB flush on map is unnecessary, as there are no outdated mappings.
-                    } else {
-                        return false;
-                    }
+            let step: usize = page_size.into();
+            for addr in (start.as_usize()..(start + size).as_usize()).step_by(step) {
+                let Some(frame) = alloc_frame(true, page_size) else {
+                    return false;
+                };
+                if let Ok(tlb) = pt.map(addr.into(), frame, page_size, flags) {
+                    tlb.ignore(); // TLB flush on map is unnecessary, as there are no outdated mappings.
+                } else {
+                    dealloc_frame(frame, page_size);
+                    return false;
                 }
             }
             true
+        } else if page_size.is_huge() {
+            // An empty huge entry cannot be remapped later, so leave the entries
+            // unmapped and create them when handling page faults.
+            true
         } else {
             // Map to a empty entry for on-demand mapping.
             let flags = MappingFlags::empty();
@@ -67,19 +94,26 @@ impl Backend {
         size: usize,
         pt: &mut PageTable,
         _populate: bool,
+        _page_size: PageSize,
     ) -> bool {
         debug!("unmap_alloc: [{:#x}, {:#x})", start, start + size);
-        for addr in PageIter4K::new(start, start + size).unwrap() {
+        // Huge pages crossing the boundaries are split first, so every page
+        // unmapped below lies entirely inside the range.
+        if split_huge_pages(pt, start, size).is_err() {
+            return false;
+        }
+        let end = start + size;
+        let mut addr = start;
+        while addr < end {
             if let Ok((frame, page_size, tlb)) = pt.unmap(addr) {
                 // Deallocate the physical frame if there is a mapping in the
                 // page table.
-                if page_size.is_huge() {
-                    return false;
-                }
                 tlb.flush();
-                dealloc_frame(frame);
+                dealloc_frame(frame, page_size);
+                addr += page_size.into();
             } else {
                 // Deallocation is needn't if the page is not mapped.
+                addr += PAGE_SIZE_4K;
             }
         }
         true
@@ -89,12 +123,48 @@ impl Backend {
         &self,
         vaddr: VirtAddr,
         orig_flags: MappingFlags,
+        area_range: VirtAddrRange,
         pt: &mut PageTable,
         populate: bool,
+        page_size: PageSize,
     ) -> bool {
         if populate {
-            false // Populated mappings should not trigger page faults.
-        } else if let Some(frame) = alloc_frame(true) {
+            return false; // Populated mappings should not trigger page faults.
+        }
+        if page_size.is_huge() {
+            // Use a huge page if it fits in the area (which may have been
+            // split) and there is contiguous memory for it.
+            let huge_start = vaddr.align_down(page_size);
+            let huge_range = VirtAddrRange::from_start_size(huge_start, page_size.into());
+            if area_range.contains_range(huge_range) {
+                if let Some(frame) = alloc_frame(true, page_size) {
+                    match pt.map(huge_start, frame, page_size, orig_flags) {
+                        Ok(tlb) => {
+                            tlb.flush();
+                            return true;
+                        }
+                        // The slot already holds a page table, e.g. one left
+                        // by an earlier mapping of 4K pages.
+                        Err(_) => dealloc_frame(frame, page_size),
+                    }
+                }
+            }
+            // Otherwise, fall back to a 4K page.
+            let Some(frame) = alloc_frame(true, PageSize::Size4K) else {
+                return false;
+            };
+            return match pt.map(vaddr.align_down_4k(), frame, PageSize::Size4K, orig_flags) {
+                Ok(tlb) => {
+                    tlb.flush();
+                    true
+                }
+                Err(_) => {
+                    dealloc_frame(frame, PageSize::Size4K);
+                    false
+                }
+            };
+        }
+        if let Some(frame) = alloc_frame(true, PageSize::Size4K) {
             // Allocate a physical frame lazily and map it to the fault address.
             // `vaddr` does not need to be aligned. It will be automatically
             // aligned during `pt.remap` regardless of the page size.
@@ -1,21 +1,28 @@
 //! Memory mapping backends.
 #![allow(dead_code)]
 
-use axhal::paging::{MappingFlags, PageTable};
-use memory_addr::VirtAddr;
+use ::alloc::sync::Arc;
+use axhal::paging::{MappingFlags, PageSize, PageTable, PagingResult};
+use memory_addr::{MemoryAddr, VirtAddr, VirtAddrRange, PAGE_SIZE_4K};
 use memory_set::MappingBackend;
 
 mod alloc;
 mod linear;
+mod shared;
+
+pub(crate) use self::alloc::{alloc_frame, dealloc_frame};
+pub use self::shared::SharedPages;
 
 /// A unified enum type for different memory mapping backends.
 ///
-/// Currently, two backends are implemented:
+/// Currently, three backends are implemented:
 ///
 /// - **Linear**: used for linear mappings. The target physical frames are
 ///   contiguous and their addresses should be known when creating the mapping.
 /// - **Allocation**: used in general, or for lazy mappings. The target physical
 ///   frames are obtained from the global allocator.
+/// - **Shared**: used for memory shared between address spaces. The target
+///   physical frames are reference-counted and owned by [`SharedPages`].
 #[derive(Clone)]
 pub enum Backend {
     /// Linear mapping backend.
@@ -24,7 +31,7 @@ pub enum Backend {
     /// constant, which is specified by `pa_va_offset`. For example, the virtual
     /// address `vaddr` is mapped to the physical address `vaddr - pa_va_offset`.
     Linear {
-        /// `vaddr - paddr`.
+        /// `vaddr - paddr` (wrapping).
         pa_va_offset: usize,
     },
     /// Allocation mapping backend.
@@ -33,9 +40,30 @@ pub enum Backend {
     /// mapping is created, and no page faults are triggered during the memory
     /// access. Otherwise, the physical frames are allocated on demand (by
     /// handling page faults).
+    ///
+    /// The frames are allocated in units of `page_size`. For huge pages, it
+    /// falls back to 4K pages on demand if there is no contiguous memory, or
+    /// the huge page cannot be mapped.
     Alloc {
         /// Whether to populate the physical frames when creating the mapping.
         populate: bool,
+        /// The size of the pages to allocate.
+        page_size: PageSize,
+    },
+    /// Shared mapping backend.
+    ///
+    /// The virtual pages are mapped to the frames in `pages` in order, starting
+    /// from the frame at byte offset `vaddr - va_offset` for the virtual
+    /// address `vaddr`. The same `pages` can be mapped into several address
+    /// spaces, and the frames are released only when all of them are unmapped.
+    Shared {
+        /// The shared physical frames.
+        pages: Arc<SharedPages>,
+        /// `vaddr - offset` (wrapping), where `offset` is the byte offset in
+        /// `pages` mapped at `vaddr`.
+        ///
+        /// It stays the same when the area is split or copied.
+        va_offset: usize,
     },
 }
 
@@ -46,14 +74,25 @@ impl MappingBackend for Backend {
     fn map(&self, start: VirtAddr, size: usize, flags: MappingFlags, pt: &mut PageTable) -> bool {
         match *self {
             Self::Linear { pa_va_offset } => self.map_linear(start, size, flags, pt, pa_va_offset),
-            Self::Alloc { populate } => self.map_alloc(start, size, flags, pt, populate),
+            Self::Alloc {
+                populate,
+                page_size,
+            } => self.map_alloc(start, size, flags, pt, populate, page_size),
+            Self::Shared {
+                ref pages,
+                va_offset,
+            } => self.map_shared(start, size, flags, pt, pages, va_offset),
         }
     }
 
     fn unmap(&self, start: VirtAddr, size: usize, pt: &mut PageTable) -> bool {
         match *self {
             Self::Linear { pa_va_offset } => self.unmap_linear(start, size, pt, pa_va_offset),
-            Self::Alloc { populate } => self.unmap_alloc(start, size, pt, populate),
+            Self::Alloc {
+                populate,
+                page_size,
+            } => self.unmap_alloc(start, size, pt, populate, page_size),
+            Self::Shared { ref pages, .. } => self.unmap_shared(start, size, pt, pages),
         }
     }
 
@@ -64,10 +103,32 @@ impl MappingBackend for Backend {
         new_flags: Self::Flags,
         page_table: &mut Self::PageTable,
     ) -> bool {
-        page_table
-            .protect_region(start, size, new_flags, true)
-            .map(|tlb| tlb.ignore())
-            .is_ok()
+        debug!(
+            "protect: [{:#x}, {:#x}) {:?}",
+            start,
+            start + size,
+            new_flags
+        );
+        if split_huge_pages(page_table, start, size).is_err() {
+            return false;
+        }
+        // Skip the empty entries of lazy mappings (mapped to 0 without flags),
+        // which get the new flags of the area when handling page faults.
+        let end = start + size;
+        let mut addr = start;
+        while addr < end {
+            match page_table.query(addr) {
+                Ok((paddr, flags, page_size)) if !flags.is_empty() || paddr.as_usize() != 0 => {
+                    match page_table.protect(addr, new_flags) {
+                        Ok((_, tlb)) => tlb.flush(),
+                        Err(_) => return false,
+                    }
+                    addr += page_size.into();
+                }
+                _ => addr += PAGE_SIZE_4K,
+            }
+        }
+        true
     }
 }
 
@@ -76,13 +137,57 @@ impl Backend {
         &self,
         vaddr: VirtAddr,
         orig_flags: MappingFlags,
+        area_range: VirtAddrRange,
         page_table: &mut PageTable,
     ) -> bool {
         match *self {
             Self::Linear { .. } => false, // Linear mappings should not trigger page faults.
-            Self::Alloc { populate } => {
-                self.handle_page_fault_alloc(vaddr, orig_flags, page_table, populate)
+            Self::Alloc {
+                populate,
+                page_size,
+            } => self.handle_page_fault_alloc(
+                vaddr, orig_flags, area_range, page_table, populate, page_size,
+            ),
+            Self::Shared { .. } => false, // Shared mappings are always populated.
+        }
+    }
+}
+
+/// Splits the huge page containing `vaddr` into pages of the next smaller size,
+/// which map to the same physical memory with the same flags.
+fn split_huge_page(pt: &mut PageTable, vaddr: VirtAddr) -> PagingResult {
+    let (paddr, flags, page_size) = pt.query(vaddr)?;
+    let sub_page_size = match page_size {
+        PageSize::Size1G => PageSize::Size2M,
+        PageSize::Size2M => PageSize::Size4K,
+        PageSize::Size4K => return Ok(()),
+    };
+    let start = vaddr.align_down(page_size);
+    let paddr = paddr - (vaddr - start);
+    debug!(
+        "split_huge_page: {:#x} ({:?} -> {:?})",
+        start, page_size, sub_page_size
+    );
+
+    pt.unmap(start)?.2.flush();
+    let (size, step): (usize, usize) = (page_size.into(), sub_page_size.into());
+    for offset in (0..size).step_by(step) {
+        pt.map(start + offset, paddr + offset, sub_page_size, flags)?
+            .ignore();
+    }
+    Ok(())
+}
+
+/// Splits the huge pages crossing the boundaries of `[start, start + size)`,
+/// so that every page in the range lies entirely inside it.
+pub(crate) fn split_huge_pages(pt: &mut PageTable, start: VirtAddr, size: usize) -> PagingResult {
+    for vaddr in [start, start + size] {
+        while let Ok((_, _, page_size)) = pt.query(vaddr) {
+            if !page_size.is_huge() || vaddr.is_aligned(page_size) {
+                break;
             }
+            split_huge_page(pt, vaddr)?;
         }
     }
+    Ok(())
 }
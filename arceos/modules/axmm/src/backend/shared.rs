@@ -0,0 +1,164 @@
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use axerrno::{AxError, AxResult};
+use axhal::mem::phys_to_virt;
+use axhal::paging::{MappingFlags, PageSize, PageTable};
+use kspin::SpinNoIrq;
+use memory_addr::{PhysAddr, VirtAddr, PAGE_SIZE_4K};
+
+use super::alloc::{alloc_frame, dealloc_frame};
+use super::Backend;
+
+/// A group of physical frames that can be mapped into several address spaces
+/// at the same time.
+///
+/// The frames are released when the last reference is dropped, i.e., after
+/// every [`Backend::Shared`] mapping them has been removed and the owner (e.g.
+/// a System V shared memory segment or a memfd) is gone.
+///
+/// The group can only grow. Frames never move once allocated, so the existing
+/// mappings stay valid after [`SharedPages::grow`].
+pub struct SharedPages {
+    frames: SpinNoIrq<Vec<PhysAddr>>,
+}
+
+impl SharedPages {
+    /// Allocates `num_pages` zeroed physical frames.
+    pub fn new(num_pages: usize) -> AxResult<Self> {
+        let pages = Self {
+            frames: SpinNoIrq::new(Vec::new()),
+        };
+        pages.grow(num_pages)?;
+        Ok(pages)
+    }
+
+    /// Returns the number of frames.
+    pub fn num_pages(&self) -> usize {
+        self.frames.lock().len()
+    }
+
+    /// Returns the total size of the frames in bytes.
+    pub fn size(&self) -> usize {
+        self.num_pages() * PAGE_SIZE_4K
+    }
+
+    /// Returns the physical address of the `idx`-th frame.
+    pub fn frame(&self, idx: usize) -> Option<PhysAddr> {
+        self.frames.lock().get(idx).copied()
+    }
+
+    /// Allocates more zeroed frames until there are at least `num_pages`.
+    pub fn grow(&self, num_pages: usize) -> AxResult {
+        let mut frames = self.frames.lock();
+        while frames.len() < num_pages {
+            frames.push(alloc_frame(true, PageSize::Size4K).ok_or(AxError::NoMemory)?);
+        }
+        Ok(())
+    }
+
+    /// Takes the frames out of the group, so the caller owns them.
+    pub(crate) fn into_frames(mut self) -> Vec<PhysAddr> {
+        core::mem::take(self.frames.get_mut())
+    }
+
+    /// Reads data at the given byte offset into `buf`.
+    ///
+    /// Returns the number of bytes read, which is less than `buf.len()` if it
+    /// reaches the end of the frames.
+    pub fn read(&self, offset: usize, buf: &mut [u8]) -> usize {
+        self.process_data(offset, buf.len(), |src, pos, len| unsafe {
+            core::ptr::copy_nonoverlapping(src, buf.as_mut_ptr().add(pos), len);
+        })
+    }
+
+    /// Writes data in `buf` at the given byte offset.
+    ///
+    /// Returns the number of bytes written, which is less than `buf.len()` if
+    /// it reaches the end of the frames.
+    pub fn write(&self, offset: usize, buf: &[u8]) -> usize {
+        self.process_data(offset, buf.len(), |dst, pos, len| unsafe {
+            core::ptr::copy_nonoverlapping(buf.as_ptr().add(pos), dst, len);
+        })
+    }
+
+    fn process_data<F>(&self, offset: usize, size: usize, mut f: F) -> usize
+    where
+        F: FnMut(*mut u8, usize, usize),
+    {
+        let frames = self.frames.lock();
+        let end = (offset + size).min(frames.len() * PAGE_SIZE_4K);
+        let mut cur = offset;
+        while cur < end {
+            let page_offset = cur % PAGE_SIZE_4K;
+            let len = (PAGE_SIZE_4K - page_offset).min(end - cur);
+            let vaddr = phys_to_virt(frames[cur / PAGE_SIZE_4K]) + page_offset;
+            f(vaddr.as_mut_ptr(), cur - offset, len);
+            cur += len;
+        }
+        end.saturating_sub(offset)
+    }
+}
+
+impl Drop for SharedPages {
+    fn drop(&mut self) {
+        for frame in self.frames.get_mut().drain(..) {
+            dealloc_frame(frame, PageSize::Size4K);
+        }
+    }
+}
+
+impl Backend {
+    /// Creates a new shared mapping backend.
+    pub const fn new_shared(pages: Arc<SharedPages>, va_offset: usize) -> Self {
+        Self::Shared { pages, va_offset }
+    }
+
+    pub(crate) fn map_shared(
+        &self,
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+        pt: &mut PageTable,
+        pages: &SharedPages,
+        va_offset: usize,
+    ) -> bool {
+        let offset = start.as_usize().wrapping_sub(va_offset);
+        debug!(
+            "map_shared: [{:#x}, {:#x}) -> offset {:#x} {:?}",
+            start,
+            start + size,
+            offset,
+            flags
+        );
+        let frames = pages.frames.lock();
+        let first = offset / PAGE_SIZE_4K;
+        let range = first.checked_add(size / PAGE_SIZE_4K).map(|end| first..end);
+        let Some(frames) = range.and_then(|range| frames.get(range)) else {
+            return false;
+        };
+        for (i, frame) in frames.iter().enumerate() {
+            let addr = start + i * PAGE_SIZE_4K;
+            if let Ok(tlb) = pt.map(addr, *frame, PageSize::Size4K, flags) {
+                tlb.ignore(); // TLB flush on map is unnecessary, as there are no outdated mappings.
+            } else {
+                return false;
+            }
+        }
+        true
+    }
+
+    pub(crate) fn unmap_shared(
+        &self,
+        start: VirtAddr,
+        size: usize,
+        pt: &mut PageTable,
+        _pages: &SharedPages,
+    ) -> bool {
+        debug!("unmap_shared: [{:#x}, {:#x})", start, start + size);
+        // The frames are owned by `SharedPages`, only remove the mappings here.
+        pt.unmap_region(start, size, true)
+            .map(|tlb| tlb.ignore())
+            .is_ok()
+    }
+}
@@ -0,0 +1,192 @@
+//! Page table walker and area listing for debugging.
+
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::fmt;
+
+use axhal::paging::{GenericPTE, MappingFlags, PageSize, PageTableEntry};
+use memory_addr::{PhysAddr, VirtAddr, PAGE_SIZE_4K};
+
+use crate::backend::Backend;
+use crate::AddrSpace;
+
+/// Number of levels of the page table (Sv39).
+#[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))]
+const PAGE_TABLE_LEVELS: usize = 3;
+/// Number of valid bits in a virtual address (Sv39).
+#[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))]
+const VA_BITS: usize = 39;
+
+/// Number of levels of the page table.
+#[cfg(not(any(target_arch = "riscv32", target_arch = "riscv64")))]
+const PAGE_TABLE_LEVELS: usize = 4;
+/// Number of valid bits in a virtual address.
+#[cfg(not(any(target_arch = "riscv32", target_arch = "riscv64")))]
+const VA_BITS: usize = 48;
+
+/// A range of virtual memory that is mapped to contiguous physical memory,
+/// with the same page size and mapping flags.
+#[derive(Debug, Clone, Copy)]
+pub struct MappingRange {
+    /// The start virtual address.
+    pub vaddr: VirtAddr,
+    /// The start physical address.
+    pub paddr: PhysAddr,
+    /// The size of the range in bytes.
+    pub size: usize,
+    /// The page size of each entry in the range.
+    pub page_size: PageSize,
+    /// The mapping flags.
+    pub flags: MappingFlags,
+}
+
+impl MappingRange {
+    fn try_merge(&mut self, other: &Self) -> bool {
+        if self.vaddr + self.size == other.vaddr
+            && self.paddr + self.size == other.paddr
+            && self.page_size == other.page_size
+            && self.flags == other.flags
+        {
+            self.size += other.size;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl fmt::Display for MappingRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "[{:#x}, {:#x}) -> [{:#x}, {:#x}) {:?} {:?}",
+            self.vaddr,
+            self.vaddr + self.size,
+            self.paddr,
+            self.paddr + self.size,
+            self.page_size,
+            self.flags,
+        )
+    }
+}
+
+/// The printable form of an [`AddrSpace`], see [`AddrSpace::dump`].
+pub struct AddrSpaceDump<'a>(&'a AddrSpace);
+
+impl fmt::Display for AddrSpaceDump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "AddrSpace [{:#x}, {:#x}), page_table_root={:#x}:",
+            self.0.base(),
+            self.0.end(),
+            self.0.page_table_root()
+        )?;
+        for range in self.0.mappings() {
+            writeln!(f, "  {}", range)?;
+        }
+        Ok(())
+    }
+}
+
+/// The memory areas of an [`AddrSpace`] in the format of `/proc/<pid>/maps`,
+/// see [`AddrSpace::maps`].
+pub struct AddrSpaceMaps<'a>(&'a AddrSpace);
+
+impl fmt::Display for AddrSpaceMaps<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for area in self.0.areas.iter() {
+            let flags = area.flags();
+            let perm = |flag, c| if flags.contains(flag) { c } else { '-' };
+            let shared = match area.backend() {
+                Backend::Shared { .. } => 's',
+                _ => 'p',
+            };
+            write!(
+                f,
+                "{:08x}-{:08x} {}{}{}{} 00000000 00:00 0",
+                area.start(),
+                area.end(),
+                perm(MappingFlags::READ, 'r'),
+                perm(MappingFlags::WRITE, 'w'),
+                perm(MappingFlags::EXECUTE, 'x'),
+                shared,
+            )?;
+            if area.end() == self.0.layout().stack_top {
+                write!(f, "{:>26}", "[stack]")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the size of the page mapped by a leaf entry at `level`.
+fn leaf_page_size(level: usize) -> PageSize {
+    match PAGE_TABLE_LEVELS - 1 - level {
+        0 => PageSize::Size4K,
+        1 => PageSize::Size2M,
+        _ => PageSize::Size1G,
+    }
+}
+
+/// Sign-extends a virtual address obtained by walking the page table.
+fn canonical_vaddr(vaddr: usize) -> VirtAddr {
+    let shift = usize::BITS as usize - VA_BITS;
+    VirtAddr::from((((vaddr << shift) as isize) >> shift) as usize)
+}
+
+impl AddrSpace {
+    /// Walks the page table and returns all present mappings within the
+    /// address space.
+    ///
+    /// Adjacent entries are coalesced into one [`MappingRange`] if both their
+    /// virtual and physical addresses are contiguous, and they have the same
+    /// page size and flags.
+    pub fn mappings(&self) -> impl Iterator<Item = MappingRange> {
+        let ranges: RefCell<Vec<MappingRange>> = RefCell::new(Vec::new());
+        let visit = |level: usize, _idx: usize, vaddr: VirtAddr, pte: &PageTableEntry| {
+            if !pte.is_present() || (level < PAGE_TABLE_LEVELS - 1 && !pte.is_huge()) {
+                return; // not mapped, or points to the next level table.
+            }
+            let vaddr = canonical_vaddr(vaddr.as_usize());
+            if !self.contains_range(vaddr, PAGE_SIZE_4K) {
+                return;
+            }
+            let page_size = leaf_page_size(level);
+            let range = MappingRange {
+                vaddr,
+                paddr: pte.paddr(),
+                size: page_size.into(),
+                page_size,
+                flags: pte.flags(),
+            };
+            let mut ranges = ranges.borrow_mut();
+            if !ranges.last_mut().is_some_and(|last| last.try_merge(&range)) {
+                ranges.push(range);
+            }
+        };
+        if let Err(err) = self.page_table().walk(usize::MAX, Some(&visit), None) {
+            warn!("failed to walk the page table: {:?}", err);
+        }
+        ranges.into_inner().into_iter()
+    }
+
+    /// Returns a printable dump of the address space, listing each mapping
+    /// range with its backing physical address, page size and flags.
+    ///
+    /// It walks the real page table, so lazily allocated pages that have not
+    /// been touched yet are not listed.
+    pub fn dump(&self) -> AddrSpaceDump<'_> {
+        AddrSpaceDump(self)
+    }
+
+    /// Returns the memory areas in the format of `/proc/<pid>/maps`, one area
+    /// per line with its range, permissions and whether it is shared.
+    ///
+    /// Unlike [`dump`](Self::dump), it lists the areas as they are mapped,
+    /// including the pages that have not been allocated yet.
+    pub fn maps(&self) -> AddrSpaceMaps<'_> {
+        AddrSpaceMaps(self)
+    }
+}
@@ -1,19 +1,35 @@
 //! [ArceOS](https://github.com/arceos-org/arceos) memory management module.
 
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 
 #[macro_use]
 extern crate log;
 extern crate alloc;
 
+mod aslr;
 mod aspace;
 mod backend;
+mod dump;
+mod swap;
+#[cfg(feature = "uspace")]
+mod uaccess;
 
+#[cfg(test)]
+mod tests;
+
+pub use self::aslr::{aslr_enabled, set_aslr_enabled, UserLayout};
 pub use self::aspace::AddrSpace;
+pub use self::backend::SharedPages;
+pub use self::dump::{AddrSpaceDump, AddrSpaceMaps, MappingRange};
+pub use self::swap::{init_swap, set_reclaim_handler, SwapDevice};
+#[cfg(feature = "uspace")]
+pub use self::uaccess::{
+    copy_from_user, copy_to_user, read_iovecs_from_user, read_to_user, strncpy_from_user,
+    write_from_user, UserIoVec, UserPtr, UserSlice, IOV_MAX,
+};
 
 use axerrno::{AxError, AxResult};
 use axhal::mem::phys_to_virt;
-use axhal::paging::PagingError;
 use kspin::SpinNoIrq;
 use lazyinit::LazyInit;
 use memory_addr::{va, PhysAddr, VirtAddr};
@@ -33,21 +49,17 @@ fn mapping_err_to_ax_err(err: MappingError) -> AxError {
     }
 }
 
-fn paging_err_to_ax_err(err: PagingError) -> AxError {
-    warn!("Paging error: {:?}", err);
-    match err {
-        PagingError::NoMemory => AxError::NoMemory,
-        PagingError::NotAligned => AxError::InvalidInput,
-        PagingError::NotMapped => AxError::NotFound,
-        PagingError::AlreadyMapped => AxError::AlreadyExists,
-        PagingError::MappedToHugePage => AxError::InvalidInput,
-    }
-}
-
 /// Creates a new address space for user processes.
+///
+/// Its layout is randomized if ASLR is enabled, see [`UserLayout`].
 pub fn new_user_aspace() -> AxResult<AddrSpace> {
     let mut aspace = AddrSpace::new_empty(VirtAddr::from(USER_ASPACE_BASE), USER_ASPACE_SIZE)?;
-    aspace.copy_mappings_from(&kernel_aspace().lock())?;
+    aspace.randomize_layout();
+    // On aarch64, the kernel space is mapped by another page table root
+    // (`TTBR1_EL1`), so there is nothing to copy.
+    if !cfg!(target_arch = "aarch64") {
+        aspace.copy_mappings_from(&kernel_aspace().lock())?;
+    }
     Ok(aspace)
 }
 
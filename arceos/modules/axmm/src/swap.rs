@@ -0,0 +1,451 @@
+//! Page reclaim and swapping.
+//!
+//! Only the pages of lazy 4K [`Backend::Alloc`] mappings (i.e., anonymous
+//! pages) are reclaimable. Each [`AddrSpace`] keeps its resident pages in a
+//! clock list once a swap device is set. When a frame cannot be allocated while
+//! handling a page fault, the cold pages are written to the swap device and
+//! their frames are released, first in the faulting address space and then in
+//! the others (see [`set_reclaim_handler`]).
+//!
+//! The page table entries set the accessed bit unconditionally on some
+//! architectures (e.g., RISC-V without Svadu), so the accessed bit is emulated:
+//! scanning a page clears its permissions, and the next access restores them
+//! through a page fault. A page that is not accessed between two scans is cold.
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use axerrno::{ax_err, AxError, AxResult};
+use axhal::mem::phys_to_virt;
+use axhal::paging::{MappingFlags, PageSize};
+use kspin::SpinNoIrq;
+use memory_addr::{MemoryAddr, PhysAddr, VirtAddr, VirtAddrRange, PAGE_SIZE_4K};
+
+use crate::backend::{alloc_frame, dealloc_frame, Backend};
+use crate::AddrSpace;
+
+/// Number of pages to reclaim at a time.
+const RECLAIM_BATCH: usize = 16;
+
+/// A block device (or any storage) to hold swapped-out pages.
+///
+/// The device is divided into page-sized slots.
+pub trait SwapDevice: Send + Sync {
+    /// Returns the number of page-sized slots.
+    fn num_slots(&self) -> usize;
+    /// Reads the page in `slot` into `buf`.
+    fn read_page(&mut self, slot: usize, buf: &mut [u8]) -> AxResult;
+    /// Writes `buf` into `slot`.
+    fn write_page(&mut self, slot: usize, buf: &[u8]) -> AxResult;
+}
+
+pub(crate) struct SwapArea {
+    dev: Box<dyn SwapDevice>,
+    /// Slots that have been used and then freed.
+    free_slots: Vec<usize>,
+    /// Slots in `[next_slot, num_slots)` have never been used.
+    next_slot: usize,
+}
+
+impl SwapArea {
+    fn alloc_slot(&mut self) -> Option<usize> {
+        if let Some(slot) = self.free_slots.pop() {
+            Some(slot)
+        } else if self.next_slot < self.dev.num_slots() {
+            self.next_slot += 1;
+            Some(self.next_slot - 1)
+        } else {
+            None
+        }
+    }
+}
+
+pub(crate) static SWAP_AREA: SpinNoIrq<Option<SwapArea>> = SpinNoIrq::new(None);
+
+static RECLAIM_HANDLER: SpinNoIrq<Option<fn(usize) -> usize>> = SpinNoIrq::new(None);
+
+/// Uses the given device as the swap area.
+///
+/// Before it is called, no pages can be reclaimed, and the pages allocated
+/// before are never reclaimed.
+pub fn init_swap(dev: Box<dyn SwapDevice>) {
+    info!("Initialize swap area: {} pages", dev.num_slots());
+    *SWAP_AREA.lock() = Some(SwapArea {
+        dev,
+        free_slots: Vec::new(),
+        next_slot: 0,
+    });
+}
+
+/// Sets the function to reclaim pages from the other address spaces.
+///
+/// An address space only knows its own pages. If a page fault cannot get a
+/// frame even after the faulting address space reclaims its pages, `handler`
+/// is called with the number of pages wanted. It should call
+/// [`AddrSpace::reclaim`] on the other address spaces, and return the number
+/// of reclaimed pages. The faulting address space is locked at that time, so
+/// `handler` must skip the address spaces it cannot lock.
+pub fn set_reclaim_handler(handler: fn(usize) -> usize) {
+    *RECLAIM_HANDLER.lock() = Some(handler);
+}
+
+fn swap_enabled() -> bool {
+    SWAP_AREA.lock().is_some()
+}
+
+/// Writes the frame into a free slot, returns the slot.
+fn swap_out(frame: PhysAddr) -> AxResult<usize> {
+    let mut swap = SWAP_AREA.lock();
+    let Some(swap) = swap.as_mut() else {
+        return ax_err!(Unsupported, "no swap area");
+    };
+    let Some(slot) = swap.alloc_slot() else {
+        return ax_err!(NoMemory, "swap area is full");
+    };
+    let buf = unsafe { core::slice::from_raw_parts(phys_to_virt(frame).as_ptr(), PAGE_SIZE_4K) };
+    if let Err(err) = swap.dev.write_page(slot, buf) {
+        swap.free_slots.push(slot);
+        return Err(err);
+    }
+    Ok(slot)
+}
+
+/// Reads the page in the slot into the frame.
+fn read_slot(slot: usize, frame: PhysAddr) -> AxResult {
+    let mut swap = SWAP_AREA.lock();
+    let swap = swap.as_mut().expect("no swap area");
+    let buf =
+        unsafe { core::slice::from_raw_parts_mut(phys_to_virt(frame).as_mut_ptr(), PAGE_SIZE_4K) };
+    swap.dev.read_page(slot, buf)
+}
+
+/// Reads the page in the slot into the frame, and frees the slot.
+fn swap_in(slot: usize, frame: PhysAddr) -> AxResult {
+    read_slot(slot, frame)?;
+    free_slot(slot);
+    Ok(())
+}
+
+fn free_slot(slot: usize) {
+    if let Some(swap) = SWAP_AREA.lock().as_mut() {
+        swap.free_slots.push(slot);
+    }
+}
+
+/// Whether the translation result `paddr` of a page of `size` is a lazy
+/// placeholder, i.e., the page is not allocated yet.
+fn is_placeholder(paddr: PhysAddr, size: PageSize) -> bool {
+    paddr.align_down(size).as_usize() == 0
+}
+
+/// Reclaimable pages of an address space.
+pub(crate) struct SwapState {
+    /// Resident pages, and whether their permissions are cleared by the last
+    /// scan (i.e., they are aged).
+    pub(crate) resident: BTreeMap<VirtAddr, bool>,
+    /// Where the next scan starts, i.e., the hand of the clock.
+    hand: VirtAddr,
+    /// Swapped-out pages and their slots.
+    pub(crate) swapped: BTreeMap<VirtAddr, usize>,
+    /// Pages that must stay resident, e.g., a user buffer being accessed by
+    /// the kernel, see [`AddrSpace::check_user_access`].
+    pub(crate) pinned: Option<VirtAddrRange>,
+}
+
+impl Default for SwapState {
+    fn default() -> Self {
+        Self {
+            resident: BTreeMap::new(),
+            hand: VirtAddr::from(0),
+            swapped: BTreeMap::new(),
+            pinned: None,
+        }
+    }
+}
+
+impl SwapState {
+    /// Returns the resident page under the clock hand, and moves the hand to
+    /// the next one.
+    fn next_resident(&mut self) -> Option<(VirtAddr, bool)> {
+        let (&page, &aged) = self
+            .resident
+            .range(self.hand..)
+            .next()
+            .or_else(|| self.resident.iter().next())?;
+        self.hand = page + PAGE_SIZE_4K;
+        Some((page, aged))
+    }
+}
+
+impl Backend {
+    /// Whether the pages of this backend can be swapped out.
+    pub(crate) const fn is_swappable(&self) -> bool {
+        matches!(
+            self,
+            Self::Alloc {
+                populate: false,
+                page_size: PageSize::Size4K,
+            }
+        )
+    }
+}
+
+impl AddrSpace {
+    /// Handles a page fault in a swappable area, see [`Backend::is_swappable`].
+    ///
+    /// The page may have been aged, swapped out, or not allocated yet. If no
+    /// frame can be allocated, pages are reclaimed and it tries again.
+    pub(crate) fn handle_swappable_page_fault(
+        &mut self,
+        vaddr: VirtAddr,
+        orig_flags: MappingFlags,
+        area_range: VirtAddrRange,
+    ) -> bool {
+        let page = vaddr.align_down_4k();
+        if self.swap.resident.get(&page) == Some(&true) {
+            // Accessed since the last scan, restore the permissions.
+            self.swap.resident.insert(page, false);
+            return self.remap_page(page, orig_flags);
+        }
+        if let Some(slot) = self.swap.swapped.remove(&page) {
+            return self.swap_in_page(page, slot, orig_flags);
+        }
+
+        let backend = Backend::new_alloc(false);
+        let mut handled = backend.handle_page_fault(vaddr, orig_flags, area_range, &mut self.pt);
+        if !handled && self.reclaim_any(RECLAIM_BATCH) > 0 {
+            handled = backend.handle_page_fault(vaddr, orig_flags, area_range, &mut self.pt);
+        }
+        if handled && swap_enabled() {
+            self.swap.resident.insert(page, false);
+        }
+        handled
+    }
+
+    fn swap_in_page(&mut self, page: VirtAddr, slot: usize, flags: MappingFlags) -> bool {
+        let Some(frame) = self.alloc_frame_or_reclaim() else {
+            self.swap.swapped.insert(page, slot);
+            return false;
+        };
+        if let Err(err) = swap_in(slot, frame) {
+            warn!("failed to swap in page {:#x}: {:?}", page, err);
+            dealloc_frame(frame, PageSize::Size4K);
+            self.swap.swapped.insert(page, slot);
+            return false;
+        }
+        debug!("swap in: {:#x} <- slot {}", page, slot);
+        if !self.remap_page_to(page, frame, flags) {
+            dealloc_frame(frame, PageSize::Size4K);
+            return false;
+        }
+        self.swap.resident.insert(page, false);
+        true
+    }
+
+    /// Maps `frame` to the unpopulated `page` of a swappable area, as if it
+    /// was allocated on a page fault.
+    pub(crate) fn map_resident_page(
+        &mut self,
+        page: VirtAddr,
+        frame: PhysAddr,
+        flags: MappingFlags,
+    ) -> bool {
+        if !self.remap_page_to(page, frame, flags) {
+            return false;
+        }
+        if swap_enabled() {
+            self.swap.resident.insert(page, false);
+        }
+        true
+    }
+
+    fn alloc_frame_or_reclaim(&mut self) -> Option<PhysAddr> {
+        alloc_frame(false, PageSize::Size4K).or_else(|| {
+            if self.reclaim_any(RECLAIM_BATCH) > 0 {
+                alloc_frame(false, PageSize::Size4K)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Reclaims pages from this address space, or from the other ones if
+    /// there are none to reclaim here.
+    fn reclaim_any(&mut self, count: usize) -> usize {
+        match self.reclaim(count) {
+            0 => {
+                let handler = *RECLAIM_HANDLER.lock();
+                handler.map_or(0, |handler| handler(count))
+            }
+            reclaimed => reclaimed,
+        }
+    }
+
+    /// Returns the number of pages swapped out from this address space.
+    pub fn num_swapped_pages(&self) -> usize {
+        self.swap.swapped.len()
+    }
+
+    /// Reclaims at most `count` resident pages by swapping them out.
+    ///
+    /// Returns the number of reclaimed pages.
+    pub fn reclaim(&mut self, count: usize) -> usize {
+        if SWAP_AREA.lock().is_none() {
+            return 0;
+        }
+        let mut reclaimed = 0;
+        // Each page is visited at most twice: the first visit ages it, and the
+        // second one swaps it out if it has not been accessed.
+        let mut budget = self.swap.resident.len() * 2;
+        while reclaimed < count && budget > 0 {
+            budget -= 1;
+            let Some((page, aged)) = self.swap.next_resident() else {
+                break;
+            };
+            if self.swap.pinned.is_some_and(|range| range.contains(page)) {
+                continue;
+            }
+            let frame = match self.pt.query(page) {
+                Ok((frame, _, _)) if frame.as_usize() != 0 => frame,
+                _ => {
+                    // Not allocated.
+                    self.swap.resident.remove(&page);
+                    continue;
+                }
+            };
+            if !aged {
+                if self.remap_page_to(page, frame, MappingFlags::empty()) {
+                    self.swap.resident.insert(page, true);
+                }
+                continue;
+            }
+            match swap_out(frame) {
+                Ok(slot) => {
+                    debug!("swap out: {:#x} -> slot {}", page, slot);
+                    self.remap_page_to(page, PhysAddr::from(0), MappingFlags::empty());
+                    dealloc_frame(frame, PageSize::Size4K);
+                    self.swap.resident.remove(&page);
+                    self.swap.swapped.insert(page, slot);
+                    reclaimed += 1;
+                }
+                Err(err) => {
+                    warn!("failed to swap out page {:#x}: {:?}", page, err);
+                    break;
+                }
+            }
+        }
+        reclaimed
+    }
+
+    /// Copies the page at `vaddr` of `other` into this address space, if it
+    /// is populated or swapped out there.
+    ///
+    /// The page is allocated by `backend` first, as if a page fault occurs.
+    pub(crate) fn copy_page_from(
+        &mut self,
+        other: &AddrSpace,
+        vaddr: VirtAddr,
+        flags: MappingFlags,
+        area_range: VirtAddrRange,
+        backend: &Backend,
+    ) -> AxResult {
+        let slot = other.swap.swapped.get(&vaddr).copied();
+        let src = match other.pt.query(vaddr) {
+            Ok((paddr, _, size)) if !is_placeholder(paddr, size) => Some(paddr),
+            _ => None,
+        };
+        if slot.is_none() && src.is_none() {
+            return Ok(()); // not populated.
+        }
+
+        let populated = matches!(
+            self.pt.query(vaddr),
+            Ok((paddr, _, size)) if !is_placeholder(paddr, size)
+        );
+        if !populated {
+            let handled = if backend.is_swappable() {
+                self.handle_swappable_page_fault(vaddr, flags, area_range)
+            } else {
+                backend.handle_page_fault(vaddr, flags, area_range, &mut self.pt)
+            };
+            if !handled {
+                return ax_err!(NoMemory);
+            }
+        }
+        let (dst, _, _) = self.pt.query(vaddr).map_err(|_| AxError::BadState)?;
+        match (slot, src) {
+            (Some(slot), _) => read_slot(slot, dst)?,
+            (None, Some(src)) => unsafe {
+                core::ptr::copy_nonoverlapping(
+                    phys_to_virt(src).as_ptr(),
+                    phys_to_virt(dst).as_mut_ptr(),
+                    PAGE_SIZE_4K,
+                )
+            },
+            (None, None) => unreachable!(),
+        }
+        Ok(())
+    }
+
+    /// Restores the permissions of aged pages in the range, so the backends
+    /// see them as normal mappings when unmapping or protecting them.
+    pub(crate) fn restore_aged_pages(&mut self, start: VirtAddr, size: usize) {
+        let end = start + size;
+        let aged: Vec<_> = self
+            .swap
+            .resident
+            .range_mut(start..end)
+            .filter(|(_, aged)| **aged)
+            .map(|(&page, aged)| {
+                *aged = false;
+                page
+            })
+            .collect();
+        for page in aged {
+            if let Some(flags) = self.areas.find(page).map(|area| area.flags()) {
+                self.remap_page(page, flags);
+            }
+        }
+    }
+
+    /// Forgets the pages in the range before they are unmapped, and frees the
+    /// slots of the swapped-out ones.
+    pub(crate) fn forget_swap_pages(&mut self, start: VirtAddr, size: usize) {
+        let range = VirtAddrRange::from_start_size(start, size);
+        let swapped: Vec<_> = self
+            .swap
+            .swapped
+            .range(start..range.end)
+            .map(|(&page, &slot)| (page, slot))
+            .collect();
+        for (page, slot) in swapped {
+            self.swap.swapped.remove(&page);
+            free_slot(slot);
+        }
+        let resident: Vec<_> = self
+            .swap
+            .resident
+            .range(start..range.end)
+            .map(|(&page, _)| page)
+            .collect();
+        for page in resident {
+            self.swap.resident.remove(&page);
+        }
+    }
+
+    /// Sets the flags of a mapped page, keeping the target frame.
+    fn remap_page(&mut self, page: VirtAddr, flags: MappingFlags) -> bool {
+        match self.pt.query(page) {
+            Ok((frame, _, _)) => self.remap_page_to(page, frame, flags),
+            Err(_) => false,
+        }
+    }
+
+    fn remap_page_to(&mut self, page: VirtAddr, frame: PhysAddr, flags: MappingFlags) -> bool {
+        self.pt
+            .remap(page, frame, flags)
+            .map(|(_, tlb)| tlb.flush())
+            .is_ok()
+    }
+}
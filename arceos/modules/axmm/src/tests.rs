@@ -0,0 +1,602 @@
+use std::sync::{Arc, Mutex, Once};
+
+use axerrno::{AxError, AxResult};
+
+use axhal::paging::{MappingFlags, PageSize};
+use memory_addr::{va, MemoryAddr, PhysAddr, PAGE_SIZE_4K};
+
+use crate::swap::SWAP_AREA;
+use crate::{init_swap, AddrSpace, SharedPages, SwapDevice};
+
+const POOL_SIZE: usize = 16 * 1024 * 1024;
+
+#[repr(align(4096))]
+struct Pool([u8; POOL_SIZE]);
+
+static mut POOL: Pool = Pool([0; POOL_SIZE]);
+static INIT: Once = Once::new();
+static SERIAL: Mutex<()> = Mutex::new(());
+
+const BASE: usize = 0x1000_0000;
+const RW: MappingFlags = MappingFlags::READ
+    .union(MappingFlags::WRITE)
+    .union(MappingFlags::USER);
+const RO: MappingFlags = MappingFlags::READ.union(MappingFlags::USER);
+
+fn new_aspace() -> AddrSpace {
+    INIT.call_once(|| {
+        let start = core::ptr::addr_of_mut!(POOL) as usize;
+        axalloc::global_init(start, POOL_SIZE);
+    });
+    AddrSpace::new_empty(va!(0), 0x40_0000_0000).unwrap()
+}
+
+fn areas(aspace: &AddrSpace) -> Vec<(usize, usize, MappingFlags)> {
+    aspace
+        .areas
+        .iter()
+        .map(|area| (area.start().as_usize(), area.end().as_usize(), area.flags()))
+        .collect()
+}
+
+/// A swap device in memory.
+struct MemSwap(Vec<[u8; PAGE_SIZE_4K]>);
+
+impl SwapDevice for MemSwap {
+    fn num_slots(&self) -> usize {
+        self.0.len()
+    }
+
+    fn read_page(&mut self, slot: usize, buf: &mut [u8]) -> AxResult {
+        buf.copy_from_slice(&self.0[slot]);
+        Ok(())
+    }
+
+    fn write_page(&mut self, slot: usize, buf: &[u8]) -> AxResult {
+        self.0[slot].copy_from_slice(buf);
+        Ok(())
+    }
+}
+
+/// Creates an address space with 4 lazy pages at `BASE`, using a swap device
+/// of `num_slots` pages.
+fn new_swap_aspace(num_slots: usize) -> AddrSpace {
+    let mut aspace = new_aspace();
+    init_swap(Box::new(MemSwap(vec![[0; PAGE_SIZE_4K]; num_slots])));
+    aspace.map_alloc(va!(BASE), 0x4000, RW, false).unwrap();
+    for i in 0..4 {
+        let vaddr = va!(BASE + i * PAGE_SIZE_4K);
+        assert!(aspace.handle_page_fault(vaddr, MappingFlags::WRITE));
+        aspace.write(vaddr, &[i as u8 + 1; 8]).unwrap();
+    }
+    aspace
+}
+
+fn frame_at(aspace: &AddrSpace, vaddr: usize) -> PhysAddr {
+    aspace.page_table().query(va!(vaddr)).unwrap().0
+}
+
+#[test]
+fn test_unmap_middle() {
+    let _lock = SERIAL.lock();
+    let mut aspace = new_aspace();
+    aspace.map_alloc(va!(BASE), 0x10000, RW, false).unwrap();
+
+    aspace.unmap(va!(BASE + 0x4000), 0x4000).unwrap();
+    assert_eq!(
+        areas(&aspace),
+        [
+            (BASE, BASE + 0x4000, RW),
+            (BASE + 0x8000, BASE + 0x10000, RW)
+        ]
+    );
+    // No page faults can be handled in the removed part.
+    assert!(!aspace.handle_page_fault(va!(BASE + 0x5000), MappingFlags::READ));
+}
+
+#[test]
+fn test_unmap_across_areas() {
+    let _lock = SERIAL.lock();
+    let mut aspace = new_aspace();
+    aspace.map_alloc(va!(BASE), 0x4000, RW, false).unwrap();
+    aspace
+        .map_alloc(va!(BASE + 0x4000), 0x4000, RO, false)
+        .unwrap();
+    aspace
+        .map_alloc(va!(BASE + 0x8000), 0x4000, RW, false)
+        .unwrap();
+
+    // Shrinks the first area, removes the second one and shrinks the third one.
+    aspace.unmap(va!(BASE + 0x2000), 0x8000).unwrap();
+    assert_eq!(
+        areas(&aspace),
+        [
+            (BASE, BASE + 0x2000, RW),
+            (BASE + 0xa000, BASE + 0xc000, RW)
+        ]
+    );
+    for vaddr in (BASE + 0x2000..BASE + 0xa000).step_by(PAGE_SIZE_4K) {
+        assert!(!aspace.handle_page_fault(va!(vaddr), MappingFlags::READ));
+    }
+
+    // Unmapping a range without any mappings is fine.
+    aspace.unmap(va!(BASE + 0x2000), 0x8000).unwrap();
+    assert_eq!(areas(&aspace).len(), 2);
+
+    // The freed range can be mapped again.
+    aspace
+        .map_alloc(va!(BASE + 0x2000), 0x8000, RO, false)
+        .unwrap();
+    assert_eq!(areas(&aspace).len(), 3);
+}
+
+#[test]
+fn test_protect_partial() {
+    let _lock = SERIAL.lock();
+    let mut aspace = new_aspace();
+    aspace.map_alloc(va!(BASE), 0x10000, RW, false).unwrap();
+
+    aspace.protect(va!(BASE + 0x4000), 0x4000, RO).unwrap();
+    assert_eq!(
+        areas(&aspace),
+        [
+            (BASE, BASE + 0x4000, RW),
+            (BASE + 0x4000, BASE + 0x8000, RO),
+            (BASE + 0x8000, BASE + 0x10000, RW),
+        ]
+    );
+    // Lazy entries are not turned into present mappings.
+    assert_eq!(aspace.mappings().count(), 0);
+    // Page faults are resolved against the new flags.
+    assert!(!aspace.handle_page_fault(va!(BASE + 0x5000), MappingFlags::WRITE));
+
+    // Overlapping the tail of one area and the head of the next one.
+    aspace.protect(va!(BASE + 0x6000), 0x4000, RW).unwrap();
+    assert_eq!(
+        areas(&aspace),
+        [
+            (BASE, BASE + 0x4000, RW),
+            (BASE + 0x4000, BASE + 0x6000, RO),
+            (BASE + 0x6000, BASE + 0x8000, RW),
+            (BASE + 0x8000, BASE + 0x10000, RW),
+        ]
+    );
+}
+
+#[test]
+fn test_unmap_after_protect() {
+    let _lock = SERIAL.lock();
+    let mut aspace = new_aspace();
+    aspace.map_alloc(va!(BASE), 0x10000, RW, false).unwrap();
+    aspace.protect(va!(BASE + 0x4000), 0x8000, RO).unwrap();
+
+    aspace.unmap(va!(BASE + 0x2000), 0x4000).unwrap();
+    assert_eq!(
+        areas(&aspace),
+        [
+            (BASE, BASE + 0x2000, RW),
+            (BASE + 0x6000, BASE + 0xc000, RO),
+            (BASE + 0xc000, BASE + 0x10000, RW),
+        ]
+    );
+
+    aspace.clear();
+    assert!(areas(&aspace).is_empty());
+    assert!(!aspace.handle_page_fault(va!(BASE), MappingFlags::READ));
+}
+
+#[test]
+fn test_discard() {
+    let _lock = SERIAL.lock();
+    let mut aspace = new_aspace();
+    aspace.map_alloc(va!(BASE), 0x4000, RW, false).unwrap();
+    assert!(aspace.is_mapped(va!(BASE), 0x4000));
+    assert!(!aspace.is_mapped(va!(BASE), 0x5000));
+    for i in 0..4 {
+        assert!(aspace.handle_page_fault(va!(BASE + i * PAGE_SIZE_4K), MappingFlags::WRITE));
+    }
+
+    aspace.discard(va!(BASE + 0x1000), 0x2000).unwrap();
+    assert_eq!(
+        areas(&aspace),
+        [
+            (BASE, BASE + 0x1000, RW),
+            (BASE + 0x1000, BASE + 0x3000, RW),
+            (BASE + 0x3000, BASE + 0x4000, RW),
+        ]
+    );
+    assert!(aspace.is_mapped(va!(BASE), 0x4000));
+    // Only the pages outside the discarded range are still present.
+    let mapped: usize = aspace.mappings().map(|range| range.size).sum();
+    assert_eq!(mapped, 0x2000);
+    // The discarded pages are allocated again on demand.
+    assert!(aspace.handle_page_fault(va!(BASE + 0x2000), MappingFlags::WRITE));
+}
+
+#[test]
+fn test_discard_keeps_backend() {
+    let _lock = SERIAL.lock();
+    let mut aspace = new_aspace();
+    aspace.map_alloc(va!(BASE), 0x4000, RW, true).unwrap();
+    aspace.write(va!(BASE + 0x1000), &[0xaa; 8]).unwrap();
+    aspace.discard(va!(BASE + 0x1000), 0x2000).unwrap();
+    // Populated mappings are filled with zero pages right away.
+    let mapped: usize = aspace.mappings().map(|range| range.size).sum();
+    assert_eq!(mapped, 0x4000);
+    let mut buf = [0xff; 8];
+    aspace.read(va!(BASE + 0x1000), &mut buf).unwrap();
+    assert_eq!(buf, [0; 8]);
+    aspace.unmap(va!(BASE), 0x4000).unwrap();
+
+    const HUGE: usize = 0x20_0000;
+    aspace
+        .map_alloc_huge(va!(BASE), 2 * HUGE, RW, false, PageSize::Size2M)
+        .unwrap();
+    assert!(aspace.handle_page_fault(va!(BASE + HUGE), MappingFlags::WRITE));
+    assert_eq!(
+        aspace.discard(va!(BASE + HUGE), 0x1000),
+        Err(AxError::InvalidInput)
+    );
+    aspace.discard(va!(BASE + HUGE), HUGE).unwrap();
+    assert_eq!(aspace.mappings().count(), 0);
+    // The discarded range still uses huge pages.
+    assert!(aspace.handle_page_fault(va!(BASE + HUGE + 0x1000), MappingFlags::WRITE));
+    let (_, _, page_size) = aspace.page_table().query(va!(BASE + HUGE)).unwrap();
+    assert_eq!(page_size, PageSize::Size2M);
+}
+
+#[test]
+fn test_populate() {
+    let _lock = SERIAL.lock();
+    let mut aspace = new_aspace();
+    aspace.map_alloc(va!(BASE), 0x4000, RW, false).unwrap();
+    aspace
+        .map_alloc(va!(BASE + 0x4000), 0x1000, MappingFlags::USER, false)
+        .unwrap();
+    assert_eq!(aspace.mappings().count(), 0);
+
+    aspace.populate(va!(BASE + 0x1000), 0x4000).unwrap();
+    let mapped: usize = aspace.mappings().map(|range| range.size).sum();
+    assert_eq!(mapped, 0x4000);
+    assert!(aspace.page_table().query(va!(BASE)).is_err());
+    aspace.write(va!(BASE + 0x1000), &[1; 0x3000]).unwrap();
+    // Populating present pages again does nothing.
+    let frame = frame_at(&aspace, BASE + 0x1000);
+    aspace.populate(va!(BASE), 0x2000).unwrap();
+    assert_eq!(frame_at(&aspace, BASE + 0x1000), frame);
+
+    assert_eq!(
+        aspace.populate(va!(BASE + 0x4000), 0x2000),
+        Err(AxError::BadAddress)
+    );
+    assert_eq!(
+        aspace.populate(va!(BASE + 0x800), 0x1000),
+        Err(AxError::InvalidInput)
+    );
+}
+
+#[test]
+fn test_layout() {
+    let _lock = SERIAL.lock();
+    let mut aspace = new_aspace();
+    let fixed = *aspace.layout();
+    assert_eq!(fixed.stack_top, aspace.end());
+    assert!(fixed.pie_base < fixed.mmap_base && fixed.mmap_base < fixed.stack_top);
+
+    for _ in 0..16 {
+        aspace.randomize_layout();
+        let layout = aspace.layout();
+        assert!(layout.stack_top <= aspace.end() && layout.stack_top.is_aligned_4k());
+        assert!(layout.mmap_base >= fixed.mmap_base && layout.mmap_base < layout.stack_top);
+        assert!(layout.pie_base >= fixed.pie_base && layout.pie_base < layout.mmap_base);
+        assert!(layout.brk_start(va!(BASE + 1)) >= va!(BASE + PAGE_SIZE_4K));
+    }
+
+    crate::set_aslr_enabled(false);
+    aspace.randomize_layout();
+    let layout = *aspace.layout();
+    crate::set_aslr_enabled(true);
+    assert_eq!(layout.stack_top, fixed.stack_top);
+    assert_eq!(layout.mmap_base, fixed.mmap_base);
+    assert_eq!(layout.pie_base, fixed.pie_base);
+    assert_eq!(layout.brk_start(va!(BASE + 1)), va!(BASE + PAGE_SIZE_4K));
+}
+
+#[test]
+fn test_shared_pages() {
+    let _lock = SERIAL.lock();
+    let mut aspace = new_aspace();
+    let pages = Arc::new(SharedPages::new(4).unwrap());
+    aspace
+        .map_shared(va!(BASE), 0x4000, RW, pages.clone(), 0)
+        .unwrap();
+    for i in 0..4 {
+        assert_eq!(
+            frame_at(&aspace, BASE + i * PAGE_SIZE_4K),
+            pages.frame(i).unwrap()
+        );
+    }
+
+    // The data is visible through the other mappings and the pages.
+    aspace.write(va!(BASE + 0x1ff8), b"shared!!").unwrap();
+    let mut buf = [0u8; 8];
+    assert_eq!(pages.read(0x1ff8, &mut buf), 8);
+    assert_eq!(&buf, b"shared!!");
+
+    // Mapping from an offset.
+    aspace
+        .map_shared(va!(BASE + 0x10000), 0x2000, RO, pages.clone(), 0x2000)
+        .unwrap();
+    assert_eq!(frame_at(&aspace, BASE + 0x10000), pages.frame(2).unwrap());
+    assert_eq!(frame_at(&aspace, BASE + 0x11000), pages.frame(3).unwrap());
+
+    // Beyond the end of the pages.
+    assert!(aspace
+        .map_shared(va!(BASE + 0x20000), 0x2000, RW, pages.clone(), 0x3000)
+        .is_err());
+    assert!(aspace
+        .map_shared(va!(BASE + 0x20000), 0x5000, RW, pages.clone(), 0)
+        .is_err());
+}
+
+#[test]
+fn test_map_alloc_from() {
+    let _lock = SERIAL.lock();
+    let mut aspace = new_aspace();
+    let pages = SharedPages::new(2).unwrap();
+    pages.write(0x1ff8, b"private!");
+    let frame = pages.frame(1).unwrap();
+    aspace.map_alloc_from(va!(BASE), 0x4000, RW, pages).unwrap();
+    assert_eq!(frame_at(&aspace, BASE + 0x1000), frame);
+    let mut buf = [0u8; 8];
+    aspace.read(va!(BASE + 0x1ff8), &mut buf).unwrap();
+    assert_eq!(&buf, b"private!");
+    // The rest is allocated on demand.
+    assert!(aspace.handle_page_fault(va!(BASE + 0x2000), MappingFlags::WRITE));
+
+    // More pages than the mapping.
+    let pages = SharedPages::new(2).unwrap();
+    assert!(aspace
+        .map_alloc_from(va!(BASE + 0x10000), 0x1000, RW, pages)
+        .is_err());
+}
+
+#[test]
+fn test_shared_pages_split_and_copy() {
+    let _lock = SERIAL.lock();
+    let mut aspace = new_aspace();
+    let pages = Arc::new(SharedPages::new(8).unwrap());
+    aspace
+        .map_shared(va!(BASE), 0x6000, RW, pages.clone(), 0x2000)
+        .unwrap();
+
+    // Splits the area into three parts, and removes the first page.
+    aspace.protect(va!(BASE + 0x2000), 0x2000, RO).unwrap();
+    aspace.unmap(va!(BASE), 0x1000).unwrap();
+    assert_eq!(
+        areas(&aspace),
+        [
+            (BASE + 0x1000, BASE + 0x2000, RW),
+            (BASE + 0x2000, BASE + 0x4000, RO),
+            (BASE + 0x4000, BASE + 0x6000, RW),
+        ]
+    );
+
+    // The copied areas are mapped to the same frames as the original ones.
+    let mut child = new_aspace();
+    child.copy_areas_from(&aspace).unwrap();
+    assert_eq!(areas(&child), areas(&aspace));
+    for i in 1..6 {
+        let vaddr = BASE + i * PAGE_SIZE_4K;
+        assert_eq!(frame_at(&aspace, vaddr), pages.frame(i + 2).unwrap());
+        assert_eq!(frame_at(&child, vaddr), pages.frame(i + 2).unwrap());
+    }
+
+    // The frames stay alive as long as they are mapped.
+    drop(aspace);
+    assert_eq!(Arc::strong_count(&pages), 4);
+    child.clear();
+    assert_eq!(Arc::strong_count(&pages), 1);
+}
+
+#[test]
+fn test_huge_page_fault() {
+    let _lock = SERIAL.lock();
+    let mut aspace = new_aspace();
+    const HUGE: usize = 0x20_0000;
+    aspace
+        .map_alloc_huge(va!(BASE), 2 * HUGE, RW, false, PageSize::Size2M)
+        .unwrap();
+    assert!(aspace.handle_page_fault(va!(BASE + 0x1234), MappingFlags::WRITE));
+    let (_, flags, page_size) = aspace.page_table().query(va!(BASE + 0x1234)).unwrap();
+    assert_eq!(page_size, PageSize::Size2M);
+    assert!(flags.contains(MappingFlags::WRITE));
+
+    // A huge page no longer fits after the area is split.
+    aspace.protect(va!(BASE + HUGE), 0x1000, RO).unwrap();
+    assert!(aspace.handle_page_fault(va!(BASE + HUGE + 0x3000), MappingFlags::READ));
+    let (_, flags, page_size) = aspace
+        .page_table()
+        .query(va!(BASE + HUGE + 0x3000))
+        .unwrap();
+    assert_eq!(page_size, PageSize::Size4K);
+    assert!(flags.contains(MappingFlags::WRITE));
+    assert!(aspace.handle_page_fault(va!(BASE + HUGE), MappingFlags::READ));
+    let (_, flags, page_size) = aspace.page_table().query(va!(BASE + HUGE)).unwrap();
+    assert_eq!(page_size, PageSize::Size4K);
+    assert!(!flags.contains(MappingFlags::WRITE));
+}
+
+#[test]
+fn test_huge_page_fault_over_page_table() {
+    let _lock = SERIAL.lock();
+    let mut aspace = new_aspace();
+    const HUGE: usize = 0x20_0000;
+    // Leaves a page table in the 2M slot.
+    aspace.map_alloc(va!(BASE), 0x1000, RW, true).unwrap();
+    aspace.unmap(va!(BASE), 0x1000).unwrap();
+
+    aspace
+        .map_alloc_huge(va!(BASE), HUGE, RW, false, PageSize::Size2M)
+        .unwrap();
+    assert!(aspace.handle_page_fault(va!(BASE + 0x5000), MappingFlags::WRITE));
+    let (_, flags, page_size) = aspace.page_table().query(va!(BASE + 0x5000)).unwrap();
+    assert_eq!(page_size, PageSize::Size4K);
+    assert!(flags.contains(MappingFlags::WRITE));
+    // The other pages of the slot are still filled on demand.
+    assert!(aspace.handle_page_fault(va!(BASE + 0x1f_f000), MappingFlags::READ));
+
+    aspace.unmap(va!(BASE), HUGE).unwrap();
+    assert_eq!(aspace.mappings().count(), 0);
+}
+
+#[test]
+fn test_swap_out_and_in() {
+    let _lock = SERIAL.lock();
+    let mut aspace = new_swap_aspace(16);
+
+    // The pages are aged by the first scan, and swapped out by the second one.
+    assert_eq!(aspace.reclaim(4), 4);
+    assert_eq!(aspace.num_swapped_pages(), 4);
+    assert!(aspace.swap.resident.is_empty());
+    assert_eq!(frame_at(&aspace, BASE).as_usize(), 0);
+
+    for i in 0..4 {
+        let vaddr = va!(BASE + i * PAGE_SIZE_4K);
+        assert!(aspace.handle_page_fault(vaddr, MappingFlags::READ));
+        let mut buf = [0; 8];
+        aspace.read(vaddr, &mut buf).unwrap();
+        assert_eq!(buf, [i as u8 + 1; 8]);
+    }
+    assert_eq!(aspace.num_swapped_pages(), 0);
+    assert_eq!(aspace.swap.resident.len(), 4);
+    SWAP_AREA.lock().take();
+}
+
+#[test]
+fn test_swap_keeps_accessed_pages() {
+    let _lock = SERIAL.lock();
+    let mut aspace = new_swap_aspace(16);
+
+    // Ages all the pages, and swaps out the first one.
+    assert_eq!(aspace.reclaim(1), 1);
+    // The second page is accessed after it is aged.
+    assert!(aspace.handle_page_fault(va!(BASE + 0x1000), MappingFlags::READ));
+    assert_eq!(aspace.reclaim(2), 2);
+    let swapped: Vec<_> = aspace
+        .swap
+        .swapped
+        .keys()
+        .map(|page| page.as_usize())
+        .collect();
+    assert_eq!(swapped, [BASE, BASE + 0x2000, BASE + 0x3000]);
+    assert_ne!(frame_at(&aspace, BASE + 0x1000).as_usize(), 0);
+
+    // Unmapping frees the slots and forgets the pages.
+    aspace.unmap(va!(BASE), 0x4000).unwrap();
+    assert_eq!(aspace.num_swapped_pages(), 0);
+    assert!(aspace.swap.resident.is_empty());
+    SWAP_AREA.lock().take();
+}
+
+#[test]
+fn test_swap_device_full() {
+    let _lock = SERIAL.lock();
+    let mut aspace = new_swap_aspace(2);
+    assert_eq!(aspace.reclaim(4), 2);
+    assert_eq!(aspace.num_swapped_pages(), 2);
+    assert_eq!(aspace.swap.resident.len(), 2);
+
+    // The pages survive a copy, including the swapped-out ones.
+    let mut child = new_aspace();
+    child.copy_areas_from(&aspace).unwrap();
+    for i in 0..4 {
+        let mut buf = [0; 8];
+        child.read(va!(BASE + i * PAGE_SIZE_4K), &mut buf).unwrap();
+        assert_eq!(buf, [i as u8 + 1; 8]);
+    }
+    SWAP_AREA.lock().take();
+}
+
+#[test]
+fn test_no_swap_device() {
+    let _lock = SERIAL.lock();
+    let mut aspace = new_aspace();
+    aspace.map_alloc(va!(BASE), 0x4000, RW, false).unwrap();
+    for i in 0..4 {
+        assert!(aspace.handle_page_fault(va!(BASE + i * PAGE_SIZE_4K), MappingFlags::WRITE));
+    }
+    // Nothing is tracked without a swap device.
+    assert!(aspace.swap.resident.is_empty());
+    assert_eq!(aspace.reclaim(4), 0);
+}
+
+#[test]
+fn test_maps() {
+    let _lock = SERIAL.lock();
+    let mut aspace = new_aspace();
+    // Listed even if the pages are not allocated yet.
+    aspace.map_alloc(va!(BASE), 0x2000, RW, false).unwrap();
+    let pages = Arc::new(SharedPages::new(1).unwrap());
+    aspace
+        .map_shared(
+            va!(BASE + 0x10000),
+            0x1000,
+            RO | MappingFlags::EXECUTE,
+            pages,
+            0,
+        )
+        .unwrap();
+    let stack_top = aspace.layout().stack_top;
+    aspace
+        .map_alloc(stack_top - 0x1000, 0x1000, RW, false)
+        .unwrap();
+
+    let maps = aspace.maps().to_string();
+    assert_eq!(
+        maps.lines().collect::<Vec<_>>(),
+        [
+            "10000000-10002000 rw-p 00000000 00:00 0",
+            "10010000-10011000 r-xs 00000000 00:00 0",
+            "3ffffff000-4000000000 rw-p 00000000 00:00 0                   [stack]",
+        ]
+    );
+
+    aspace.unmap(va!(BASE), 0x1000).unwrap();
+    aspace.protect(va!(BASE + 0x1000), 0x1000, RO).unwrap();
+    assert_eq!(
+        aspace.maps().to_string().lines().next(),
+        Some("10001000-10002000 r--p 00000000 00:00 0")
+    );
+}
+
+#[test]
+fn test_translated_byte_buffer() {
+    let _lock = SERIAL.lock();
+    let mut aspace = new_aspace();
+    aspace.map_alloc(va!(BASE), 0x3000, RW, true).unwrap();
+    aspace
+        .map_alloc(va!(BASE + 0x3000), 0x1000, RW, false)
+        .unwrap();
+
+    let buffers = aspace
+        .translated_byte_buffer(va!(BASE + 0x800), 0x2000)
+        .unwrap();
+    let lens: Vec<_> = buffers.iter().map(|buf| buf.len()).collect();
+    assert_eq!(lens, [0x800, 0x1000, 0x800]);
+    assert!(aspace
+        .translated_byte_buffer(va!(BASE + 0x1000), 0x2000)
+        .is_some());
+
+    // Not longer than the area, but beyond its end.
+    assert!(aspace
+        .translated_byte_buffer(va!(BASE + 0x1000), 0x2001)
+        .is_none());
+    assert!(aspace
+        .translated_byte_buffer(va!(BASE + 0x2fff), usize::MAX)
+        .is_none());
+    // Not populated.
+    assert!(aspace
+        .translated_byte_buffer(va!(BASE + 0x3000), 0x10)
+        .is_none());
+}
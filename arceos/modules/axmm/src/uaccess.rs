@@ -0,0 +1,432 @@
+//! Safe access to user memory.
+//!
+//! User pointers are checked against the memory areas of the address space,
+//! and the pages are populated before accessing them. The copy itself goes
+//! through [`axhal::uaccess::copy_user`], so that a bad pointer results in
+//! [`AxError::BadAddress`] (i.e., `EFAULT`) rather than a kernel panic.
+//!
+//! The address space must be the current one, and is locked during the check
+//! and the copy, so the checked pages cannot be reclaimed in between.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem::{size_of, MaybeUninit};
+use core::ops::DerefMut;
+
+use axerrno::{ax_err, AxError, AxResult};
+use axhal::paging::MappingFlags;
+use axhal::uaccess::copy_user;
+use memory_addr::{MemoryAddr, VirtAddr, VirtAddrRange, PAGE_SIZE_4K};
+
+use crate::AddrSpace;
+
+/// The size of the kernel buffer used by [`read_to_user`] and
+/// [`write_from_user`].
+const KERNEL_BUF_SIZE: usize = 4 * PAGE_SIZE_4K;
+
+/// Maximum number of I/O vectors in a call, like `IOV_MAX` in Linux.
+pub const IOV_MAX: usize = 1024;
+
+impl AddrSpace {
+    /// Checks whether `[start, start + size)` is covered by memory areas that
+    /// allow `access_flags`, and populates the pages that are not mapped yet.
+    ///
+    /// Returns [`AxError::BadAddress`] if the check fails.
+    pub fn check_user_access(
+        &mut self,
+        start: VirtAddr,
+        size: usize,
+        access_flags: MappingFlags,
+    ) -> AxResult {
+        if size == 0 {
+            return Ok(());
+        }
+        if start.as_usize().checked_add(size).is_none() || !self.contains_range(start, size) {
+            return ax_err!(BadAddress);
+        }
+        let end = (start + size).align_up_4k();
+        let addr = start.align_down_4k();
+        // Populating a page may reclaim others, which must not be the ones
+        // already checked.
+        self.swap.pinned = Some(VirtAddrRange::new(addr, end));
+        let res = self.populate_user_range(addr, end, access_flags);
+        self.swap.pinned = None;
+        res
+    }
+
+    fn populate_user_range(
+        &mut self,
+        mut addr: VirtAddr,
+        end: VirtAddr,
+        access_flags: MappingFlags,
+    ) -> AxResult {
+        while addr < end {
+            let Some(area) = self.areas.find(addr) else {
+                return ax_err!(BadAddress);
+            };
+            if !area.flags().contains(access_flags | MappingFlags::USER) {
+                return ax_err!(BadAddress);
+            }
+            let area_end = area.end().min(end);
+            while addr < area_end {
+                let populated = matches!(
+                    self.pt.query(addr),
+                    Ok((_, flags, _)) if flags.contains(access_flags)
+                );
+                if !populated && !self.handle_page_fault(addr, access_flags) {
+                    return ax_err!(BadAddress);
+                }
+                addr += PAGE_SIZE_4K;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Copies `dst.len()` bytes from the user address `src`.
+pub fn copy_from_user(aspace: &mut AddrSpace, dst: &mut [u8], src: VirtAddr) -> AxResult {
+    aspace.check_user_access(src, dst.len(), MappingFlags::READ)?;
+    match unsafe { copy_user(dst.as_mut_ptr(), src.as_ptr(), dst.len()) } {
+        0 => Ok(()),
+        _ => ax_err!(BadAddress),
+    }
+}
+
+/// Copies `src` to the user address `dst`.
+pub fn copy_to_user(aspace: &mut AddrSpace, dst: VirtAddr, src: &[u8]) -> AxResult {
+    aspace.check_user_access(dst, src.len(), MappingFlags::WRITE)?;
+    match unsafe { copy_user(dst.as_mut_ptr(), src.as_ptr(), src.len()) } {
+        0 => Ok(()),
+        _ => ax_err!(BadAddress),
+    }
+}
+
+/// Copies a NUL-terminated string from the user address `src` into `dst`,
+/// including the terminating NUL.
+///
+/// Returns the length of the string (not including the NUL). If there is no
+/// NUL within `dst.len()` bytes, `dst` is filled and `dst.len()` is returned,
+/// like `strncpy_from_user` in Linux.
+pub fn strncpy_from_user(aspace: &mut AddrSpace, dst: &mut [u8], src: VirtAddr) -> AxResult<usize> {
+    let mut copied = 0;
+    while copied < dst.len() {
+        // Never read across a page boundary beyond the NUL, where it may be
+        // unmapped.
+        let addr = src + copied;
+        let len = (dst.len() - copied).min(PAGE_SIZE_4K - addr.align_offset_4k());
+        let chunk = &mut dst[copied..copied + len];
+        copy_from_user(aspace, chunk, addr)?;
+        if let Some(pos) = chunk.iter().position(|&c| c == 0) {
+            return Ok(copied + pos);
+        }
+        copied += len;
+    }
+    Ok(copied)
+}
+
+/// Reads at most `len` bytes into the user buffer at `dst` with `read`, e.g.
+/// for `read(2)`.
+///
+/// The data goes through a kernel buffer of bounded size, in chunks. `read`
+/// fills the given buffer and returns the number of bytes read, and
+/// `aspace` locks the address space, which is not held during `read` as it may
+/// block.
+///
+/// It stops at a short read, and returns the number of bytes read in total. An
+/// error is returned only if nothing has been read.
+pub fn read_to_user<A, R, E>(
+    aspace: impl Fn() -> A,
+    dst: VirtAddr,
+    len: usize,
+    mut read: R,
+) -> Result<usize, E>
+where
+    A: DerefMut<Target = AddrSpace>,
+    R: FnMut(&mut [u8]) -> Result<usize, E>,
+    E: From<AxError>,
+{
+    if len == 0 {
+        return read(&mut []);
+    }
+    if dst.as_usize().checked_add(len).is_none() {
+        return Err(AxError::BadAddress.into());
+    }
+    let mut buf = vec![0u8; len.min(KERNEL_BUF_SIZE)];
+    let mut total = 0;
+    while total < len {
+        let chunk_len = (len - total).min(buf.len());
+        let res = read(&mut buf[..chunk_len]).and_then(|n| {
+            copy_to_user(&mut aspace(), dst + total, &buf[..n])?;
+            Ok(n)
+        });
+        match res {
+            Ok(n) => {
+                total += n;
+                if n < chunk_len {
+                    break;
+                }
+            }
+            Err(err) if total == 0 => return Err(err),
+            Err(_) => break,
+        }
+    }
+    Ok(total)
+}
+
+/// Writes at most `len` bytes from the user buffer at `src` with `write`, e.g.
+/// for `write(2)`.
+///
+/// Like [`read_to_user`], the data goes through a kernel buffer of bounded
+/// size, in chunks, and the address space is not locked during `write`.
+///
+/// It stops at a short write, and returns the number of bytes written in
+/// total. An error is returned only if nothing has been written.
+pub fn write_from_user<A, W, E>(
+    aspace: impl Fn() -> A,
+    src: VirtAddr,
+    len: usize,
+    mut write: W,
+) -> Result<usize, E>
+where
+    A: DerefMut<Target = AddrSpace>,
+    W: FnMut(&[u8]) -> Result<usize, E>,
+    E: From<AxError>,
+{
+    if len == 0 {
+        return write(&[]);
+    }
+    if src.as_usize().checked_add(len).is_none() {
+        return Err(AxError::BadAddress.into());
+    }
+    let mut buf = vec![0u8; len.min(KERNEL_BUF_SIZE)];
+    let mut total = 0;
+    while total < len {
+        let chunk_len = (len - total).min(buf.len());
+        let res = copy_from_user(&mut aspace(), &mut buf[..chunk_len], src + total)
+            .map_err(E::from)
+            .and_then(|_| write(&buf[..chunk_len]));
+        match res {
+            Ok(n) => {
+                total += n;
+                if n < chunk_len {
+                    break;
+                }
+            }
+            Err(err) if total == 0 => return Err(err),
+            Err(_) => break,
+        }
+    }
+    Ok(total)
+}
+
+/// An I/O vector in user space, with the layout of `struct iovec`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct UserIoVec {
+    /// The user address of the buffer.
+    pub base: usize,
+    /// The length of the buffer.
+    pub len: usize,
+}
+
+/// Copies the buffers of the `iovcnt` I/O vectors at `iov` into one kernel
+/// buffer, e.g. for `writev(2)`.
+///
+/// Returns [`AxError::InvalidInput`] if there are more than [`IOV_MAX`]
+/// vectors or the total length overflows.
+pub fn read_iovecs_from_user(
+    aspace: &mut AddrSpace,
+    iov: UserPtr<UserIoVec>,
+    iovcnt: usize,
+) -> AxResult<Vec<u8>> {
+    if iovcnt > IOV_MAX {
+        return ax_err!(InvalidInput, "too many I/O vectors");
+    }
+    let iovs = UserSlice::new(iov, iovcnt).read_to_vec(aspace)?;
+    let mut total = 0usize;
+    for iov in iovs.iter() {
+        total = total
+            .checked_add(iov.len)
+            .filter(|&total| total <= isize::MAX as usize)
+            .ok_or(AxError::InvalidInput)?;
+        // Check before allocating, as the length comes from the user.
+        aspace.check_user_access(VirtAddr::from(iov.base), iov.len, MappingFlags::READ)?;
+    }
+    let mut buf = Vec::new();
+    buf.try_reserve_exact(total)
+        .map_err(|_| AxError::NoMemory)?;
+    for iov in iovs {
+        let start = buf.len();
+        buf.resize(start + iov.len, 0);
+        copy_from_user(aspace, &mut buf[start..], VirtAddr::from(iov.base))?;
+    }
+    Ok(buf)
+}
+
+/// A pointer to a value of type `T` in user space.
+#[repr(transparent)]
+pub struct UserPtr<T> {
+    addr: usize,
+    _marker: PhantomData<*mut T>,
+}
+
+impl<T> Clone for UserPtr<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for UserPtr<T> {}
+
+impl<T> fmt::Debug for UserPtr<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "UserPtr({:#x})", self.addr)
+    }
+}
+
+impl<T> From<usize> for UserPtr<T> {
+    fn from(addr: usize) -> Self {
+        Self {
+            addr,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> From<*mut T> for UserPtr<T> {
+    fn from(ptr: *mut T) -> Self {
+        Self::from(ptr as usize)
+    }
+}
+
+impl<T> From<*const T> for UserPtr<T> {
+    fn from(ptr: *const T) -> Self {
+        Self::from(ptr as usize)
+    }
+}
+
+impl<T> UserPtr<T> {
+    /// Returns the user address.
+    pub const fn address(&self) -> VirtAddr {
+        VirtAddr::from_usize(self.addr)
+    }
+
+    /// Whether the pointer is null.
+    pub const fn is_null(&self) -> bool {
+        self.addr == 0
+    }
+
+    /// Returns a pointer to the `idx`-th value after this one.
+    ///
+    /// Returns [`AxError::BadAddress`] if the address overflows.
+    pub fn add(&self, idx: usize) -> AxResult<Self> {
+        idx.checked_mul(size_of::<T>())
+            .and_then(|offset| self.addr.checked_add(offset))
+            .map(Self::from)
+            .ok_or(AxError::BadAddress)
+    }
+
+    fn check_align(&self) -> AxResult {
+        if self.addr % core::mem::align_of::<T>() != 0 {
+            return ax_err!(BadAddress, "unaligned user pointer");
+        }
+        Ok(())
+    }
+}
+
+impl<T: Copy> UserPtr<T> {
+    /// Reads the value from user space.
+    pub fn read(&self, aspace: &mut AddrSpace) -> AxResult<T> {
+        self.check_align()?;
+        let mut value = MaybeUninit::<T>::uninit();
+        let buf = unsafe {
+            core::slice::from_raw_parts_mut(value.as_mut_ptr() as *mut u8, size_of::<T>())
+        };
+        copy_from_user(aspace, buf, self.address())?;
+        Ok(unsafe { value.assume_init() })
+    }
+
+    /// Writes the value to user space.
+    pub fn write(&self, aspace: &mut AddrSpace, value: T) -> AxResult {
+        self.check_align()?;
+        let buf =
+            unsafe { core::slice::from_raw_parts(&value as *const T as *const u8, size_of::<T>()) };
+        copy_to_user(aspace, self.address(), buf)
+    }
+}
+
+/// A slice of `len` values of type `T` in user space.
+#[derive(Debug, Clone, Copy)]
+pub struct UserSlice<T> {
+    ptr: UserPtr<T>,
+    len: usize,
+}
+
+impl<T> UserSlice<T> {
+    /// Creates a user slice from the address and the number of values.
+    pub fn new(ptr: impl Into<UserPtr<T>>, len: usize) -> Self {
+        Self {
+            ptr: ptr.into(),
+            len,
+        }
+    }
+
+    /// Returns the user address of the first value.
+    pub const fn address(&self) -> VirtAddr {
+        self.ptr.address()
+    }
+
+    /// Returns the number of values.
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the slice is empty.
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn size(&self) -> AxResult<usize> {
+        self.len
+            .checked_mul(size_of::<T>())
+            .ok_or(AxError::BadAddress)
+    }
+}
+
+impl<T: Copy> UserSlice<T> {
+    /// Reads the values from user space into `dst`, whose length must be the
+    /// same as the slice.
+    pub fn read(&self, aspace: &mut AddrSpace, dst: &mut [T]) -> AxResult {
+        assert_eq!(dst.len(), self.len);
+        self.ptr.check_align()?;
+        let size = self.size()?;
+        let buf = unsafe { core::slice::from_raw_parts_mut(dst.as_mut_ptr() as *mut u8, size) };
+        copy_from_user(aspace, buf, self.address())
+    }
+
+    /// Reads the values from user space into a new vector.
+    pub fn read_to_vec(&self, aspace: &mut AddrSpace) -> AxResult<Vec<T>> {
+        self.ptr.check_align()?;
+        let size = self.size()?;
+        // Check before allocating, as the length comes from the user.
+        aspace.check_user_access(self.address(), size, MappingFlags::READ)?;
+        let mut vec = Vec::<T>::with_capacity(self.len);
+        if unsafe { copy_user(vec.as_mut_ptr() as *mut u8, self.address().as_ptr(), size) } != 0 {
+            return ax_err!(BadAddress);
+        }
+        unsafe { vec.set_len(self.len) };
+        Ok(vec)
+    }
+
+    /// Writes the values in `src` to user space, whose length must be the same
+    /// as the slice.
+    pub fn write(&self, aspace: &mut AddrSpace, src: &[T]) -> AxResult {
+        assert_eq!(src.len(), self.len);
+        self.ptr.check_align()?;
+        let size = self.size()?;
+        let buf = unsafe { core::slice::from_raw_parts(src.as_ptr() as *const u8, size) };
+        copy_to_user(aspace, self.address(), buf)
+    }
+}
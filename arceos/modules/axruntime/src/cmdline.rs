@@ -0,0 +1,108 @@
+//! Kernel command line, i.e., the `bootargs` property of the `/chosen` node in
+//! the device tree passed by the bootloader.
+//!
+//! Supported options:
+//!
+//! - `norandmaps`: disables address space layout randomization of user address
+//!   spaces, like in Linux.
+
+use axhal::mem::phys_to_virt;
+
+const FDT_MAGIC: u32 = 0xd00d_feed;
+
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_NOP: u32 = 4;
+
+/// A device tree blob in memory.
+struct Fdt<'a>(&'a [u8]);
+
+impl<'a> Fdt<'a> {
+    fn u32_at(&self, offset: usize) -> Option<u32> {
+        let bytes = self.0.get(offset..offset.checked_add(4)?)?;
+        Some(u32::from_be_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn str_at(&self, offset: usize) -> Option<&'a str> {
+        let data: &'a [u8] = self.0;
+        let bytes = data.get(offset..)?;
+        let len = bytes.iter().position(|&c| c == 0)?;
+        core::str::from_utf8(&bytes[..len]).ok()
+    }
+
+    /// Returns the value of the property `name` of the node `/node`.
+    fn property(&self, node: &str, name: &str) -> Option<&'a [u8]> {
+        let data: &'a [u8] = self.0;
+        let off_struct = self.u32_at(8)? as usize;
+        let off_strings = self.u32_at(12)? as usize;
+        let mut pos = off_struct;
+        let mut depth = 0;
+        let mut in_node = false;
+        loop {
+            let token = self.u32_at(pos)?;
+            pos += 4;
+            match token {
+                FDT_BEGIN_NODE => {
+                    let node_name = self.str_at(pos)?;
+                    pos = (pos + node_name.len() + 1).next_multiple_of(4);
+                    depth += 1;
+                    if depth == 2 {
+                        // Ignore the unit address.
+                        in_node = node_name.split('@').next() == Some(node);
+                    }
+                }
+                FDT_END_NODE => depth -= 1,
+                FDT_PROP => {
+                    let len = self.u32_at(pos)? as usize;
+                    let name_off = self.u32_at(pos + 4)? as usize;
+                    pos += 8;
+                    if in_node && depth == 2 && self.str_at(off_strings + name_off)? == name {
+                        return data.get(pos..pos + len);
+                    }
+                    pos = (pos + len).next_multiple_of(4);
+                }
+                FDT_NOP => {}
+                _ => return None,
+            }
+        }
+    }
+}
+
+/// Returns the kernel command line in the device tree at the physical address
+/// `dtb`, if there is one.
+fn bootargs(dtb: usize) -> Option<&'static str> {
+    if dtb == 0 {
+        return None;
+    }
+    let header = phys_to_virt(dtb.into()).as_ptr() as *const u32;
+    if u32::from_be(unsafe { header.read_unaligned() }) != FDT_MAGIC {
+        return None;
+    }
+    let total_size = u32::from_be(unsafe { header.add(1).read_unaligned() }) as usize;
+    let fdt = Fdt(unsafe { core::slice::from_raw_parts(header as *const u8, total_size) });
+    let value = fdt.property("chosen", "bootargs")?;
+    let value = value.strip_suffix(&[0]).unwrap_or(value);
+    core::str::from_utf8(value).ok()
+}
+
+/// Applies the options of the kernel command line in the device tree at the
+/// physical address `dtb`.
+///
+/// It must be called before the memory of the device tree is reused, i.e.,
+/// before the global allocator is initialized.
+pub(crate) fn init(dtb: usize) {
+    let Some(cmdline) = bootargs(dtb) else {
+        return;
+    };
+    info!("Kernel command line: {}", cmdline);
+    for option in cmdline.split_ascii_whitespace() {
+        match option {
+            "norandmaps" => {
+                #[cfg(feature = "paging")]
+                axmm::set_aslr_enabled(false);
+            }
+            _ => debug!("Unknown kernel option: {}", option),
+        }
+    }
+}
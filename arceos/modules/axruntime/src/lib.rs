@@ -13,6 +13,7 @@
 //! - `fs`: Enable filesystem support.
 //! - `net`: Enable networking support.
 //! - `display`: Enable graphics support.
+//! - `swap`: Enable swapping user pages to a block device.
 //!
 //! All the features are optional and disabled by default.
 
@@ -25,9 +26,13 @@ extern crate axlog;
 #[cfg(all(target_os = "none", not(test)))]
 mod lang_items;
 
+mod cmdline;
 #[cfg(feature = "smp")]
 mod mp;
 
+#[cfg(feature = "swap")]
+mod swap;
+
 #[cfg(feature = "smp")]
 pub use self::mp::rust_main_secondary;
 
@@ -129,6 +134,7 @@ pub extern "C" fn rust_main(cpu_id: usize, dtb: usize) -> ! {
     axlog::set_max_level(option_env!("AX_LOG").unwrap_or("")); // no effect if set `log-level-*` features
     info!("Logging is enabled.");
     info!("Primary CPU {} started, dtb = {:#x}.", cpu_id, dtb);
+    self::cmdline::init(dtb);
 
     info!("Found physcial memory regions:");
     for r in axhal::mem::memory_regions() {
@@ -153,10 +159,13 @@ pub extern "C" fn rust_main(cpu_id: usize, dtb: usize) -> ! {
     #[cfg(feature = "multitask")]
     axtask::init_scheduler();
 
-    #[cfg(any(feature = "fs", feature = "net", feature = "display"))]
+    #[cfg(any(feature = "fs", feature = "net", feature = "display", feature = "swap"))]
     {
-        #[allow(unused_variables)]
-        let all_devices = axdriver::init_drivers();
+        #[allow(unused_variables, unused_mut)]
+        let mut all_devices = axdriver::init_drivers();
+
+        #[cfg(feature = "swap")]
+        self::swap::init_swap(&mut all_devices.block);
 
         #[cfg(feature = "fs")]
         axfs::init_filesystems(all_devices.block);
@@ -283,6 +292,8 @@ fn init_interrupt() {
 
     axhal::irq::register_handler(TIMER_IRQ_NUM, || {
         update_timer();
+        // The jitter of timer interrupts feeds the random number generator.
+        axhal::random::add_entropy(axhal::time::current_ticks());
         #[cfg(feature = "multitask")]
         axtask::on_timer_tick();
     });
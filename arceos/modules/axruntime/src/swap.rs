@@ -0,0 +1,61 @@
+extern crate alloc;
+
+use alloc::boxed::Box;
+
+use axdriver::prelude::*;
+use axdriver::AxDeviceContainer;
+use axerrno::{AxError, AxResult};
+use axhal::mem::PAGE_SIZE_4K;
+use axmm::SwapDevice;
+
+/// A block device used as the swap area.
+struct SwapDisk(AxBlockDevice);
+
+impl SwapDisk {
+    fn first_block(&self, slot: usize) -> u64 {
+        (slot * PAGE_SIZE_4K / self.0.block_size()) as u64
+    }
+}
+
+impl SwapDevice for SwapDisk {
+    fn num_slots(&self) -> usize {
+        self.0.num_blocks() as usize * self.0.block_size() / PAGE_SIZE_4K
+    }
+
+    fn read_page(&mut self, slot: usize, buf: &mut [u8]) -> AxResult {
+        let first = self.first_block(slot);
+        let block_size = self.0.block_size();
+        for (i, block) in buf.chunks_mut(block_size).enumerate() {
+            self.0
+                .read_block(first + i as u64, block)
+                .map_err(|_| AxError::Io)?;
+        }
+        Ok(())
+    }
+
+    fn write_page(&mut self, slot: usize, buf: &[u8]) -> AxResult {
+        let first = self.first_block(slot);
+        let block_size = self.0.block_size();
+        for (i, block) in buf.chunks(block_size).enumerate() {
+            self.0
+                .write_block(first + i as u64, block)
+                .map_err(|_| AxError::Io)?;
+        }
+        Ok(())
+    }
+}
+
+/// Takes the last block device as the swap area.
+///
+/// The first block device is left for the file system if `fs` is enabled.
+pub(crate) fn init_swap(blk_devs: &mut AxDeviceContainer<AxBlockDevice>) {
+    info!("Initialize swap area...");
+    let min_devs = if cfg!(feature = "fs") { 2 } else { 1 };
+    if blk_devs.len() < min_devs {
+        warn!("  no block device for swap");
+        return;
+    }
+    let dev = blk_devs.take_last().unwrap();
+    info!("  use block device: {:?}", dev.device_name());
+    axmm::init_swap(Box::new(SwapDisk(dev)));
+}
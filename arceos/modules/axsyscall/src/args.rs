@@ -0,0 +1,82 @@
+use axhal::arch::TrapFrame;
+
+/// A type that can be decoded from a raw syscall argument register.
+pub trait SyscallArg: Sized {
+    /// Decodes the value from the raw register value.
+    fn from_raw(raw: usize) -> Self;
+}
+
+macro_rules! impl_int_arg {
+    ($($ty:ty),*) => {
+        $(impl SyscallArg for $ty {
+            #[inline]
+            fn from_raw(raw: usize) -> Self {
+                raw as _
+            }
+        })*
+    };
+}
+
+impl_int_arg!(usize, isize, u64, i64, u32, i32, u16, i16, u8, i8);
+
+impl SyscallArg for bool {
+    #[inline]
+    fn from_raw(raw: usize) -> Self {
+        raw != 0
+    }
+}
+
+impl<T> SyscallArg for *const T {
+    #[inline]
+    fn from_raw(raw: usize) -> Self {
+        raw as _
+    }
+}
+
+impl<T> SyscallArg for *mut T {
+    #[inline]
+    fn from_raw(raw: usize) -> Self {
+        raw as _
+    }
+}
+
+/// The six raw arguments of a syscall.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyscallArgs([usize; 6]);
+
+impl SyscallArgs {
+    /// Creates the arguments from raw values.
+    pub const fn new(raw: [usize; 6]) -> Self {
+        Self(raw)
+    }
+
+    /// Takes the arguments from the registers saved in the trap frame.
+    pub const fn from_trap_frame(tf: &TrapFrame) -> Self {
+        Self([
+            tf.arg0(),
+            tf.arg1(),
+            tf.arg2(),
+            tf.arg3(),
+            tf.arg4(),
+            tf.arg5(),
+        ])
+    }
+
+    /// Returns the raw value of the `idx`-th argument.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is not less than 6.
+    pub const fn raw(&self, idx: usize) -> usize {
+        self.0[idx]
+    }
+
+    /// Decodes the `idx`-th argument as type `T`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is not less than 6.
+    pub fn get<T: SyscallArg>(&self, idx: usize) -> T {
+        T::from_raw(self.0[idx])
+    }
+}
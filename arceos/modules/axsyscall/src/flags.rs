@@ -0,0 +1,139 @@
+//! Names of flags and constants in syscall arguments, for tracing.
+
+/// A table of names and values of flags or constants.
+pub type NameTable = &'static [(&'static str, usize)];
+
+pub const OPEN_FLAGS: NameTable = &[
+    ("O_RDONLY", 0),
+    ("O_WRONLY", 0o1),
+    ("O_RDWR", 0o2),
+    ("O_CREAT", 0o100),
+    ("O_EXCL", 0o200),
+    ("O_NOCTTY", 0o400),
+    ("O_TRUNC", 0o1000),
+    ("O_APPEND", 0o2000),
+    ("O_NONBLOCK", 0o4000),
+    (
+        "O_DIRECTORY",
+        if cfg!(target_arch = "aarch64") {
+            0o40000
+        } else {
+            0o200000
+        },
+    ),
+    ("O_CLOEXEC", 0o2000000),
+];
+
+pub const AT_FLAGS: NameTable = &[
+    ("AT_SYMLINK_NOFOLLOW", 0x100),
+    ("AT_REMOVEDIR", 0x200),
+    ("AT_SYMLINK_FOLLOW", 0x400),
+    ("AT_EMPTY_PATH", 0x1000),
+];
+
+pub const RENAME_FLAGS: NameTable = &[
+    ("RENAME_NOREPLACE", 0x1),
+    ("RENAME_EXCHANGE", 0x2),
+    ("RENAME_WHITEOUT", 0x4),
+];
+
+pub const PROT_FLAGS: NameTable = &[
+    ("PROT_NONE", 0),
+    ("PROT_READ", 0x1),
+    ("PROT_WRITE", 0x2),
+    ("PROT_EXEC", 0x4),
+];
+
+pub const MAP_FLAGS: NameTable = &[
+    ("MAP_SHARED", 0x1),
+    ("MAP_PRIVATE", 0x2),
+    ("MAP_FIXED", 0x10),
+    ("MAP_ANONYMOUS", 0x20),
+    ("MAP_NORESERVE", 0x4000),
+    ("MAP_POPULATE", 0x8000),
+    ("MAP_STACK", 0x20000),
+    ("MAP_HUGETLB", 0x40000),
+    ("MAP_FIXED_NOREPLACE", 0x100000),
+];
+
+pub const CLONE_FLAGS: NameTable = &[
+    ("CLONE_VM", 0x100),
+    ("CLONE_FS", 0x200),
+    ("CLONE_FILES", 0x400),
+    ("CLONE_SIGHAND", 0x800),
+    ("CLONE_PIDFD", 0x1000),
+    ("CLONE_VFORK", 0x4000),
+    ("CLONE_PARENT", 0x8000),
+    ("CLONE_THREAD", 0x10000),
+    ("CLONE_SYSVSEM", 0x40000),
+    ("CLONE_SETTLS", 0x80000),
+    ("CLONE_PARENT_SETTID", 0x100000),
+    ("CLONE_CHILD_CLEARTID", 0x200000),
+    ("CLONE_CHILD_SETTID", 0x1000000),
+];
+
+pub const WAIT_OPTIONS: NameTable = &[("WNOHANG", 0x1), ("WUNTRACED", 0x2), ("WCONTINUED", 0x8)];
+
+pub const MFD_FLAGS: NameTable = &[("MFD_CLOEXEC", 0x1), ("MFD_ALLOW_SEALING", 0x2)];
+
+pub const IPC_FLAGS: NameTable = &[("IPC_CREAT", 0o1000), ("IPC_EXCL", 0o2000)];
+
+pub const IPC_CMDS: NameTable = &[("IPC_RMID", 0), ("IPC_SET", 1), ("IPC_STAT", 2)];
+
+pub const SEEK_WHENCE: NameTable = &[("SEEK_SET", 0), ("SEEK_CUR", 1), ("SEEK_END", 2)];
+
+pub const SIGPROCMASK_HOW: NameTable = &[("SIG_BLOCK", 0), ("SIG_UNBLOCK", 1), ("SIG_SETMASK", 2)];
+
+pub const MADVISE_ADVICE: NameTable = &[
+    ("MADV_NORMAL", 0),
+    ("MADV_RANDOM", 1),
+    ("MADV_SEQUENTIAL", 2),
+    ("MADV_WILLNEED", 3),
+    ("MADV_DONTNEED", 4),
+    ("MADV_FREE", 8),
+];
+
+pub const CLOCK_IDS: NameTable = &[
+    ("CLOCK_REALTIME", 0),
+    ("CLOCK_MONOTONIC", 1),
+    ("CLOCK_PROCESS_CPUTIME_ID", 2),
+    ("CLOCK_THREAD_CPUTIME_ID", 3),
+    ("CLOCK_MONOTONIC_RAW", 4),
+    ("CLOCK_REALTIME_COARSE", 5),
+    ("CLOCK_MONOTONIC_COARSE", 6),
+    ("CLOCK_BOOTTIME", 7),
+];
+
+pub const SIGNALS: NameTable = &[
+    ("SIGHUP", 1),
+    ("SIGINT", 2),
+    ("SIGQUIT", 3),
+    ("SIGILL", 4),
+    ("SIGTRAP", 5),
+    ("SIGABRT", 6),
+    ("SIGBUS", 7),
+    ("SIGFPE", 8),
+    ("SIGKILL", 9),
+    ("SIGUSR1", 10),
+    ("SIGSEGV", 11),
+    ("SIGUSR2", 12),
+    ("SIGPIPE", 13),
+    ("SIGALRM", 14),
+    ("SIGTERM", 15),
+    ("SIGSTKFLT", 16),
+    ("SIGCHLD", 17),
+    ("SIGCONT", 18),
+    ("SIGSTOP", 19),
+    ("SIGTSTP", 20),
+    ("SIGTTIN", 21),
+    ("SIGTTOU", 22),
+    ("SIGURG", 23),
+    ("SIGXCPU", 24),
+    ("SIGXFSZ", 25),
+    ("SIGVTALRM", 26),
+    ("SIGPROF", 27),
+    ("SIGWINCH", 28),
+    ("SIGIO", 29),
+    ("SIGPWR", 30),
+    ("SIGSYS", 31),
+];
@@ -0,0 +1,124 @@
+//! [ArceOS](https://github.com/arceos-org/arceos) syscall dispatcher for
+//! monolithic kernels.
+//!
+//! It provides:
+//!
+//! - [`Sysno`]: the Linux syscall numbers of the target architecture.
+//!   riscv64 and aarch64 share the `asm-generic` table, x86_64 has its own.
+//! - [`SyscallArgs`]: the six raw arguments taken from a [`TrapFrame`], which
+//!   can be decoded into typed values with [`SyscallArgs::get`].
+//! - [`syscall_body!`]: converts a function body that returns a
+//!   [`LinuxResult`](axerrno::LinuxResult) into a syscall return value.
+//! - [`dispatch`]: looks up the syscall number and calls the handler with
+//!   the decoded arguments.
+//! - An strace-like tracer, which prints every syscall with its decoded
+//!   arguments, result and duration, and collects per-syscall statistics
+//!   (see [`set_trace_enabled`] and [`print_summary`]).
+//!
+//! A kernel usually registers its syscall handler like this:
+//!
+//! ```ignore
+//! use axsyscall::{SyscallArgs, Sysno};
+//!
+//! #[register_trap_handler(SYSCALL)]
+//! fn handle_syscall(tf: &TrapFrame, syscall_num: usize) -> isize {
+//!     axsyscall::dispatch(tf, syscall_num, |sysno, args| match sysno {
+//!         Sysno::write => sys_write(args.get(0), args.get(1), args.get(2)),
+//!         Sysno::exit => axtask::exit(args.get(0)),
+//!         _ => -LinuxError::ENOSYS.code() as _,
+//!     })
+//! }
+//! ```
+//!
+//! To display strings and structures that syscall arguments point to, the
+//! kernel registers a function that reads user memory:
+//!
+//! ```ignore
+//! #[register_user_reader(USER_READER)]
+//! fn read_user(addr: usize, buf: &mut [u8]) -> bool {
+//!     let curr = axtask::current();
+//!     copy_from_user(&mut curr.task_ext().aspace.lock(), buf, addr.into()).is_ok()
+//! }
+//! ```
+//!
+//! # Cargo Features
+//!
+//! - `strace`: Enable syscall tracing at boot. It can also be enabled at
+//!   runtime with [`set_trace_enabled`].
+
+#![cfg_attr(not(test), no_std)]
+
+#[macro_use]
+extern crate log;
+extern crate alloc;
+
+mod args;
+mod flags;
+mod sysno;
+mod trace;
+
+#[cfg(test)]
+mod tests;
+
+use axerrno::LinuxError;
+use axhal::arch::TrapFrame;
+
+pub use self::args::{SyscallArg, SyscallArgs};
+pub use self::flags::NameTable;
+pub use self::sysno::{ArgKind, StructKind, Sysno};
+pub use self::trace::{print_summary, set_trace_enabled, trace_enabled, USER_READER};
+
+pub use linkme::distributed_slice as register_user_reader;
+
+#[doc(hidden)]
+pub use log as __log;
+
+/// Macro to generate syscall body
+///
+/// It will receive a function which return Result<_, LinuxError> and convert it to
+/// the type which is specified by the caller.
+#[macro_export]
+macro_rules! syscall_body {
+    ($fn: ident, $($stmt: tt)*) => {{
+        #[allow(clippy::redundant_closure_call)]
+        let res = (|| -> axerrno::LinuxResult<_> { $($stmt)* })();
+        match res {
+            Ok(_) | Err(axerrno::LinuxError::EAGAIN) => {
+                $crate::__log::debug!(concat!(stringify!($fn), " => {:?}"), res)
+            }
+            Err(_) => $crate::__log::info!(concat!(stringify!($fn), " => {:?}"), res),
+        }
+        match res {
+            Ok(v) => v as _,
+            Err(e) => {
+                -e.code() as _
+            }
+        }
+    }};
+}
+
+/// Decodes the syscall `syscall_num` with arguments from `tf`, and calls
+/// `handler` to handle it.
+///
+/// Unknown syscall numbers are rejected with `ENOSYS` without calling the
+/// handler. If tracing is enabled, the syscall is printed along with its
+/// result and recorded in the statistics.
+pub fn dispatch<F>(tf: &TrapFrame, syscall_num: usize, handler: F) -> isize
+where
+    F: FnOnce(Sysno, SyscallArgs) -> isize,
+{
+    let Some(sysno) = Sysno::new(syscall_num) else {
+        warn!("unknown syscall: {}", syscall_num);
+        return -LinuxError::ENOSYS.code() as _;
+    };
+    let args = SyscallArgs::from_trap_frame(tf);
+    if !trace_enabled() {
+        return handler(sysno, args);
+    }
+    trace::enter(sysno, &args);
+    let start = axhal::time::monotonic_time_nanos();
+    let ret = handler(sysno, args);
+    let nanos = axhal::time::monotonic_time_nanos() - start;
+    trace::exit(sysno, &args, ret, nanos);
+    ret
+}
@@ -0,0 +1,201 @@
+use crate::flags::*;
+
+/// How a syscall argument is displayed when tracing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgKind {
+    /// A signed integer, such as a file descriptor or a PID.
+    Int,
+    /// An unsigned integer, such as a length.
+    UInt,
+    /// Flags or other bit patterns, in hexadecimal.
+    Hex,
+    /// A user pointer.
+    Ptr,
+    /// A directory file descriptor, which may be `AT_FDCWD`.
+    Dirfd,
+    /// A pointer to a NUL-terminated string.
+    Str,
+    /// A pointer to a buffer whose length is the next argument, or the return
+    /// value if it is smaller.
+    Buf,
+    /// Bit flags, displayed as the names of the bits set.
+    Flags(NameTable),
+    /// One of the named constants.
+    Enum(NameTable),
+    /// A pointer to a structure.
+    Struct(StructKind),
+}
+
+/// The structure that a syscall argument points to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StructKind {
+    /// `struct timespec`.
+    Timespec,
+    /// A signal set (`sigset_t`).
+    Sigset,
+    /// An `int`.
+    I32,
+    /// An array of two `int`s, e.g., the file descriptors of a pipe.
+    I32Pair,
+}
+
+macro_rules! syscall_table {
+    ($($name:ident = $generic:literal, $x86_64:literal ($($arg:ident: $kind:expr),*);)*) => {
+        /// A Linux syscall of the target architecture.
+        #[allow(non_camel_case_types)]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub enum Sysno {
+            $(
+                #[doc = concat!("`", stringify!($name), "`")]
+                $name,
+            )*
+        }
+
+        impl Sysno {
+            /// All syscalls in the table.
+            pub const ALL: &'static [Sysno] = &[$(Self::$name),*];
+
+            /// Looks up the syscall with the number `num`.
+            pub const fn new(num: usize) -> Option<Self> {
+                if cfg!(target_arch = "x86_64") {
+                    Self::from_x86_64(num)
+                } else {
+                    Self::from_generic(num)
+                }
+            }
+
+            /// Returns the syscall number.
+            pub const fn number(self) -> usize {
+                if cfg!(target_arch = "x86_64") {
+                    self.x86_64_number()
+                } else {
+                    self.generic_number()
+                }
+            }
+
+            /// Looks up the syscall with the number `num` in the `asm-generic`
+            /// table.
+            const fn from_generic(num: usize) -> Option<Self> {
+                match num {
+                    $($generic => Some(Self::$name),)*
+                    _ => None,
+                }
+            }
+
+            /// Looks up the syscall with the number `num` in the x86_64 table.
+            const fn from_x86_64(num: usize) -> Option<Self> {
+                match num {
+                    $($x86_64 => Some(Self::$name),)*
+                    _ => None,
+                }
+            }
+
+            const fn generic_number(self) -> usize {
+                match self {
+                    $(Self::$name => $generic,)*
+                }
+            }
+
+            const fn x86_64_number(self) -> usize {
+                match self {
+                    $(Self::$name => $x86_64,)*
+                }
+            }
+
+            /// Returns the syscall name.
+            pub const fn name(self) -> &'static str {
+                match self {
+                    $(Self::$name => stringify!($name),)*
+                }
+            }
+
+            /// Returns the names and kinds of the syscall arguments.
+            pub const fn args(self) -> &'static [(&'static str, ArgKind)] {
+                use ArgKind::*;
+                use StructKind::*;
+                match self {
+                    $(Self::$name => &[$((stringify!($arg), $kind)),*],)*
+                }
+            }
+        }
+    };
+}
+
+// name = riscv64 & aarch64 (asm-generic), x86_64 (arguments);
+//
+// Note that x86_64 passes the last two arguments of `clone` in the reverse
+// order (`child_tid`, `tls`).
+syscall_table! {
+    getcwd = 17, 79 (buf: Ptr, size: UInt);
+    dup = 23, 32 (oldfd: Int);
+    dup3 = 24, 292 (oldfd: Int, newfd: Int, flags: Flags(OPEN_FLAGS));
+    fcntl = 25, 72 (fd: Int, cmd: Int, arg: Hex);
+    ioctl = 29, 16 (fd: Int, request: Hex, arg: Ptr);
+    mkdirat = 34, 258 (dirfd: Dirfd, pathname: Str, mode: Hex);
+    unlinkat = 35, 263 (dirfd: Dirfd, pathname: Str, flags: Flags(AT_FLAGS));
+    ftruncate = 46, 77 (fd: Int, length: Int);
+    faccessat = 48, 269 (dirfd: Dirfd, pathname: Str, mode: Hex);
+    chdir = 49, 80 (path: Str);
+    fchdir = 50, 81 (fd: Int);
+    openat = 56, 257 (dirfd: Dirfd, pathname: Str, flags: Flags(OPEN_FLAGS), mode: Hex);
+    close = 57, 3 (fd: Int);
+    pipe2 = 59, 293 (pipefd: Struct(I32Pair), flags: Flags(OPEN_FLAGS));
+    getdents64 = 61, 217 (fd: Int, dirp: Ptr, count: UInt);
+    lseek = 62, 8 (fd: Int, offset: Int, whence: Enum(SEEK_WHENCE));
+    read = 63, 0 (fd: Int, buf: Buf, count: UInt);
+    write = 64, 1 (fd: Int, buf: Buf, count: UInt);
+    readv = 65, 19 (fd: Int, iov: Ptr, iovcnt: Int);
+    writev = 66, 20 (fd: Int, iov: Ptr, iovcnt: Int);
+    pread64 = 67, 17 (fd: Int, buf: Buf, count: UInt, offset: Int);
+    pwrite64 = 68, 18 (fd: Int, buf: Buf, count: UInt, offset: Int);
+    sendfile = 71, 40 (out_fd: Int, in_fd: Int, offset: Ptr, count: UInt);
+    ppoll = 73, 271 (fds: Ptr, nfds: UInt, tmo_p: Struct(Timespec), sigmask: Struct(Sigset));
+    readlinkat = 78, 267 (dirfd: Dirfd, pathname: Str, buf: Buf, bufsiz: UInt);
+    newfstatat = 79, 262 (dirfd: Dirfd, pathname: Str, statbuf: Ptr, flags: Flags(AT_FLAGS));
+    fstat = 80, 5 (fd: Int, statbuf: Ptr);
+    fsync = 82, 74 (fd: Int);
+    utimensat = 88, 280 (dirfd: Dirfd, pathname: Str, times: Ptr, flags: Flags(AT_FLAGS));
+    exit = 93, 60 (status: Int);
+    exit_group = 94, 231 (status: Int);
+    set_tid_address = 96, 218 (tidptr: Ptr);
+    futex = 98, 202 (uaddr: Ptr, futex_op: Int, val: UInt, timeout: Ptr, uaddr2: Ptr, val3: UInt);
+    set_robust_list = 99, 273 (head: Ptr, len: UInt);
+    nanosleep = 101, 35 (req: Struct(Timespec), rem: Struct(Timespec));
+    clock_gettime = 113, 228 (clockid: Enum(CLOCK_IDS), tp: Struct(Timespec));
+    sched_yield = 124, 24 ();
+    kill = 129, 62 (pid: Int, sig: Enum(SIGNALS));
+    tkill = 130, 200 (tid: Int, sig: Enum(SIGNALS));
+    tgkill = 131, 234 (tgid: Int, tid: Int, sig: Enum(SIGNALS));
+    sigaltstack = 132, 131 (ss: Ptr, old_ss: Ptr);
+    rt_sigaction = 134, 13 (signum: Enum(SIGNALS), act: Ptr, oldact: Ptr, sigsetsize: UInt);
+    rt_sigprocmask = 135, 14 (how: Enum(SIGPROCMASK_HOW), set: Struct(Sigset), oldset: Struct(Sigset), sigsetsize: UInt);
+    rt_sigpending = 136, 127 (set: Struct(Sigset), sigsetsize: UInt);
+    rt_sigreturn = 139, 15 ();
+    times = 153, 100 (buf: Ptr);
+    uname = 160, 63 (buf: Ptr);
+    gettimeofday = 169, 96 (tv: Ptr, tz: Ptr);
+    getpid = 172, 39 ();
+    getppid = 173, 110 ();
+    getuid = 174, 102 ();
+    geteuid = 175, 107 ();
+    getgid = 176, 104 ();
+    getegid = 177, 108 ();
+    gettid = 178, 186 ();
+    shmget = 194, 29 (key: Int, size: UInt, shmflg: Flags(IPC_FLAGS));
+    shmctl = 195, 31 (shmid: Int, cmd: Enum(IPC_CMDS), buf: Ptr);
+    shmat = 196, 30 (shmid: Int, shmaddr: Ptr, shmflg: Hex);
+    shmdt = 197, 67 (shmaddr: Ptr);
+    brk = 214, 12 (addr: Ptr);
+    munmap = 215, 11 (addr: Ptr, length: UInt);
+    clone = 220, 56 (flags: Flags(CLONE_FLAGS), stack: Ptr, parent_tid: Ptr, tls: Ptr, child_tid: Ptr);
+    execve = 221, 59 (pathname: Str, argv: Ptr, envp: Ptr);
+    mmap = 222, 9 (addr: Ptr, length: UInt, prot: Flags(PROT_FLAGS), flags: Flags(MAP_FLAGS), fd: Int, offset: Hex);
+    mprotect = 226, 10 (addr: Ptr, len: UInt, prot: Flags(PROT_FLAGS));
+    madvise = 233, 28 (addr: Ptr, length: UInt, advice: Enum(MADVISE_ADVICE));
+    wait4 = 260, 61 (pid: Int, wstatus: Struct(I32), options: Flags(WAIT_OPTIONS), rusage: Ptr);
+    prlimit64 = 261, 302 (pid: Int, resource: Int, new_limit: Ptr, old_limit: Ptr);
+    renameat2 = 276, 316 (olddirfd: Dirfd, oldpath: Str, newdirfd: Dirfd, newpath: Str, flags: Flags(RENAME_FLAGS));
+    getrandom = 278, 318 (buf: Ptr, buflen: UInt, flags: Hex);
+    memfd_create = 279, 319 (name: Str, flags: Flags(MFD_FLAGS));
+    faccessat2 = 439, 439 (dirfd: Dirfd, pathname: Str, mode: Hex, flags: Hex);
+}
@@ -0,0 +1,77 @@
+use alloc::collections::BTreeSet;
+
+use super::*;
+
+#[test]
+fn test_generic_table() {
+    let mut numbers = BTreeSet::new();
+    for &sysno in Sysno::ALL {
+        let num = sysno.generic_number();
+        assert!(
+            numbers.insert(num),
+            "duplicate number {} ({})",
+            num,
+            sysno.name()
+        );
+        assert_eq!(Sysno::from_generic(num), Some(sysno));
+    }
+    assert_eq!(Sysno::write.generic_number(), 64);
+    assert_eq!(Sysno::exit.generic_number(), 93);
+    assert_eq!(Sysno::mmap.generic_number(), 222);
+    assert_eq!(Sysno::from_generic(1000), None);
+}
+
+#[test]
+fn test_x86_64_table() {
+    let mut numbers = BTreeSet::new();
+    for &sysno in Sysno::ALL {
+        let num = sysno.x86_64_number();
+        assert!(
+            numbers.insert(num),
+            "duplicate number {} ({})",
+            num,
+            sysno.name()
+        );
+        assert_eq!(Sysno::from_x86_64(num), Some(sysno));
+    }
+    assert_eq!(Sysno::read.x86_64_number(), 0);
+    assert_eq!(Sysno::write.x86_64_number(), 1);
+    assert_eq!(Sysno::exit.x86_64_number(), 60);
+    assert_eq!(Sysno::clone.x86_64_number(), 56);
+    assert_eq!(Sysno::from_x86_64(1000), None);
+}
+
+#[test]
+fn test_current_arch() {
+    for &sysno in Sysno::ALL {
+        assert_eq!(Sysno::new(sysno.number()), Some(sysno));
+    }
+    let write = if cfg!(target_arch = "x86_64") { 1 } else { 64 };
+    assert_eq!(Sysno::write.number(), write);
+}
+
+#[test]
+fn test_names() {
+    let names: BTreeSet<_> = Sysno::ALL.iter().map(|sysno| sysno.name()).collect();
+    assert_eq!(names.len(), Sysno::ALL.len());
+    assert_eq!(Sysno::exit_group.name(), "exit_group");
+    for &sysno in Sysno::ALL {
+        assert!(
+            sysno.args().len() <= 6,
+            "{} has too many arguments",
+            sysno.name()
+        );
+        assert_eq!(format!("{:?}", sysno), sysno.name());
+    }
+}
+
+#[test]
+fn test_args() {
+    let args = SyscallArgs::new([1, usize::MAX, 0, 0x1000, 42, 7]);
+    assert_eq!(args.get::<i32>(0), 1);
+    assert_eq!(args.get::<isize>(1), -1);
+    assert!(!args.get::<bool>(2));
+    assert_eq!(args.get::<*const u8>(3), 0x1000 as *const u8);
+    assert_eq!(args.raw(4), 42);
+    assert_eq!(args.get::<u8>(5), 7);
+}
@@ -0,0 +1,357 @@
+//! strace-like syscall tracing and statistics.
+
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use axerrno::LinuxError;
+
+use crate::flags::NameTable;
+use crate::{ArgKind, StructKind, SyscallArgs, Sysno};
+
+/// The maximum number of bytes of strings and buffers to display.
+const MAX_DISPLAY_LEN: usize = 32;
+
+const PAGE_SIZE: usize = 0x1000;
+const AT_FDCWD: isize = -100;
+
+/// A slice of functions that copy `buf.len()` bytes from the user address,
+/// which are used to decode pointer arguments. Returns `false` if the
+/// address is not readable.
+///
+/// If there is none, pointer arguments are displayed as addresses only.
+#[linkme::distributed_slice]
+pub static USER_READER: [fn(usize, &mut [u8]) -> bool];
+
+static TRACE_ENABLED: AtomicBool = AtomicBool::new(cfg!(feature = "strace"));
+
+struct SyscallStat {
+    calls: AtomicU64,
+    errors: AtomicU64,
+    nanos: AtomicU64,
+}
+
+impl SyscallStat {
+    const fn new() -> Self {
+        Self {
+            calls: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            nanos: AtomicU64::new(0),
+        }
+    }
+}
+
+static STATS: [SyscallStat; Sysno::ALL.len()] = {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INIT: SyscallStat = SyscallStat::new();
+    [INIT; Sysno::ALL.len()]
+};
+
+/// Whether syscall tracing is enabled.
+///
+/// It is enabled at boot if the `strace` feature is enabled.
+pub fn trace_enabled() -> bool {
+    TRACE_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Enables or disables syscall tracing.
+///
+/// When enabled, every syscall is printed with its decoded arguments, the
+/// return value and the time it took, and is counted in the statistics
+/// printed by [`print_summary`].
+pub fn set_trace_enabled(enabled: bool) {
+    TRACE_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+fn read_user(addr: usize, buf: &mut [u8]) -> bool {
+    addr != 0 && USER_READER.first().is_some_and(|read| read(addr, buf))
+}
+
+fn read_user_value<const N: usize>(addr: usize) -> Option<[u8; N]> {
+    let mut buf = [0; N];
+    read_user(addr, &mut buf).then_some(buf)
+}
+
+fn read_i32(addr: usize) -> Option<i32> {
+    read_user_value(addr).map(i32::from_ne_bytes)
+}
+
+fn read_u64(addr: usize) -> Option<u64> {
+    read_user_value(addr).map(u64::from_ne_bytes)
+}
+
+fn write_escaped(f: &mut fmt::Formatter, bytes: &[u8], truncated: bool) -> fmt::Result {
+    f.write_char('"')?;
+    for &b in bytes {
+        match b {
+            b'\n' => f.write_str("\\n")?,
+            b'\r' => f.write_str("\\r")?,
+            b'\t' => f.write_str("\\t")?,
+            b'"' => f.write_str("\\\"")?,
+            b'\\' => f.write_str("\\\\")?,
+            0x20..=0x7e => f.write_char(b as char)?,
+            _ => write!(f, "\\x{:02x}", b)?,
+        }
+    }
+    f.write_char('"')?;
+    if truncated {
+        f.write_str("...")?;
+    }
+    Ok(())
+}
+
+fn write_str(f: &mut fmt::Formatter, addr: usize) -> fmt::Result {
+    let mut buf = [0; MAX_DISPLAY_LEN];
+    let mut len = 0;
+    // Read page by page, so that we never fault beyond the terminating NUL.
+    while len < buf.len() {
+        let cur = addr + len;
+        let chunk = (PAGE_SIZE - cur % PAGE_SIZE).min(buf.len() - len);
+        if !read_user(cur, &mut buf[len..len + chunk]) {
+            if len == 0 {
+                return write!(f, "{:#x}", addr);
+            }
+            break;
+        }
+        if let Some(pos) = buf[len..len + chunk].iter().position(|&b| b == 0) {
+            return write_escaped(f, &buf[..len + pos], false);
+        }
+        len += chunk;
+    }
+    write_escaped(f, &buf[..len], true)
+}
+
+fn write_buf(f: &mut fmt::Formatter, addr: usize, len: usize) -> fmt::Result {
+    let mut buf = [0; MAX_DISPLAY_LEN];
+    let shown = len.min(buf.len());
+    if !read_user(addr, &mut buf[..shown]) {
+        return write!(f, "{:#x}", addr);
+    }
+    write_escaped(f, &buf[..shown], shown < len)
+}
+
+fn write_flags(f: &mut fmt::Formatter, table: NameTable, value: usize) -> fmt::Result {
+    if value == 0 {
+        return match table.iter().find(|(_, v)| *v == 0) {
+            Some((name, _)) => f.write_str(name),
+            None => f.write_char('0'),
+        };
+    }
+    let mut rest = value;
+    let mut first = true;
+    for &(name, bit) in table {
+        if bit != 0 && value & bit == bit {
+            if !first {
+                f.write_char('|')?;
+            }
+            f.write_str(name)?;
+            rest &= !bit;
+            first = false;
+        }
+    }
+    if rest != 0 {
+        if !first {
+            f.write_char('|')?;
+        }
+        write!(f, "{:#x}", rest)?;
+    }
+    Ok(())
+}
+
+fn write_enum(f: &mut fmt::Formatter, table: NameTable, value: usize) -> fmt::Result {
+    match table.iter().find(|(_, v)| *v == value) {
+        Some((name, _)) => f.write_str(name),
+        None => write!(f, "{}", value as isize),
+    }
+}
+
+fn write_sigset(f: &mut fmt::Formatter, set: u64) -> fmt::Result {
+    f.write_char('[')?;
+    let mut first = true;
+    for sig in 1..=64 {
+        if set & (1 << (sig - 1)) != 0 {
+            if !first {
+                f.write_char(' ')?;
+            }
+            match crate::flags::SIGNALS.iter().find(|(_, v)| *v == sig) {
+                // Strip the "SIG" prefix like strace.
+                Some((name, _)) => f.write_str(&name[3..])?,
+                None => write!(f, "{}", sig)?,
+            }
+            first = false;
+        }
+    }
+    f.write_char(']')
+}
+
+fn write_struct(f: &mut fmt::Formatter, kind: StructKind, addr: usize) -> fmt::Result {
+    let res = match kind {
+        StructKind::Timespec => read_user_value::<16>(addr).map(|buf| {
+            let sec = i64::from_ne_bytes(buf[..8].try_into().unwrap());
+            let nsec = i64::from_ne_bytes(buf[8..].try_into().unwrap());
+            write!(f, "{{tv_sec={}, tv_nsec={}}}", sec, nsec)
+        }),
+        StructKind::Sigset => read_u64(addr).map(|set| write_sigset(f, set)),
+        StructKind::I32 => read_i32(addr).map(|v| write!(f, "[{}]", v)),
+        StructKind::I32Pair => read_user_value::<8>(addr).map(|buf| {
+            let a = i32::from_ne_bytes(buf[..4].try_into().unwrap());
+            let b = i32::from_ne_bytes(buf[4..].try_into().unwrap());
+            write!(f, "[{}, {}]", a, b)
+        }),
+    };
+    res.unwrap_or_else(|| write!(f, "{:#x}", addr))
+}
+
+struct Call<'a> {
+    sysno: Sysno,
+    args: &'a SyscallArgs,
+    ret: Option<isize>,
+}
+
+impl fmt::Display for Call<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}(", self.sysno.name())?;
+        for (i, &(_, kind)) in self.sysno.args().iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            let raw = self.args.raw(i);
+            match kind {
+                ArgKind::Int => write!(f, "{}", raw as isize)?,
+                ArgKind::UInt => write!(f, "{}", raw)?,
+                ArgKind::Hex => write!(f, "{:#x}", raw)?,
+                ArgKind::Dirfd if raw as isize == AT_FDCWD => f.write_str("AT_FDCWD")?,
+                ArgKind::Dirfd => write!(f, "{}", raw as isize)?,
+                ArgKind::Flags(table) => write_flags(f, table, raw)?,
+                ArgKind::Enum(table) => write_enum(f, table, raw)?,
+                _ if raw == 0 => f.write_str("NULL")?,
+                ArgKind::Ptr => write!(f, "{:#x}", raw)?,
+                ArgKind::Str => write_str(f, raw)?,
+                ArgKind::Buf => {
+                    let mut len = self.args.raw(i + 1);
+                    if let Some(ret) = self.ret.filter(|&ret| ret >= 0) {
+                        len = len.min(ret as usize);
+                    }
+                    write_buf(f, raw, len)?
+                }
+                ArgKind::Struct(kind) => write_struct(f, kind, raw)?,
+            }
+        }
+        f.write_str(")")
+    }
+}
+
+fn errno(ret: isize) -> Option<LinuxError> {
+    if (-4095..0).contains(&ret) {
+        LinuxError::try_from(-ret as i32).ok()
+    } else {
+        None
+    }
+}
+
+/// Whether the syscall does not return to the caller on success.
+fn no_return(sysno: Sysno) -> bool {
+    matches!(sysno, Sysno::exit | Sysno::exit_group | Sysno::execve)
+}
+
+pub(crate) fn enter(sysno: Sysno, args: &SyscallArgs) {
+    if no_return(sysno) {
+        // Counted here, as `exit` may never be called.
+        STATS[sysno as usize].calls.fetch_add(1, Ordering::Relaxed);
+        let call = Call {
+            sysno,
+            args,
+            ret: None,
+        };
+        axlog::ax_println!("[strace] {} = ?", call);
+    }
+}
+
+pub(crate) fn exit(sysno: Sysno, args: &SyscallArgs, ret: isize, nanos: u64) {
+    let stat = &STATS[sysno as usize];
+    if !no_return(sysno) {
+        stat.calls.fetch_add(1, Ordering::Relaxed);
+    }
+    stat.nanos.fetch_add(nanos, Ordering::Relaxed);
+    let err = errno(ret);
+    if err.is_some() {
+        stat.errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    if no_return(sysno) && err.is_none() {
+        return;
+    }
+    let call = Call {
+        sysno,
+        args,
+        ret: Some(ret),
+    };
+    let (secs, micros) = (nanos / 1_000_000_000, nanos % 1_000_000_000 / 1000);
+    match err {
+        Some(e) => axlog::ax_println!(
+            "[strace] {} = -1 {:?} ({}) <{}.{:06}>",
+            call,
+            e,
+            e.as_str(),
+            secs,
+            micros
+        ),
+        None => axlog::ax_println!("[strace] {} = {} <{}.{:06}>", call, ret, secs, micros),
+    }
+}
+
+/// Prints the statistics of the traced syscalls, like `strace -c`, and then
+/// clears them.
+///
+/// It prints nothing if no syscall has been traced.
+pub fn print_summary() {
+    let mut rows = Vec::new();
+    for (i, stat) in STATS.iter().enumerate() {
+        let calls = stat.calls.swap(0, Ordering::Relaxed);
+        let errors = stat.errors.swap(0, Ordering::Relaxed);
+        let nanos = stat.nanos.swap(0, Ordering::Relaxed);
+        if calls > 0 {
+            rows.push((Sysno::ALL[i], calls, errors, nanos));
+        }
+    }
+    if rows.is_empty() {
+        return;
+    }
+    rows.sort_by(|a, b| b.3.cmp(&a.3));
+
+    let total_calls: u64 = rows.iter().map(|r| r.1).sum();
+    let total_errors: u64 = rows.iter().map(|r| r.2).sum();
+    let total_nanos: u64 = rows.iter().map(|r| r.3).sum();
+    // In hundredths of a percent, to avoid floating-point in the kernel.
+    let percent = |nanos: u64| match total_nanos {
+        0 => 0,
+        total => nanos * 10000 / total,
+    };
+    let line = "------ ----------- ----------- --------- --------- ----------------";
+
+    axlog::ax_println!("% time     seconds  usecs/call     calls    errors syscall");
+    axlog::ax_println!("{}", line);
+    for (sysno, calls, errors, nanos) in rows {
+        let pct = percent(nanos);
+        axlog::ax_println!(
+            "{:3}.{:02} {:4}.{:06} {:11} {:9} {:9} {}",
+            pct / 100,
+            pct % 100,
+            nanos / 1_000_000_000,
+            nanos % 1_000_000_000 / 1000,
+            nanos / 1000 / calls,
+            calls,
+            errors,
+            sysno.name()
+        );
+    }
+    axlog::ax_println!("{}", line);
+    axlog::ax_println!(
+        "100.00 {:4}.{:06} {:11} {:9} {:9} total",
+        total_nanos / 1_000_000_000,
+        total_nanos % 1_000_000_000 / 1000,
+        total_nanos / 1000 / total_calls,
+        total_calls,
+        total_errors
+    );
+}
@@ -5,7 +5,7 @@ use alloc::{string::String, sync::Arc};
 pub(crate) use crate::run_queue::{AxRunQueue, RUN_QUEUE};
 
 #[doc(cfg(feature = "multitask"))]
-pub use crate::task::{CurrentTask, TaskId, TaskInner};
+pub use crate::task::{CurrentTask, TaskId, TaskInner, TaskState};
 #[doc(cfg(feature = "multitask"))]
 pub use crate::task_ext::{TaskExtMut, TaskExtRef};
 #[doc(cfg(feature = "multitask"))]
@@ -48,6 +48,17 @@ impl kernel_guard::KernelGuardIf for KernelGuardIfImpl {
     }
 }
 
+/// Returns the name of the scheduler in use, i.e., `fifo`, `rr` or `cfs`.
+pub const fn scheduler_name() -> &'static str {
+    if cfg!(feature = "sched_rr") {
+        "rr"
+    } else if cfg!(feature = "sched_cfs") {
+        "cfs"
+    } else {
+        "fifo"
+    }
+}
+
 /// Gets the current task, or returns [`None`] if the current task is not
 /// initialized.
 pub fn current_may_uninit() -> Option<CurrentTask> {
@@ -119,6 +130,21 @@ where
     spawn_raw(f, "".into(), axconfig::TASK_STACK_SIZE)
 }
 
+/// Returns all tasks that have not been dropped, ordered by the task ID.
+///
+/// It includes the idle and the garbage collection tasks, and the tasks that
+/// have exited but have not been dropped yet.
+pub fn tasks() -> alloc::vec::Vec<AxTaskRef> {
+    crate::task::all_tasks()
+}
+
+/// Finds the task with the given ID.
+///
+/// Returns [`None`] if there is no such task or it has been dropped.
+pub fn get_task(id: u64) -> Option<AxTaskRef> {
+    crate::task::find_task(id)
+}
+
 /// Set the priority for current task.
 ///
 /// The range of the priority is dependent on the underlying scheduler. For
@@ -132,6 +158,27 @@ pub fn set_priority(prio: isize) -> bool {
     RUN_QUEUE.lock().set_current_priority(prio)
 }
 
+/// Sets the CPUs the current task is allowed to run on, one bit per CPU in
+/// `cpumask`.
+///
+/// The current task migrates at once if the current CPU is not allowed.
+/// Returns `false` if none of the CPUs in `cpumask` exists.
+pub fn set_current_affinity(cpumask: usize) -> bool {
+    let all_cpus = match 1usize.checked_shl(axconfig::SMP as u32) {
+        Some(bit) => bit - 1,
+        None => usize::MAX,
+    };
+    if cpumask & all_cpus == 0 {
+        return false;
+    }
+    let curr = current();
+    curr.set_cpumask(cpumask);
+    if !curr.can_run_on(axhal::cpu::this_cpu_id()) {
+        yield_now();
+    }
+    true
+}
+
 /// Current task gives up the CPU time voluntarily, and switches to another
 /// ready task.
 pub fn yield_now() {
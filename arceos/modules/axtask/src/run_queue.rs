@@ -1,5 +1,6 @@
 use alloc::collections::VecDeque;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 use kspin::SpinNoIrq;
 use lazyinit::LazyInit;
 use scheduler::BaseScheduler;
@@ -156,13 +157,30 @@ impl AxRunQueue {
                 self.scheduler.put_prev_task(prev.clone(), preempt);
             }
         }
-        let next = self.scheduler.pick_next_task().unwrap_or_else(|| unsafe {
+        let next = self.pick_next_task().unwrap_or_else(|| unsafe {
             // Safety: IRQs must be disabled at this time.
             IDLE_TASK.current_ref_raw().get_unchecked().clone()
         });
         self.switch_to(prev, next);
     }
 
+    /// Picks the next task that is allowed to run on this CPU. The tasks
+    /// skipped are put back to the scheduler.
+    fn pick_next_task(&mut self) -> Option<AxTaskRef> {
+        let cpu_id = axhal::cpu::this_cpu_id();
+        let mut skipped = Vec::new();
+        let next = loop {
+            match self.scheduler.pick_next_task() {
+                Some(task) if !task.can_run_on(cpu_id) => skipped.push(task),
+                next => break next,
+            }
+        };
+        for task in skipped {
+            self.scheduler.put_prev_task(task, false);
+        }
+        next
+    }
+
     fn switch_to(&mut self, prev_task: CurrentTask, next_task: AxTaskRef) {
         trace!(
             "context switch: {} -> {}",
@@ -1,15 +1,14 @@
-use alloc::{boxed::Box, string::String, sync::Arc};
+use alloc::collections::BTreeMap;
+use alloc::{boxed::Box, string::String, sync::Arc, sync::Weak, vec::Vec};
 use core::ops::Deref;
-use core::sync::atomic::{AtomicBool, AtomicI32, AtomicU64, AtomicU8, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicI32, AtomicU64, AtomicU8, AtomicUsize, Ordering};
 use core::{alloc::Layout, cell::UnsafeCell, fmt, ptr::NonNull};
 
-#[cfg(feature = "preempt")]
-use core::sync::atomic::AtomicUsize;
-
 #[cfg(feature = "tls")]
 use axhal::tls::TlsArea;
 
 use axhal::arch::TaskContext;
+use kspin::SpinNoIrq;
 use memory_addr::{align_up_4k, VirtAddr};
 
 use crate::task_ext::AxTaskExt;
@@ -22,10 +21,14 @@ pub struct TaskId(u64);
 /// The possible states of a task.
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
-pub(crate) enum TaskState {
+pub enum TaskState {
+    /// The task is running on a CPU.
     Running = 1,
+    /// The task is in the run queue, waiting for a CPU.
     Ready = 2,
+    /// The task is waiting for an event, e.g., in a wait queue or sleeping.
     Blocked = 3,
+    /// The task has exited, but not been dropped.
     Exited = 4,
 }
 
@@ -38,6 +41,8 @@ pub struct TaskInner {
 
     entry: Option<*mut dyn FnOnce()>,
     state: AtomicU8,
+    /// The CPUs the task is allowed to run on, one bit per CPU.
+    cpumask: AtomicUsize,
 
     in_wait_queue: AtomicBool,
     #[cfg(feature = "irq")]
@@ -126,6 +131,20 @@ impl TaskInner {
         alloc::format!("Task({}, {:?})", self.id.as_u64(), self.name)
     }
 
+    /// Gets the mask of the CPUs the task is allowed to run on, one bit per
+    /// CPU.
+    pub fn cpumask(&self) -> usize {
+        self.cpumask.load(Ordering::Acquire)
+    }
+
+    /// Sets the mask of the CPUs the task is allowed to run on, one bit per
+    /// CPU.
+    ///
+    /// It takes effect the next time the task is scheduled.
+    pub fn set_cpumask(&self, cpumask: usize) {
+        self.cpumask.store(cpumask, Ordering::Release)
+    }
+
     /// Wait for the task to exit, and return the exit code.
     ///
     /// It will return immediately if the task has already exited (but not dropped).
@@ -171,6 +190,7 @@ impl TaskInner {
             is_init: false,
             entry: None,
             state: AtomicU8::new(TaskState::Ready as u8),
+            cpumask: AtomicUsize::new(usize::MAX),
             in_wait_queue: AtomicBool::new(false),
             #[cfg(feature = "irq")]
             in_timer_list: AtomicBool::new(false),
@@ -206,11 +226,15 @@ impl TaskInner {
     }
 
     pub(crate) fn into_arc(self) -> AxTaskRef {
-        Arc::new(AxTask::new(self))
+        let id = self.id.as_u64();
+        let task = Arc::new(AxTask::new(self));
+        TASK_LIST.lock().insert(id, Arc::downgrade(&task));
+        task
     }
 
+    /// Gets the state of the task.
     #[inline]
-    pub(crate) fn state(&self) -> TaskState {
+    pub fn state(&self) -> TaskState {
         self.state.load(Ordering::Acquire).into()
     }
 
@@ -219,6 +243,12 @@ impl TaskInner {
         self.state.store(state as u8, Ordering::Release)
     }
 
+    /// Whether the task is allowed to run on the CPU `cpu_id`.
+    #[inline]
+    pub(crate) fn can_run_on(&self, cpu_id: usize) -> bool {
+        cpu_id < usize::BITS as usize && self.cpumask() & (1 << cpu_id) != 0
+    }
+
     #[inline]
     pub(crate) fn is_running(&self) -> bool {
         matches!(self.state(), TaskState::Running)
@@ -343,9 +373,27 @@ impl fmt::Debug for TaskInner {
 impl Drop for TaskInner {
     fn drop(&mut self) {
         debug!("task drop: {}", self.id_name());
+        TASK_LIST.lock().remove(&self.id.as_u64());
     }
 }
 
+/// All tasks that have not been dropped, indexed by the task ID.
+static TASK_LIST: SpinNoIrq<BTreeMap<u64, Weak<AxTask>>> = SpinNoIrq::new(BTreeMap::new());
+
+/// Returns all tasks that have not been dropped, ordered by the task ID.
+pub(crate) fn all_tasks() -> Vec<AxTaskRef> {
+    TASK_LIST
+        .lock()
+        .values()
+        .filter_map(Weak::upgrade)
+        .collect()
+}
+
+/// Finds the task with the given ID, if it has not been dropped.
+pub(crate) fn find_task(id: u64) -> Option<AxTaskRef> {
+    TASK_LIST.lock().get(&id).and_then(Weak::upgrade)
+}
+
 struct TaskStack {
     ptr: NonNull<u8>,
     layout: Layout,
@@ -128,3 +128,15 @@ fn test_task_join() {
         assert_eq!(tasks[i].join(), Some(i as _));
     }
 }
+
+#[test]
+fn test_affinity() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    assert!(!axtask::set_current_affinity(0));
+    assert!(axtask::set_current_affinity(0b1));
+    assert_eq!(current().cpumask(), 0b1);
+    axtask::yield_now(); // still runs on CPU 0
+    assert!(axtask::set_current_affinity(usize::MAX));
+}
@@ -0,0 +1,439 @@
+//! [ArceOS](https://github.com/arceos-org/arceos) VM configurations, in a
+//! subset of TOML.
+//!
+//! A configuration has keys at the top level, and arrays of tables
+//! (`[[name]]`) for the regions and devices, e.g.:
+//!
+//! ```toml
+//! name = "vm0"
+//! cpu_num = 2
+//! cpu_affinity = [0, 1]        # optional, host cpu of each vcpu
+//! entry = 0x8020_0000
+//! bootargs = "console=ttyS0"   # optional
+//! dtb_addr = 0x80f0_0000       # optional, the end of memory by default
+//!
+//! [[memory]]
+//! gpa = 0x8000_0000
+//! size = 0x100_0000
+//!
+//! [[image]]
+//! path = "/sbin/m_1_1_riscv64-qemu-virt.bin"
+//! load_addr = 0x8020_0000
+//!
+//! [[passthrough]]
+//! gpa = 0x2200_0000
+//! size = 0x200_0000
+//!
+//! [[emulated]]
+//! type = "uart16550"       # or "plic", "virtio-blk", "virtio-console"
+//! gpa = 0x1000_0000
+//! irq = 10                 # optional, source on the plic
+//!
+//! [[emulated]]
+//! type = "virtio-blk"
+//! gpa = 0x1000_1000
+//! irq = 1
+//! path = "/vm/disk.img"    # the backing file of a virtio-blk
+//! ```
+//!
+//! Values are strings, integers (decimal, `0x`, `0o` or `0b`, with optional
+//! `_` separators) and single-line arrays of them.
+
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+#[cfg(test)]
+mod tests;
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use axerrno::{ax_err_type, AxResult};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Int(usize),
+    Str(String),
+    Array(Vec<Value>),
+}
+
+type Table = BTreeMap<String, Value>;
+
+/// A parsed document: the top-level table, and the arrays of tables.
+#[derive(Default)]
+struct Document {
+    root: Table,
+    arrays: BTreeMap<String, Vec<Table>>,
+}
+
+struct Parser<'a> {
+    s: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.s.get(self.pos).copied()
+    }
+
+    fn skip_spaces(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t')) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), &'static str> {
+        self.skip_spaces();
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err("unexpected character")
+        }
+    }
+
+    /// Whether the rest of the line is blank or a comment.
+    fn at_line_end(&mut self) -> bool {
+        self.skip_spaces();
+        matches!(self.peek(), None | Some(b'#'))
+    }
+
+    fn key(&mut self) -> Result<String, &'static str> {
+        self.skip_spaces();
+        let start = self.pos;
+        while matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric() || c == b'_' || c == b'-')
+        {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return Err("expected a key");
+        }
+        Ok(String::from_utf8_lossy(&self.s[start..self.pos]).into_owned())
+    }
+
+    fn value(&mut self) -> Result<Value, &'static str> {
+        self.skip_spaces();
+        match self.peek().ok_or("expected a value")? {
+            b'"' => self.string().map(Value::Str),
+            b'[' => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                loop {
+                    self.skip_spaces();
+                    if self.peek() == Some(b']') {
+                        self.pos += 1;
+                        return Ok(Value::Array(items));
+                    }
+                    items.push(self.value()?);
+                    self.skip_spaces();
+                    match self.peek() {
+                        Some(b',') => self.pos += 1,
+                        Some(b']') => {}
+                        _ => return Err("expected `,` or `]`"),
+                    }
+                }
+            }
+            _ => {
+                let start = self.pos;
+                while matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric() || c == b'_') {
+                    self.pos += 1;
+                }
+                let word = core::str::from_utf8(&self.s[start..self.pos]).unwrap();
+                parse_int(word).map(Value::Int).ok_or("invalid value")
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, &'static str> {
+        self.pos += 1;
+        let mut bytes = Vec::new();
+        loop {
+            let c = self.peek().ok_or("unterminated string")?;
+            self.pos += 1;
+            match c {
+                b'"' => break,
+                b'\\' => {
+                    let c = self.peek().ok_or("unterminated string")?;
+                    self.pos += 1;
+                    bytes.push(match c {
+                        b'n' => b'\n',
+                        b't' => b'\t',
+                        b'"' | b'\\' => c,
+                        _ => return Err("unknown escape"),
+                    });
+                }
+                _ => bytes.push(c),
+            }
+        }
+        String::from_utf8(bytes).map_err(|_| "invalid UTF-8 in string")
+    }
+}
+
+fn parse_int(word: &str) -> Option<usize> {
+    let digits: String = word.chars().filter(|&c| c != '_').collect();
+    let (radix, digits) = match digits.get(..2) {
+        Some("0x") => (16, &digits[2..]),
+        Some("0o") => (8, &digits[2..]),
+        Some("0b") => (2, &digits[2..]),
+        _ => (10, &digits[..]),
+    };
+    usize::from_str_radix(digits, radix).ok()
+}
+
+impl Document {
+    fn parse(text: &str) -> AxResult<Self> {
+        let mut doc = Self::default();
+        // The array of tables the keys go to, or the top-level table.
+        let mut current: Option<String> = None;
+        for (i, line) in text.lines().enumerate() {
+            doc.parse_line(line, &mut current).map_err(|msg| {
+                ax_err_type!(InvalidData, format!("line {}: {}: {:?}", i + 1, msg, line))
+            })?;
+        }
+        Ok(doc)
+    }
+
+    fn parse_line(&mut self, line: &str, current: &mut Option<String>) -> Result<(), &'static str> {
+        let mut p = Parser {
+            s: line.as_bytes(),
+            pos: 0,
+        };
+        if p.at_line_end() {
+            return Ok(());
+        }
+        if p.peek() == Some(b'[') {
+            p.expect(b'[')?;
+            p.expect(b'[')
+                .map_err(|_| "only arrays of tables `[[name]]` are supported")?;
+            let name = p.key()?;
+            p.expect(b']')?;
+            p.expect(b']')?;
+            self.arrays
+                .entry(name.clone())
+                .or_default()
+                .push(Table::new());
+            *current = Some(name);
+        } else {
+            let key = p.key()?;
+            p.expect(b'=')?;
+            let value = p.value()?;
+            let table = match current {
+                Some(name) => self.arrays.get_mut(name).unwrap().last_mut().unwrap(),
+                None => &mut self.root,
+            };
+            if table.insert(key, value).is_some() {
+                return Err("duplicate key");
+            }
+        }
+        if p.at_line_end() {
+            Ok(())
+        } else {
+            Err("trailing characters")
+        }
+    }
+
+    fn tables(&self, name: &str) -> &[Table] {
+        self.arrays
+            .get(name)
+            .map_or(&[], |tables| tables.as_slice())
+    }
+}
+
+fn get_usize(table: &Table, key: &str) -> AxResult<usize> {
+    get_usize_opt(table, key)?
+        .ok_or_else(|| ax_err_type!(InvalidData, format!("missing integer `{}`", key)))
+}
+
+fn get_usize_opt(table: &Table, key: &str) -> AxResult<Option<usize>> {
+    match table.get(key) {
+        None => Ok(None),
+        Some(Value::Int(val)) => Ok(Some(*val)),
+        Some(_) => Err(ax_err_type!(
+            InvalidData,
+            format!("`{}` is not an integer", key)
+        )),
+    }
+}
+
+fn get_str(table: &Table, key: &str) -> AxResult<String> {
+    get_str_opt(table, key)?
+        .ok_or_else(|| ax_err_type!(InvalidData, format!("missing string `{}`", key)))
+}
+
+fn get_str_opt(table: &Table, key: &str) -> AxResult<Option<String>> {
+    match table.get(key) {
+        None => Ok(None),
+        Some(Value::Str(val)) => Ok(Some(val.clone())),
+        Some(_) => Err(ax_err_type!(
+            InvalidData,
+            format!("`{}` is not a string", key)
+        )),
+    }
+}
+
+/// A region of guest physical memory.
+#[derive(Debug, Clone)]
+pub struct MemRegion {
+    pub gpa: usize,
+    pub size: usize,
+}
+
+/// An image loaded into guest memory.
+#[derive(Debug, Clone)]
+pub struct ImageConfig {
+    /// The path of the image on the host file system.
+    pub path: String,
+    pub load_addr: usize,
+}
+
+/// The type of an emulated device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmuDevType {
+    Uart16550,
+    Plic,
+    VirtioBlk,
+    VirtioConsole,
+}
+
+/// An emulated device.
+#[derive(Debug, Clone)]
+pub struct EmuDevConfig {
+    pub ty: EmuDevType,
+    pub gpa: usize,
+    /// The size of the register region, or the default of the device type.
+    pub size: Option<usize>,
+    /// The interrupt source of the device on the emulated PLIC.
+    pub irq: Option<usize>,
+    /// The backing file of the device on the host file system, required by
+    /// a virtio-blk.
+    pub path: Option<String>,
+}
+
+/// The maximum number of vCPUs of a VM.
+pub const MAX_CPU_NUM: usize = 64;
+
+/// The configuration of a VM.
+#[derive(Debug, Clone)]
+pub struct VmConfig {
+    pub name: String,
+    /// The host CPU each vCPU is pinned to, if any.
+    pub cpu_affinity: Vec<Option<usize>>,
+    /// The guest physical address vCPU 0 starts at.
+    pub entry: usize,
+    /// The kernel command line, in `/chosen/bootargs` of the device tree.
+    pub bootargs: Option<String>,
+    /// The guest physical address of the device tree, or the end of the
+    /// first memory region.
+    pub dtb_addr: Option<usize>,
+    pub memory: Vec<MemRegion>,
+    pub images: Vec<ImageConfig>,
+    /// The host MMIO regions mapped to the guest at the same addresses.
+    pub passthrough: Vec<MemRegion>,
+    pub emulated: Vec<EmuDevConfig>,
+}
+
+impl VmConfig {
+    /// Parses a configuration from its TOML text.
+    pub fn from_toml(text: &str) -> AxResult<Self> {
+        let doc = Document::parse(text)?;
+        let root = &doc.root;
+
+        let cpu_num = get_usize_opt(root, "cpu_num")?.unwrap_or(1);
+        if cpu_num == 0 || cpu_num > MAX_CPU_NUM {
+            return Err(ax_err_type!(InvalidData, "`cpu_num` is 0 or too large"));
+        }
+        let cpu_affinity = match root.get("cpu_affinity") {
+            None => vec![None; cpu_num],
+            Some(Value::Array(cpus)) if cpus.len() == cpu_num => cpus
+                .iter()
+                .map(|cpu| match cpu {
+                    Value::Int(cpu) => Ok(Some(*cpu)),
+                    _ => Err(ax_err_type!(InvalidData, "`cpu_affinity` is not integers")),
+                })
+                .collect::<AxResult<_>>()?,
+            Some(_) => {
+                return Err(ax_err_type!(
+                    InvalidData,
+                    "`cpu_affinity` is not an array of `cpu_num` integers"
+                ))
+            }
+        };
+
+        let region = |table: &Table| {
+            Ok(MemRegion {
+                gpa: get_usize(table, "gpa")?,
+                size: get_usize(table, "size")?,
+            })
+        };
+        let memory = doc
+            .tables("memory")
+            .iter()
+            .map(region)
+            .collect::<AxResult<_>>()?;
+        let passthrough = doc
+            .tables("passthrough")
+            .iter()
+            .map(region)
+            .collect::<AxResult<_>>()?;
+        let images = doc
+            .tables("image")
+            .iter()
+            .map(|table| {
+                Ok(ImageConfig {
+                    path: get_str(table, "path")?,
+                    load_addr: get_usize(table, "load_addr")?,
+                })
+            })
+            .collect::<AxResult<_>>()?;
+        let emulated = doc
+            .tables("emulated")
+            .iter()
+            .map(|table| {
+                let ty = match get_str(table, "type")?.as_str() {
+                    "uart16550" => EmuDevType::Uart16550,
+                    "plic" => EmuDevType::Plic,
+                    "virtio-blk" => EmuDevType::VirtioBlk,
+                    "virtio-console" => EmuDevType::VirtioConsole,
+                    ty => {
+                        return Err(ax_err_type!(
+                            InvalidData,
+                            format!("unknown device type {:?}", ty)
+                        ))
+                    }
+                };
+                let path = get_str_opt(table, "path")?;
+                if ty == EmuDevType::VirtioBlk && path.is_none() {
+                    return Err(ax_err_type!(InvalidData, "virtio-blk without `path`"));
+                }
+                Ok(EmuDevConfig {
+                    ty,
+                    gpa: get_usize(table, "gpa")?,
+                    size: get_usize_opt(table, "size")?,
+                    irq: get_usize_opt(table, "irq")?,
+                    path,
+                })
+            })
+            .collect::<AxResult<_>>()?;
+
+        Ok(Self {
+            name: get_str(root, "name")?,
+            cpu_affinity,
+            entry: get_usize(root, "entry")?,
+            bootargs: get_str_opt(root, "bootargs")?,
+            dtb_addr: get_usize_opt(root, "dtb_addr")?,
+            memory,
+            images,
+            passthrough,
+            emulated,
+        })
+    }
+
+    /// Returns the number of vCPUs.
+    pub fn cpu_num(&self) -> usize {
+        self.cpu_affinity.len()
+    }
+}
@@ -0,0 +1,142 @@
+use axerrno::AxError;
+
+use super::*;
+
+const FULL: &str = r#"
+# A comment line.
+name = "vm0"
+cpu_num = 2
+cpu_affinity = [0, 1]        # trailing comment
+entry = 0x8020_0000
+bootargs = "console=ttyS0 \"quoted\""
+dtb_addr = 0x80f0_0000
+
+[[memory]]
+gpa = 0x8000_0000
+size = 0x100_0000
+
+[[memory]]
+gpa = 0x9000_0000
+size = 16_777_216
+
+[[image]]
+path = "/sbin/m_1_1_riscv64-qemu-virt.bin"
+load_addr = 0x8020_0000
+
+[[passthrough]]
+gpa = 0x2200_0000
+size = 0x200_0000
+
+[[emulated]]
+type = "plic"
+gpa = 0x0c00_0000
+size = 0o1000
+
+[[emulated]]
+type = "virtio-blk"
+gpa = 0x1000_1000
+irq = 0b1
+path = "/vm/disk.img"
+"#;
+
+fn parse_err(text: &str) -> AxError {
+    VmConfig::from_toml(text).unwrap_err()
+}
+
+#[test]
+fn test_full() {
+    let config = VmConfig::from_toml(FULL).unwrap();
+    assert_eq!(config.name, "vm0");
+    assert_eq!(config.cpu_num(), 2);
+    assert_eq!(config.cpu_affinity, [Some(0), Some(1)]);
+    assert_eq!(config.entry, 0x8020_0000);
+    assert_eq!(config.bootargs.as_deref(), Some("console=ttyS0 \"quoted\""));
+    assert_eq!(config.dtb_addr, Some(0x80f0_0000));
+
+    assert_eq!(config.memory.len(), 2);
+    assert_eq!(config.memory[0].gpa, 0x8000_0000);
+    assert_eq!(config.memory[0].size, 0x100_0000);
+    assert_eq!(config.memory[1].gpa, 0x9000_0000);
+    assert_eq!(config.memory[1].size, 0x100_0000);
+    assert_eq!(config.images.len(), 1);
+    assert_eq!(config.images[0].path, "/sbin/m_1_1_riscv64-qemu-virt.bin");
+    assert_eq!(config.images[0].load_addr, 0x8020_0000);
+    assert_eq!(config.passthrough.len(), 1);
+    assert_eq!(config.passthrough[0].gpa, 0x2200_0000);
+
+    assert_eq!(config.emulated.len(), 2);
+    let plic = &config.emulated[0];
+    assert_eq!(plic.ty, EmuDevType::Plic);
+    assert_eq!(plic.gpa, 0x0c00_0000);
+    assert_eq!(plic.size, Some(0o1000));
+    assert_eq!(plic.irq, None);
+    assert_eq!(plic.path, None);
+    let blk = &config.emulated[1];
+    assert_eq!(blk.ty, EmuDevType::VirtioBlk);
+    assert_eq!(blk.size, None);
+    assert_eq!(blk.irq, Some(1));
+    assert_eq!(blk.path.as_deref(), Some("/vm/disk.img"));
+}
+
+#[test]
+fn test_defaults() {
+    let config = VmConfig::from_toml("name = \"vm1\"\nentry = 0x8000_0000\n").unwrap();
+    assert_eq!(config.cpu_affinity, [None]);
+    assert_eq!(config.bootargs, None);
+    assert_eq!(config.dtb_addr, None);
+    assert!(config.memory.is_empty());
+    assert!(config.images.is_empty());
+    assert!(config.passthrough.is_empty());
+    assert!(config.emulated.is_empty());
+
+    let config = VmConfig::from_toml("name = \"vm1\"\ncpu_num = 3\nentry = 0\n").unwrap();
+    assert_eq!(config.cpu_affinity, [None, None, None]);
+}
+
+#[test]
+fn test_syntax_errors() {
+    let bad = [
+        // Tables other than arrays of tables.
+        "[memory]",
+        "[[memory]",
+        "name",
+        "name = ",
+        "name = \"vm0",
+        "name = \"\\x\"",
+        "name = vm0",
+        "entry = 0x",
+        "entry = 0x8000_0000 0x1000",
+        "cpu_affinity = [0, 1",
+        "cpu_affinity = [0 1]",
+        "name = \"vm0\"\nname = \"vm1\"",
+    ];
+    for text in bad {
+        assert_eq!(parse_err(text), AxError::InvalidData, "{:?}", text);
+    }
+}
+
+#[test]
+fn test_invalid_config() {
+    let bad = [
+        // Missing or mistyped keys.
+        "entry = 0",
+        "name = \"vm0\"",
+        "name = 0\nentry = 0",
+        "name = \"vm0\"\nentry = \"0\"",
+        // The affinity of each vCPU.
+        "name = \"vm0\"\nentry = 0\ncpu_num = 0\ncpu_affinity = []",
+        "name = \"vm0\"\nentry = 0\ncpu_num = 65",
+        "name = \"vm0\"\nentry = 0\ncpu_num = 0x7fff_ffff_ffff_ffff",
+        "name = \"vm0\"\nentry = 0\ncpu_num = 2\ncpu_affinity = [0]",
+        "name = \"vm0\"\nentry = 0\ncpu_affinity = [\"0\"]",
+        "name = \"vm0\"\nentry = 0\ncpu_affinity = 0",
+        // Regions and devices.
+        "name = \"vm0\"\nentry = 0\n[[memory]]\ngpa = 0",
+        "name = \"vm0\"\nentry = 0\n[[image]]\nload_addr = 0",
+        "name = \"vm0\"\nentry = 0\n[[emulated]]\ntype = \"e1000\"\ngpa = 0",
+        "name = \"vm0\"\nentry = 0\n[[emulated]]\ntype = \"virtio-blk\"\ngpa = 0",
+    ];
+    for text in bad {
+        assert_eq!(parse_err(text), AxError::InvalidData, "{:?}", text);
+    }
+}
@@ -0,0 +1,124 @@
+//! Decoding of the guest load and store instructions that trap on emulated
+//! MMIO regions.
+//!
+//! It is plain code without assembly, so it is unit-tested on the host.
+
+use super::regs::GprIndex;
+
+/// The width of an access.
+///
+/// Note that the term "word" here refers to 16-bit data, as in the x86 architecture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AccessWidth {
+    /// 8-bit access.
+    Byte,
+    /// 16-bit access.
+    Word,
+    /// 32-bit access.
+    Dword,
+    /// 64-bit access.
+    Qword,
+}
+
+impl AccessWidth {
+    /// Returns the size of the access in bytes.
+    pub const fn size(self) -> usize {
+        match self {
+            Self::Byte => 1,
+            Self::Word => 2,
+            Self::Dword => 4,
+            Self::Qword => 8,
+        }
+    }
+}
+
+/// A decoded load or store instruction.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct MmioInsn {
+    /// Whether it is a store.
+    pub is_store: bool,
+    /// The width of the access.
+    pub width: AccessWidth,
+    /// The destination register of a load, or the source register of a store.
+    pub reg: GprIndex,
+    /// Whether a load sign-extends the value.
+    pub signed_ext: bool,
+    /// The length of the instruction in bytes.
+    pub len: usize,
+}
+
+impl MmioInsn {
+    /// Decodes the transformed instruction written to `htinst` on a guest
+    /// page fault.
+    ///
+    /// Returns [`None`] if it is a pseudoinstruction, i.e., the fault happened
+    /// on an implicit access of the VS-stage page table walk.
+    pub fn from_htinst(htinst: usize) -> Option<Self> {
+        // Bit 0 is set for a transformed instruction, whose bit 1 is cleared
+        // if the trapping instruction is compressed.
+        if htinst & 0b01 == 0 {
+            return None;
+        }
+        let len = if htinst & 0b10 == 0 { 2 } else { 4 };
+        let insn = Self::decode32(htinst as u32 | 0b11)?;
+        Some(Self { len, ..insn })
+    }
+
+    /// Decodes a 32-bit or compressed instruction fetched from guest memory.
+    pub fn decode(insn: u32) -> Option<Self> {
+        if insn & 0b11 == 0b11 {
+            Self::decode32(insn)
+        } else {
+            Self::decode16(insn as u16)
+        }
+    }
+
+    fn decode32(insn: u32) -> Option<Self> {
+        let (is_store, reg) = match insn & 0x7f {
+            0b000_0011 => (false, (insn >> 7) & 0x1f), // LOAD, rd
+            0b010_0011 => (true, (insn >> 20) & 0x1f), // STORE, rs2
+            _ => return None,
+        };
+        let (width, signed_ext) = match (is_store, (insn >> 12) & 0x7) {
+            (_, 0b000) => (AccessWidth::Byte, true),       // lb, sb
+            (_, 0b001) => (AccessWidth::Word, true),       // lh, sh
+            (_, 0b010) => (AccessWidth::Dword, true),      // lw, sw
+            (_, 0b011) => (AccessWidth::Qword, false),     // ld, sd
+            (false, 0b100) => (AccessWidth::Byte, false),  // lbu
+            (false, 0b101) => (AccessWidth::Word, false),  // lhu
+            (false, 0b110) => (AccessWidth::Dword, false), // lwu
+            _ => return None,
+        };
+        Some(Self {
+            is_store,
+            width,
+            reg: GprIndex::from_raw(reg)?,
+            signed_ext,
+            len: 4,
+        })
+    }
+
+    fn decode16(insn: u16) -> Option<Self> {
+        let insn = insn as u32;
+        // The 3-bit register fields of CL/CS formats encode x8-x15.
+        let reg_prime = ((insn >> 2) & 0x7) + 8;
+        let (is_store, width, reg) = match (insn & 0b11, insn >> 13) {
+            (0b00, 0b010) => (false, AccessWidth::Dword, reg_prime), // c.lw
+            (0b00, 0b011) => (false, AccessWidth::Qword, reg_prime), // c.ld
+            (0b00, 0b110) => (true, AccessWidth::Dword, reg_prime),  // c.sw
+            (0b00, 0b111) => (true, AccessWidth::Qword, reg_prime),  // c.sd
+            (0b10, 0b010) => (false, AccessWidth::Dword, (insn >> 7) & 0x1f), // c.lwsp
+            (0b10, 0b011) => (false, AccessWidth::Qword, (insn >> 7) & 0x1f), // c.ldsp
+            (0b10, 0b110) => (true, AccessWidth::Dword, (insn >> 2) & 0x1f), // c.swsp
+            (0b10, 0b111) => (true, AccessWidth::Qword, (insn >> 2) & 0x1f), // c.sdsp
+            _ => return None,
+        };
+        Some(Self {
+            is_store,
+            width,
+            reg: GprIndex::from_raw(reg)?,
+            signed_ext: width == AccessWidth::Dword,
+            len: 2,
+        })
+    }
+}
@@ -1,27 +1,45 @@
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 #![feature(doc_cfg)]
 #![feature(naked_functions)]
-#![feature(riscv_ext_intrinsics)]
+#![cfg_attr(target_arch = "riscv64", feature(riscv_ext_intrinsics))]
 #![feature(asm_const)]
 #![doc = include_str!("../README.md")]
 
-#[macro_use]
+#[cfg_attr(target_arch = "riscv64", macro_use)]
 extern crate log;
 
-pub mod csrs;
-mod detect;
+// Only used by the vCPU on riscv64 except in the tests.
+#[cfg_attr(not(target_arch = "riscv64"), allow(dead_code))]
+mod decode;
+#[cfg_attr(not(target_arch = "riscv64"), allow(dead_code))]
 mod regs;
-pub mod sbi;
-mod vcpu;
 
-pub use self::vcpu::RISCVVCpu;
-pub use detect::detect_h_extension as has_hardware_support;
-pub use vcpu::AxVCpuExitReason;
-use csrs::{traps, CSR, RiscvCsrTrait};
+// The parts with assembly and RISC-V intrinsics, so that the others can be
+// tested on the host.
+cfg_if::cfg_if! {
+    if #[cfg(target_arch = "riscv64")] {
+        pub mod csrs;
+        mod detect;
+        mod mmio;
+        pub mod sbi;
+        mod vcpu;
+
+        pub use self::vcpu::RISCVVCpu;
+        pub use detect::detect_h_extension as has_hardware_support;
+        pub use vcpu::AxVCpuExitReason;
+        use csrs::{traps, CSR, RiscvCsrTrait};
+    }
+}
+
+#[cfg(test)]
+mod tests;
+
+pub use decode::AccessWidth;
 
 pub struct RISCVPerCpu {}
 
 /// Initialize (H)S-level CSRs to a reasonable state.
+#[cfg(target_arch = "riscv64")]
 pub unsafe fn setup_csrs() {
     // Delegate some synchronous exceptions.
     CSR.hedeleg.write_value(
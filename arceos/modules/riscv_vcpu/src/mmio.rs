@@ -0,0 +1,20 @@
+//! Access to the guest memory with the hypervisor load and store instructions.
+
+// The guest memory accessors, whose faults are recovered through the
+// exception table.
+core::arch::global_asm!(include_str!("mem_extable.S"));
+
+extern "C" {
+    fn _fetch_guest_instruction(gva: usize, raw_insn: *mut u32) -> isize;
+}
+
+/// Fetches the instruction at the guest virtual address `gva`, using the
+/// address translation of the guest that has just exited.
+///
+/// Returns [`None`] if the fetch faults, e.g., if another vCPU of the guest
+/// has unmapped the page since the trap.
+pub(crate) fn fetch_guest_insn(gva: usize) -> Option<u32> {
+    let mut insn = 0;
+    let ret = unsafe { _fetch_guest_instruction(gva, &mut insn) };
+    (ret == 0).then_some(insn)
+}
@@ -1,11 +1,55 @@
+use axerrno::{AxError, AxResult};
+
+/// The extension ID of the Debug Console extension ("DBCN").
+pub const EID_DBCN: usize = 0x4442_434e;
+
+const CONSOLE_WRITE: usize = 0;
+const CONSOLE_READ: usize = 1;
+const CONSOLE_WRITE_BYTE: usize = 2;
+
 /// Functions for the Debug Console extension
 #[derive(Copy, Clone, Debug)]
 pub enum DebugConsoleFunction {
-    /// Prints the given string to the system console.
-    PutString {
-        /// The length of the string to print.
-        len: u64,
-        /// The address of the string.
-        addr: u64,
+    /// Writes the bytes of the given buffer to the console.
+    Write {
+        /// The number of bytes to write.
+        num_bytes: u64,
+        /// The guest physical address of the buffer.
+        base_addr: u64,
     },
+    /// Reads at most `num_bytes` bytes from the console to the given buffer.
+    Read {
+        /// The size of the buffer.
+        num_bytes: u64,
+        /// The guest physical address of the buffer.
+        base_addr: u64,
+    },
+    /// Writes a single byte to the console.
+    WriteByte(u8),
+}
+
+impl DebugConsoleFunction {
+    /// Attempts to parse `Self` from the passed in `a0-a7`.
+    pub(crate) fn from_regs(args: &[usize]) -> AxResult<Self> {
+        // The upper bits of the address are in a2, which is 0 for any
+        // physical address on RV64.
+        let buffer = || {
+            if args[2] != 0 {
+                return Err(AxError::InvalidInput);
+            }
+            Ok((args[0] as u64, args[1] as u64))
+        };
+        match args[6] {
+            CONSOLE_WRITE => buffer().map(|(num_bytes, base_addr)| Self::Write {
+                num_bytes,
+                base_addr,
+            }),
+            CONSOLE_READ => buffer().map(|(num_bytes, base_addr)| Self::Read {
+                num_bytes,
+                base_addr,
+            }),
+            CONSOLE_WRITE_BYTE => Ok(Self::WriteByte(args[0] as u8)),
+            _ => Err(AxError::NotFound),
+        }
+    }
 }
@@ -0,0 +1,55 @@
+use sbi_spec::hsm::{HART_GET_STATUS, HART_START, HART_STOP, HART_SUSPEND};
+
+use axerrno::{AxError, AxResult};
+
+/// Functions for the Hart State Management extension
+#[derive(Clone, Copy, Debug)]
+pub enum HsmFunction {
+    /// Starts the given hart at `start_addr`, with `opaque` in its a1.
+    HartStart {
+        /// The ID of the hart to start.
+        hartid: u64,
+        /// The guest physical address the hart starts at.
+        start_addr: u64,
+        /// The value passed to the hart in a1.
+        opaque: u64,
+    },
+    /// Stops the calling hart.
+    HartStop,
+    /// Returns the state of the given hart.
+    HartStatus {
+        /// The ID of the hart.
+        hartid: u64,
+    },
+    /// Suspends the calling hart.
+    HartSuspend {
+        /// The type of the suspension.
+        suspend_type: u64,
+        /// The address the hart resumes at after a non-retentive suspension.
+        resume_addr: u64,
+        /// The value passed to the hart in a1 when it resumes.
+        opaque: u64,
+    },
+}
+
+impl HsmFunction {
+    pub(crate) fn from_regs(args: &[usize]) -> AxResult<Self> {
+        match args[6] {
+            HART_START => Ok(Self::HartStart {
+                hartid: args[0] as u64,
+                start_addr: args[1] as u64,
+                opaque: args[2] as u64,
+            }),
+            HART_STOP => Ok(Self::HartStop),
+            HART_GET_STATUS => Ok(Self::HartStatus {
+                hartid: args[0] as u64,
+            }),
+            HART_SUSPEND => Ok(Self::HartSuspend {
+                suspend_type: args[0] as u64,
+                resume_addr: args[1] as u64,
+                opaque: args[2] as u64,
+            }),
+            _ => Err(AxError::NotFound),
+        }
+    }
+}
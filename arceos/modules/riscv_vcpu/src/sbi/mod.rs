@@ -1,16 +1,20 @@
 mod base;
 mod dbcn;
+mod hsm;
 mod pmu;
 mod rfnc;
+mod spi;
 mod srst;
 
 use axerrno::{AxError, AxResult};
 pub use base::BaseFunction;
-use dbcn::DebugConsoleFunction;
+pub use dbcn::DebugConsoleFunction;
+pub use hsm::HsmFunction;
 pub use pmu::PmuFunction;
 pub use rfnc::RemoteFenceFunction;
 use sbi_spec;
-pub use srst::ResetFunction;
+pub use spi::IpiFunction;
+pub use srst::{ResetFunction, ResetReason, ResetType};
 
 pub const SBI_SUCCESS: usize = 0;
 pub const SBI_ERR_FAILUER: isize = -1;
@@ -58,6 +62,10 @@ pub enum SbiMessage {
     RemoteFence(RemoteFenceFunction),
     /// The PMU Extension
     PMU(PmuFunction),
+    /// The Hart State Management Extension
+    Hsm(HsmFunction),
+    /// The IPI Extension
+    Ipi(IpiFunction),
 }
 
 impl SbiMessage {
@@ -71,18 +79,38 @@ impl SbiMessage {
             sbi_spec::legacy::LEGACY_CONSOLE_GETCHAR => Ok(SbiMessage::GetChar),
             sbi_spec::legacy::LEGACY_SET_TIMER => Ok(SbiMessage::SetTimer(args[0])),
             sbi_spec::legacy::LEGACY_SHUTDOWN => Ok(SbiMessage::Reset(ResetFunction::shutdown())),
-            sbi_spec::time::EID_TIME => Ok(SbiMessage::SetTimer(args[0])),
+            sbi_spec::time::EID_TIME => match args[6] {
+                sbi_spec::time::SET_TIMER => Ok(SbiMessage::SetTimer(args[0])),
+                _ => Err(AxError::NotFound),
+            },
             sbi_spec::srst::EID_SRST => ResetFunction::from_regs(args).map(SbiMessage::Reset),
             sbi_spec::rfnc::EID_RFNC => {
                 RemoteFenceFunction::from_args(args).map(SbiMessage::RemoteFence)
             }
             sbi_spec::pmu::EID_PMU => PmuFunction::from_regs(args).map(SbiMessage::PMU),
-            _ => {
-                error!("args: {:?}", args);
-                error!("args[7]: {:#x}", args[7]);
-                error!("EID_RFENCE: {:#x}", sbi_spec::rfnc::EID_RFNC);
-                Err(AxError::NotFound)
-            }
+            sbi_spec::hsm::EID_HSM => HsmFunction::from_regs(args).map(SbiMessage::Hsm),
+            sbi_spec::spi::EID_SPI => IpiFunction::from_regs(args).map(SbiMessage::Ipi),
+            dbcn::EID_DBCN => DebugConsoleFunction::from_regs(args).map(SbiMessage::DebugConsole),
+            _ => Err(AxError::NotFound),
         }
     }
+
+    /// Returns whether the extension `eid` is available to the guest.
+    pub fn probe_extension(eid: usize) -> bool {
+        matches!(
+            eid,
+            sbi_spec::base::EID_BASE
+                | sbi_spec::legacy::LEGACY_CONSOLE_PUTCHAR
+                | sbi_spec::legacy::LEGACY_CONSOLE_GETCHAR
+                | sbi_spec::legacy::LEGACY_SET_TIMER
+                | sbi_spec::legacy::LEGACY_SHUTDOWN
+                | sbi_spec::time::EID_TIME
+                | sbi_spec::srst::EID_SRST
+                | sbi_spec::rfnc::EID_RFNC
+                | sbi_spec::pmu::EID_PMU
+                | sbi_spec::hsm::EID_HSM
+                | sbi_spec::spi::EID_SPI
+                | dbcn::EID_DBCN
+        )
+    }
 }
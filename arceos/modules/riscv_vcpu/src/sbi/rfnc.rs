@@ -1,6 +1,6 @@
-use sbi_spec::rfnc::{REMOTE_FENCE_I, REMOTE_SFENCE_VMA};
+use sbi_spec::rfnc::{REMOTE_FENCE_I, REMOTE_SFENCE_VMA, REMOTE_SFENCE_VMA_ASID};
 
-use axerrno::AxResult;
+use axerrno::{AxError, AxResult};
 
 #[derive(Clone, Copy, Debug)]
 pub enum RemoteFenceFunction {
@@ -14,9 +14,37 @@ pub enum RemoteFenceFunction {
         start_addr: u64,
         size: u64,
     },
+    RemoteSFenceVMAWithASID {
+        hart_mask: u64,
+        hart_mask_base: u64,
+        start_addr: u64,
+        size: u64,
+        asid: u64,
+    },
 }
 
 impl RemoteFenceFunction {
+    /// Returns the mask of the target harts, and the ID of the first hart in
+    /// the mask.
+    pub fn hart_mask(&self) -> (u64, u64) {
+        match *self {
+            Self::FenceI {
+                hart_mask,
+                hart_mask_base,
+            }
+            | Self::RemoteSFenceVMA {
+                hart_mask,
+                hart_mask_base,
+                ..
+            }
+            | Self::RemoteSFenceVMAWithASID {
+                hart_mask,
+                hart_mask_base,
+                ..
+            } => (hart_mask, hart_mask_base),
+        }
+    }
+
     pub fn from_args(args: &[usize]) -> AxResult<Self> {
         match args[6] {
             REMOTE_FENCE_I => Ok(Self::FenceI {
@@ -29,7 +57,14 @@ impl RemoteFenceFunction {
                 start_addr: args[2] as u64,
                 size: args[3] as u64,
             }),
-            _ => panic!("Unsupported yet!"),
+            REMOTE_SFENCE_VMA_ASID => Ok(Self::RemoteSFenceVMAWithASID {
+                hart_mask: args[0] as u64,
+                hart_mask_base: args[1] as u64,
+                start_addr: args[2] as u64,
+                size: args[3] as u64,
+                asid: args[4] as u64,
+            }),
+            _ => Err(AxError::NotFound),
         }
     }
 }
@@ -0,0 +1,27 @@
+use sbi_spec::spi::SEND_IPI;
+
+use axerrno::{AxError, AxResult};
+
+/// Functions for the IPI extension
+#[derive(Clone, Copy, Debug)]
+pub enum IpiFunction {
+    /// Sends a supervisor software interrupt to the harts in the mask.
+    SendIpi {
+        /// The mask of the harts, starting from `hart_mask_base`.
+        hart_mask: u64,
+        /// The ID of the first hart in the mask, or `-1` for all harts.
+        hart_mask_base: u64,
+    },
+}
+
+impl IpiFunction {
+    pub(crate) fn from_regs(args: &[usize]) -> AxResult<Self> {
+        match args[6] {
+            SEND_IPI => Ok(Self::SendIpi {
+                hart_mask: args[0] as u64,
+                hart_mask_base: args[1] as u64,
+            }),
+            _ => Err(AxError::NotFound),
+        }
+    }
+}
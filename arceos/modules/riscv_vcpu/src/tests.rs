@@ -0,0 +1,147 @@
+use crate::decode::{AccessWidth, MmioInsn};
+use crate::regs::GprIndex;
+
+fn check(insn: Option<MmioInsn>, is_store: bool, width: AccessWidth, reg: GprIndex, len: usize) {
+    let insn = insn.unwrap();
+    assert_eq!(insn.is_store, is_store);
+    assert_eq!(insn.width, width);
+    assert_eq!(insn.reg, reg);
+    assert_eq!(insn.len, len);
+}
+
+#[test]
+fn test_decode() {
+    // lw a0, 8(a1)
+    let insn = MmioInsn::decode(0x0085_a503);
+    check(insn, false, AccessWidth::Dword, GprIndex::A0, 4);
+    assert!(insn.unwrap().signed_ext);
+    // lbu t0, 0(a0)
+    let insn = MmioInsn::decode(0x0005_4283);
+    check(insn, false, AccessWidth::Byte, GprIndex::T0, 4);
+    assert!(!insn.unwrap().signed_ext);
+    // ld ra, 0(sp)
+    let insn = MmioInsn::decode(0x0001_3083);
+    check(insn, false, AccessWidth::Qword, GprIndex::RA, 4);
+    // sd a2, 16(sp)
+    check(
+        MmioInsn::decode(0x00c1_3823),
+        true,
+        AccessWidth::Qword,
+        GprIndex::A2,
+        4,
+    );
+    // sh t6, 2(s0)
+    check(
+        MmioInsn::decode(0x01f4_1123),
+        true,
+        AccessWidth::Word,
+        GprIndex::T6,
+        4,
+    );
+
+    // addi a0, a0, 1
+    assert!(MmioInsn::decode(0x0015_0513).is_none());
+    // Load with the reserved funct3 = 0b111.
+    assert!(MmioInsn::decode(0x0000_7503).is_none());
+    // Store with funct3 = 0b100.
+    assert!(MmioInsn::decode(0x00a0_4023).is_none());
+}
+
+#[test]
+fn test_decode_compressed() {
+    // c.lw a0, 0(a1)
+    let insn = MmioInsn::decode(0x4188);
+    check(insn, false, AccessWidth::Dword, GprIndex::A0, 2);
+    assert!(insn.unwrap().signed_ext);
+    // c.ld s1, 8(a5)
+    let insn = MmioInsn::decode(0x6784);
+    check(insn, false, AccessWidth::Qword, GprIndex::S1, 2);
+    assert!(!insn.unwrap().signed_ext);
+    // c.sw a5, 4(a0)
+    check(
+        MmioInsn::decode(0xc15c),
+        true,
+        AccessWidth::Dword,
+        GprIndex::A5,
+        2,
+    );
+    // c.sd s0, 8(a5)
+    check(
+        MmioInsn::decode(0xe780),
+        true,
+        AccessWidth::Qword,
+        GprIndex::S0,
+        2,
+    );
+    // c.lwsp t1, 4(sp)
+    check(
+        MmioInsn::decode(0x4312),
+        false,
+        AccessWidth::Dword,
+        GprIndex::T1,
+        2,
+    );
+    // c.ldsp ra, 8(sp)
+    check(
+        MmioInsn::decode(0x60a2),
+        false,
+        AccessWidth::Qword,
+        GprIndex::RA,
+        2,
+    );
+    // c.swsp a0, 12(sp)
+    check(
+        MmioInsn::decode(0xc62a),
+        true,
+        AccessWidth::Dword,
+        GprIndex::A0,
+        2,
+    );
+    // c.sdsp s11, 0(sp)
+    check(
+        MmioInsn::decode(0xe06e),
+        true,
+        AccessWidth::Qword,
+        GprIndex::S11,
+        2,
+    );
+
+    // c.addi a0, 1
+    assert!(MmioInsn::decode(0x0505).is_none());
+    // c.fld fa0, 0(a1)
+    assert!(MmioInsn::decode(0x2188).is_none());
+}
+
+#[test]
+fn test_from_htinst() {
+    // lw a0 with the address offset and rs1 cleared.
+    let insn = MmioInsn::from_htinst(0x0000_2503);
+    check(insn, false, AccessWidth::Dword, GprIndex::A0, 4);
+    // sd a2
+    check(
+        MmioInsn::from_htinst(0x00c0_3023),
+        true,
+        AccessWidth::Qword,
+        GprIndex::A2,
+        4,
+    );
+    // c.lw a0, transformed into lw with bit 1 cleared.
+    let insn = MmioInsn::from_htinst(0x0000_2501);
+    check(insn, false, AccessWidth::Dword, GprIndex::A0, 2);
+    assert!(insn.unwrap().signed_ext);
+    // c.sd s0
+    check(
+        MmioInsn::from_htinst(0x0080_3021),
+        true,
+        AccessWidth::Qword,
+        GprIndex::S0,
+        2,
+    );
+
+    // Not a transformed instruction.
+    assert!(MmioInsn::from_htinst(0).is_none());
+    // A pseudoinstruction for an implicit load of the VS-stage page table.
+    assert!(MmioInsn::from_htinst(0x0000_3000).is_none());
+    // Transformed, but not a load or a store.
+    assert!(MmioInsn::from_htinst(0x0000_0513).is_none());
+}
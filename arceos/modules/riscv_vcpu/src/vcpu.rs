@@ -6,16 +6,25 @@ use riscv::register::{htinst, htval, scause, sstatus, stval};
 use sbi_rt::{pmu_counter_get_info, pmu_counter_stop};
 use tock_registers::LocalRegisterCopy;
 
-use axerrno::AxResult;
+use axerrno::{ax_err, AxError, AxResult};
 
 use super::csrs::defs::hstatus;
 use super::csrs::{traps, RiscvCsrTrait, CSR};
-use super::sbi::{BaseFunction, PmuFunction, RemoteFenceFunction, SbiMessage};
+use super::decode::{AccessWidth, MmioInsn};
+use super::mmio::fetch_guest_insn;
+use super::sbi::{
+    BaseFunction, DebugConsoleFunction, HsmFunction, IpiFunction, PmuFunction, RemoteFenceFunction,
+    ResetFunction, ResetReason, ResetType, SbiMessage, SBI_ERR_INAVLID_PARAM,
+    SBI_ERR_NOT_SUPPORTED,
+};
 
 use super::regs::{GeneralPurposeRegisters, GprIndex};
 use memory_addr::{VirtAddr, PhysAddr};
 use axhal::paging::MappingFlags;
 
+/// The SBI specification version presented to the guest, v2.0.
+const SBI_SPEC_VERSION: usize = 2 << 24;
+
 /// Guest physical address.
 pub type GuestPhysAddr = VirtAddr;
 /// Host physical address.
@@ -211,6 +220,22 @@ pub struct VCpuConfig {}
 /// A virtual CPU within a guest
 pub struct RISCVVCpu {
     regs: VmCpuRegisters,
+    /// The load or store of the last `MmioRead` or `MmioWrite` exit, which
+    /// is not completed yet.
+    pending_mmio: Option<MmioInsn>,
+    /// The length of the load or store of the last `NestedPageFault` exit,
+    /// if it is known.
+    fault_insn_len: Option<usize>,
+    /// Whether the virtual supervisor external interrupt is asserted.
+    vseip: bool,
+    /// Whether the virtual supervisor software interrupt is pending.
+    vssip: bool,
+    /// The time (in ticks) the guest timer interrupt is pending from, as set
+    /// by SBI `set_timer`.
+    timer_deadline: u64,
+    /// Whether the guest TLB and instruction cache are to be flushed before
+    /// the next entry.
+    flush_pending: bool,
 }
 
 impl RISCVVCpu {
@@ -233,12 +258,52 @@ impl RISCVVCpu {
     }
 
     pub fn run(&mut self) -> AxResult<AxVCpuExitReason> {
+        // An uncompleted MMIO access is executed again.
+        self.pending_mmio = None;
+        self.fault_insn_len = None;
+        // The vCPU may run on a different host CPU than the last time.
+        self.load_hgatp();
+        if core::mem::take(&mut self.flush_pending) {
+            unsafe {
+                core::arch::riscv64::hfence_vvma_all();
+                core::arch::asm!("fence.i");
+            }
+        }
+        if self.vseip {
+            CSR.hvip
+                .read_and_set_bits(traps::interrupt::VIRTUAL_SUPERVISOR_EXTERNAL);
+        } else {
+            CSR.hvip
+                .read_and_clear_bits(traps::interrupt::VIRTUAL_SUPERVISOR_EXTERNAL);
+        }
+        if self.vssip {
+            CSR.hvip
+                .read_and_set_bits(traps::interrupt::VIRTUAL_SUPERVISOR_SOFT);
+        } else {
+            CSR.hvip
+                .read_and_clear_bits(traps::interrupt::VIRTUAL_SUPERVISOR_SOFT);
+        }
+        // The host timer is left to the host, whose periodic interrupts make
+        // the guest exit to check its deadline again.
+        if riscv::register::time::read() as u64 >= self.timer_deadline {
+            CSR.hvip
+                .read_and_set_bits(traps::interrupt::VIRTUAL_SUPERVISOR_TIMER);
+        } else {
+            CSR.hvip
+                .read_and_clear_bits(traps::interrupt::VIRTUAL_SUPERVISOR_TIMER);
+        }
+        // Another vCPU may have run on this host CPU since the last time.
+        self.load_vs_csrs();
         let regs = &mut self.regs;
         unsafe {
             // Safe to run the guest as it only touches memory assigned to it by being owned
             // by its page table
             _run_guest(regs);
         }
+        self.save_vs_csrs();
+        // The guest clears its software interrupt through `sip`, which is an
+        // alias of `hvip.VSSIP`.
+        self.vssip = CSR.hvip.get_value() & traps::interrupt::VIRTUAL_SUPERVISOR_SOFT != 0;
         self.vmexit_handler()
     }
 }
@@ -261,9 +326,15 @@ impl RISCVVCpu {
         sstatus.set_spp(sstatus::SPP::Supervisor);
         regs.guest_regs.sstatus = sstatus.bits();
 
-        CSR.sie
-            .read_and_clear_bits(traps::interrupt::SUPERVISOR_TIMER);
-        Self { regs }
+        Self {
+            regs,
+            pending_mmio: None,
+            fault_insn_len: None,
+            vseip: false,
+            vssip: false,
+            timer_deadline: u64::MAX,
+            flush_pending: false,
+        }
     }
 
     /// Gets one of the vCPU's general purpose registers.
@@ -285,9 +356,159 @@ impl RISCVVCpu {
     pub fn regs(&mut self) -> &mut VmCpuRegisters {
         &mut self.regs
     }
+
+    /// Asserts or deasserts the external interrupt of the guest (VSEIP),
+    /// which takes effect on the next [`RISCVVCpu::run`].
+    ///
+    /// It is a level, so the caller (usually the virtual interrupt controller)
+    /// deasserts it once no interrupt is pending.
+    pub fn set_external_interrupt(&mut self, pending: bool) {
+        self.vseip = pending;
+    }
+
+    /// Makes a supervisor software interrupt pending for the guest (VSSIP),
+    /// e.g., an IPI from another vCPU.
+    pub fn inject_soft_interrupt(&mut self) {
+        self.vssip = true;
+    }
+
+    /// Flushes the guest TLB and instruction cache before the next
+    /// [`RISCVVCpu::run`], for a remote fence from another vCPU.
+    pub fn request_flush(&mut self) {
+        self.flush_pending = true;
+    }
+
+    /// Sets the arguments a secondary vCPU starts with, as SBI HSM
+    /// `hart_start` does: the hart ID in a0, and `opaque` in a1.
+    pub fn set_start_args(&mut self, hart_id: usize, opaque: usize) {
+        self.set_gpr_from_gpr_index(GprIndex::A0, hart_id);
+        self.set_gpr_from_gpr_index(GprIndex::A1, opaque);
+    }
+
+    /// Sets the error and value returned by the SBI call of the last exit,
+    /// which the hypervisor has handled.
+    pub fn set_sbi_return(&mut self, error: isize, value: usize) {
+        self.set_gpr_from_gpr_index(GprIndex::A0, error as usize);
+        self.set_gpr_from_gpr_index(GprIndex::A1, value);
+    }
+
+    /// Completes the emulated MMIO read of the last [`AxVCpuExitReason::MmioRead`]
+    /// exit with the value read from the device.
+    ///
+    /// `data` is truncated to the access width and extended as the load does,
+    /// written back to the target register, and the guest pc is advanced past
+    /// the load.
+    pub fn complete_mmio_read(&mut self, data: u64) -> AxResult {
+        let insn = match self.pending_mmio.take() {
+            Some(insn) if !insn.is_store => insn,
+            _ => return ax_err!(BadState, "no pending MMIO read"),
+        };
+        let shift = 64 - insn.width.size() * 8;
+        let val = if insn.signed_ext {
+            ((data << shift) as i64 >> shift) as u64
+        } else {
+            data << shift >> shift
+        };
+        self.set_gpr_from_gpr_index(insn.reg, val as usize);
+        self.advance_pc(insn.len);
+        Ok(())
+    }
+
+    /// Completes the emulated MMIO write of the last
+    /// [`AxVCpuExitReason::MmioWrite`] exit, by advancing the guest pc past the
+    /// store.
+    pub fn complete_mmio_write(&mut self) -> AxResult {
+        match self.pending_mmio.take() {
+            Some(insn) if insn.is_store => {
+                self.advance_pc(insn.len);
+                Ok(())
+            }
+            _ => ax_err!(BadState, "no pending MMIO write"),
+        }
+    }
+
+    /// Skips the load or store of the last
+    /// [`AxVCpuExitReason::NestedPageFault`] exit, which cannot be emulated,
+    /// e.g., as no device is at the address.
+    ///
+    /// The destination register of a load is left as is.
+    pub fn skip_faulting_insn(&mut self) -> AxResult {
+        match self.fault_insn_len.take() {
+            Some(len) => {
+                self.advance_pc(len);
+                Ok(())
+            }
+            None => ax_err!(BadState, "no faulting instruction to skip"),
+        }
+    }
 }
 
 impl RISCVVCpu {
+    fn load_hgatp(&self) {
+        let hgatp = self.regs.virtual_hs_csrs.hgatp;
+        unsafe {
+            let current: usize;
+            core::arch::asm!("csrr {}, hgatp", out(reg) current);
+            if current != hgatp {
+                core::arch::asm!("csrw hgatp, {}", in(reg) hgatp);
+                core::arch::riscv64::hfence_gvma_all();
+            }
+        }
+    }
+
+    fn load_vs_csrs(&self) {
+        let csrs = &self.regs.vs_csrs;
+        unsafe {
+            core::arch::asm!(
+                "csrw vsstatus, {vsstatus}",
+                "csrw vsie, {vsie}",
+                "csrw vstvec, {vstvec}",
+                "csrw vsscratch, {vsscratch}",
+                "csrw vsepc, {vsepc}",
+                "csrw vscause, {vscause}",
+                "csrw vstval, {vstval}",
+                vsstatus = in(reg) csrs.vsstatus,
+                vsie = in(reg) csrs.vsie,
+                vstvec = in(reg) csrs.vstvec,
+                vsscratch = in(reg) csrs.vsscratch,
+                vsepc = in(reg) csrs.vsepc,
+                vscause = in(reg) csrs.vscause,
+                vstval = in(reg) csrs.vstval,
+            );
+            let current: usize;
+            core::arch::asm!("csrr {}, vsatp", out(reg) current);
+            if current != csrs.vsatp {
+                // The cached guest translations belong to the previous vsatp.
+                core::arch::asm!("csrw vsatp, {}", in(reg) csrs.vsatp);
+                core::arch::riscv64::hfence_vvma_all();
+            }
+        }
+    }
+
+    fn save_vs_csrs(&mut self) {
+        let csrs = &mut self.regs.vs_csrs;
+        unsafe {
+            core::arch::asm!(
+                "csrr {vsstatus}, vsstatus",
+                "csrr {vsie}, vsie",
+                "csrr {vstvec}, vstvec",
+                "csrr {vsscratch}, vsscratch",
+                "csrr {vsepc}, vsepc",
+                "csrr {vscause}, vscause",
+                "csrr {vstval}, vstval",
+                "csrr {vsatp}, vsatp",
+                vsstatus = out(reg) csrs.vsstatus,
+                vsie = out(reg) csrs.vsie,
+                vstvec = out(reg) csrs.vstvec,
+                vsscratch = out(reg) csrs.vsscratch,
+                vsepc = out(reg) csrs.vsepc,
+                vscause = out(reg) csrs.vscause,
+                vstval = out(reg) csrs.vstval,
+                vsatp = out(reg) csrs.vsatp,
+            );
+        }
+    }
+
     fn vmexit_handler(&mut self) -> AxResult<AxVCpuExitReason> {
         self.regs.trap_csrs.scause = scause::read().bits();
         self.regs.trap_csrs.stval = stval::read();
@@ -298,90 +519,156 @@ impl RISCVVCpu {
         use scause::{Exception, Interrupt, Trap};
         match scause.cause() {
             Trap::Exception(Exception::VirtualSupervisorEnvCall) => {
-                let sbi_msg = SbiMessage::from_regs(self.regs.guest_regs.gprs.a_regs()).ok();
+                let args = self.regs.guest_regs.gprs.a_regs();
+                let (eid, fid) = (args[7], args[6]);
+                let sbi_msg = SbiMessage::from_regs(args);
                 debug!("VSuperEcall: {:?}", sbi_msg);
-                if let Some(sbi_msg) = sbi_msg {
-                    match sbi_msg {
-                        SbiMessage::Base(base) => {
-                            self.handle_base_function(base).unwrap();
-                        }
-                        SbiMessage::GetChar => {
-                            #[allow(deprecated)]
-                            let c = sbi_rt::legacy::console_getchar();
-                            self.set_gpr_from_gpr_index(GprIndex::A0, c);
-                        }
-                        SbiMessage::PutChar(c) => {
-                            #[allow(deprecated)]
-                            sbi_rt::legacy::console_putchar(c);
-                        }
-                        SbiMessage::SetTimer(timer) => {
-                            info!("Set timer... ");
-                            sbi_rt::set_timer(timer as u64);
-                            // Clear guest timer interrupt
-                            CSR.hvip
-                                .read_and_clear_bits(traps::interrupt::VIRTUAL_SUPERVISOR_TIMER);
-                            //  Enable host timer interrupt
-                            CSR.sie
-                                .read_and_set_bits(traps::interrupt::SUPERVISOR_TIMER);
-                        }
-                        SbiMessage::Reset(_) => {
-                            sbi_rt::system_reset(sbi_rt::Shutdown, sbi_rt::SystemFailure);
-                        }
-                        SbiMessage::RemoteFence(rfnc) => {
-                            self.handle_rfnc_function(rfnc).unwrap();
-                        }
-                        SbiMessage::PMU(pmu) => {
-                            self.handle_pmu_function(pmu).unwrap();
-                        }
-                        _ => todo!(),
+                let mut exit_reason = AxVCpuExitReason::Nothing;
+                match sbi_msg {
+                    Ok(SbiMessage::Base(base)) => {
+                        self.handle_base_function(base).unwrap();
+                    }
+                    Ok(SbiMessage::GetChar) => {
+                        #[allow(deprecated)]
+                        let c = sbi_rt::legacy::console_getchar();
+                        self.set_gpr_from_gpr_index(GprIndex::A0, c);
+                    }
+                    Ok(SbiMessage::PutChar(c)) => {
+                        #[allow(deprecated)]
+                        sbi_rt::legacy::console_putchar(c);
+                    }
+                    Ok(SbiMessage::SetTimer(timer)) => {
+                        debug!("Set timer: {:#x}", timer);
+                        // Also clears the pending guest timer interrupt if
+                        // the deadline is in the future.
+                        self.timer_deadline = timer as u64;
+                        self.set_sbi_return(0, 0);
+                    }
+                    Ok(SbiMessage::DebugConsole(dbcn)) => {
+                        exit_reason = self.handle_dbcn_function(dbcn);
+                    }
+                    Ok(SbiMessage::Reset(reset)) => {
+                        exit_reason = self.handle_reset_function(reset);
+                    }
+                    Ok(SbiMessage::RemoteFence(rfnc)) => {
+                        exit_reason = self.handle_rfnc_function(rfnc);
+                    }
+                    Ok(SbiMessage::PMU(pmu)) => {
+                        self.handle_pmu_function(pmu).unwrap();
+                    }
+                    Ok(SbiMessage::Hsm(hsm)) => {
+                        exit_reason = self.handle_hsm_function(hsm);
+                    }
+                    Ok(SbiMessage::Ipi(IpiFunction::SendIpi {
+                        hart_mask,
+                        hart_mask_base,
+                    })) => {
+                        self.set_sbi_return(0, 0);
+                        exit_reason = AxVCpuExitReason::SendIpi {
+                            hart_mask,
+                            hart_mask_base,
+                        };
+                    }
+                    Err(AxError::InvalidInput) => {
+                        self.set_sbi_return(SBI_ERR_INAVLID_PARAM, 0);
+                    }
+                    Err(_) => {
+                        warn!("Unsupported SBI call: eid {:#x}, fid {:#x}", eid, fid);
+                        self.set_sbi_return(SBI_ERR_NOT_SUPPORTED, 0);
                     }
-                    self.advance_pc(4);
-                    Ok(AxVCpuExitReason::Nothing)
-                } else {
-                    panic!()
                 }
+                self.advance_pc(4);
+                Ok(exit_reason)
             }
-            Trap::Interrupt(Interrupt::SupervisorTimer) => {
-                info!("timer irq emulation");
-                // Enable guest timer interrupt
-                CSR.hvip
-                    .read_and_set_bits(traps::interrupt::VIRTUAL_SUPERVISOR_TIMER);
-                // Clear host timer interrupt
-                CSR.sie
-                    .read_and_clear_bits(traps::interrupt::SUPERVISOR_TIMER);
-                Ok(AxVCpuExitReason::Nothing)
-            }
+            // Handled by the host once the irqs are enabled again, and the
+            // guest timer is checked on the next entry.
+            Trap::Interrupt(Interrupt::SupervisorTimer) => Ok(AxVCpuExitReason::Nothing),
             Trap::Interrupt(Interrupt::SupervisorExternal) => {
                 Ok(AxVCpuExitReason::ExternalInterrupt { vector: 0 })
             }
             Trap::Exception(Exception::LoadGuestPageFault)
             | Trap::Exception(Exception::StoreGuestPageFault) => {
                 let fault_addr = self.regs.trap_csrs.htval << 2 | self.regs.trap_csrs.stval & 0x3;
-                Ok(AxVCpuExitReason::NestedPageFault {
-                    addr: GuestPhysAddr::from(fault_addr),
-                    access_flags: MappingFlags::empty(),
-                })
+                let is_store = matches!(
+                    scause.cause(),
+                    Trap::Exception(Exception::StoreGuestPageFault)
+                );
+                Ok(self.handle_guest_page_fault(GuestPhysAddr::from(fault_addr), is_store))
             }
             _ => {
-                panic!(
+                warn!(
                     "Unhandled trap: {:?}, sepc: {:#x}, stval: {:#x}",
                     scause.cause(),
                     self.regs.guest_regs.sepc,
                     self.regs.trap_csrs.stval
                 );
+                Ok(AxVCpuExitReason::UnhandledTrap {
+                    scause: self.regs.trap_csrs.scause,
+                    sepc: self.regs.guest_regs.sepc,
+                    stval: self.regs.trap_csrs.stval,
+                })
+            }
+        }
+    }
+
+    /// Decodes the load or store that faults on `addr` into an MMIO exit. The
+    /// fault is reported as is if the instruction cannot be emulated.
+    fn handle_guest_page_fault(&mut self, addr: GuestPhysAddr, is_store: bool) -> AxVCpuExitReason {
+        let htinst = self.regs.trap_csrs.htinst;
+        let (insn, len) = if htinst != 0 {
+            // Bit 1 of a transformed instruction is cleared if it is
+            // compressed, and bit 0 is cleared for a pseudoinstruction.
+            let len = match htinst & 0b11 {
+                0b11 => Some(4),
+                0b01 => Some(2),
+                _ => None,
+            };
+            (MmioInsn::from_htinst(htinst), len)
+        } else {
+            let raw = fetch_guest_insn(self.regs.guest_regs.sepc);
+            let len = raw.map(|raw| if raw & 0b11 == 0b11 { 4 } else { 2 });
+            (raw.and_then(MmioInsn::decode), len)
+        };
+        match insn {
+            Some(insn) if insn.is_store == is_store => {
+                self.pending_mmio = Some(insn);
+                if is_store {
+                    let shift = 64 - insn.width.size() * 8;
+                    let data = (self.get_gpr(insn.reg) as u64) << shift >> shift;
+                    AxVCpuExitReason::MmioWrite {
+                        addr,
+                        width: insn.width,
+                        data,
+                    }
+                } else {
+                    AxVCpuExitReason::MmioRead {
+                        addr,
+                        width: insn.width,
+                        reg: insn.reg as usize,
+                        reg_width: AccessWidth::Qword,
+                        signed_ext: insn.signed_ext,
+                    }
+                }
+            }
+            _ => {
+                self.fault_insn_len = len;
+                AxVCpuExitReason::NestedPageFault {
+                    addr,
+                    access_flags: if is_store {
+                        MappingFlags::WRITE
+                    } else {
+                        MappingFlags::READ
+                    },
+                }
             }
         }
     }
 
     fn handle_base_function(&mut self, base: BaseFunction) -> AxResult<()> {
         match base {
+            // The guest sees the extensions implemented here, of SBI v2.0.
             BaseFunction::GetSepcificationVersion => {
-                let version = sbi_rt::get_spec_version();
-                self.set_gpr_from_gpr_index(GprIndex::A1, version.major() << 24 | version.minor());
-                debug!(
-                    "GetSepcificationVersion: {}",
-                    version.major() << 24 | version.minor()
-                );
+                self.set_gpr_from_gpr_index(GprIndex::A1, SBI_SPEC_VERSION);
             }
             BaseFunction::GetImplementationID => {
                 let id = sbi_rt::get_sbi_impl_id();
@@ -392,8 +679,8 @@ impl RISCVVCpu {
                 self.set_gpr_from_gpr_index(GprIndex::A1, impl_version);
             }
             BaseFunction::ProbeSbiExtension(extension) => {
-                let extension = sbi_rt::probe_extension(extension as usize).raw;
-                self.set_gpr_from_gpr_index(GprIndex::A1, extension);
+                let available = SbiMessage::probe_extension(extension as usize);
+                self.set_gpr_from_gpr_index(GprIndex::A1, available as usize);
             }
             BaseFunction::GetMachineVendorID => {
                 let mvendorid = sbi_rt::get_mvendorid();
@@ -412,34 +699,77 @@ impl RISCVVCpu {
         Ok(())
     }
 
-    fn handle_rfnc_function(&mut self, rfnc: RemoteFenceFunction) -> AxResult<()> {
-        self.set_gpr_from_gpr_index(GprIndex::A0, 0);
-        match rfnc {
-            RemoteFenceFunction::FenceI {
-                hart_mask,
-                hart_mask_base,
-            } => {
-                let sbi_ret = sbi_rt::remote_fence_i(hart_mask as usize, hart_mask_base as usize);
-                self.set_gpr_from_gpr_index(GprIndex::A0, sbi_ret.error);
-                self.set_gpr_from_gpr_index(GprIndex::A1, sbi_ret.value);
-            }
-            RemoteFenceFunction::RemoteSFenceVMA {
-                hart_mask,
-                hart_mask_base,
+    /// Remote fences are coordinated across the vCPUs by the hypervisor, as
+    /// a full flush of the guest TLB and instruction cache on each target.
+    fn handle_rfnc_function(&mut self, rfnc: RemoteFenceFunction) -> AxVCpuExitReason {
+        self.set_sbi_return(0, 0);
+        let (hart_mask, hart_mask_base) = rfnc.hart_mask();
+        AxVCpuExitReason::RemoteFence {
+            hart_mask,
+            hart_mask_base,
+        }
+    }
+
+    /// The buffers of the debug console are in guest memory, so the console
+    /// is left to the hypervisor.
+    fn handle_dbcn_function(&mut self, dbcn: DebugConsoleFunction) -> AxVCpuExitReason {
+        self.set_sbi_return(0, 0);
+        match dbcn {
+            DebugConsoleFunction::Write {
+                num_bytes,
+                base_addr,
+            } => AxVCpuExitReason::ConsoleWrite {
+                addr: GuestPhysAddr::from(base_addr as usize),
+                len: num_bytes as usize,
+            },
+            DebugConsoleFunction::Read {
+                num_bytes,
+                base_addr,
+            } => AxVCpuExitReason::ConsoleRead {
+                addr: GuestPhysAddr::from(base_addr as usize),
+                len: num_bytes as usize,
+            },
+            DebugConsoleFunction::WriteByte(byte) => AxVCpuExitReason::ConsoleWriteByte { byte },
+        }
+    }
+
+    /// A system reset stops the whole guest, not the host.
+    fn handle_reset_function(&mut self, reset: ResetFunction) -> AxVCpuExitReason {
+        let ResetFunction::Reset { reset_type, reason } = reset;
+        if reason == ResetReason::SystemFailure {
+            warn!("Guest system failure, {:?}", reset_type);
+        }
+        match reset_type {
+            ResetType::Shutdown => AxVCpuExitReason::SystemDown,
+            ResetType::ColdReset | ResetType::WarmReset => AxVCpuExitReason::SystemReset,
+        }
+    }
+
+    fn handle_hsm_function(&mut self, hsm: HsmFunction) -> AxVCpuExitReason {
+        self.set_sbi_return(0, 0);
+        match hsm {
+            HsmFunction::HartStart {
+                hartid,
                 start_addr,
-                size,
-            } => {
-                let sbi_ret = sbi_rt::remote_sfence_vma(
-                    hart_mask as usize,
-                    hart_mask_base as usize,
-                    start_addr as usize,
-                    size as usize,
-                );
-                self.set_gpr_from_gpr_index(GprIndex::A0, sbi_ret.error);
-                self.set_gpr_from_gpr_index(GprIndex::A1, sbi_ret.value);
+                opaque,
+            } => AxVCpuExitReason::CpuUp {
+                target_cpu: hartid,
+                entry_point: GuestPhysAddr::from(start_addr as usize),
+                arg: opaque,
+            },
+            HsmFunction::HartStop => AxVCpuExitReason::CpuDown,
+            HsmFunction::HartStatus { hartid } => {
+                AxVCpuExitReason::CpuStatus { target_cpu: hartid }
+            }
+            // A retentive suspension resumes like `wfi`.
+            HsmFunction::HartSuspend { suspend_type, .. } if suspend_type < 0x8000_0000 => {
+                AxVCpuExitReason::Halt
+            }
+            HsmFunction::HartSuspend { .. } => {
+                self.set_sbi_return(SBI_ERR_NOT_SUPPORTED, 0);
+                AxVCpuExitReason::Nothing
             }
         }
-        Ok(())
     }
 
     fn handle_pmu_function(&mut self, pmu: PmuFunction) -> AxResult<()> {
@@ -471,21 +801,6 @@ impl RISCVVCpu {
     }
 }
 
-/// The width of an access.
-///
-/// Note that the term "word" here refers to 16-bit data, as in the x86 architecture.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-pub enum AccessWidth {
-    /// 8-bit access.
-    Byte,
-    /// 16-bit access.
-    Word,
-    /// 32-bit access.
-    Dword,
-    /// 64-bit access.
-    Qword,
-}
-
 /// The port number of an I/O operation.
 type Port = u16;
 
@@ -511,6 +826,8 @@ pub enum AxVCpuExitReason {
         reg: usize,
         /// The width of the reg to be read
         reg_width: AccessWidth,
+        /// Whether the value read is sign-extended to the width of the reg.
+        signed_ext: bool,
     },
     /// The instruction executed by the vcpu performs a MMIO write operation.
     MmioWrite {
@@ -559,14 +876,100 @@ pub enum AxVCpuExitReason {
     },
     /// The vcpu is halted.
     Halt,
+    /// Another vcpu of the guest is to be started at `entry_point`, with its
+    /// ID and `arg` as the arguments (SBI HSM `hart_start`).
+    ///
+    /// The guest pc has been advanced, and the result of the SBI call is
+    /// success unless set by [`RISCVVCpu::set_sbi_return`].
+    CpuUp {
+        /// The ID of the vcpu to start.
+        target_cpu: u64,
+        /// The guest physical address the vcpu starts at.
+        entry_point: GuestPhysAddr,
+        /// The argument passed to the vcpu.
+        arg: u64,
+    },
     /// The vcpu is powered off.
     ///
     /// This vcpu may be resumed later.
     CpuDown,
+    /// The state of another vcpu is queried (SBI HSM `hart_get_status`).
+    ///
+    /// The guest pc has been advanced, and the state is to be returned with
+    /// [`RISCVVCpu::set_sbi_return`].
+    CpuStatus {
+        /// The ID of the vcpu.
+        target_cpu: u64,
+    },
+    /// Software interrupts are to be sent to the vcpus in the mask (SBI IPI
+    /// `send_ipi`).
+    ///
+    /// The guest pc has been advanced, and the result of the SBI call is
+    /// success unless set by [`RISCVVCpu::set_sbi_return`].
+    SendIpi {
+        /// The mask of the vcpus, starting from `hart_mask_base`.
+        hart_mask: u64,
+        /// The ID of the first vcpu in the mask, or `u64::MAX` for all vcpus.
+        hart_mask_base: u64,
+    },
+    /// The guest TLB and instruction cache of the vcpus in the mask are to be
+    /// flushed (SBI RFENCE), see [`RISCVVCpu::request_flush`].
+    ///
+    /// The guest pc has been advanced, and the result of the SBI call is
+    /// success unless set by [`RISCVVCpu::set_sbi_return`].
+    RemoteFence {
+        /// The mask of the vcpus, starting from `hart_mask_base`.
+        hart_mask: u64,
+        /// The ID of the first vcpu in the mask, or `u64::MAX` for all vcpus.
+        hart_mask_base: u64,
+    },
     /// The system should be powered off.
     ///
     /// This is used to notify the hypervisor that the whole system should be powered off.
     SystemDown,
+    /// The system should be rebooted (SBI SRST cold or warm reboot).
+    SystemReset,
+    /// The bytes of a buffer are to be written to the console (SBI DBCN
+    /// `console_write`).
+    ///
+    /// The guest pc has been advanced, and the number of bytes written is to
+    /// be returned with [`RISCVVCpu::set_sbi_return`].
+    ConsoleWrite {
+        /// The guest physical address of the buffer.
+        addr: GuestPhysAddr,
+        /// The length of the buffer.
+        len: usize,
+    },
+    /// Bytes of the console input are to be read into a buffer (SBI DBCN
+    /// `console_read`).
+    ///
+    /// The guest pc has been advanced, and the number of bytes read is to be
+    /// returned with [`RISCVVCpu::set_sbi_return`].
+    ConsoleRead {
+        /// The guest physical address of the buffer.
+        addr: GuestPhysAddr,
+        /// The length of the buffer.
+        len: usize,
+    },
+    /// A byte is to be written to the console (SBI DBCN
+    /// `console_write_byte`).
+    ///
+    /// The guest pc has been advanced, and the result of the SBI call is
+    /// success unless set by [`RISCVVCpu::set_sbi_return`].
+    ConsoleWriteByte {
+        /// The byte to write.
+        byte: u8,
+    },
+    /// The guest trapped for a reason the vcpu cannot handle, and cannot be
+    /// resumed.
+    UnhandledTrap {
+        /// The `scause` of the trap.
+        scause: usize,
+        /// The guest pc of the trap.
+        sepc: usize,
+        /// The `stval` of the trap.
+        stval: usize,
+    },
     /// Nothing special happened, the vcpu has handled the exit itself.
     ///
     /// This exists to allow the caller to have a chance to check virtual devices/physical devices/virtual interrupts.
@@ -0,0 +1,109 @@
+#define _GNU_SOURCE
+#include <errno.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include <sys/mman.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+
+static void check(int cond, const char *what)
+{
+    if (!cond) {
+        printf("FS test failed: %s\n", what);
+        exit(-1);
+    }
+}
+
+static void check_cwd(const char *expected)
+{
+    char buf[64];
+
+    check(getcwd(buf, sizeof(buf)) != NULL, "getcwd");
+    check(strcmp(buf, expected) == 0, "wrong cwd");
+}
+
+static void test_chdir(void)
+{
+    int fd, status;
+    pid_t pid;
+
+    check(mkdir("/fs_test", 0755) == 0, "mkdir");
+    check(chdir("/fs_test") == 0, "chdir");
+    check_cwd("/fs_test");
+    fd = open("file", O_CREAT | O_WRONLY, 0644);
+    check(fd >= 0, "create relative");
+    close(fd);
+    check(access("/fs_test/file", F_OK) == 0, "created elsewhere");
+
+    pid = fork();
+    check(pid >= 0, "fork");
+    if (pid == 0) {
+        /* Inherited from the parent, but changing it is private. */
+        if (access("file", F_OK) != 0 || chdir("/") != 0)
+            _exit(1);
+        _exit(access("file", F_OK) == 0);
+    }
+    check(waitpid(pid, &status, 0) == pid, "waitpid");
+    check(WIFEXITED(status) && WEXITSTATUS(status) == 0, "child cwd");
+    check_cwd("/fs_test");
+
+    fd = open("/", O_RDONLY | O_DIRECTORY);
+    check(fd >= 0, "open /");
+    check(fchdir(fd) == 0, "fchdir");
+    close(fd);
+    check_cwd("/");
+    check(chdir("fs_test/file") == -1 && errno == ENOTDIR, "chdir to a file");
+    check(chdir("fs_test") == 0, "chdir relative");
+    check_cwd("/fs_test");
+}
+
+static void test_getdents(void)
+{
+    int fd, found = 0;
+    long n, pos;
+    char *buf;
+
+    /* A huge count with a single mapped page. */
+    buf = mmap(NULL, 4096, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+    check(buf != MAP_FAILED, "mmap");
+    fd = open(".", O_RDONLY | O_DIRECTORY);
+    check(fd >= 0, "open .");
+    while ((n = syscall(SYS_getdents64, fd, buf, 1UL << 30)) > 0) {
+        for (pos = 0; pos < n; pos += *(unsigned short *)(buf + pos + 16)) {
+            if (strcmp(buf + pos + 19, "file") == 0)
+                found = 1;
+        }
+    }
+    check(n == 0, "getdents64");
+    check(found, "entry not found");
+    close(fd);
+    munmap(buf, 4096);
+}
+
+static void test_faccessat(void)
+{
+    int fd;
+
+    check(faccessat(AT_FDCWD, "file", R_OK | W_OK, AT_EACCESS) == 0, "AT_EACCESS");
+    check(faccessat(AT_FDCWD, "file", F_OK, 0x8000) == -1 && errno == EINVAL,
+          "unknown flag");
+    check(faccessat(AT_FDCWD, "missing", F_OK, 0) == -1 && errno == ENOENT, "missing file");
+    fd = open("file", O_RDONLY);
+    check(fd >= 0, "open file");
+    check(faccessat(fd, "", R_OK, AT_EMPTY_PATH) == 0, "AT_EMPTY_PATH");
+    close(fd);
+}
+
+int main(void)
+{
+    test_chdir();
+    test_getdents();
+    test_faccessat();
+    unlink("/fs_test/file");
+    rmdir("/fs_test");
+    printf("FS tests passed!\n");
+    return 0;
+}
@@ -4,6 +4,7 @@
 use core::panic::PanicInfo;
 
 
+#[cfg(target_arch = "riscv64")]
 #[no_mangle]
 unsafe extern "C" fn _start() -> ! {
     core::arch::asm!(
@@ -38,6 +39,51 @@ unsafe extern "C" fn _start() -> ! {
     )
 }
 
+#[cfg(target_arch = "aarch64")]
+#[no_mangle]
+unsafe extern "C" fn _start() -> ! {
+    core::arch::asm!(
+        "movz x9, #0x6568",
+        "movk x9, #0x6c6c, lsl #16",
+        "movk x9, #0x0a6f, lsl #32", // "hello\n\0"
+        "str x9, [sp, #-16]!",
+        "mov x0, #1",
+        "mov x1, sp",
+        "mov x2, #7",
+        "mov x8, #64",
+        "svc #0", // write "hello\n"
+        "mov x8, #93",
+        "cmp x0, x2", // check return value
+        "csetm x0, ne",
+        "svc #0", // exit with 0 or -1
+        options(noreturn)
+    )
+}
+
+#[cfg(target_arch = "x86_64")]
+#[no_mangle]
+unsafe extern "C" fn _start() -> ! {
+    core::arch::asm!(
+        "mov rax, 0x0a6f6c6c6568", // "hello\n\0"
+        "push rax",
+        "mov edi, 1",
+        "mov rsi, rsp",
+        "mov edx, 7",
+        "mov eax, 1",
+        "syscall", // write "hello\n"
+        "cmp rax, rdx", // check return value
+        "jne 1f",
+        "xor edi, edi",
+        "mov eax, 60",
+        "syscall", // exit
+        "1:",
+        "mov edi, -1",
+        "mov eax, 60",
+        "syscall", // error exit
+        options(noreturn)
+    )
+}
+
 #[panic_handler]
 fn panic(_info: &PanicInfo) -> ! {
     loop {}
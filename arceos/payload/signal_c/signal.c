@@ -0,0 +1,125 @@
+#include <setjmp.h>
+#include <signal.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include <sys/wait.h>
+
+static volatile sig_atomic_t usr1_count;
+static volatile void *fault_addr;
+static sigjmp_buf fault_env;
+
+static void check(int cond, const char *what)
+{
+    if (!cond) {
+        printf("Signal test failed: %s\n", what);
+        exit(-1);
+    }
+}
+
+static void on_usr1(int sig)
+{
+    usr1_count++;
+}
+
+static void on_segv(int sig, siginfo_t *info, void *ucontext)
+{
+    fault_addr = info->si_addr;
+    siglongjmp(fault_env, 1);
+}
+
+static void test_handler(void)
+{
+    struct sigaction sa;
+
+    memset(&sa, 0, sizeof(sa));
+    sa.sa_handler = on_usr1;
+    check(sigaction(SIGUSR1, &sa, NULL) == 0, "sigaction");
+    check(kill(getpid(), SIGUSR1) == 0, "kill self");
+    check(usr1_count == 1, "handler not run");
+    check(raise(SIGUSR1) == 0, "raise");
+    check(usr1_count == 2, "handler not run on raise");
+}
+
+static void test_blocked(void)
+{
+    sigset_t set, pending;
+
+    sigemptyset(&set);
+    sigaddset(&set, SIGUSR1);
+    check(sigprocmask(SIG_BLOCK, &set, NULL) == 0, "sigprocmask");
+    kill(getpid(), SIGUSR1);
+    check(usr1_count == 2, "blocked signal delivered");
+    check(sigpending(&pending) == 0 && sigismember(&pending, SIGUSR1), "not pending");
+    sigprocmask(SIG_UNBLOCK, &set, NULL);
+    check(usr1_count == 3, "unblocked signal not delivered");
+}
+
+static void test_fault(void)
+{
+    struct sigaction sa;
+
+    memset(&sa, 0, sizeof(sa));
+    sa.sa_sigaction = on_segv;
+    sa.sa_flags = SA_SIGINFO;
+    check(sigaction(SIGSEGV, &sa, NULL) == 0, "sigaction SIGSEGV");
+    if (sigsetjmp(fault_env, 1) == 0) {
+        *(volatile int *)8 = 1;
+        check(0, "no SIGSEGV");
+    }
+    check(fault_addr == (void *)8, "wrong si_addr");
+}
+
+static void test_kill_child(void)
+{
+    int status;
+    pid_t pid = fork();
+
+    check(pid >= 0, "fork");
+    if (pid == 0) {
+        /* Interrupted by the timer, never enters the kernel itself. */
+        for (;;)
+            ;
+    }
+    check(kill(pid, SIGTERM) == 0, "kill child");
+    check(waitpid(pid, &status, 0) == pid, "waitpid");
+    check(WIFSIGNALED(status) && WTERMSIG(status) == SIGTERM, "child not terminated");
+}
+
+static void test_kill_all(void)
+{
+    int status;
+    pid_t zombie, pid;
+
+    zombie = fork();
+    check(zombie >= 0, "fork");
+    if (zombie == 0)
+        _exit(7);
+    pid = fork();
+    check(pid >= 0, "fork");
+    if (pid == 0) {
+        for (;;)
+            sleep(1);
+    }
+    /* Let the first child become a zombie. */
+    sleep(1);
+    check(kill(-1, SIGUSR1) == 0, "kill -1");
+    check(usr1_count == 3, "kill -1 signaled the caller");
+    check(waitpid(pid, &status, 0) == pid, "waitpid");
+    check(WIFSIGNALED(status) && WTERMSIG(status) == SIGUSR1, "kill -1 missed a child");
+    check(waitpid(zombie, &status, 0) == zombie, "waitpid zombie");
+    check(WIFEXITED(status) && WEXITSTATUS(status) == 7, "zombie exit status");
+    check(kill(-1, SIGUSR1) == -1, "kill -1 without targets");
+}
+
+int main(void)
+{
+    test_handler();
+    test_blocked();
+    test_fault();
+    test_kill_child();
+    test_kill_all();
+    printf("Signal tests passed!\n");
+    return 0;
+}
@@ -6,23 +6,26 @@ extern crate log;
 #[macro_use]
 extern crate alloc;
 extern crate axstd as std;
-use alloc::string::ToString;
 use riscv_vcpu::AxVCpuExitReason;
 use axerrno::{ax_err_type, AxResult};
-use memory_addr::VirtAddr;
+use memory_addr::{align_down_4k, VirtAddr};
 use alloc::string::String;
 use std::fs::File;
+use std::io::Write;
 use riscv_vcpu::RISCVVCpu;
 use riscv_vcpu::AxVCpuExitReason::NestedPageFault;
+use axvmconfig::VmConfig;
+use riscv_vcpu::sbi::{SBI_ERR_ALREADY_AVAILABLE, SBI_ERR_INAVLID_PARAM};
 
 const VM_ASPACE_BASE: usize = 0x0;
 const VM_ASPACE_SIZE: usize = 0x7fff_ffff_f000;
-const PHY_MEM_START: usize = 0x8000_0000;
-const PHY_MEM_SIZE: usize = 0x100_0000;
-const KERNEL_BASE: usize = 0x8020_0000;
+/// The configuration of the VM on the disk.
+const VM_CONFIG: &str = "/vm/h_3_0.toml";
+/// The maximum number of bytes of an SBI debug console call.
+const CONSOLE_BUF_SIZE: usize = 0x1000;
 
 use axmm::AddrSpace;
-use axhal::paging::MappingFlags;
+use axhal::paging::{MappingFlags, PageSize};
 
 #[no_mangle]
 fn main() {
@@ -31,43 +34,105 @@ fn main() {
         riscv_vcpu::setup_csrs();
     }
 
+    let config = load_vm_config(VM_CONFIG).expect("Failed to load VM config");
+    if config.cpu_num() > 1 {
+        warn!("VM {}: only vcpu 0 is run", config.name);
+    }
+
     // Setup AddressSpace and regions.
     let mut aspace = AddrSpace::new_empty(VirtAddr::from(VM_ASPACE_BASE), VM_ASPACE_SIZE).unwrap();
 
-    // Physical memory region. Full access flags.
+    // Physical memory regions. Full access flags.
     let mapping_flags = MappingFlags::from_bits(0xf).unwrap();
-    aspace.map_alloc(PHY_MEM_START.into(), PHY_MEM_SIZE, mapping_flags, true).unwrap();
+    for region in config.memory.iter() {
+        // Backed by 2M huge pages to save page table entries, if aligned.
+        let page_size = if (region.gpa | region.size) % usize::from(PageSize::Size2M) == 0 {
+            PageSize::Size2M
+        } else {
+            PageSize::Size4K
+        };
+        aspace.map_alloc_huge(region.gpa.into(), region.size, mapping_flags, true, page_size).unwrap();
+    }
 
     // Load corresponding images for VM.
     info!("VM created success, loading images...");
-    let image_fname = "/sbin/u_6_0_riscv64-qemu-virt.bin";
-    load_vm_image(image_fname.to_string(), KERNEL_BASE.into(), &aspace).expect("Failed to load VM images");
+    for image in config.images.iter() {
+        load_vm_image(image.path.clone(), image.load_addr.into(), &aspace).expect("Failed to load VM images");
+    }
 
     // Create VCpus.
     let mut arch_vcpu = RISCVVCpu::init();
 
     // Setup VCpus.
-    info!("bsp_entry: {:#x}; ept: {:#x}", KERNEL_BASE, aspace.page_table_root());
-    arch_vcpu.set_entry(KERNEL_BASE.into()).unwrap();
+    info!("bsp_entry: {:#x}; ept: {:#x}", config.entry, aspace.page_table_root());
+    arch_vcpu.set_entry(config.entry.into()).unwrap();
     arch_vcpu.set_ept_root(aspace.page_table_root()).unwrap();
 
     loop {
         match vcpu_run(&mut arch_vcpu) {
             Ok(exit_reason) => match exit_reason {
                 AxVCpuExitReason::Nothing => {},
-                NestedPageFault{addr, access_flags} => {
-                    debug!("addr {:#x} access {:#x}", addr, access_flags);
-                    assert_eq!(addr, 0x2200_0000.into(), "Now we ONLY handle pflash#2.");
+                AxVCpuExitReason::RemoteFence{..} => {
+                    // The only vcpu is the target.
+                    arch_vcpu.request_flush();
+                },
+                AxVCpuExitReason::ExternalInterrupt{..} | AxVCpuExitReason::Halt => {
+                    // Handled by the host once the irqs are enabled again.
+                },
+                // The only vcpu is 0, which is always started.
+                AxVCpuExitReason::CpuUp{target_cpu, ..} => {
+                    let err = if target_cpu == 0 { SBI_ERR_ALREADY_AVAILABLE } else { SBI_ERR_INAVLID_PARAM };
+                    arch_vcpu.set_sbi_return(err, 0);
+                },
+                AxVCpuExitReason::CpuStatus{target_cpu} => {
+                    let err = if target_cpu == 0 { 0 } else { SBI_ERR_INAVLID_PARAM };
+                    arch_vcpu.set_sbi_return(err, 0);
+                },
+                AxVCpuExitReason::SendIpi{hart_mask, hart_mask_base} => {
+                    if hart_mask_base == u64::MAX || (hart_mask_base == 0 && hart_mask & 1 != 0) {
+                        arch_vcpu.inject_soft_interrupt();
+                    }
+                },
+                AxVCpuExitReason::ConsoleWrite{addr, len} => {
+                    match console_write(&aspace, addr, len) {
+                        Some(count) => arch_vcpu.set_sbi_return(0, count),
+                        None => arch_vcpu.set_sbi_return(SBI_ERR_INAVLID_PARAM, 0),
+                    }
+                },
+                AxVCpuExitReason::ConsoleRead{addr, len} => {
+                    // There is no input for the guest, so no byte is read.
+                    if aspace.translated_byte_buffer(addr, len.min(CONSOLE_BUF_SIZE)).is_none() {
+                        arch_vcpu.set_sbi_return(SBI_ERR_INAVLID_PARAM, 0);
+                    }
+                },
+                AxVCpuExitReason::ConsoleWriteByte{byte} => {
+                    let _ = std::io::stdout().write_all(&[byte]);
+                },
+                AxVCpuExitReason::SystemDown | AxVCpuExitReason::SystemReset | AxVCpuExitReason::CpuDown => {
+                    info!("VM stopped: {:?}", exit_reason);
+                    break;
+                },
+                // Loads and stores on pflash are reported as MMIO accesses
+                // unless they cannot be decoded.
+                NestedPageFault{addr, ..}
+                | AxVCpuExitReason::MmioRead{addr, ..}
+                | AxVCpuExitReason::MmioWrite{addr, ..} => {
+                    debug!("addr {:#x} exit {:?}", addr, exit_reason);
+                    let addr = align_down_4k(addr.as_usize());
+                    assert!(
+                        config.passthrough.iter().any(|region| (region.gpa..region.gpa + region.size).contains(&addr)),
+                        "Now we ONLY handle the passthrough regions, e.g., pflash#2."
+                    );
                     let mapping_flags = MappingFlags::from_bits(0xf).unwrap();
                     // Passthrough-Mode
-                    let _ = aspace.map_linear(addr, addr.as_usize().into(), 4096, mapping_flags);
+                    let _ = aspace.map_linear(addr.into(), addr.into(), 4096, mapping_flags);
 
                     /*
                     // Emulator-Mode
                     // Pretend to load file to fill buffer.
                     let buf = "pfld";
-                    aspace.map_alloc(addr, 4096, mapping_flags, true);
-                    aspace.write(addr, buf.as_bytes());
+                    aspace.map_alloc(addr.into(), 4096, mapping_flags, true);
+                    aspace.write(addr.into(), buf.as_bytes());
                     */
                 },
                 _ => {
@@ -81,6 +146,23 @@ fn main() {
     }
 }
 
+/// Writes the bytes at `addr` in guest memory to the console, and returns the
+/// number of bytes written, or `None` if they are not in guest memory.
+fn console_write(aspace: &AddrSpace, addr: VirtAddr, len: usize) -> Option<usize> {
+    let len = len.min(CONSOLE_BUF_SIZE);
+    let mut stdout = std::io::stdout();
+    for buf in aspace.translated_byte_buffer(addr, len)? {
+        let _ = stdout.write_all(buf);
+    }
+    Some(len)
+}
+
+fn load_vm_config(path: &str) -> AxResult<VmConfig> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|err| ax_err_type!(NotFound, format!("Failed to read {}, err {:?}, please check your disk.img", path, err)))?;
+    VmConfig::from_toml(&text)
+}
+
 fn load_vm_image(image_path: String, image_load_gpa: VirtAddr, aspace: &AddrSpace) -> AxResult {
     use std::io::{BufReader, Read};
     let (image_file, image_size) = open_image_file(image_path.as_str())?;
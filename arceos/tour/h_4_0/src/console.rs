@@ -0,0 +1,145 @@
+//! Virtual consoles of the guests, multiplexed on the host serial port.
+//!
+//! Only the output of the active console is written to the host, while the
+//! others are buffered until they become active. The host input goes to the
+//! active console.
+//!
+//! Press `Ctrl-A` followed by:
+//!
+//! - a digit `n`: switch to console `n`;
+//! - `n`: switch to the next console;
+//! - `Ctrl-A`: send a `Ctrl-A` to the active console.
+
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use axhal::console::{getchar, putchar, write_bytes};
+
+/// The escape key, `Ctrl-A`.
+const ESCAPE: u8 = 0x01;
+
+/// The capacity of the input and output buffers of each console.
+const BUF_SIZE: usize = 4096;
+
+static CONSOLES: Mutex<Vec<Arc<VConsole>>> = Mutex::new(Vec::new());
+static ACTIVE: AtomicUsize = AtomicUsize::new(0);
+static ESCAPED: AtomicBool = AtomicBool::new(false);
+
+/// A bounded FIFO that drops the oldest bytes when it is full.
+struct RingBuffer(VecDeque<u8>);
+
+impl RingBuffer {
+    const fn new() -> Self {
+        Self(VecDeque::new())
+    }
+
+    fn push(&mut self, c: u8) {
+        if self.0.len() == BUF_SIZE {
+            self.0.pop_front();
+        }
+        self.0.push_back(c);
+    }
+}
+
+/// The console of a guest.
+pub struct VConsole {
+    id: usize,
+    name: String,
+    input: Mutex<RingBuffer>,
+    output: Mutex<RingBuffer>,
+}
+
+impl VConsole {
+    fn is_active(&self) -> bool {
+        ACTIVE.load(Ordering::Acquire) == self.id
+    }
+
+    /// Writes a byte from the guest, which is buffered if the console is not
+    /// active.
+    pub fn write_byte(&self, c: u8) {
+        if self.is_active() {
+            putchar(c);
+        } else {
+            self.output.lock().push(c);
+        }
+    }
+
+    /// Reads a byte of the host input to the guest.
+    pub fn read_byte(&self) -> Option<u8> {
+        poll_input();
+        self.input.lock().0.pop_front()
+    }
+
+    /// Returns whether there is input to read.
+    pub fn has_input(&self) -> bool {
+        poll_input();
+        !self.input.lock().0.is_empty()
+    }
+}
+
+/// Creates a console named `name` for a guest, or returns the existing one
+/// with that name, e.g. for a rebooted guest. The first console is active.
+pub fn new_console(name: &str) -> Arc<VConsole> {
+    let mut consoles = CONSOLES.lock();
+    if let Some(console) = consoles.iter().find(|console| console.name == name) {
+        return console.clone();
+    }
+    let console = Arc::new(VConsole {
+        id: consoles.len(),
+        name: name.into(),
+        input: Mutex::new(RingBuffer::new()),
+        output: Mutex::new(RingBuffer::new()),
+    });
+    consoles.push(console.clone());
+    console
+}
+
+fn switch_to(id: usize) {
+    let consoles = CONSOLES.lock();
+    let Some(console) = consoles.get(id) else {
+        return;
+    };
+    ACTIVE.store(id, Ordering::Release);
+    let banner = alloc::format!("\r\n[console {}: {}]\r\n", id, console.name);
+    write_bytes(banner.as_bytes());
+    let mut output = console.output.lock();
+    let (a, b) = output.0.as_slices();
+    write_bytes(a);
+    write_bytes(b);
+    output.0.clear();
+}
+
+fn send_input(c: u8) {
+    let consoles = CONSOLES.lock();
+    if let Some(console) = consoles.get(ACTIVE.load(Ordering::Acquire)) {
+        console.input.lock().push(c);
+    }
+}
+
+/// Moves the pending host input to the active console, and handles the
+/// escape sequences.
+pub fn poll_input() {
+    while let Some(c) = getchar() {
+        if !ESCAPED.swap(false, Ordering::AcqRel) {
+            if c == ESCAPE {
+                ESCAPED.store(true, Ordering::Release);
+            } else {
+                send_input(c);
+            }
+            continue;
+        }
+        match c {
+            ESCAPE => send_input(c),
+            b'0'..=b'9' => switch_to((c - b'0') as usize),
+            b'n' => {
+                let count = CONSOLES.lock().len().max(1);
+                switch_to((ACTIVE.load(Ordering::Acquire) + 1) % count);
+            }
+            _ => {}
+        }
+    }
+}
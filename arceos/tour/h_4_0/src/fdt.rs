@@ -0,0 +1,309 @@
+//! Generation of the flattened device tree (FDT) of a VM, which tells the
+//! guest its memory, vCPUs and emulated devices.
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use axerrno::{ax_err_type, AxResult};
+use axhal::time::{nanos_to_ticks, NANOS_PER_SEC};
+use axvmconfig::{EmuDevType, VmConfig};
+use memory_addr::align_down_4k;
+
+use crate::uart16550::Uart16550;
+use crate::virtio;
+use crate::vplic::{self, VPlic};
+
+const FDT_MAGIC: u32 = 0xd00d_feed;
+const FDT_VERSION: u32 = 17;
+const FDT_LAST_COMP_VERSION: u32 = 16;
+const FDT_HEADER_SIZE: usize = 40;
+/// The size of an entry of the memory reservation block, as well as of its
+/// terminator.
+const FDT_RSVMAP_ENTRY_SIZE: usize = 16;
+
+const FDT_BEGIN_NODE: u32 = 0x1;
+const FDT_END_NODE: u32 = 0x2;
+const FDT_PROP: u32 = 0x3;
+const FDT_END: u32 = 0x9;
+
+/// The frequency of the clock of the emulated UART, as in QEMU `virt`.
+const UART_CLOCK_FREQ: u32 = 0x38_4000;
+
+/// The interrupts of a hart connected to the PLIC: the supervisor and the
+/// machine external interrupts.
+const IRQ_S_EXT: u32 = 9;
+const IRQ_M_EXT: u32 = 11;
+
+/// Builds the structure and strings blocks of a device tree.
+struct FdtWriter {
+    structs: Vec<u8>,
+    strings: Vec<u8>,
+    string_offsets: BTreeMap<String, u32>,
+}
+
+impl FdtWriter {
+    fn new() -> Self {
+        Self {
+            structs: Vec::new(),
+            strings: Vec::new(),
+            string_offsets: BTreeMap::new(),
+        }
+    }
+
+    fn push_u32(&mut self, val: u32) {
+        self.structs.extend_from_slice(&val.to_be_bytes());
+    }
+
+    fn push_bytes_aligned(&mut self, bytes: &[u8]) {
+        self.structs.extend_from_slice(bytes);
+        while self.structs.len() % 4 != 0 {
+            self.structs.push(0);
+        }
+    }
+
+    fn begin_node(&mut self, name: &str) {
+        self.push_u32(FDT_BEGIN_NODE);
+        let mut bytes = Vec::from(name.as_bytes());
+        bytes.push(0);
+        self.push_bytes_aligned(&bytes);
+    }
+
+    fn end_node(&mut self) {
+        self.push_u32(FDT_END_NODE);
+    }
+
+    fn string_offset(&mut self, name: &str) -> u32 {
+        if let Some(&off) = self.string_offsets.get(name) {
+            return off;
+        }
+        let off = self.strings.len() as u32;
+        self.strings.extend_from_slice(name.as_bytes());
+        self.strings.push(0);
+        self.string_offsets.insert(name.into(), off);
+        off
+    }
+
+    fn property(&mut self, name: &str, val: &[u8]) {
+        let name_off = self.string_offset(name);
+        self.push_u32(FDT_PROP);
+        self.push_u32(val.len() as u32);
+        self.push_u32(name_off);
+        self.push_bytes_aligned(val);
+    }
+
+    fn property_null(&mut self, name: &str) {
+        self.property(name, &[]);
+    }
+
+    fn property_u32(&mut self, name: &str, val: u32) {
+        self.property(name, &val.to_be_bytes());
+    }
+
+    fn property_cells(&mut self, name: &str, cells: &[u32]) {
+        let bytes: Vec<u8> = cells.iter().flat_map(|c| c.to_be_bytes()).collect();
+        self.property(name, &bytes);
+    }
+
+    /// A `reg` property of one region, with 2 address and 2 size cells.
+    fn property_reg(&mut self, addr: usize, size: usize) {
+        let (addr, size) = (addr as u64, size as u64);
+        let cells = [
+            (addr >> 32) as u32,
+            addr as u32,
+            (size >> 32) as u32,
+            size as u32,
+        ];
+        self.property_cells("reg", &cells);
+    }
+
+    fn property_string(&mut self, name: &str, val: &str) {
+        self.property_strings(name, &[val]);
+    }
+
+    fn property_strings(&mut self, name: &str, vals: &[&str]) {
+        let mut bytes = Vec::new();
+        for val in vals {
+            bytes.extend_from_slice(val.as_bytes());
+            bytes.push(0);
+        }
+        self.property(name, &bytes);
+    }
+
+    /// The size of the blob [`FdtWriter::finish`] returns with `rsv_num`
+    /// reserved regions.
+    fn total_size(&self, rsv_num: usize) -> usize {
+        // The structure block is terminated with `FDT_END`.
+        FDT_HEADER_SIZE
+            + (rsv_num + 1) * FDT_RSVMAP_ENTRY_SIZE
+            + self.structs.len()
+            + 4
+            + self.strings.len()
+    }
+
+    /// Returns the blob, with the header and the memory reservation block of
+    /// the `(address, size)` regions.
+    fn finish(mut self, boot_cpuid: u32, reserved: &[(usize, usize)]) -> Vec<u8> {
+        let total_size = self.total_size(reserved.len());
+        self.push_u32(FDT_END);
+        let off_rsvmap = FDT_HEADER_SIZE;
+        let off_struct = off_rsvmap + (reserved.len() + 1) * FDT_RSVMAP_ENTRY_SIZE;
+        let off_strings = off_struct + self.structs.len();
+
+        let mut blob = Vec::with_capacity(total_size);
+        for val in [
+            FDT_MAGIC,
+            total_size as u32,
+            off_struct as u32,
+            off_strings as u32,
+            off_rsvmap as u32,
+            FDT_VERSION,
+            FDT_LAST_COMP_VERSION,
+            boot_cpuid,
+            self.strings.len() as u32,
+            self.structs.len() as u32,
+        ] {
+            blob.extend_from_slice(&val.to_be_bytes());
+        }
+        for &(addr, size) in reserved {
+            blob.extend_from_slice(&(addr as u64).to_be_bytes());
+            blob.extend_from_slice(&(size as u64).to_be_bytes());
+        }
+        blob.resize(off_struct, 0);
+        blob.extend_from_slice(&self.structs);
+        blob.extend_from_slice(&self.strings);
+        debug_assert_eq!(blob.len(), total_size);
+        blob
+    }
+}
+
+/// Generates the device tree blob of a VM, and places it in guest memory:
+/// at `dtb_addr` of the config, or at the end of the first memory region.
+///
+/// Returns the blob and its guest physical address. The blob reserves the
+/// memory it is placed in, so that the guest does not overwrite it.
+pub fn generate_fdt(config: &VmConfig) -> AxResult<(Vec<u8>, usize)> {
+    let cpu_num = config.cpu_num();
+    // The phandles of the interrupt controllers of the harts, then of the PLIC.
+    let intc_phandle = |cpu_id: usize| cpu_id as u32 + 1;
+    let plic_phandle = cpu_num as u32 + 1;
+    let plic = config
+        .emulated
+        .iter()
+        .find(|dev| dev.ty == EmuDevType::Plic);
+
+    let mut fdt = FdtWriter::new();
+    fdt.begin_node("");
+    fdt.property_u32("#address-cells", 2);
+    fdt.property_u32("#size-cells", 2);
+    fdt.property_string("compatible", "riscv-virtio");
+    fdt.property_string("model", "riscv-virtio,qemu");
+
+    fdt.begin_node("chosen");
+    if let Some(bootargs) = &config.bootargs {
+        fdt.property_string("bootargs", bootargs);
+    }
+    let uart = config
+        .emulated
+        .iter()
+        .find(|dev| dev.ty == EmuDevType::Uart16550);
+    if let Some(uart) = uart {
+        fdt.property_string("stdout-path", &format!("/soc/serial@{:x}", uart.gpa));
+    }
+    fdt.end_node();
+
+    for region in config.memory.iter() {
+        fdt.begin_node(&format!("memory@{:x}", region.gpa));
+        fdt.property_string("device_type", "memory");
+        fdt.property_reg(region.gpa, region.size);
+        fdt.end_node();
+    }
+
+    fdt.begin_node("cpus");
+    fdt.property_u32("#address-cells", 1);
+    fdt.property_u32("#size-cells", 0);
+    fdt.property_u32("timebase-frequency", nanos_to_ticks(NANOS_PER_SEC) as u32);
+    for cpu_id in 0..cpu_num {
+        fdt.begin_node(&format!("cpu@{}", cpu_id));
+        fdt.property_string("device_type", "cpu");
+        fdt.property_u32("reg", cpu_id as u32);
+        fdt.property_string("status", "okay");
+        fdt.property_string("compatible", "riscv");
+        fdt.property_string("riscv,isa", "rv64imafdc");
+        fdt.property_string("mmu-type", "riscv,sv39");
+        fdt.begin_node("interrupt-controller");
+        fdt.property_u32("#interrupt-cells", 1);
+        fdt.property_null("interrupt-controller");
+        fdt.property_string("compatible", "riscv,cpu-intc");
+        fdt.property_u32("phandle", intc_phandle(cpu_id));
+        fdt.end_node();
+        fdt.end_node();
+    }
+    fdt.end_node();
+
+    fdt.begin_node("soc");
+    fdt.property_u32("#address-cells", 2);
+    fdt.property_u32("#size-cells", 2);
+    fdt.property_string("compatible", "simple-bus");
+    fdt.property_null("ranges");
+    for dev in config.emulated.iter() {
+        match dev.ty {
+            EmuDevType::Plic => {
+                fdt.begin_node(&format!("plic@{:x}", dev.gpa));
+                fdt.property_strings("compatible", &["sifive,plic-1.0.0", "riscv,plic0"]);
+                fdt.property_reg(dev.gpa, dev.size.unwrap_or(VPlic::SIZE));
+                fdt.property_u32("#address-cells", 0);
+                fdt.property_u32("#interrupt-cells", 1);
+                fdt.property_null("interrupt-controller");
+                fdt.property_u32("riscv,ndev", vplic::NUM_SOURCES as u32 - 1);
+                // The M-mode and the S-mode contexts of each hart.
+                let contexts: Vec<u32> = (0..cpu_num)
+                    .flat_map(|cpu_id| {
+                        let phandle = intc_phandle(cpu_id);
+                        [phandle, IRQ_M_EXT, phandle, IRQ_S_EXT]
+                    })
+                    .collect();
+                fdt.property_cells("interrupts-extended", &contexts);
+                fdt.property_u32("phandle", plic_phandle);
+                fdt.end_node();
+            }
+            EmuDevType::Uart16550 => {
+                fdt.begin_node(&format!("serial@{:x}", dev.gpa));
+                fdt.property_string("compatible", "ns16550a");
+                fdt.property_reg(dev.gpa, dev.size.unwrap_or(Uart16550::SIZE));
+                fdt.property_u32("clock-frequency", UART_CLOCK_FREQ);
+                if let (Some(irq), Some(_)) = (dev.irq, plic) {
+                    fdt.property_u32("interrupt-parent", plic_phandle);
+                    fdt.property_u32("interrupts", irq as u32);
+                }
+                fdt.end_node();
+            }
+            EmuDevType::VirtioBlk | EmuDevType::VirtioConsole => {
+                fdt.begin_node(&format!("virtio_mmio@{:x}", dev.gpa));
+                fdt.property_string("compatible", "virtio,mmio");
+                fdt.property_reg(dev.gpa, dev.size.unwrap_or(virtio::MMIO_SIZE));
+                if let (Some(irq), Some(_)) = (dev.irq, plic) {
+                    fdt.property_u32("interrupt-parent", plic_phandle);
+                    fdt.property_u32("interrupts", irq as u32);
+                }
+                fdt.end_node();
+            }
+        }
+    }
+    fdt.end_node();
+
+    fdt.end_node();
+
+    let size = fdt.total_size(1);
+    let dtb_addr = match config.dtb_addr {
+        Some(addr) => addr,
+        None => {
+            let region = config
+                .memory
+                .first()
+                .ok_or_else(|| ax_err_type!(InvalidData, "no memory region"))?;
+            align_down_4k(region.gpa + region.size - size)
+        }
+    };
+    Ok((fdt.finish(0, &[(dtb_addr, size)]), dtb_addr))
+}
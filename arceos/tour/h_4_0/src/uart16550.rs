@@ -0,0 +1,160 @@
+//! Emulated NS16550A UART, with byte-wide registers (`reg-shift = 0`).
+//!
+//! Transmitted bytes go to the virtual console of the guest, and received
+//! bytes come from it. The baud rate and line settings are kept but have no
+//! effect.
+
+use alloc::sync::Arc;
+use axerrno::AxResult;
+use riscv_vcpu::AccessWidth;
+use std::sync::Mutex;
+
+use crate::console::VConsole;
+use crate::vmdev::EmuDev;
+
+/// Receiver buffer (read), transmitter holding (write), or divisor latch low.
+const RBR_THR_DLL: usize = 0;
+/// Interrupt enable, or divisor latch high.
+const IER_DLM: usize = 1;
+/// Interrupt identification (read), or FIFO control (write).
+const IIR_FCR: usize = 2;
+/// Line control.
+const LCR: usize = 3;
+/// Modem control.
+const MCR: usize = 4;
+/// Line status.
+const LSR: usize = 5;
+/// Modem status.
+const MSR: usize = 6;
+/// Scratch.
+const SCR: usize = 7;
+
+const IER_RDI: u8 = 0x01;
+const IER_THRI: u8 = 0x02;
+
+const IIR_NO_INT: u8 = 0x01;
+const IIR_THRI: u8 = 0x02;
+const IIR_RDI: u8 = 0x04;
+const IIR_FIFO_ENABLED: u8 = 0xc0;
+
+const FCR_FIFO_ENABLE: u8 = 0x01;
+
+const LCR_DLAB: u8 = 0x80;
+
+const LSR_DR: u8 = 0x01;
+const LSR_THRE: u8 = 0x20;
+const LSR_TEMT: u8 = 0x40;
+
+/// Data carrier detect, data set ready and clear to send.
+const MSR_DCD_DSR_CTS: u8 = 0xb0;
+
+#[derive(Default)]
+struct UartRegs {
+    dll: u8,
+    dlm: u8,
+    ier: u8,
+    fcr: u8,
+    lcr: u8,
+    mcr: u8,
+    scr: u8,
+    /// The transmitter empty interrupt is pending, until `IIR` is read or
+    /// `THR` is written.
+    thri_pending: bool,
+}
+
+/// An emulated 16550 UART connected to a virtual console.
+pub struct Uart16550 {
+    console: Arc<VConsole>,
+    regs: Mutex<UartRegs>,
+}
+
+impl Uart16550 {
+    /// The size of the register region.
+    pub const SIZE: usize = 0x100;
+
+    /// Creates a UART connected to `console`.
+    pub fn new(console: Arc<VConsole>) -> Self {
+        Self {
+            console,
+            regs: Mutex::new(UartRegs::default()),
+        }
+    }
+
+    fn iir(&self, regs: &UartRegs) -> u8 {
+        let id = if regs.ier & IER_RDI != 0 && self.console.has_input() {
+            IIR_RDI
+        } else if regs.ier & IER_THRI != 0 && regs.thri_pending {
+            IIR_THRI
+        } else {
+            IIR_NO_INT
+        };
+        if regs.fcr & FCR_FIFO_ENABLE != 0 {
+            id | IIR_FIFO_ENABLED
+        } else {
+            id
+        }
+    }
+}
+
+impl EmuDev for Uart16550 {
+    fn read(&self, offset: usize, _width: AccessWidth) -> AxResult<u64> {
+        let mut regs = self.regs.lock();
+        let dlab = regs.lcr & LCR_DLAB != 0;
+        let val = match offset {
+            RBR_THR_DLL if dlab => regs.dll,
+            RBR_THR_DLL => self.console.read_byte().unwrap_or(0),
+            IER_DLM if dlab => regs.dlm,
+            IER_DLM => regs.ier,
+            IIR_FCR => {
+                let iir = self.iir(&regs);
+                if iir & 0x0f == IIR_THRI {
+                    regs.thri_pending = false;
+                }
+                iir
+            }
+            LCR => regs.lcr,
+            MCR => regs.mcr,
+            LSR => {
+                let dr = if self.console.has_input() { LSR_DR } else { 0 };
+                dr | LSR_THRE | LSR_TEMT
+            }
+            MSR => MSR_DCD_DSR_CTS,
+            SCR => regs.scr,
+            _ => 0,
+        };
+        Ok(val as u64)
+    }
+
+    fn write(&self, offset: usize, _width: AccessWidth, data: u64) -> AxResult {
+        let mut regs = self.regs.lock();
+        let dlab = regs.lcr & LCR_DLAB != 0;
+        let val = data as u8;
+        match offset {
+            RBR_THR_DLL if dlab => regs.dll = val,
+            RBR_THR_DLL => {
+                self.console.write_byte(val);
+                // The byte is sent at once, so the transmitter is empty again.
+                regs.thri_pending = true;
+            }
+            IER_DLM if dlab => regs.dlm = val,
+            IER_DLM => {
+                // Enabling the interrupt with an empty transmitter raises it.
+                if val & IER_THRI != 0 && regs.ier & IER_THRI == 0 {
+                    regs.thri_pending = true;
+                }
+                regs.ier = val & 0x0f;
+            }
+            IIR_FCR => regs.fcr = val,
+            LCR => regs.lcr = val,
+            MCR => regs.mcr = val,
+            SCR => regs.scr = val,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn irq_level(&self) -> bool {
+        let regs = self.regs.lock();
+        self.iir(&regs) & IIR_NO_INT == 0
+    }
+}
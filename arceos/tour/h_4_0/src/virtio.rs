@@ -0,0 +1,481 @@
+//! Emulated virtio devices with the virtio-mmio transport (version 2).
+//!
+//! The transport keeps the registers and the split virtqueues of a device,
+//! and the device processes the buffers of the guest in guest memory. The
+//! buffers of a queue are processed when the guest notifies the queue, and
+//! also when the host side of the device has something for the guest (see
+//! [`VirtioDevice::poll`]).
+//!
+//! A device that finds a malformed queue sets `DEVICE_NEEDS_RESET`, instead of
+//! failing the access of the guest.
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::ops::Range;
+use core::sync::atomic::{fence, Ordering};
+use std::sync::Mutex;
+
+use axerrno::{ax_err, AxResult};
+use axmm::AddrSpace;
+use riscv_vcpu::AccessWidth;
+
+use crate::vm::{check_guest, read_guest, write_guest};
+use crate::vmdev::EmuDev;
+
+const MAGIC_VALUE: usize = 0x000;
+const VERSION: usize = 0x004;
+const DEVICE_ID: usize = 0x008;
+const VENDOR_ID: usize = 0x00c;
+const DEVICE_FEATURES: usize = 0x010;
+const DEVICE_FEATURES_SEL: usize = 0x014;
+const DRIVER_FEATURES: usize = 0x020;
+const DRIVER_FEATURES_SEL: usize = 0x024;
+const QUEUE_SEL: usize = 0x030;
+const QUEUE_NUM_MAX: usize = 0x034;
+const QUEUE_NUM: usize = 0x038;
+const QUEUE_READY: usize = 0x044;
+const QUEUE_NOTIFY: usize = 0x050;
+const INTERRUPT_STATUS: usize = 0x060;
+const INTERRUPT_ACK: usize = 0x064;
+const STATUS: usize = 0x070;
+const QUEUE_DESC_LOW: usize = 0x080;
+const QUEUE_DESC_HIGH: usize = 0x084;
+const QUEUE_DRIVER_LOW: usize = 0x090;
+const QUEUE_DRIVER_HIGH: usize = 0x094;
+const QUEUE_DEVICE_LOW: usize = 0x0a0;
+const QUEUE_DEVICE_HIGH: usize = 0x0a4;
+const CONFIG_GENERATION: usize = 0x0fc;
+/// The start of the device configuration space.
+const CONFIG: usize = 0x100;
+
+/// The size of the register region of a device.
+pub const MMIO_SIZE: usize = 0x1000;
+
+/// "virt" in little endian.
+const MAGIC: u32 = 0x7472_6976;
+/// "QEMU" in little endian, as the devices follow the QEMU ones.
+const VENDOR: u32 = 0x554d_4551;
+
+/// The device follows the virtio 1.x specification, which is offered by all
+/// devices of the version 2 transport.
+const VIRTIO_F_VERSION_1: u64 = 1 << 32;
+
+const STATUS_DRIVER_OK: u32 = 0x04;
+const STATUS_DEVICE_NEEDS_RESET: u32 = 0x40;
+
+const INTERRUPT_USED_BUFFER: u32 = 0x1;
+const INTERRUPT_CONFIG_CHANGE: u32 = 0x2;
+
+/// The maximum size of the queues.
+const QUEUE_SIZE_MAX: u16 = 256;
+
+const VIRTQ_DESC_SIZE: usize = 16;
+const VIRTQ_DESC_F_NEXT: u16 = 0x1;
+const VIRTQ_DESC_F_WRITE: u16 = 0x2;
+
+/// The device type specific part of a virtio device.
+pub trait VirtioDevice: Send + Sync {
+    /// The virtio device ID.
+    fn device_id(&self) -> u32;
+    /// The device-specific features offered to the driver.
+    fn features(&self) -> u64;
+    /// The number of virtqueues.
+    fn num_queues(&self) -> usize;
+    /// Returns the device configuration space.
+    fn config(&self) -> Vec<u8>;
+    /// Processes the available buffers of queue `index` notified by the
+    /// driver. Returns whether any buffer is used.
+    fn notify(&self, index: usize, queue: &mut VirtQueue, aspace: &AddrSpace) -> AxResult<bool>;
+    /// Passes the events of the host side, e.g. input, to the driver. Returns
+    /// whether any buffer is used.
+    fn poll(&self, _queues: &mut [VirtQueue], _aspace: &AddrSpace) -> AxResult<bool> {
+        Ok(false)
+    }
+}
+
+/// A buffer of the driver, i.e. a chain of descriptors.
+pub struct DescChain {
+    head: u16,
+    /// The device-readable parts, as `(gpa, len)`.
+    readable: Vec<(usize, usize)>,
+    /// The device-writable parts, as `(gpa, len)`.
+    writable: Vec<(usize, usize)>,
+}
+
+/// Calls `f` with the guest physical address of each piece of the `len` bytes
+/// at `offset` of `parts`, and the range of the piece in the bytes. Returns
+/// the number of bytes in `parts`, which may be less than `len`.
+fn for_each_piece(
+    parts: &[(usize, usize)],
+    offset: usize,
+    len: usize,
+    mut f: impl FnMut(usize, Range<usize>) -> AxResult,
+) -> AxResult<usize> {
+    let mut skip = offset;
+    let mut done = 0;
+    for &(gpa, part_len) in parts {
+        if done == len {
+            break;
+        }
+        if skip >= part_len {
+            skip -= part_len;
+            continue;
+        }
+        let n = (part_len - skip).min(len - done);
+        f(gpa + skip, done..done + n)?;
+        done += n;
+        skip = 0;
+    }
+    Ok(done)
+}
+
+impl DescChain {
+    /// Returns the length of the device-readable part.
+    pub fn readable_len(&self) -> usize {
+        self.readable.iter().map(|&(_, len)| len).sum()
+    }
+
+    /// Reads the device-readable part at `offset` into `buf`, and returns the
+    /// number of bytes read, which is less than the length of `buf` at the
+    /// end of the part.
+    ///
+    /// The lengths are given by the driver, so the buffers are not read at
+    /// once.
+    pub fn read_at(&self, aspace: &AddrSpace, offset: usize, buf: &mut [u8]) -> AxResult<usize> {
+        for_each_piece(&self.readable, offset, buf.len(), |gpa, range| {
+            read_guest(aspace, gpa.into(), &mut buf[range])
+        })
+    }
+
+    /// Returns the length of the device-writable part.
+    pub fn writable_len(&self) -> usize {
+        self.writable.iter().map(|&(_, len)| len).sum()
+    }
+
+    /// Writes `data` to the device-writable part at `offset`, and returns the
+    /// number of bytes written.
+    pub fn write_at(&self, aspace: &AddrSpace, offset: usize, data: &[u8]) -> AxResult<usize> {
+        for_each_piece(&self.writable, offset, data.len(), |gpa, range| {
+            write_guest(aspace, gpa.into(), &data[range])
+        })
+    }
+
+    /// Writes `data` to the start of the device-writable part, and returns the
+    /// number of bytes written.
+    pub fn write(&self, aspace: &AddrSpace, data: &[u8]) -> AxResult<usize> {
+        self.write_at(aspace, 0, data)
+    }
+}
+
+/// A split virtqueue in guest memory.
+#[derive(Default)]
+pub struct VirtQueue {
+    size: u16,
+    ready: bool,
+    desc: usize,
+    avail: usize,
+    used: usize,
+    /// The index of the next available buffer to process.
+    next_avail: u16,
+    /// The index of the next used buffer to return.
+    next_used: u16,
+}
+
+fn read_u16(aspace: &AddrSpace, gpa: usize) -> AxResult<u16> {
+    let mut buf = [0; 2];
+    read_guest(aspace, gpa.into(), &mut buf)?;
+    Ok(u16::from_le_bytes(buf))
+}
+
+impl VirtQueue {
+    /// Checks that the rings given by the driver are in guest memory.
+    fn check_rings(&self, aspace: &AddrSpace) -> AxResult {
+        if self.size == 0 {
+            return ax_err!(InvalidInput, "queue size not set");
+        }
+        let size = self.size as usize;
+        check_guest(aspace, self.desc.into(), size * VIRTQ_DESC_SIZE)?;
+        // The flags, the index, the ring, and `used_event`.
+        check_guest(aspace, self.avail.into(), 6 + size * 2)?;
+        // The flags, the index, the ring, and `avail_event`.
+        check_guest(aspace, self.used.into(), 6 + size * 8)
+    }
+
+    /// Takes the next available buffer, if any.
+    pub fn pop(&mut self, aspace: &AddrSpace) -> AxResult<Option<DescChain>> {
+        if !self.ready || read_u16(aspace, self.avail + 2)? == self.next_avail {
+            return Ok(None);
+        }
+        // Read the descriptors after the index of the ring.
+        fence(Ordering::Acquire);
+        let slot = (self.next_avail % self.size) as usize;
+        let head = read_u16(aspace, self.avail + 4 + slot * 2)?;
+        self.next_avail = self.next_avail.wrapping_add(1);
+
+        let mut chain = DescChain {
+            head,
+            readable: Vec::new(),
+            writable: Vec::new(),
+        };
+        let mut idx = head;
+        // A chain has at most `size` descriptors, so it cannot loop.
+        for _ in 0..self.size {
+            if idx >= self.size {
+                return ax_err!(InvalidData, "descriptor index out of range");
+            }
+            let mut desc = [0; VIRTQ_DESC_SIZE];
+            read_guest(
+                aspace,
+                (self.desc + idx as usize * VIRTQ_DESC_SIZE).into(),
+                &mut desc,
+            )?;
+            let addr = u64::from_le_bytes(desc[0..8].try_into().unwrap()) as usize;
+            let len = u32::from_le_bytes(desc[8..12].try_into().unwrap()) as usize;
+            let flags = u16::from_le_bytes(desc[12..14].try_into().unwrap());
+            let next = u16::from_le_bytes(desc[14..16].try_into().unwrap());
+            if flags & VIRTQ_DESC_F_WRITE != 0 {
+                chain.writable.push((addr, len));
+            } else if chain.writable.is_empty() {
+                chain.readable.push((addr, len));
+            } else {
+                return ax_err!(InvalidData, "readable descriptor after writable ones");
+            }
+            if flags & VIRTQ_DESC_F_NEXT == 0 {
+                return Ok(Some(chain));
+            }
+            idx = next;
+        }
+        ax_err!(InvalidData, "descriptor chain too long")
+    }
+
+    /// Returns a buffer to the driver, with `len` bytes written to it.
+    pub fn push(&mut self, aspace: &AddrSpace, chain: &DescChain, len: usize) -> AxResult {
+        let slot = (self.next_used % self.size) as usize;
+        let mut elem = [0; 8];
+        elem[0..4].copy_from_slice(&(chain.head as u32).to_le_bytes());
+        elem[4..8].copy_from_slice(&(len as u32).to_le_bytes());
+        write_guest(aspace, (self.used + 4 + slot * 8).into(), &elem)?;
+        self.next_used = self.next_used.wrapping_add(1);
+        // The element is visible before the index of the ring.
+        fence(Ordering::Release);
+        write_guest(
+            aspace,
+            (self.used + 2).into(),
+            &self.next_used.to_le_bytes(),
+        )
+    }
+}
+
+struct MmioRegs {
+    status: u32,
+    device_features_sel: u32,
+    driver_features_sel: u32,
+    driver_features: u64,
+    queue_sel: u32,
+    queues: Vec<VirtQueue>,
+    interrupt_status: u32,
+}
+
+impl MmioRegs {
+    fn new(num_queues: usize) -> Self {
+        Self {
+            status: 0,
+            device_features_sel: 0,
+            driver_features_sel: 0,
+            driver_features: 0,
+            queue_sel: 0,
+            queues: (0..num_queues).map(|_| VirtQueue::default()).collect(),
+            interrupt_status: 0,
+        }
+    }
+
+    /// Returns whether the driver is ready and the device has not failed.
+    fn running(&self) -> bool {
+        self.status & (STATUS_DRIVER_OK | STATUS_DEVICE_NEEDS_RESET) == STATUS_DRIVER_OK
+    }
+
+    fn queue(&mut self) -> Option<&mut VirtQueue> {
+        self.queues.get_mut(self.queue_sel as usize)
+    }
+
+    /// Raises the interrupt if buffers are used, or stops the device if it
+    /// fails.
+    fn update(&mut self, result: AxResult<bool>) {
+        match result {
+            Ok(true) => self.interrupt_status |= INTERRUPT_USED_BUFFER,
+            Ok(false) => {}
+            Err(err) => {
+                warn!("virtio: device needs reset: {:?}", err);
+                self.status |= STATUS_DEVICE_NEEDS_RESET;
+                self.interrupt_status |= INTERRUPT_CONFIG_CHANGE;
+            }
+        }
+    }
+}
+
+fn set_low(addr: &mut usize, val: u32) {
+    *addr = (*addr & !0xffff_ffff) | val as usize;
+}
+
+fn set_high(addr: &mut usize, val: u32) {
+    *addr = (*addr & 0xffff_ffff) | (val as usize) << 32;
+}
+
+/// The virtio-mmio transport of a virtio device.
+pub struct VirtioMmio<D: VirtioDevice> {
+    device: D,
+    aspace: Arc<Mutex<AddrSpace>>,
+    regs: Mutex<MmioRegs>,
+}
+
+impl<D: VirtioDevice> VirtioMmio<D> {
+    /// Creates the transport of `device`, which accesses the guest memory in
+    /// `aspace`.
+    pub fn new(device: D, aspace: Arc<Mutex<AddrSpace>>) -> Self {
+        let regs = MmioRegs::new(device.num_queues());
+        Self {
+            device,
+            aspace,
+            regs: Mutex::new(regs),
+        }
+    }
+
+    fn features(&self) -> u64 {
+        self.device.features() | VIRTIO_F_VERSION_1
+    }
+
+    fn read_config(&self, offset: usize, width: AccessWidth) -> u64 {
+        let config = self.device.config();
+        let mut bytes = [0; 8];
+        for (i, byte) in bytes.iter_mut().take(width.size()).enumerate() {
+            *byte = config.get(offset + i).copied().unwrap_or(0);
+        }
+        u64::from_le_bytes(bytes)
+    }
+}
+
+impl<D: VirtioDevice> EmuDev for VirtioMmio<D> {
+    fn read(&self, offset: usize, width: AccessWidth) -> AxResult<u64> {
+        if offset >= CONFIG {
+            return Ok(self.read_config(offset - CONFIG, width));
+        }
+        // The registers are 32-bit, other accesses read 0.
+        if width != AccessWidth::Dword {
+            warn!("virtio: ignored {:?} read at {:#x}", width, offset);
+            return Ok(0);
+        }
+        let mut regs = self.regs.lock();
+        let val = match offset {
+            MAGIC_VALUE => MAGIC,
+            VERSION => 2,
+            DEVICE_ID => self.device.device_id(),
+            VENDOR_ID => VENDOR,
+            DEVICE_FEATURES => match regs.device_features_sel {
+                0 => self.features() as u32,
+                1 => (self.features() >> 32) as u32,
+                _ => 0,
+            },
+            QUEUE_NUM_MAX => match regs.queue() {
+                Some(_) => QUEUE_SIZE_MAX as u32,
+                None => 0,
+            },
+            QUEUE_READY => regs.queue().map_or(0, |queue| queue.ready as u32),
+            INTERRUPT_STATUS => regs.interrupt_status,
+            STATUS => regs.status,
+            // The configuration space never changes.
+            CONFIG_GENERATION => 0,
+            _ => 0,
+        };
+        Ok(val as u64)
+    }
+
+    fn write(&self, offset: usize, width: AccessWidth, data: u64) -> AxResult {
+        if offset >= CONFIG {
+            // No device has writable configuration fields.
+            return Ok(());
+        }
+        if width != AccessWidth::Dword {
+            warn!("virtio: ignored {:?} write at {:#x}", width, offset);
+            return Ok(());
+        }
+        let mut regs = self.regs.lock();
+        let val = data as u32;
+        match offset {
+            DEVICE_FEATURES_SEL => regs.device_features_sel = val,
+            DRIVER_FEATURES => {
+                let features = match regs.driver_features_sel {
+                    0 => (regs.driver_features & !0xffff_ffff) | val as u64,
+                    1 => (regs.driver_features & 0xffff_ffff) | (val as u64) << 32,
+                    _ => regs.driver_features,
+                };
+                regs.driver_features = features & self.features();
+            }
+            DRIVER_FEATURES_SEL => regs.driver_features_sel = val,
+            QUEUE_SEL => regs.queue_sel = val,
+            QUEUE_NUM => {
+                if let Some(queue) = regs.queue().filter(|queue| !queue.ready) {
+                    queue.size = (val as u16).clamp(1, QUEUE_SIZE_MAX);
+                }
+            }
+            QUEUE_READY => {
+                let aspace = self.aspace.lock();
+                let result = regs.queue().map_or(Ok(()), |queue| {
+                    queue.ready = false;
+                    queue.next_avail = 0;
+                    queue.next_used = 0;
+                    if val & 1 != 0 {
+                        queue.check_rings(&aspace)?;
+                        queue.ready = true;
+                    }
+                    Ok(())
+                });
+                // A queue out of guest memory is never used.
+                if let Err(err) = result {
+                    regs.update(Err(err));
+                }
+            }
+            QUEUE_NOTIFY => {
+                let index = val as usize;
+                if regs.running() && index < regs.queues.len() {
+                    let aspace = self.aspace.lock();
+                    let result = self.device.notify(index, &mut regs.queues[index], &aspace);
+                    regs.update(result);
+                }
+            }
+            INTERRUPT_ACK => regs.interrupt_status &= !val,
+            STATUS if val == 0 => *regs = MmioRegs::new(self.device.num_queues()),
+            STATUS => regs.status = val,
+            QUEUE_DESC_LOW..=QUEUE_DEVICE_HIGH => {
+                // The rings are checked when the queue becomes ready, and
+                // cannot change since then.
+                if let Some(queue) = regs.queue().filter(|queue| !queue.ready) {
+                    match offset {
+                        QUEUE_DESC_LOW => set_low(&mut queue.desc, val),
+                        QUEUE_DESC_HIGH => set_high(&mut queue.desc, val),
+                        QUEUE_DRIVER_LOW => set_low(&mut queue.avail, val),
+                        QUEUE_DRIVER_HIGH => set_high(&mut queue.avail, val),
+                        QUEUE_DEVICE_LOW => set_low(&mut queue.used, val),
+                        QUEUE_DEVICE_HIGH => set_high(&mut queue.used, val),
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn poll(&self) {
+        let mut regs = self.regs.lock();
+        if !regs.running() {
+            return;
+        }
+        let aspace = self.aspace.lock();
+        let result = self.device.poll(&mut regs.queues, &aspace);
+        regs.update(result);
+    }
+
+    fn irq_level(&self) -> bool {
+        self.regs.lock().interrupt_status != 0
+    }
+}
@@ -0,0 +1,197 @@
+//! Emulated virtio block device, backed by a file on the host file system.
+//!
+//! Requests are served synchronously when the guest notifies the request
+//! queue.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::sync::Mutex;
+
+use axerrno::{ax_err, ax_err_type, AxResult};
+use axmm::AddrSpace;
+
+use crate::virtio::{DescChain, VirtQueue, VirtioDevice};
+
+const VIRTIO_ID_BLOCK: u32 = 2;
+
+/// The device supports the flush command.
+const VIRTIO_BLK_F_FLUSH: u64 = 1 << 9;
+
+const VIRTIO_BLK_T_IN: u32 = 0;
+const VIRTIO_BLK_T_OUT: u32 = 1;
+const VIRTIO_BLK_T_FLUSH: u32 = 4;
+const VIRTIO_BLK_T_GET_ID: u32 = 8;
+
+const VIRTIO_BLK_S_OK: u8 = 0;
+const VIRTIO_BLK_S_IOERR: u8 = 1;
+const VIRTIO_BLK_S_UNSUPP: u8 = 2;
+
+/// The size of the request header: type, reserved and sector.
+const HEADER_SIZE: usize = 16;
+/// The maximum length of the ID string.
+const ID_BYTES: usize = 20;
+
+const SECTOR_SIZE: u64 = 512;
+/// The size of the buffer the data is copied through, a multiple of sectors.
+const BOUNCE_BUF_SIZE: usize = 4096;
+
+/// An emulated virtio block device.
+pub struct VirtioBlk {
+    file: Mutex<File>,
+    /// The capacity in sectors.
+    capacity: u64,
+    /// The ID string, which is the path of the file.
+    id: String,
+}
+
+impl VirtioBlk {
+    /// Opens the file at `path` on the host file system as the disk.
+    pub fn open(path: &str) -> AxResult<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .map_err(|err| {
+                ax_err_type!(NotFound, format!("Failed to open {}, err {:?}", path, err))
+            })?;
+        let size = file.metadata()?.size();
+        if size % SECTOR_SIZE != 0 {
+            warn!("virtio-blk: size of {} is not a multiple of sectors", path);
+        }
+        Ok(Self {
+            file: Mutex::new(file),
+            capacity: size / SECTOR_SIZE,
+            id: path.into(),
+        })
+    }
+
+    /// Returns the offset of `len` bytes at `sector`, if they are in the disk.
+    fn offset(&self, sector: u64, len: usize) -> AxResult<u64> {
+        let range = sector.checked_mul(SECTOR_SIZE).and_then(|start| {
+            let end = start.checked_add(len as u64)?;
+            Some((start, end))
+        });
+        match range {
+            Some((start, end)) if end <= self.capacity * SECTOR_SIZE => Ok(start),
+            _ => ax_err!(InvalidInput, "access beyond the end of the disk"),
+        }
+    }
+
+    fn read_sectors(&self, sector: u64, buf: &mut [u8]) -> AxResult {
+        let offset = self.offset(sector, buf.len())?;
+        let mut file = self.file.lock();
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(buf)
+    }
+
+    fn write_sectors(&self, sector: u64, data: &[u8]) -> AxResult {
+        let offset = self.offset(sector, data.len())?;
+        let mut file = self.file.lock();
+        file.seek(SeekFrom::Start(offset))?;
+        file.write_all(data)
+    }
+
+    /// Reads `len` bytes at `sector` into the device-writable part of `chain`.
+    fn read_to_guest(
+        &self,
+        sector: u64,
+        len: usize,
+        chain: &DescChain,
+        aspace: &AddrSpace,
+    ) -> AxResult {
+        self.offset(sector, len)?;
+        let mut buf = [0; BOUNCE_BUF_SIZE];
+        for offset in (0..len).step_by(BOUNCE_BUF_SIZE) {
+            let buf = &mut buf[..BOUNCE_BUF_SIZE.min(len - offset)];
+            self.read_sectors(sector + (offset as u64) / SECTOR_SIZE, buf)?;
+            chain.write_at(aspace, offset, buf)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the device-readable part of `chain` after the header to
+    /// `sector`.
+    fn write_from_guest(&self, sector: u64, chain: &DescChain, aspace: &AddrSpace) -> AxResult {
+        let len = chain.readable_len() - HEADER_SIZE;
+        self.offset(sector, len)?;
+        let mut buf = [0; BOUNCE_BUF_SIZE];
+        for offset in (0..len).step_by(BOUNCE_BUF_SIZE) {
+            let buf = &mut buf[..BOUNCE_BUF_SIZE.min(len - offset)];
+            chain.read_at(aspace, HEADER_SIZE + offset, buf)?;
+            self.write_sectors(sector + (offset as u64) / SECTOR_SIZE, buf)?;
+        }
+        Ok(())
+    }
+
+    /// Handles a request, and returns the number of bytes written to the
+    /// device-writable part, whose last byte is the status.
+    ///
+    /// The data is copied between the disk and guest memory through a bounce
+    /// buffer, as its length is given by the driver.
+    fn handle_request(&self, chain: &DescChain, aspace: &AddrSpace) -> AxResult<usize> {
+        // The status byte is the last one of the writable part.
+        let Some(data_len) = chain.writable_len().checked_sub(1) else {
+            return Ok(0);
+        };
+        let mut header = [0; HEADER_SIZE];
+        let status = if chain.read_at(aspace, 0, &mut header)? < HEADER_SIZE {
+            VIRTIO_BLK_S_UNSUPP
+        } else {
+            let ty = u32::from_le_bytes(header[0..4].try_into().unwrap());
+            let sector = u64::from_le_bytes(header[8..16].try_into().unwrap());
+            let result = match ty {
+                VIRTIO_BLK_T_IN => Some(self.read_to_guest(sector, data_len, chain, aspace)),
+                VIRTIO_BLK_T_OUT => Some(self.write_from_guest(sector, chain, aspace)),
+                VIRTIO_BLK_T_FLUSH => Some(self.file.lock().flush()),
+                VIRTIO_BLK_T_GET_ID => {
+                    let id = self.id.as_bytes();
+                    let len = id.len().min(ID_BYTES).min(data_len);
+                    Some(chain.write(aspace, &id[..len]).map(|_| ()))
+                }
+                _ => None,
+            };
+            match result {
+                Some(Ok(())) => VIRTIO_BLK_S_OK,
+                Some(Err(err)) => {
+                    warn!("virtio-blk: request {} at sector {}: {:?}", ty, sector, err);
+                    VIRTIO_BLK_S_IOERR
+                }
+                None => VIRTIO_BLK_S_UNSUPP,
+            }
+        };
+        chain.write_at(aspace, data_len, &[status])?;
+        Ok(data_len + 1)
+    }
+}
+
+impl VirtioDevice for VirtioBlk {
+    fn device_id(&self) -> u32 {
+        VIRTIO_ID_BLOCK
+    }
+
+    fn features(&self) -> u64 {
+        VIRTIO_BLK_F_FLUSH
+    }
+
+    fn num_queues(&self) -> usize {
+        1
+    }
+
+    fn config(&self) -> Vec<u8> {
+        // Only the capacity, as no other feature of the configuration is
+        // offered.
+        self.capacity.to_le_bytes().into()
+    }
+
+    fn notify(&self, _index: usize, queue: &mut VirtQueue, aspace: &AddrSpace) -> AxResult<bool> {
+        let mut used = false;
+        while let Some(chain) = queue.pop(aspace)? {
+            let len = self.handle_request(&chain, aspace)?;
+            queue.push(aspace, &chain, len)?;
+            used = true;
+        }
+        Ok(used)
+    }
+}
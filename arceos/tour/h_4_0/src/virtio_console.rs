@@ -0,0 +1,104 @@
+//! Emulated virtio console device with a single port, connected to a virtual
+//! console of the guest.
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use axerrno::AxResult;
+use axmm::AddrSpace;
+
+use crate::console::VConsole;
+use crate::virtio::{VirtQueue, VirtioDevice};
+
+const VIRTIO_ID_CONSOLE: u32 = 3;
+
+const RECEIVEQ: usize = 0;
+const TRANSMITQ: usize = 1;
+
+/// The number of bytes of a transmit buffer read from guest memory at a time.
+const TRANSMIT_BUF_SIZE: usize = 256;
+
+/// An emulated virtio console.
+pub struct VirtioConsole {
+    console: Arc<VConsole>,
+}
+
+impl VirtioConsole {
+    /// Creates a virtio console connected to `console`.
+    pub fn new(console: Arc<VConsole>) -> Self {
+        Self { console }
+    }
+
+    /// Fills the receive buffers with the input of the console.
+    fn receive(&self, queue: &mut VirtQueue, aspace: &AddrSpace) -> AxResult<bool> {
+        let mut used = false;
+        while self.console.has_input() {
+            let Some(chain) = queue.pop(aspace)? else {
+                break;
+            };
+            let mut data = Vec::new();
+            while data.len() < chain.writable_len() {
+                match self.console.read_byte() {
+                    Some(c) => data.push(c),
+                    None => break,
+                }
+            }
+            let len = chain.write(aspace, &data)?;
+            queue.push(aspace, &chain, len)?;
+            used = true;
+        }
+        Ok(used)
+    }
+
+    fn transmit(&self, queue: &mut VirtQueue, aspace: &AddrSpace) -> AxResult<bool> {
+        let mut used = false;
+        while let Some(chain) = queue.pop(aspace)? {
+            let mut buf = [0; TRANSMIT_BUF_SIZE];
+            let mut offset = 0;
+            loop {
+                let n = chain.read_at(aspace, offset, &mut buf)?;
+                if n == 0 {
+                    break;
+                }
+                for &c in &buf[..n] {
+                    self.console.write_byte(c);
+                }
+                offset += n;
+            }
+            queue.push(aspace, &chain, 0)?;
+            used = true;
+        }
+        Ok(used)
+    }
+}
+
+impl VirtioDevice for VirtioConsole {
+    fn device_id(&self) -> u32 {
+        VIRTIO_ID_CONSOLE
+    }
+
+    fn features(&self) -> u64 {
+        0
+    }
+
+    fn num_queues(&self) -> usize {
+        2
+    }
+
+    fn config(&self) -> Vec<u8> {
+        // No feature of the configuration is offered.
+        Vec::new()
+    }
+
+    fn notify(&self, index: usize, queue: &mut VirtQueue, aspace: &AddrSpace) -> AxResult<bool> {
+        match index {
+            RECEIVEQ => self.receive(queue, aspace),
+            TRANSMITQ => self.transmit(queue, aspace),
+            _ => Ok(false),
+        }
+    }
+
+    fn poll(&self, queues: &mut [VirtQueue], aspace: &AddrSpace) -> AxResult<bool> {
+        self.receive(&mut queues[RECEIVEQ], aspace)
+    }
+}
@@ -0,0 +1,486 @@
+//! A VM with several vCPUs, each run in its own task.
+//!
+//! The boot vCPU starts at the kernel entry, and the others are started by
+//! the guest with SBI HSM `hart_start`. IPIs and remote fences from a vCPU are
+//! delivered to the others the next time they enter the guest.
+//!
+//! The VM stops when the guest shuts down or reboots with SBI SRST, or when
+//! a vCPU stops on an error, e.g. an exit that cannot be handled: each vCPU
+//! stops the next time it exits, and the outcome is left to the VM manager.
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use axerrno::{ax_err_type, AxResult};
+use axmm::AddrSpace;
+use axtask::{AxTaskRef, WaitQueue};
+use memory_addr::VirtAddr;
+use riscv_vcpu::sbi::{SBI_ERR_ALREADY_AVAILABLE, SBI_ERR_INAVLID_PARAM};
+use riscv_vcpu::{AxVCpuExitReason, RISCVVCpu};
+
+use crate::console::VConsole;
+use crate::vmdev::VmDevGroup;
+use crate::vplic::VPlic;
+
+/// The states of a vCPU, with the values of SBI HSM `hart_get_status`.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum VCpuState {
+    Started = 0,
+    Stopped = 1,
+    StartPending = 2,
+}
+
+/// The maximum number of bytes of an SBI debug console call, which may
+/// return fewer bytes than asked.
+const CONSOLE_BUF_SIZE: usize = 0x1000;
+
+/// The host CPUs whose hypervisor CSRs are set up, one bit per CPU.
+static HOST_CPUS_READY: AtomicUsize = AtomicUsize::new(0);
+
+/// How a VM stops, as requested by the guest or on an error.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VmOutcome {
+    Shutdown = 1,
+    Reboot = 2,
+    /// A vCPU could not be run further.
+    Error = 3,
+}
+
+/// The state of a vCPU shared with the other vCPUs.
+struct VCpuSlot {
+    state: AtomicU8,
+    /// The entry and the argument of the next start.
+    start: Mutex<(VirtAddr, usize)>,
+    /// The host CPU the vCPU is pinned to.
+    cpu_id: Option<usize>,
+    ipi_pending: AtomicBool,
+    flush_pending: AtomicBool,
+    wq: WaitQueue,
+}
+
+impl VCpuSlot {
+    fn state(&self) -> VCpuState {
+        match self.state.load(Ordering::Acquire) {
+            0 => VCpuState::Started,
+            1 => VCpuState::Stopped,
+            _ => VCpuState::StartPending,
+        }
+    }
+
+    fn set_state(&self, state: VCpuState) {
+        self.state.store(state as u8, Ordering::Release);
+    }
+}
+
+/// A VM with its memory, devices and vCPUs.
+pub struct Vm {
+    aspace: Arc<Mutex<AddrSpace>>,
+    devs: VmDevGroup,
+    plic: Arc<VPlic>,
+    /// The console of the SBI debug console calls.
+    console: Arc<VConsole>,
+    vcpus: Vec<VCpuSlot>,
+    /// The [`VmOutcome`] once the VM is stopping, or 0.
+    outcome: AtomicU8,
+}
+
+impl Vm {
+    /// Creates a VM with a vCPU for each element of `cpu_ids`, which is the
+    /// host CPU the vCPU is pinned to, if any. The address space is shared
+    /// with the emulated devices that access guest memory.
+    pub fn new(
+        aspace: Arc<Mutex<AddrSpace>>,
+        devs: VmDevGroup,
+        plic: Arc<VPlic>,
+        console: Arc<VConsole>,
+        cpu_ids: &[Option<usize>],
+    ) -> Arc<Self> {
+        let vcpus = cpu_ids
+            .iter()
+            .map(|&cpu_id| VCpuSlot {
+                state: AtomicU8::new(VCpuState::Stopped as u8),
+                start: Mutex::new((VirtAddr::from(0), 0)),
+                cpu_id,
+                ipi_pending: AtomicBool::new(false),
+                flush_pending: AtomicBool::new(false),
+                wq: WaitQueue::new(),
+            })
+            .collect();
+        Arc::new(Self {
+            aspace,
+            devs,
+            plic,
+            console,
+            vcpus,
+            outcome: AtomicU8::new(0),
+        })
+    }
+
+    /// Returns how the VM stops, once it is stopping.
+    pub fn outcome(&self) -> Option<VmOutcome> {
+        match self.outcome.load(Ordering::Acquire) {
+            1 => Some(VmOutcome::Shutdown),
+            2 => Some(VmOutcome::Reboot),
+            3 => Some(VmOutcome::Error),
+            _ => None,
+        }
+    }
+
+    /// Stops all the vCPUs. The first outcome requested wins.
+    fn stop(&self, outcome: VmOutcome) {
+        let first = self
+            .outcome
+            .compare_exchange(0, outcome as u8, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok();
+        if first {
+            for slot in self.vcpus.iter() {
+                slot.wq.notify_all(false);
+            }
+        }
+    }
+
+    /// Spawns the tasks of the vCPUs, and starts vCPU 0 at `entry` with
+    /// `arg` in its a1.
+    pub fn boot(self: &Arc<Self>, entry: VirtAddr, arg: usize) -> Vec<AxTaskRef> {
+        let tasks = (0..self.vcpus.len())
+            .map(|vcpu_id| {
+                let vm = self.clone();
+                axtask::spawn(move || vm.vcpu_main(vcpu_id))
+            })
+            .collect();
+        self.start_vcpu(0, entry, arg);
+        tasks
+    }
+
+    fn start_vcpu(&self, vcpu_id: usize, entry: VirtAddr, arg: usize) -> isize {
+        let Some(slot) = self.vcpus.get(vcpu_id) else {
+            return SBI_ERR_INAVLID_PARAM;
+        };
+        let mut start = slot.start.lock();
+        if slot.state() != VCpuState::Stopped {
+            return SBI_ERR_ALREADY_AVAILABLE;
+        }
+        *start = (entry, arg);
+        slot.set_state(VCpuState::StartPending);
+        slot.wq.notify_one(false);
+        0
+    }
+
+    /// Returns the IDs of the vCPUs in an SBI hart mask, or `None` if the
+    /// mask reaches beyond the largest hart ID.
+    fn targets(&self, hart_mask: u64, hart_mask_base: u64) -> Option<Vec<usize>> {
+        if hart_mask_base == u64::MAX {
+            return Some((0..self.vcpus.len()).collect());
+        }
+        let mut targets = Vec::new();
+        for i in (0..u64::BITS as usize).filter(|i| hart_mask & (1 << i) != 0) {
+            let vcpu_id = (hart_mask_base as usize).checked_add(i)?;
+            if vcpu_id < self.vcpus.len() {
+                targets.push(vcpu_id);
+            }
+        }
+        Some(targets)
+    }
+
+    /// Applies the IPIs and remote fences from the other vCPUs.
+    fn take_requests(&self, vcpu_id: usize, vcpu: &mut RISCVVCpu) {
+        let slot = &self.vcpus[vcpu_id];
+        if slot.ipi_pending.swap(false, Ordering::AcqRel) {
+            vcpu.inject_soft_interrupt();
+        }
+        if slot.flush_pending.swap(false, Ordering::AcqRel) {
+            vcpu.request_flush();
+        }
+    }
+
+    fn vcpu_main(&self, vcpu_id: usize) {
+        let slot = &self.vcpus[vcpu_id];
+        if let Some(cpu_id) = slot.cpu_id {
+            if !axtask::set_current_affinity(1 << cpu_id) {
+                warn!("vcpu {}: no host cpu {} to pin to", vcpu_id, cpu_id);
+            }
+        }
+        let ept_root = self.aspace.lock().page_table_root();
+        loop {
+            slot.wq
+                .wait_until(|| slot.state() == VCpuState::StartPending || self.outcome().is_some());
+            if self.outcome().is_some() {
+                break;
+            }
+            let (entry, arg) = *slot.start.lock();
+            info!("vcpu {}: start at {:#x}", vcpu_id, entry);
+
+            let mut vcpu = RISCVVCpu::init();
+            vcpu.set_entry(entry).unwrap();
+            vcpu.set_start_args(vcpu_id, arg);
+            vcpu.set_ept_root(ept_root).unwrap();
+            slot.flush_pending.store(false, Ordering::Release);
+            slot.set_state(VCpuState::Started);
+
+            match self.run_vcpu(vcpu_id, &mut vcpu) {
+                Ok(()) => info!("vcpu {}: stopped", vcpu_id),
+                Err(err) => {
+                    warn!("vcpu {}: stopped on error: {:?}", vcpu_id, err);
+                    self.stop(VmOutcome::Error);
+                }
+            }
+            slot.set_state(VCpuState::Stopped);
+        }
+    }
+
+    /// Runs the vCPU until it stops, or the VM stops.
+    fn run_vcpu(&self, vcpu_id: usize, vcpu: &mut RISCVVCpu) -> AxResult {
+        loop {
+            if self.outcome().is_some() {
+                return Ok(());
+            }
+            self.take_requests(vcpu_id, vcpu);
+            // Update the interrupt lines, and inject the external interrupt if any.
+            self.devs.sync_irqs(&self.plic);
+            vcpu.set_external_interrupt(self.plic.irq_pending(vcpu_id));
+
+            match vcpu_run(vcpu)? {
+                AxVCpuExitReason::Nothing => {}
+                AxVCpuExitReason::ExternalInterrupt { .. } => {
+                    // Handled by the host once the irqs are enabled again.
+                }
+                AxVCpuExitReason::Halt => axtask::yield_now(),
+                AxVCpuExitReason::MmioRead { addr, width, .. } => {
+                    let data = match self.devs.find_dev(addr) {
+                        Some(dev) if dev.is_passthrough() => {
+                            // Map the region and execute the load again.
+                            dev.handle_mmio(addr, &mut self.aspace.lock())?;
+                            continue;
+                        }
+                        // A faulty access reads 0, instead of stopping the VM.
+                        Some(dev) => dev.handle_read(addr, width).unwrap_or_else(|err| {
+                            warn!("vcpu {}: bad read at {:#x}: {:?}", vcpu_id, addr, err);
+                            0
+                        }),
+                        None => {
+                            warn!("vcpu {}: read at unassigned {:#x}", vcpu_id, addr);
+                            0
+                        }
+                    };
+                    vcpu.complete_mmio_read(data)?;
+                }
+                AxVCpuExitReason::MmioWrite { addr, width, data } => {
+                    match self.devs.find_dev(addr) {
+                        Some(dev) if dev.is_passthrough() => {
+                            // Map the region and execute the store again.
+                            dev.handle_mmio(addr, &mut self.aspace.lock())?;
+                            continue;
+                        }
+                        Some(dev) => {
+                            if let Err(err) = dev.handle_write(addr, width, data) {
+                                warn!("vcpu {}: bad write at {:#x}: {:?}", vcpu_id, addr, err);
+                            }
+                        }
+                        None => warn!("vcpu {}: write at unassigned {:#x}", vcpu_id, addr),
+                    }
+                    vcpu.complete_mmio_write()?;
+                }
+                AxVCpuExitReason::NestedPageFault { addr, access_flags } => {
+                    debug!("addr {:#x} access {:#x}", addr, access_flags);
+                    match self.devs.find_dev(addr) {
+                        // Find dev and handle mmio region.
+                        Some(dev) if dev.is_passthrough() => {
+                            dev.handle_mmio(addr, &mut self.aspace.lock())?
+                        }
+                        // The access cannot be emulated, so it is skipped as
+                        // an unassigned one.
+                        _ => {
+                            warn!(
+                                "vcpu {}: skip access {:#x} at {:#x}",
+                                vcpu_id, access_flags, addr
+                            );
+                            vcpu.skip_faulting_insn()?;
+                        }
+                    }
+                }
+                AxVCpuExitReason::CpuUp {
+                    target_cpu,
+                    entry_point,
+                    arg,
+                } => {
+                    let err = self.start_vcpu(target_cpu as usize, entry_point, arg as usize);
+                    vcpu.set_sbi_return(err, 0);
+                }
+                AxVCpuExitReason::CpuDown => return Ok(()),
+                AxVCpuExitReason::CpuStatus { target_cpu } => {
+                    match self.vcpus.get(target_cpu as usize) {
+                        Some(target) => vcpu.set_sbi_return(0, target.state() as usize),
+                        None => vcpu.set_sbi_return(SBI_ERR_INAVLID_PARAM, 0),
+                    }
+                }
+                AxVCpuExitReason::SendIpi {
+                    hart_mask,
+                    hart_mask_base,
+                } => {
+                    let Some(targets) = self.targets(hart_mask, hart_mask_base) else {
+                        vcpu.set_sbi_return(SBI_ERR_INAVLID_PARAM, 0);
+                        continue;
+                    };
+                    for target in targets {
+                        if target == vcpu_id {
+                            vcpu.inject_soft_interrupt();
+                        } else {
+                            self.vcpus[target]
+                                .ipi_pending
+                                .store(true, Ordering::Release);
+                        }
+                    }
+                }
+                AxVCpuExitReason::RemoteFence {
+                    hart_mask,
+                    hart_mask_base,
+                } => self.remote_fence(vcpu_id, vcpu, hart_mask, hart_mask_base),
+                AxVCpuExitReason::ConsoleWrite { addr, len } => {
+                    set_console_return(vcpu, self.console_write(addr, len));
+                }
+                AxVCpuExitReason::ConsoleRead { addr, len } => {
+                    set_console_return(vcpu, self.console_read(addr, len));
+                }
+                AxVCpuExitReason::ConsoleWriteByte { byte } => self.console.write_byte(byte),
+                AxVCpuExitReason::SystemDown => {
+                    info!("vcpu {}: system shutdown", vcpu_id);
+                    self.stop(VmOutcome::Shutdown);
+                }
+                AxVCpuExitReason::SystemReset => {
+                    info!("vcpu {}: system reboot", vcpu_id);
+                    self.stop(VmOutcome::Reboot);
+                }
+                exit_reason => {
+                    warn!("vcpu {}: unhandled VM-Exit: {:?}", vcpu_id, exit_reason);
+                    return Err(ax_err_type!(Unsupported, "unhandled VM-Exit"));
+                }
+            }
+        }
+    }
+
+    /// Writes the bytes at `addr` in guest memory to the console.
+    fn console_write(&self, addr: VirtAddr, len: usize) -> AxResult<usize> {
+        let mut buf = vec![0; len.min(CONSOLE_BUF_SIZE)];
+        read_guest(&self.aspace.lock(), addr, &mut buf)?;
+        for &c in buf.iter() {
+            self.console.write_byte(c);
+        }
+        Ok(buf.len())
+    }
+
+    /// Reads the pending input of the console to `addr` in guest memory.
+    fn console_read(&self, addr: VirtAddr, len: usize) -> AxResult<usize> {
+        let mut buf = Vec::new();
+        while buf.len() < len.min(CONSOLE_BUF_SIZE) {
+            match self.console.read_byte() {
+                Some(c) => buf.push(c),
+                None => break,
+            }
+        }
+        write_guest(&self.aspace.lock(), addr, &buf)?;
+        Ok(buf.len())
+    }
+
+    /// Flushes the guest TLB and instruction cache of the target vCPUs, and
+    /// waits for the started ones to do it.
+    fn remote_fence(
+        &self,
+        vcpu_id: usize,
+        vcpu: &mut RISCVVCpu,
+        hart_mask: u64,
+        hart_mask_base: u64,
+    ) {
+        let Some(targets) = self.targets(hart_mask, hart_mask_base) else {
+            vcpu.set_sbi_return(SBI_ERR_INAVLID_PARAM, 0);
+            return;
+        };
+        for &target in targets.iter() {
+            if target == vcpu_id {
+                vcpu.request_flush();
+            } else {
+                self.vcpus[target]
+                    .flush_pending
+                    .store(true, Ordering::Release);
+            }
+        }
+        loop {
+            // Serve the fences to this vCPU, which may be waited for too.
+            self.take_requests(vcpu_id, vcpu);
+            let done = targets.iter().all(|&target| {
+                let slot = &self.vcpus[target];
+                slot.state() != VCpuState::Started || !slot.flush_pending.load(Ordering::Acquire)
+            });
+            if done {
+                break;
+            }
+            axtask::yield_now();
+        }
+    }
+}
+
+/// Returns the number of bytes of an SBI debug console call, or an error if
+/// the buffer is not in guest memory.
+fn set_console_return(vcpu: &mut RISCVVCpu, result: AxResult<usize>) {
+    match result {
+        Ok(count) => vcpu.set_sbi_return(0, count),
+        Err(_) => vcpu.set_sbi_return(SBI_ERR_INAVLID_PARAM, 0),
+    }
+}
+
+/// Reads guest memory at `gpa` into `buf`.
+pub fn read_guest(aspace: &AddrSpace, gpa: VirtAddr, buf: &mut [u8]) -> AxResult {
+    let mut buf = buf;
+    for region in guest_buffers(aspace, gpa, buf.len())? {
+        let (head, rest) = buf.split_at_mut(region.len());
+        head.copy_from_slice(region);
+        buf = rest;
+    }
+    Ok(())
+}
+
+/// Writes `data` to guest memory at `gpa`.
+pub fn write_guest(aspace: &AddrSpace, gpa: VirtAddr, data: &[u8]) -> AxResult {
+    let mut data = data;
+    for region in guest_buffers(aspace, gpa, data.len())? {
+        let (head, rest) = data.split_at(region.len());
+        region.copy_from_slice(head);
+        data = rest;
+    }
+    Ok(())
+}
+
+/// Checks that the `len` bytes at `gpa` are in guest memory.
+pub fn check_guest(aspace: &AddrSpace, gpa: VirtAddr, len: usize) -> AxResult {
+    guest_buffers(aspace, gpa, len).map(|_| ())
+}
+
+fn guest_buffers(
+    aspace: &AddrSpace,
+    gpa: VirtAddr,
+    len: usize,
+) -> AxResult<Vec<&'static mut [u8]>> {
+    aspace
+        .translated_byte_buffer(gpa, len)
+        .ok_or_else(|| ax_err_type!(InvalidInput, "out of guest memory"))
+}
+
+fn vcpu_run(vcpu: &mut RISCVVCpu) -> AxResult<AxVCpuExitReason> {
+    use axhal::arch::{local_irq_restore, local_irq_save_and_disable};
+    let flags = local_irq_save_and_disable();
+    // An unpinned vCPU may be migrated to a host CPU that has not run a vCPU
+    // yet, and the task is not migrated with the irqs disabled.
+    let cpu_mask = 1 << axhal::cpu::this_cpu_id();
+    if HOST_CPUS_READY.load(Ordering::Acquire) & cpu_mask == 0 {
+        unsafe {
+            riscv_vcpu::setup_csrs();
+        }
+        HOST_CPUS_READY.fetch_or(cpu_mask, Ordering::AcqRel);
+    }
+    let ret = vcpu.run();
+    local_irq_restore(flags);
+    ret
+}
@@ -4,21 +4,77 @@ use axerrno::AxResult;
 use memory_addr::VirtAddr;
 use axhal::paging::MappingFlags;
 use axmm::AddrSpace;
+use riscv_vcpu::AccessWidth;
+use crate::vplic::VPlic;
+
+/// A device whose registers are emulated by the hypervisor.
+pub trait EmuDev: Send + Sync {
+    /// Reads the register at `offset` from the start of the device.
+    fn read(&self, offset: usize, width: AccessWidth) -> AxResult<u64>;
+    /// Writes `data` to the register at `offset` from the start of the device.
+    fn write(&self, offset: usize, width: AccessWidth, data: u64) -> AxResult;
+    /// Handles the events of the host side of the device, e.g. input.
+    fn poll(&self) {}
+    /// Returns the level of the interrupt line of the device.
+    fn irq_level(&self) -> bool {
+        false
+    }
+}
+
+enum VmDevKind {
+    /// The guest accesses the host device directly once it is mapped.
+    Passthrough,
+    /// Each access of the guest traps and is emulated.
+    Emulated(Arc<dyn EmuDev>),
+}
 
 pub struct VmDev {
     start: VirtAddr,
     size: usize,
+    kind: VmDevKind,
+    /// The interrupt source of the device on the virtual PLIC.
+    irq: Option<usize>,
 }
 
 impl VmDev {
     pub fn new(start: VirtAddr, size: usize) -> Self {
-        Self { start, size }
+        Self { start, size, kind: VmDevKind::Passthrough, irq: None }
+    }
+
+    pub fn new_emulated(start: VirtAddr, size: usize, dev: Arc<dyn EmuDev>, irq: Option<usize>) -> Self {
+        Self { start, size, kind: VmDevKind::Emulated(dev), irq }
+    }
+
+    pub fn is_passthrough(&self) -> bool {
+        matches!(self.kind, VmDevKind::Passthrough)
     }
 
+    /// Maps the page at `addr` to the host device.
+    ///
+    /// The caller holds the lock of `aspace`, so the page may have been
+    /// mapped by another vCPU that faulted on it at the same time.
     pub fn handle_mmio(&self, addr: VirtAddr , aspace: &mut AddrSpace) -> AxResult {
+        let page = addr.align_down_4k();
+        if aspace.is_mapped(page, 4096) {
+            return Ok(());
+        }
         let mapping_flags = MappingFlags::from_bits(0xf).unwrap();
         // Passthrough-Mode
-        aspace.map_linear(addr, addr.as_usize().into(), 4096, mapping_flags)
+        aspace.map_linear(page, page.as_usize().into(), 4096, mapping_flags)
+    }
+
+    pub fn handle_read(&self, addr: VirtAddr, width: AccessWidth) -> AxResult<u64> {
+        match &self.kind {
+            VmDevKind::Emulated(dev) => dev.read(addr - self.start, width),
+            VmDevKind::Passthrough => unreachable!("passthrough device is not emulated"),
+        }
+    }
+
+    pub fn handle_write(&self, addr: VirtAddr, width: AccessWidth, data: u64) -> AxResult {
+        match &self.kind {
+            VmDevKind::Emulated(dev) => dev.write(addr - self.start, width, data),
+            VmDevKind::Passthrough => unreachable!("passthrough device is not emulated"),
+        }
     }
 
     pub fn check_addr(&self, addr: VirtAddr) -> bool {
@@ -40,10 +96,28 @@ impl VmDevGroup {
         self.devices.push(Arc::new(dev));
     }
 
+    pub fn add_emu_dev(&mut self, addr: VirtAddr, size: usize, dev: Arc<dyn EmuDev>, irq: Option<usize>) {
+        let dev = VmDev::new_emulated(addr, size, dev, irq);
+        self.devices.push(Arc::new(dev));
+    }
+
     pub fn find_dev(&self, addr: VirtAddr) -> Option<Arc<VmDev>> {
         self.devices
             .iter()
             .find(|&dev| dev.check_addr(addr))
             .cloned()
     }
+
+    /// Polls the emulated devices, and passes their interrupt lines to the
+    /// PLIC.
+    pub fn sync_irqs(&self, plic: &VPlic) {
+        for dev in self.devices.iter() {
+            if let VmDevKind::Emulated(emu) = &dev.kind {
+                emu.poll();
+                if let Some(irq) = dev.irq {
+                    plic.set_irq(irq, emu.irq_level());
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,236 @@
+//! The VM manager, which builds and boots the VMs from their configurations.
+
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use std::fs::File;
+use std::sync::Mutex;
+
+use axerrno::{ax_err, ax_err_type, AxResult};
+use axhal::paging::{MappingFlags, PageSize};
+use axmm::AddrSpace;
+use axvmconfig::{EmuDevType, VmConfig};
+use memory_addr::VirtAddr;
+
+use crate::console;
+use crate::fdt::generate_fdt;
+use crate::uart16550::Uart16550;
+use crate::virtio::{self, VirtioMmio};
+use crate::virtio_blk::VirtioBlk;
+use crate::virtio_console::VirtioConsole;
+use crate::vm::{write_guest, Vm, VmOutcome};
+use crate::vmdev::VmDevGroup;
+use crate::vplic::VPlic;
+
+const VM_ASPACE_BASE: usize = 0x0;
+const VM_ASPACE_SIZE: usize = 0x7fff_ffff_f000;
+
+/// Builds a VM from its configuration: maps its memory, loads its images,
+/// registers its devices, and places its device tree.
+///
+/// Returns the VM and the address of the device tree.
+pub fn build_vm(config: &VmConfig) -> AxResult<(Arc<Vm>, usize)> {
+    info!(
+        "VM {}: creating with {} vcpus...",
+        config.name,
+        config.cpu_num()
+    );
+    let mut aspace = AddrSpace::new_empty(VirtAddr::from(VM_ASPACE_BASE), VM_ASPACE_SIZE)?;
+
+    // Physical memory regions. Full access flags.
+    let mapping_flags = MappingFlags::from_bits(0xf).unwrap();
+    for region in config.memory.iter() {
+        // Backed by 2M huge pages to save page table entries, if aligned.
+        let page_size = if (region.gpa | region.size) % usize::from(PageSize::Size2M) == 0 {
+            PageSize::Size2M
+        } else {
+            PageSize::Size4K
+        };
+        aspace.map_alloc_huge(
+            region.gpa.into(),
+            region.size,
+            mapping_flags,
+            true,
+            page_size,
+        )?;
+    }
+
+    // Load corresponding images for VM.
+    for image in config.images.iter() {
+        info!(
+            "VM {}: loading {} at {:#x}",
+            config.name, image.path, image.load_addr
+        );
+        load_vm_image(image.path.clone(), image.load_addr.into(), &aspace)?;
+    }
+
+    // Place the device tree, whose address is passed to the guest in a1.
+    let (fdt, dtb_addr) = generate_fdt(config)?;
+    info!("VM {}: device tree at {:#x}", config.name, dtb_addr);
+    write_guest(&aspace, dtb_addr.into(), &fdt)?;
+
+    // Shared with the devices that access guest memory.
+    let aspace = Arc::new(Mutex::new(aspace));
+    // Shared by the SBI debug console and the emulated UART.
+    let vm_console = console::new_console(&config.name);
+    let mut vmdevs = VmDevGroup::new();
+    for region in config.passthrough.iter() {
+        vmdevs.add_dev(region.gpa.into(), region.size);
+    }
+    // The plic is also used to inject the interrupts of the emulated devices
+    // if the guest does not see it.
+    let plic = Arc::new(VPlic::new(config.cpu_num()));
+    for dev in config.emulated.iter() {
+        match dev.ty {
+            EmuDevType::Plic => {
+                let size = dev.size.unwrap_or(VPlic::SIZE);
+                vmdevs.add_emu_dev(dev.gpa.into(), size, plic.clone(), dev.irq);
+            }
+            EmuDevType::Uart16550 => {
+                let size = dev.size.unwrap_or(Uart16550::SIZE);
+                let uart = Uart16550::new(vm_console.clone());
+                vmdevs.add_emu_dev(dev.gpa.into(), size, Arc::new(uart), dev.irq);
+            }
+            EmuDevType::VirtioBlk => {
+                let size = dev.size.unwrap_or(virtio::MMIO_SIZE);
+                // The path is checked when the config is parsed.
+                let blk = VirtioBlk::open(dev.path.as_deref().unwrap())?;
+                let virtio = VirtioMmio::new(blk, aspace.clone());
+                vmdevs.add_emu_dev(dev.gpa.into(), size, Arc::new(virtio), dev.irq);
+            }
+            EmuDevType::VirtioConsole => {
+                let size = dev.size.unwrap_or(virtio::MMIO_SIZE);
+                let name = format!("{} virtio", config.name);
+                let console = VirtioConsole::new(console::new_console(&name));
+                let virtio = VirtioMmio::new(console, aspace.clone());
+                vmdevs.add_emu_dev(dev.gpa.into(), size, Arc::new(virtio), dev.irq);
+            }
+        }
+    }
+
+    Ok((
+        Vm::new(aspace, vmdevs, plic, vm_console, &config.cpu_affinity),
+        dtb_addr,
+    ))
+}
+
+/// Reads and parses the configuration at `path` on the host file system.
+fn load_config(path: &str) -> AxResult<VmConfig> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|err| ax_err_type!(NotFound, format!("Failed to read {}, err {:?}", path, err)))?;
+    VmConfig::from_toml(&text).map_err(|err| {
+        error!("Invalid VM config {}", path);
+        err
+    })
+}
+
+/// Builds and boots the VMs of all configurations (`*.toml`) in `dir`, and
+/// waits for them to stop.
+pub fn run_vms(dir: &str) -> AxResult {
+    let entries = std::fs::read_dir(dir)
+        .map_err(|err| ax_err_type!(NotFound, format!("Failed to read {}, err {:?}", dir, err)))?;
+    let mut configs = Vec::new();
+    for entry in entries {
+        let Ok(entry) = entry else { continue };
+        if entry.file_name().ends_with(".toml") {
+            configs.push(load_config(&entry.path())?);
+        }
+    }
+    if configs.is_empty() {
+        return ax_err!(NotFound, "no VM config, please check your disk.img");
+    }
+
+    // Build all the VMs first, to report the errors of any configuration.
+    let mut vms = Vec::new();
+    for config in configs {
+        let vm = build_vm(&config)?;
+        vms.push((config, vm));
+    }
+    let tasks: Vec<_> = vms
+        .into_iter()
+        .map(|(config, vm)| {
+            axtask::spawn(move || {
+                if let Err(err) = run_vm(&config, vm) {
+                    error!("VM {}: failed to reboot: {:?}", config.name, err);
+                }
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.join();
+    }
+    Ok(())
+}
+
+/// Boots the built VM, and waits for it to shut down. The VM is built again
+/// each time it reboots.
+fn run_vm(config: &VmConfig, vm: (Arc<Vm>, usize)) -> AxResult {
+    let mut built = Some(vm);
+    loop {
+        let (vm, dtb_addr) = match built.take() {
+            Some(vm) => vm,
+            None => build_vm(config)?,
+        };
+        info!("VM {}: booting at {:#x}", config.name, config.entry);
+        for task in vm.boot(config.entry.into(), dtb_addr) {
+            task.join();
+        }
+        match vm.outcome() {
+            Some(VmOutcome::Reboot) => info!("VM {}: rebooting", config.name),
+            Some(VmOutcome::Error) => {
+                error!("VM {}: stopped on error", config.name);
+                return Ok(());
+            }
+            outcome => {
+                info!("VM {}: stopped, {:?}", config.name, outcome);
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn load_vm_image(image_path: String, image_load_gpa: VirtAddr, aspace: &AddrSpace) -> AxResult {
+    use std::io::{BufReader, Read};
+    let (image_file, image_size) = open_image_file(image_path.as_str())?;
+
+    let image_load_regions = aspace
+        .translated_byte_buffer(image_load_gpa, image_size)
+        .ok_or_else(|| ax_err_type!(InvalidInput, "image is out of guest memory"))?;
+    let mut file = BufReader::new(image_file);
+
+    for buffer in image_load_regions {
+        file.read_exact(buffer).map_err(|err| {
+            ax_err_type!(
+                Io,
+                format!("Failed in reading from file {}, err {:?}", image_path, err)
+            )
+        })?
+    }
+
+    Ok(())
+}
+
+fn open_image_file(file_name: &str) -> AxResult<(File, usize)> {
+    let file = File::open(file_name).map_err(|err| {
+        ax_err_type!(
+            NotFound,
+            format!(
+                "Failed to open {}, err {:?}, please check your disk.img",
+                file_name, err
+            )
+        )
+    })?;
+    let file_size = file
+        .metadata()
+        .map_err(|err| {
+            ax_err_type!(
+                Io,
+                format!(
+                    "Failed to get metadate of file {}, err {:?}",
+                    file_name, err
+                )
+            )
+        })?
+        .size() as usize;
+    Ok((file, file_size))
+}
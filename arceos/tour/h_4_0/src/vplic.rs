@@ -0,0 +1,238 @@
+//! Emulated platform-level interrupt controller (PLIC), with the register
+//! layout of the PLIC of QEMU `virt`.
+//!
+//! Each hart has two contexts, `2 * hart` for M-mode and `2 * hart + 1` for
+//! S-mode. Only the S-mode contexts deliver interrupts, as the external
+//! interrupt (VSEIP) of the vCPU of the hart.
+//!
+//! The interrupt sources are level-triggered: a source is pending while its
+//! line is high and it is not claimed, and becomes pending again on completion
+//! if its line is still high.
+
+use alloc::vec::Vec;
+use axerrno::AxResult;
+use riscv_vcpu::AccessWidth;
+use std::sync::Mutex;
+
+use crate::vmdev::EmuDev;
+
+/// The number of interrupt sources, including the reserved source 0.
+pub const NUM_SOURCES: usize = 128;
+
+const NUM_WORDS: usize = NUM_SOURCES / 32;
+
+const PRIORITY_BASE: usize = 0x0;
+const PENDING_BASE: usize = 0x1000;
+const ENABLE_BASE: usize = 0x2000;
+const ENABLE_STRIDE: usize = 0x80;
+const CONTEXT_BASE: usize = 0x20_0000;
+const CONTEXT_STRIDE: usize = 0x1000;
+const CONTEXT_THRESHOLD: usize = 0x0;
+const CONTEXT_CLAIM: usize = 0x4;
+
+/// The interrupt priorities are in `0..=7`, where 0 never interrupts.
+const PRIORITY_MASK: u32 = 0x7;
+
+/// A register of the PLIC, with its indexes checked against the number of
+/// sources and contexts.
+enum PlicReg {
+    Priority(usize),
+    Pending(usize),
+    Enable(usize, usize),
+    Threshold(usize),
+    Claim(usize),
+    Reserved,
+}
+
+impl PlicReg {
+    fn decode(offset: usize, num_contexts: usize) -> Self {
+        if offset < PENDING_BASE {
+            let irq = (offset - PRIORITY_BASE) / 4;
+            if irq < NUM_SOURCES {
+                return Self::Priority(irq);
+            }
+        } else if offset < ENABLE_BASE {
+            let word = (offset - PENDING_BASE) / 4;
+            if word < NUM_WORDS {
+                return Self::Pending(word);
+            }
+        } else if offset < CONTEXT_BASE {
+            let ctx = (offset - ENABLE_BASE) / ENABLE_STRIDE;
+            let word = (offset - ENABLE_BASE) % ENABLE_STRIDE / 4;
+            if ctx < num_contexts && word < NUM_WORDS {
+                return Self::Enable(ctx, word);
+            }
+        } else {
+            let ctx = (offset - CONTEXT_BASE) / CONTEXT_STRIDE;
+            if ctx < num_contexts {
+                match (offset - CONTEXT_BASE) % CONTEXT_STRIDE {
+                    CONTEXT_THRESHOLD => return Self::Threshold(ctx),
+                    CONTEXT_CLAIM => return Self::Claim(ctx),
+                    _ => {}
+                }
+            }
+        }
+        Self::Reserved
+    }
+}
+
+struct PlicContext {
+    enable: [u32; NUM_WORDS],
+    threshold: u32,
+}
+
+struct PlicState {
+    priority: [u32; NUM_SOURCES],
+    pending: [u32; NUM_WORDS],
+    /// The sources claimed by a context and not completed yet.
+    claimed: [u32; NUM_WORDS],
+    /// The levels of the interrupt lines.
+    level: [u32; NUM_WORDS],
+    contexts: Vec<PlicContext>,
+}
+
+fn test_bit(bits: &[u32], irq: usize) -> bool {
+    bits[irq / 32] & (1 << (irq % 32)) != 0
+}
+
+fn set_bit(bits: &mut [u32], irq: usize, val: bool) {
+    if val {
+        bits[irq / 32] |= 1 << (irq % 32);
+    } else {
+        bits[irq / 32] &= !(1 << (irq % 32));
+    }
+}
+
+impl PlicState {
+    /// Returns the pending and enabled source of the highest priority above
+    /// the threshold of the context, the lowest numbered one on ties.
+    fn best_irq(&self, ctx: usize) -> Option<usize> {
+        let context = &self.contexts[ctx];
+        let mut best = None;
+        let mut best_priority = context.threshold;
+        for irq in 1..NUM_SOURCES {
+            if test_bit(&self.pending, irq)
+                && test_bit(&context.enable, irq)
+                && self.priority[irq] > best_priority
+            {
+                best = Some(irq);
+                best_priority = self.priority[irq];
+            }
+        }
+        best
+    }
+
+    fn claim(&mut self, ctx: usize) -> u32 {
+        match self.best_irq(ctx) {
+            Some(irq) => {
+                set_bit(&mut self.pending, irq, false);
+                set_bit(&mut self.claimed, irq, true);
+                irq as u32
+            }
+            None => 0,
+        }
+    }
+
+    fn complete(&mut self, ctx: usize, irq: usize) {
+        // Completions of sources that are not enabled for the context are
+        // ignored, as the PLIC spec requires.
+        if irq == 0 || irq >= NUM_SOURCES || !test_bit(&self.contexts[ctx].enable, irq) {
+            return;
+        }
+        set_bit(&mut self.claimed, irq, false);
+        if test_bit(&self.level, irq) {
+            set_bit(&mut self.pending, irq, true);
+        }
+    }
+}
+
+/// An emulated PLIC of a VM.
+pub struct VPlic {
+    state: Mutex<PlicState>,
+}
+
+impl VPlic {
+    /// The size of the register region.
+    pub const SIZE: usize = 0x400_0000;
+
+    /// Creates a PLIC for a VM with `num_harts` harts.
+    pub fn new(num_harts: usize) -> Self {
+        let contexts = (0..num_harts * 2)
+            .map(|_| PlicContext {
+                enable: [0; NUM_WORDS],
+                threshold: 0,
+            })
+            .collect();
+        Self {
+            state: Mutex::new(PlicState {
+                priority: [0; NUM_SOURCES],
+                pending: [0; NUM_WORDS],
+                claimed: [0; NUM_WORDS],
+                level: [0; NUM_WORDS],
+                contexts,
+            }),
+        }
+    }
+
+    /// Sets the level of the interrupt line of source `irq`.
+    pub fn set_irq(&self, irq: usize, level: bool) {
+        if irq == 0 || irq >= NUM_SOURCES {
+            return;
+        }
+        let mut state = self.state.lock();
+        set_bit(&mut state.level, irq, level);
+        if level && !test_bit(&state.claimed, irq) {
+            set_bit(&mut state.pending, irq, true);
+        } else if !level {
+            set_bit(&mut state.pending, irq, false);
+        }
+    }
+
+    /// Returns whether an interrupt is to be delivered to the S-mode of
+    /// `hart`.
+    pub fn irq_pending(&self, hart: usize) -> bool {
+        let state = self.state.lock();
+        let ctx = hart * 2 + 1;
+        ctx < state.contexts.len() && state.best_irq(ctx).is_some()
+    }
+}
+
+impl EmuDev for VPlic {
+    fn read(&self, offset: usize, width: AccessWidth) -> AxResult<u64> {
+        // The registers are 32-bit, other accesses read 0.
+        if width != AccessWidth::Dword {
+            warn!("vplic: ignored {:?} read at {:#x}", width, offset);
+            return Ok(0);
+        }
+        let mut state = self.state.lock();
+        let val = match PlicReg::decode(offset, state.contexts.len()) {
+            PlicReg::Priority(irq) => state.priority[irq],
+            PlicReg::Pending(word) => state.pending[word],
+            PlicReg::Enable(ctx, word) => state.contexts[ctx].enable[word],
+            PlicReg::Threshold(ctx) => state.contexts[ctx].threshold,
+            PlicReg::Claim(ctx) => state.claim(ctx),
+            PlicReg::Reserved => 0,
+        };
+        Ok(val as u64)
+    }
+
+    fn write(&self, offset: usize, width: AccessWidth, data: u64) -> AxResult {
+        if width != AccessWidth::Dword {
+            warn!("vplic: ignored {:?} write at {:#x}", width, offset);
+            return Ok(());
+        }
+        let mut state = self.state.lock();
+        let val = data as u32;
+        match PlicReg::decode(offset, state.contexts.len()) {
+            PlicReg::Priority(irq) => state.priority[irq] = val & PRIORITY_MASK,
+            // Source 0 does not exist.
+            PlicReg::Enable(ctx, 0) => state.contexts[ctx].enable[0] = val & !1,
+            PlicReg::Enable(ctx, word) => state.contexts[ctx].enable[word] = val,
+            PlicReg::Threshold(ctx) => state.contexts[ctx].threshold = val & PRIORITY_MASK,
+            PlicReg::Claim(ctx) => state.complete(ctx, val as usize),
+            // The pending bits are read-only.
+            PlicReg::Pending(_) | PlicReg::Reserved => {}
+        }
+        Ok(())
+    }
+}
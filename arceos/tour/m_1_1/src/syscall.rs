@@ -3,21 +3,19 @@
 use axhal::arch::TrapFrame;
 use axhal::trap::{register_trap_handler, SYSCALL};
 use axerrno::LinuxError;
-
-const SYS_EXIT: usize = 93;
+use axsyscall::Sysno;
 
 #[register_trap_handler(SYSCALL)]
 fn handle_syscall(tf: &TrapFrame, syscall_num: usize) -> isize {
     ax_println!("handle_syscall ...");
-    let ret = match syscall_num {
-        SYS_EXIT => {
+    axsyscall::dispatch(tf, syscall_num, |sysno, args| match sysno {
+        Sysno::exit => {
             ax_println!("[SYS_EXIT]: process is exiting ..");
-            axtask::exit(tf.arg0() as _)
+            axtask::exit(args.get(0))
         },
         _ => {
-            ax_println!("Unimplemented syscall: {}", syscall_num);
+            ax_println!("Unimplemented syscall: {}", sysno.name());
             -LinuxError::ENOSYS.code() as _
         }
-    };
-    ret
+    })
 }
@@ -53,7 +53,7 @@ fn main() {
 }
 
 fn init_user_stack(uspace: &mut AddrSpace, populating: bool) -> io::Result<VirtAddr> {
-    let ustack_top = uspace.end();
+    let ustack_top = uspace.layout().stack_top;
     let ustack_vaddr = ustack_top - crate::USER_STACK_SIZE;
     ax_println!(
         "Mapping user stack: {:#x?} -> {:#x?}",
@@ -78,6 +78,7 @@ fn handle_page_fault(vaddr: VirtAddr, access_flags: MappingFlags, is_user: bool)
             .handle_page_fault(vaddr, access_flags)
         {
             ax_println!("{}: segmentation fault, exit!", axtask::current().id_name());
+            ax_println!("{}", axtask::current().task_ext().aspace.lock().dump());
             axtask::exit(-1);
         } else {
             ax_println!("{}: handle page fault OK!", axtask::current().id_name());
@@ -4,41 +4,46 @@ use core::ffi::c_void;
 use axhal::arch::TrapFrame;
 use axhal::trap::{register_trap_handler, SYSCALL};
 use axerrno::LinuxError;
+use axsyscall::{register_user_reader, syscall_body, Sysno, USER_READER};
 use axtask::current;
 use axtask::TaskExtRef;
 use arceos_posix_api as api;
-
-const SYS_IOCTL: usize = 29;
-const SYS_WRITEV: usize = 66;
-const SYS_EXIT: usize = 93;
-const SYS_EXIT_GROUP: usize = 94;
-const SYS_SET_TID_ADDRESS: usize = 96;
+use axmm::{copy_from_user, read_iovecs_from_user, UserIoVec};
 
 #[register_trap_handler(SYSCALL)]
 fn handle_syscall(tf: &TrapFrame, syscall_num: usize) -> isize {
-    ax_println!("handle_syscall [{}] ...", syscall_num);
-    let ret = match syscall_num {
-         SYS_IOCTL => sys_ioctl(tf.arg0() as _, tf.arg1() as _, tf.arg2() as _) as _,
-        SYS_SET_TID_ADDRESS => sys_set_tid_address(tf.arg0() as _),
-        SYS_WRITEV => sys_writev(tf.arg0() as _, tf.arg1() as _, tf.arg2() as _),
-        SYS_EXIT_GROUP => {
+    axsyscall::dispatch(tf, syscall_num, |sysno, args| match sysno {
+        Sysno::ioctl => sys_ioctl(args.get(0), args.get(1), args.get(2)) as _,
+        Sysno::set_tid_address => sys_set_tid_address(args.get(0)),
+        Sysno::writev => sys_writev(args.get(0), args.get(1), args.get(2)),
+        Sysno::exit_group => {
             ax_println!("[SYS_EXIT_GROUP]: system is exiting ..");
-            axtask::exit(tf.arg0() as _)
+            axtask::exit(args.get(0))
         },
-        SYS_EXIT => {
+        Sysno::exit => {
             ax_println!("[SYS_EXIT]: system is exiting ..");
-            axtask::exit(tf.arg0() as _)
+            axtask::exit(args.get(0))
         },
         _ => {
-            ax_println!("Unimplemented syscall: {}", syscall_num);
+            ax_println!("Unimplemented syscall: {}", sysno.name());
             -LinuxError::ENOSYS.code() as _
         }
-    };
-    ret
+    })
+}
+
+/// Reads user memory for the syscall tracer.
+#[register_user_reader(USER_READER)]
+fn read_user(addr: usize, buf: &mut [u8]) -> bool {
+    copy_from_user(&mut current().task_ext().aspace.lock(), buf, addr.into()).is_ok()
 }
 
-fn sys_writev(fd: i32, iov: *const api::ctypes::iovec, iocnt: i32) -> isize {
-    unsafe { api::sys_writev(fd, iov, iocnt) }
+fn sys_writev(fd: i32, iov: *const UserIoVec, iocnt: i32) -> isize {
+    syscall_body!(sys_writev, {
+        let iocnt = usize::try_from(iocnt).map_err(|_| LinuxError::EINVAL)?;
+        let kbuf =
+            read_iovecs_from_user(&mut current().task_ext().aspace.lock(), iov.into(), iocnt)?;
+        Ok(api::sys_write(fd, kbuf.as_ptr() as _, kbuf.len()))
+    })
 }
 
 pub(crate) fn sys_set_tid_address(tid_ptd: *const i32) -> isize {
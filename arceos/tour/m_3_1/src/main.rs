@@ -10,7 +10,6 @@ extern crate axlog;
 
 mod task;
 mod syscall;
-mod loader;
 
 use axstd::io;
 use axhal::paging::MappingFlags;
@@ -21,7 +20,6 @@ use alloc::sync::Arc;
 use alloc::string::String;
 use alloc::collections::BTreeMap;
 use axmm::AddrSpace;
-use loader::load_user_app;
 
 const USER_STACK_SIZE: usize = 0x10000;
 const KERNEL_STACK_SIZE: usize = 0x40000; // 256 KiB
@@ -32,29 +30,34 @@ fn main() {
     let mut uspace = axmm::new_user_aspace().unwrap();
 
     // Load user app binary file into address space.
-    let entry = match load_user_app("/sbin/fileops", &mut uspace) {
-        Ok(e) => e,
+    let app = match axloader::load_user_app("/sbin/fileops", &mut uspace) {
+        Ok(app) => app,
         Err(err) => panic!("Cannot load app! {:?}", err),
     };
-    ax_println!("entry: {:#x}", entry);
+    ax_println!("entry: {:#x}", app.entry);
 
     // Init user stack.
-    let ustack_top = init_user_stack(&mut uspace, true).unwrap();
+    let ustack_top = init_user_stack(&mut uspace, &app.auxv, true).unwrap();
     ax_println!("New user address space: {:#x?}", uspace);
 
     // Let's kick off the user process.
     let user_task = task::spawn_user_task(
         Arc::new(Mutex::new(uspace)),
-        UspaceContext::new(entry, ustack_top),
+        UspaceContext::new(app.entry.as_usize(), ustack_top),
     );
 
     // Wait for user process to exit ...
     let exit_code = user_task.join();
+    axsyscall::print_summary();
     ax_println!("monolithic kernel exit [{:?}] normally!", exit_code);
 }
 
-fn init_user_stack(uspace: &mut AddrSpace, populating: bool) -> io::Result<VirtAddr> {
-    let ustack_top = uspace.end();
+fn init_user_stack(
+    uspace: &mut AddrSpace,
+    auxv: &BTreeMap<u8, usize>,
+    populating: bool,
+) -> io::Result<VirtAddr> {
+    let ustack_top = uspace.layout().stack_top;
     let ustack_vaddr = ustack_top - crate::USER_STACK_SIZE;
     ax_println!(
         "Mapping user stack: {:#x?} -> {:#x?}",
@@ -68,11 +71,10 @@ fn init_user_stack(uspace: &mut AddrSpace, populating: bool) -> io::Result<VirtA
     ).unwrap();
 
     let app_name = "hello";
-    let av = BTreeMap::new();
     let (stack_data, ustack_pointer) = kernel_elf_parser::get_app_stack_region(
         &[String::from(app_name)],
         &[],
-        &av,
+        auxv,
         ustack_vaddr,
         crate::USER_STACK_SIZE,
     );
@@ -4,52 +4,66 @@ use core::ffi::{c_void, c_char, c_int};
 use axhal::arch::TrapFrame;
 use axhal::trap::{register_trap_handler, SYSCALL};
 use axerrno::LinuxError;
+use axsyscall::{register_user_reader, syscall_body, Sysno, USER_READER};
 use axtask::current;
 use axtask::TaskExtRef;
 use arceos_posix_api as api;
-
-const SYS_IOCTL: usize = 29;
-const SYS_OPENAT: usize = 56;
-const SYS_CLOSE: usize = 57;
-const SYS_READ: usize = 63;
-const SYS_WRITE: usize = 64;
-const SYS_WRITEV: usize = 66;
-const SYS_EXIT: usize = 93;
-const SYS_EXIT_GROUP: usize = 94;
-const SYS_SET_TID_ADDRESS: usize = 96;
+use axhal::mem::VirtAddr;
+use axmm::{
+    copy_from_user, read_iovecs_from_user, read_to_user, strncpy_from_user, UserIoVec, UserSlice,
+};
+use alloc::vec;
 
 const AT_FDCWD: i32 = -100;
 
+/// Maximum length of a path, including the terminating NUL.
+const PATH_MAX: usize = 4096;
+
 #[register_trap_handler(SYSCALL)]
 fn handle_syscall(tf: &TrapFrame, syscall_num: usize) -> isize {
-    ax_println!("handle_syscall [{}] ...", syscall_num);
-    let ret = match syscall_num {
-         SYS_IOCTL => sys_ioctl(tf.arg0() as _, tf.arg1() as _, tf.arg2() as _) as _,
-        SYS_SET_TID_ADDRESS => sys_set_tid_address(tf.arg0() as _),
-        SYS_OPENAT => sys_openat(tf.arg0() as _, tf.arg1() as _, tf.arg2() as _, tf.arg3() as _),
-        SYS_CLOSE => sys_close(tf.arg0() as _),
-        SYS_READ => sys_read(tf.arg0() as _, tf.arg1() as _, tf.arg2() as _),
-        SYS_WRITE => sys_write(tf.arg0() as _, tf.arg1() as _, tf.arg2() as _),
-        SYS_WRITEV => sys_writev(tf.arg0() as _, tf.arg1() as _, tf.arg2() as _),
-        SYS_EXIT_GROUP => {
+    axsyscall::dispatch(tf, syscall_num, |sysno, args| match sysno {
+        Sysno::ioctl => sys_ioctl(args.get(0), args.get(1), args.get(2)) as _,
+        Sysno::set_tid_address => sys_set_tid_address(args.get(0)),
+        Sysno::openat => sys_openat(args.get(0), args.get(1), args.get(2), args.get(3)),
+        Sysno::close => sys_close(args.get(0)),
+        Sysno::read => sys_read(args.get(0), args.get(1), args.get(2)),
+        Sysno::write => sys_write(args.get(0), args.get(1), args.get(2)),
+        Sysno::writev => sys_writev(args.get(0), args.get(1), args.get(2)),
+        Sysno::exit_group => {
             ax_println!("[SYS_EXIT_GROUP]: system is exiting ..");
-            axtask::exit(tf.arg0() as _)
+            axtask::exit(args.get(0))
         },
-        SYS_EXIT => {
+        Sysno::exit => {
             ax_println!("[SYS_EXIT]: system is exiting ..");
-            axtask::exit(tf.arg0() as _)
+            axtask::exit(args.get(0))
         },
         _ => {
-            ax_println!("Unimplemented syscall: {}", syscall_num);
+            ax_println!("Unimplemented syscall: {}", sysno.name());
             -LinuxError::ENOSYS.code() as _
         }
-    };
-    ret
+    })
+}
+
+/// Reads user memory for the syscall tracer.
+#[register_user_reader(USER_READER)]
+fn read_user(addr: usize, buf: &mut [u8]) -> bool {
+    copy_from_user(&mut current().task_ext().aspace.lock(), buf, addr.into()).is_ok()
 }
 
 fn sys_openat(dfd: c_int, fname: *const c_char, flags: c_int, mode: api::ctypes::mode_t) -> isize {
     assert_eq!(dfd, AT_FDCWD);
-    api::sys_open(fname, flags, mode) as isize
+    syscall_body!(sys_openat, {
+        let mut path = vec![0u8; PATH_MAX];
+        let len = strncpy_from_user(
+            &mut current().task_ext().aspace.lock(),
+            &mut path,
+            VirtAddr::from(fname as usize),
+        )?;
+        if len >= PATH_MAX {
+            return Err(LinuxError::ENAMETOOLONG);
+        }
+        Ok(api::sys_open(path.as_ptr() as _, flags, mode))
+    })
 }
 
 fn sys_close(fd: i32) -> isize {
@@ -57,15 +71,33 @@ fn sys_close(fd: i32) -> isize {
 }
 
 fn sys_read(fd: i32, buf: *mut c_void, count: usize) -> isize {
-    api::sys_read(fd, buf, count)
+    syscall_body!(sys_read, {
+        let curr = current();
+        let file = api::get_file_like(fd)?;
+        read_to_user(
+            || curr.task_ext().aspace.lock(),
+            VirtAddr::from(buf as usize),
+            count,
+            |kbuf| file.read(kbuf),
+        )
+    })
 }
 
 fn sys_write(fd: i32, buf: *const c_void, count: usize) -> isize {
-    api::sys_write(fd, buf, count)
+    syscall_body!(sys_write, {
+        let kbuf = UserSlice::new(buf as *const u8, count)
+            .read_to_vec(&mut current().task_ext().aspace.lock())?;
+        Ok(api::sys_write(fd, kbuf.as_ptr() as _, count))
+    })
 }
 
-fn sys_writev(fd: i32, iov: *const api::ctypes::iovec, iocnt: i32) -> isize {
-    unsafe { api::sys_writev(fd, iov, iocnt) }
+fn sys_writev(fd: i32, iov: *const UserIoVec, iocnt: i32) -> isize {
+    syscall_body!(sys_writev, {
+        let iocnt = usize::try_from(iocnt).map_err(|_| LinuxError::EINVAL)?;
+        let kbuf =
+            read_iovecs_from_user(&mut current().task_ext().aspace.lock(), iov.into(), iocnt)?;
+        Ok(api::sys_write(fd, kbuf.as_ptr() as _, kbuf.len()))
+    })
 }
 
 fn sys_set_tid_address(tid_ptd: *const i32) -> isize {
@@ -179,8 +179,9 @@ fn main() {
     // 在第四节实验中提到，内核启动时必须先初始化栈，之后才可以进入 Rust 代码申请局部变量乃至调用函数。
     // 但上面的用户程序代码却没有这一步。这当然是因为内核替用户完成了初始化。
 
-    // 首先，取地址空间的最高处的一段地址，用来放置用户栈。
-    let ustack_top = uspace.end();
+    // 首先，在地址空间布局给出的栈顶下方取一段地址，用来放置用户栈。
+    // 开启 ASLR 时，栈顶的位置是随机的。
+    let ustack_top = uspace.layout().stack_top;
     let ustack_vaddr = ustack_top - crate::USER_STACK_SIZE;
     println!(
         "Mapping user stack: [{:#x?},{:#x?}]",
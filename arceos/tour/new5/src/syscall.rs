@@ -10,6 +10,7 @@
 use axerrno::LinuxError;
 use axhal::arch::TrapFrame;
 use axhal::trap::{register_trap_handler, SYSCALL};
+use axsyscall::Sysno;
 
 // 5.5.1 系统调用的参数定义
 
@@ -20,8 +21,8 @@ use axhal::trap::{register_trap_handler, SYSCALL};
 // 系统调用编号与名称的对应关系一般定义在系统头文件中，但也可在网上查询。
 // RISC-V 的系统调用编号可以查询 https://jborza.com/post/2021-05-11-riscv-linux-syscalls/
 // 其他一些常用架构可以查 https://syscall.sh/
-const SYS_EXIT: usize = 93;
-const SYS_WRITE: usize = 64;
+// 这些编号表已经整理在 modules/axsyscall 中：riscv64 和 aarch64 共用一张表（如 exit 是 93，write 是 64），
+// x86_64 则是另一张表。axsyscall::Sysno 会按照目标架构把编号翻译成系统调用的名字。
 
 // 5.5.2 系统调用如何触发
 // 在 5.2.2 提到，系统调用(syscall)是由 ecall 触发的异常指令。
@@ -43,22 +44,26 @@ fn handle_syscall(tf: &TrapFrame, syscall_num: usize) -> isize {
         tf.arg4(),
         tf.arg5()
     );
-    let ret: isize = match syscall_num {
+    // axsyscall::dispatch 查表得到编号对应的系统调用 sysno，并把 a0-a5 打包成 args，
+    // 之后可以用 args.get(i) 把第 i 个参数转换成需要的类型。
+    // 表中查不到的编号会直接返回 ENOSYS 错误。
+    axsyscall::dispatch(tf, syscall_num, |sysno, args| match sysno {
         // sys_exit 的语义是用户程序希望退出执行，它有1个参数，表示退出状态。
-        SYS_EXIT => {
+        Sysno::exit => {
             println!("[SYS_EXIT]");
             // 将退出状态交给 axtask 并退出当前用户
-            axtask::exit(tf.arg0() as _)
+            axtask::exit(args.get(0))
             // 注意，exit 是一个特殊的系统调用，它没有返回值。axtask::exit 会【中止当前执行流并销毁】，甚至当前这个函数 handle_syscall 都不会执行完
         }
         // 相对而言，write 是一个相对正常的系统调用。
         // 它完成系统调用之后将结果返回给用户
-        SYS_WRITE => {
+        Sysno::write => {
             println!("[SYS_WRITE]");
             // sys_write 有三个参数，分别代表文件标识符、输出数组指针、输出数组长度
             // 特别地，在程序启动时，文件表示符为 0 表示标准读入，即从键盘读入。
             // 文件标识符为 1 表示标准输出，为 2 表示标准错误输出，即使用 print 即可，目前不必区分。
-            if tf.arg0() != 1 && tf.arg0() != 2 {
+            let fd: i32 = args.get(0);
+            if fd != 1 && fd != 2 {
                 // 当前实验不支持文件读写，所以这个内核不支持其他的文件输出。
                 // 所以此处返回一个错误代码。
                 // “错误代码”没有复杂的结构，它只是一个(有符号整数中的)负数。
@@ -75,7 +80,7 @@ fn handle_syscall(tf: &TrapFrame, syscall_num: usize) -> isize {
                 // Rust 的编译器无法得知这些数值实际的类型和含义，也无法保证它们的安全。
                 // 所以此处必须套用 unsafe 块，才能强行转换出一个 slice 类型用作数组。
                 let user_data =
-                    unsafe { core::slice::from_raw_parts(tf.arg1() as *const u8, tf.arg2()) };
+                    unsafe { core::slice::from_raw_parts(args.get::<*const u8>(1), args.get(2)) };
                 // 将它们转换成 utf8 字符串的过程也是 unsafe 的，因为数组里不一定所有的字符都是可打印的。
                 println!("{}", unsafe { core::str::from_utf8_unchecked(user_data) });
                 // 现在我们完成了用户的请求——将传入的字符串打印到标准输出（文件标识符为1）中
@@ -91,7 +96,7 @@ fn handle_syscall(tf: &TrapFrame, syscall_num: usize) -> isize {
                 /*
                 let current = axtask::current();
                 let uspace = current.task_ext().aspace.lock();
-                let user_data = uspace.translated_byte_buffer(tf.arg1().into(), tf.arg2());
+                let user_data = uspace.translated_byte_buffer(args.raw(1).into(), args.get(2));
 
                 if let Some(data) = user_data {
                     let mut totoal_len = 0;
@@ -108,9 +113,8 @@ fn handle_syscall(tf: &TrapFrame, syscall_num: usize) -> isize {
             // 接下来请回到 main.rs 中完成实验
         }
         _ => {
-            ax_println!("Unimplemented syscall: {}", syscall_num);
+            ax_println!("Unimplemented syscall: {}", sysno.name());
             -LinuxError::ENOSYS.code() as _
         }
-    };
-    ret
+    })
 }
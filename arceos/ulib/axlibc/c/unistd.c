@@ -54,13 +54,6 @@ int usleep(unsigned useconds)
 
 #ifdef AX_CONFIG_FS
 
-// TODO:
-int access(const char *pathname, int mode)
-{
-    unimplemented();
-    return 0;
-}
-
 // TODO:
 ssize_t readlink(const char *path, char *buf, size_t bufsiz)
 {
@@ -68,32 +61,9 @@ ssize_t readlink(const char *path, char *buf, size_t bufsiz)
     return 0;
 }
 
-// TODO:
-int unlink(const char *pathname)
-{
-    unimplemented();
-    return 0;
-}
-
-// TODO:
-int rmdir(const char *pathname)
-{
-    unimplemented();
-    return 0;
-}
-
-// TODO:
-int fsync(int fd)
-{
-    unimplemented();
-    return 0;
-}
-
-// TODO
 int fdatasync(int __fildes)
 {
-    unimplemented();
-    return 0;
+    return fsync(__fildes);
 }
 
 // TODO:
@@ -103,20 +73,6 @@ int fchown(int fd, uid_t owner, gid_t group)
     return 0;
 }
 
-// TODO:
-int ftruncate(int fd, off_t length)
-{
-    unimplemented();
-    return 0;
-}
-
-// TODO
-int chdir(const char *__path)
-{
-    unimplemented();
-    return 0;
-}
-
 // TODO
 int truncate(const char *path, off_t length)
 {
@@ -126,32 +82,6 @@ int truncate(const char *path, off_t length)
 
 #endif // AX_CONFIG_FS
 
-#ifdef AX_CONFIG_PIPE
-
-int pipe2(int fd[2], int flag)
-{
-    if (!flag)
-        return pipe(fd);
-    if (flag & ~(O_CLOEXEC | O_NONBLOCK))
-        return -EINVAL;
-
-    int res = pipe(fd);
-    if (res != 0)
-        return res;
-
-    if (flag & O_CLOEXEC) {
-        fcntl(fd[0], F_SETFD, FD_CLOEXEC);
-        fcntl(fd[1], F_SETFD, FD_CLOEXEC);
-    }
-    if (flag & O_NONBLOCK) {
-        fcntl(fd[0], F_SETFL, O_NONBLOCK);
-        fcntl(fd[1], F_SETFL, O_NONBLOCK);
-    }
-
-    return 0;
-}
-
-#endif // AX_CONFIG_PIPE
 
 // TODO
 _Noreturn void _exit(int status)
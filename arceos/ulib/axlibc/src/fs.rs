@@ -1,7 +1,9 @@
-use core::ffi::{c_char, c_int};
+use core::ffi::{c_char, c_int, c_void};
 
 use arceos_posix_api::{
-    sys_fstat, sys_getcwd, sys_lseek, sys_lstat, sys_open, sys_rename, sys_stat,
+    sys_chdir, sys_faccessat, sys_fchdir, sys_fstat, sys_fstatat, sys_fsync, sys_ftruncate,
+    sys_getcwd, sys_getdents64, sys_lseek, sys_lstat, sys_mkdirat, sys_open, sys_openat,
+    sys_rename, sys_renameat2, sys_stat, sys_unlinkat, sys_utimensat,
 };
 
 use crate::{ctypes, utils::e};
@@ -19,6 +21,20 @@ pub unsafe extern "C" fn ax_open(
     e(sys_open(filename, flags, mode))
 }
 
+/// Open a file by `filename` relative to the directory `dirfd` and insert it
+/// into the file descriptor table.
+///
+/// Return its index in the file table (`fd`).
+#[no_mangle]
+pub unsafe extern "C" fn ax_openat(
+    dirfd: c_int,
+    filename: *const c_char,
+    flags: c_int,
+    mode: ctypes::mode_t,
+) -> c_int {
+    e(sys_openat(dirfd, filename, flags, mode))
+}
+
 /// Set the position of the file indicated by `fd`.
 ///
 /// Return its position after seek.
@@ -65,3 +81,150 @@ pub unsafe extern "C" fn getcwd(buf: *mut c_char, size: usize) -> *mut c_char {
 pub unsafe extern "C" fn rename(old: *const c_char, new: *const c_char) -> c_int {
     e(sys_rename(old, new))
 }
+
+/// Rename `old` relative to `olddirfd` to `new` relative to `newdirfd`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn renameat(
+    olddirfd: c_int,
+    old: *const c_char,
+    newdirfd: c_int,
+    new: *const c_char,
+) -> c_int {
+    e(sys_renameat2(olddirfd, old, newdirfd, new, 0))
+}
+
+/// Get the metadata of the file at `path` relative to the directory `fd` and
+/// write into `buf`.
+///
+/// Return 0 if success.
+#[no_mangle]
+pub unsafe extern "C" fn fstatat(
+    fd: c_int,
+    path: *const c_char,
+    buf: *mut ctypes::stat,
+    flag: c_int,
+) -> c_int {
+    e(sys_fstatat(fd, path, buf, flag))
+}
+
+/// Read directory entries of `fd` into `dirp`.
+///
+/// Return the number of bytes read.
+#[no_mangle]
+pub unsafe extern "C" fn getdents64(fd: c_int, dirp: *mut c_void, count: usize) -> ctypes::ssize_t {
+    e(sys_getdents64(fd, dirp, count) as _) as _
+}
+
+/// Create a directory.
+///
+/// Return 0 if success.
+#[no_mangle]
+pub unsafe extern "C" fn mkdir(path: *const c_char, mode: ctypes::mode_t) -> c_int {
+    e(sys_mkdirat(ctypes::AT_FDCWD, path, mode))
+}
+
+/// Create a directory at `path` relative to the directory `dirfd`.
+///
+/// Return 0 if success.
+#[no_mangle]
+pub unsafe extern "C" fn mkdirat(dirfd: c_int, path: *const c_char, mode: ctypes::mode_t) -> c_int {
+    e(sys_mkdirat(dirfd, path, mode))
+}
+
+/// Remove a file.
+///
+/// Return 0 if success.
+#[no_mangle]
+pub unsafe extern "C" fn unlink(path: *const c_char) -> c_int {
+    e(sys_unlinkat(ctypes::AT_FDCWD, path, 0))
+}
+
+/// Remove a file or directory at `path` relative to the directory `dirfd`.
+///
+/// Return 0 if success.
+#[no_mangle]
+pub unsafe extern "C" fn unlinkat(dirfd: c_int, path: *const c_char, flags: c_int) -> c_int {
+    e(sys_unlinkat(dirfd, path, flags))
+}
+
+/// Remove an empty directory.
+///
+/// Return 0 if success.
+#[no_mangle]
+pub unsafe extern "C" fn rmdir(path: *const c_char) -> c_int {
+    e(sys_unlinkat(
+        ctypes::AT_FDCWD,
+        path,
+        ctypes::AT_REMOVEDIR as _,
+    ))
+}
+
+/// Truncate or extend the file `fd` to `length` bytes.
+///
+/// Return 0 if success.
+#[no_mangle]
+pub unsafe extern "C" fn ftruncate(fd: c_int, length: ctypes::off_t) -> c_int {
+    e(sys_ftruncate(fd, length))
+}
+
+/// Write the buffered data of the file `fd` to the device.
+///
+/// Return 0 if success.
+#[no_mangle]
+pub unsafe extern "C" fn fsync(fd: c_int) -> c_int {
+    e(sys_fsync(fd))
+}
+
+/// Change the current directory.
+///
+/// Return 0 if success.
+#[no_mangle]
+pub unsafe extern "C" fn chdir(path: *const c_char) -> c_int {
+    e(sys_chdir(path))
+}
+
+/// Change the current directory to the directory `fd`.
+///
+/// Return 0 if success.
+#[no_mangle]
+pub unsafe extern "C" fn fchdir(fd: c_int) -> c_int {
+    e(sys_fchdir(fd))
+}
+
+/// Check whether the calling task can access the file `path`.
+///
+/// Return 0 if success.
+#[no_mangle]
+pub unsafe extern "C" fn access(path: *const c_char, mode: c_int) -> c_int {
+    e(sys_faccessat(ctypes::AT_FDCWD, path, mode, 0))
+}
+
+/// Check whether the calling task can access the file at `path` relative to
+/// the directory `dirfd`.
+///
+/// Return 0 if success.
+#[no_mangle]
+pub unsafe extern "C" fn faccessat(
+    dirfd: c_int,
+    path: *const c_char,
+    mode: c_int,
+    flags: c_int,
+) -> c_int {
+    e(sys_faccessat(dirfd, path, mode, flags))
+}
+
+/// Change the timestamps of the file at `path` relative to the directory
+/// `dirfd`.
+///
+/// Return 0 if success.
+#[no_mangle]
+pub unsafe extern "C" fn utimensat(
+    dirfd: c_int,
+    path: *const c_char,
+    times: *const ctypes::timespec,
+    flags: c_int,
+) -> c_int {
+    e(sys_utimensat(dirfd, path, times, flags))
+}
@@ -100,7 +100,11 @@ pub use self::strftime::strftime;
 pub use self::fd_ops::{ax_fcntl, close, dup, dup2, dup3};
 
 #[cfg(feature = "fs")]
-pub use self::fs::{ax_open, fstat, getcwd, lseek, lstat, rename, stat};
+pub use self::fs::{
+    access, ax_open, ax_openat, chdir, faccessat, fchdir, fstat, fstatat, fsync, ftruncate, getcwd,
+    getdents64, lseek, lstat, mkdir, mkdirat, rename, renameat, rmdir, stat, unlink, unlinkat,
+    utimensat,
+};
 
 #[cfg(feature = "net")]
 pub use self::net::{
@@ -1,6 +1,6 @@
 use core::ffi::c_int;
 
-use arceos_posix_api::sys_pipe;
+use arceos_posix_api::{sys_pipe, sys_pipe2};
 
 use crate::utils::e;
 
@@ -12,3 +12,12 @@ pub unsafe extern "C" fn pipe(fd: *mut c_int) -> c_int {
     let fds = unsafe { core::slice::from_raw_parts_mut(fd, 2) };
     e(sys_pipe(fds))
 }
+
+/// Create a pipe with flags
+///
+/// Return 0 if succeed
+#[no_mangle]
+pub unsafe extern "C" fn pipe2(fd: *mut c_int, flags: c_int) -> c_int {
+    let fds = unsafe { core::slice::from_raw_parts_mut(fd, 2) };
+    e(sys_pipe2(fds, flags))
+}
@@ -1,11 +1,12 @@
 #!/bin/sh
 
-if [ $# -ne 1 ]; then
-    printf "Usage: ./update.sh [userapp path]\n"
+if [ $# -ne 1 ] && [ $# -ne 2 ]; then
+    printf "Usage: ./update.sh [userapp path] [dir in disk, default: /sbin]\n"
     exit
 fi
 
 FILE=$1
+DIR=${2:-/sbin}
 
 if [ ! -f $FILE ]; then
     printf "File '$FILE' doesn't exist!\n"
@@ -17,11 +18,11 @@ if [ ! -f ./disk.img ]; then
     exit
 fi
 
-printf "Write file '$FILE' into disk.img\n"
+printf "Write file '$FILE' into disk.img:$DIR\n"
 
 mkdir -p ./mnt
 sudo mount ./disk.img ./mnt
-sudo mkdir -p ./mnt/sbin
-sudo cp $FILE ./mnt/sbin
+sudo mkdir -p ./mnt$DIR
+sudo cp $FILE ./mnt$DIR
 sudo umount ./mnt
 rm -rf mnt
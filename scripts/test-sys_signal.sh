@@ -0,0 +1,29 @@
+#!/bin/bash
+
+tmp_file=signal_test_output.txt
+grep_content="Signal tests passed!"
+
+cd arceos/ || exit
+
+rm pflash.img -f
+rm disk.img -f
+
+make pflash_img
+make disk_img
+
+make payload
+./update_disk.sh payload/signal_c/signal
+
+SYS_MAP_APP=/sbin/signal make run A=exercises/sys_map/ BLK=y > $tmp_file 2>/dev/null
+
+output=$(grep -Ea "$grep_content" ./$tmp_file)
+
+rm -rf $tmp_file 
+
+if [[ -z "$output" ]]; then
+    echo "sys_signal default"
+    exit 1
+else 
+    echo "sys_signal pass"
+    exit 0
+fi
@@ -36,6 +36,18 @@ else
     echo "test-sys_map failed" >> $file_name
 fi
 
+if ./scripts/test-sys_signal.sh ; then
+    ((score += 100))
+else
+    echo "test-sys_signal failed" >> $file_name
+fi
+
+if ./scripts/test-sys_fs.sh ; then
+    ((score += 100))
+else
+    echo "test-sys_fs failed" >> $file_name
+fi
+
 if ./scripts/test-simple_hv.sh ; then
     ((score += 100))
 else